@@ -0,0 +1,70 @@
+//! Accesses to the zero page and the stack, which `Machine` reaches directly, against the same accesses to
+//! page $02, which go through the device search, the attributes and the page map. Run with `cargo bench`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use steel6502::cpu::assembler;
+use steel6502::prelude::*;
+
+/// Instructions stepped per iteration of the program benchmarks.
+const STEPS: u64 = 10_000;
+
+/// A loop that stores, loads and increments every byte of a page, through absolute indexed addressing so that the
+/// instructions are the same wherever the page is.
+fn program(page: u8) -> String{
+    format!("
+        .org $8000
+start:  LDX #0
+loop:   TXA
+        STA ${page:02X}00,X
+        LDA ${page:02X}00,X
+        INC ${page:02X}00,X
+        INX
+        BNE loop
+        JMP start
+
+        .org $FFFA
+        .word start, start, start
+")
+}
+
+fn accesses(c: &mut Criterion){
+    let mut machine = Machine::new_32k_ram_32k_rom(&[]).unwrap();
+    let mut group = c.benchmark_group("access");
+    group.throughput(Throughput::Elements(256));
+    for page in [0x00u16, 0x01, 0x02]{
+        group.bench_with_input(BenchmarkId::new("read", format!("${:02X}", page)), &page, |b, &page| b.iter(|| {
+            for address in page << 8..=page << 8 | 0xff{
+                black_box(machine.read(black_box(address)).unwrap());
+            }
+        }));
+        group.bench_with_input(BenchmarkId::new("write", format!("${:02X}", page)), &page, |b, &page| b.iter(|| {
+            for address in page << 8..=page << 8 | 0xff{
+                machine.write(black_box(address), address as u8).unwrap();
+            }
+        }));
+    }
+    group.finish();
+}
+
+fn programs(c: &mut Criterion){
+    let mut group = c.benchmark_group("program");
+    group.throughput(Throughput::Elements(STEPS));
+    for page in [0x00u8, 0x02]{
+        let image = assembler::assemble(&program(page)).unwrap().image;
+        let mut machine = Machine::new_32k_ram_32k_rom(&image).unwrap();
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut machine).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(format!("${:02X}", page)), |b| b.iter(|| {
+            for _ in 0..STEPS{
+                cpu.step(&mut machine).unwrap();
+            }
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, accesses, programs);
+criterion_main!(benches);
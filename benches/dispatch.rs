@@ -0,0 +1,141 @@
+//! Instructions per second of the ways the CPU can get from an opcode to its operation, on programs run from
+//! ROM: the table of function pointers it used to dispatch through, the match on the mnemonic it dispatches with
+//! now, and that match fed instructions the machine decoded once and kept. Run with
+//! `cargo bench --features dispatch-bench --bench dispatch`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use steel6502::bus::bus::RegionAttributes;
+use steel6502::cpu::assembler;
+use steel6502::cpu::bench::WORKLOAD;
+use steel6502::prelude::*;
+
+/// Instructions stepped per iteration.
+const STEPS: u64 = 10_000;
+
+/// Sieve of Eratosthenes over the numbers below 256, again and again: indexed loads and stores, and adds.
+const SIEVE: &str = "
+        .org $8000
+start:  LDX #0
+        LDA #1
+clear:  STA $0300,X
+        INX
+        BNE clear
+        LDX #2
+outer:  LDA $0300,X
+        BEQ skip
+        STX $10
+        TXA
+mark:   CLC
+        ADC $10
+        BCS skip
+        TAY
+        LDA #0
+        STA $0300,Y
+        TYA
+        BRA mark
+skip:   INX
+        BNE outer
+        JMP start
+
+        .org $FFFA
+        .word start, start, start
+";
+
+/// CRC-16 of the first page of the ROM, again and again: shifts, rotates and branches on bits.
+const CRC: &str = "
+        .org $8000
+start:  LDA #$FF
+        STA $20
+        STA $21
+        LDY #0
+byte:   LDA $8000,Y
+        EOR $21
+        STA $21
+        LDX #8
+bit:    ASL $20
+        ROL $21
+        BCC next
+        LDA $21
+        EOR #$10
+        STA $21
+        LDA $20
+        EOR #$21
+        STA $20
+next:   DEX
+        BNE bit
+        INY
+        BNE byte
+        JMP start
+
+        .org $FFFA
+        .word start, start, start
+";
+
+/// A machine without its decode cache, so that every instruction is fetched through `read`.
+struct Fetched(Machine);
+impl Bus for Fetched{
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        self.0.read(address)
+    }
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError> {
+        self.0.write(address, val)
+    }
+    fn attributes(&self, address: u16) -> RegionAttributes {
+        self.0.attributes(address)
+    }
+    fn begin_instruction(&mut self, pc: u16) {
+        self.0.begin_instruction(pc);
+    }
+    fn tick(&mut self, cycles: u32) {
+        self.0.tick(cycles);
+    }
+    fn irq(&self) -> bool {
+        self.0.irq()
+    }
+    fn nmi(&self) -> bool {
+        self.0.nmi()
+    }
+}
+
+fn boot(source: &str) -> (W65C02S, Machine){
+    let image = assembler::assemble(source).unwrap().image;
+    let mut machine = Machine::new_32k_ram_32k_rom(&image).unwrap();
+    let mut cpu = W65C02S::default();
+    cpu.reset(&mut machine).unwrap();
+    (cpu, machine)
+}
+
+fn dispatch(c: &mut Criterion){
+    for (name, source) in [("workload", WORKLOAD), ("sieve", SIEVE), ("crc", CRC)]{
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Elements(STEPS));
+
+        let (mut cpu, mut machine) = boot(source);
+        group.bench_function(BenchmarkId::from_parameter("table"), |b| b.iter(|| {
+            for _ in 0..STEPS{
+                cpu.step_by_table(&mut machine).unwrap();
+            }
+        }));
+
+        let (mut cpu, machine) = boot(source);
+        let mut fetched = Fetched(machine);
+        group.bench_function(BenchmarkId::from_parameter("match"), |b| b.iter(|| {
+            for _ in 0..STEPS{
+                cpu.step(&mut fetched).unwrap();
+            }
+        }));
+
+        let (mut cpu, mut machine) = boot(source);
+        group.bench_function(BenchmarkId::from_parameter("decoded"), |b| b.iter(|| {
+            for _ in 0..STEPS{
+                cpu.step(&mut machine).unwrap();
+            }
+        }));
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+//! Random bytes as the whole of a 64KB RAM machine, vectors included, and random registers, stepped until the
+//! CPU stops. With RAM at every address nothing the CPU does can fault on the bus, so the only errors allowed
+//! are opcodes it does not decode or emulate; fuzz builds check for overflow, so a PC or SP that fails to wrap
+//! panics.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use steel6502::bus::bus::Machine;
+use steel6502::cpu::w65c02s::{CpuError, Register, W65C02S};
+
+const STEPS: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((registers, memory)) = data.split_first_chunk::<5>() else { return };
+    let image = memory.iter().copied().cycle().take(0x10000).collect::<Vec<u8>>();
+    let Ok(mut machine) = Machine::new_64k_ram(if image.is_empty(){ &[] } else { &image }) else { return };
+
+    let mut cpu = W65C02S::default();
+    cpu.reset(&mut machine).expect("the reset vector is in RAM");
+    let [a, x, y, sp, p] = *registers;
+    cpu.set_register(Register::A, a as u16);
+    cpu.set_register(Register::X, x as u16);
+    cpu.set_register(Register::Y, y as u16);
+    cpu.set_register(Register::SP, sp as u16);
+    cpu.set_register(Register::P, (p | 0x20) as u16);
+
+    for _ in 0..STEPS{
+        let cycles = cpu.cycles();
+        match cpu.step(&mut machine){
+            Ok(_) => {},
+            Err(CpuError::InvalidOpcode(_) | CpuError::Unsupported(_)) => return,
+            Err(error) => panic!("{:?} at ${:04X}", error, cpu.register(Register::PC)),
+        }
+        assert!(cpu.cycles() > cycles, "an instruction took no cycles");
+        assert_eq!(cpu.register(Register::P) & 0x20, 0x20, "bit 5 of P was cleared");
+    }
+});
@@ -0,0 +1,86 @@
+//! Random machine configurations, from a built-in layout with mappings and attributes added over it, running a
+//! random image. Faults are expected; panics, and reads or peeks that disagree, are not.
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use steel6502::bus::bus::{Bus, Machine, Mapping, RegionAttributes, SegmentKind};
+use steel6502::cpu::w65c02s::{Register, W65C02S};
+
+#[derive(Arbitrary, Debug)]
+enum Layout{
+    Ram32kRom32k,
+    Ram16kRom16k,
+    Ram48kRom8k,
+    BenEater,
+    Ram64k,
+}
+
+#[derive(Arbitrary, Debug)]
+struct ExtraMapping{
+    rom: Option<u8>,    // the segment, RAM when None
+    first_page: u8,
+    last_page: u8,
+    segment_page: u8,
+    priority: u8,
+    write_through: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input{
+    layout: Layout,
+    image: Vec<u8>,
+    mappings: Vec<ExtraMapping>,
+    attributes: Vec<(u8, u8, u8)>,  // first page, last page, and the attributes as bits
+    steps: u16,
+}
+
+const ATTRIBUTES: [RegionAttributes; 4] = [RegionAttributes::READ_ONLY, RegionAttributes::WRITE_ONLY, RegionAttributes::NO_EXECUTE, RegionAttributes::SIDE_EFFECTS];
+
+fuzz_target!(|input: Input| {
+    let built = match input.layout{
+        Layout::Ram32kRom32k => Machine::new_32k_ram_32k_rom(&input.image),
+        Layout::Ram16kRom16k => Machine::new_16k_ram_16k_rom(&input.image),
+        Layout::Ram48kRom8k => Machine::new_48k_ram_8k_rom(&input.image),
+        Layout::BenEater => Machine::new_ben_eater(&input.image),
+        Layout::Ram64k => Machine::new_64k_ram(&input.image),
+    };
+    let Ok(mut machine) = built else { return };
+
+    for extra in input.mappings.iter().take(16){
+        let kind = extra.rom.map_or(SegmentKind::RAM, |segment| SegmentKind::ROM(segment as usize));
+        let mut mapping = Mapping::new(kind, extra.first_page.min(extra.last_page)..=extra.last_page.max(extra.first_page), extra.segment_page as usize)
+            .with_priority(extra.priority);
+        if extra.write_through{
+            mapping = mapping.write_through();
+        }
+        // a mapping past the end of its segment is refused, which is as good a test as one that is taken
+        let _ = machine.map(mapping);
+    }
+    for &(first, last, bits) in input.attributes.iter().take(16){
+        let attributes = ATTRIBUTES.iter().enumerate()
+            .filter(|(bit, _)| bits & (1 << bit) != 0)
+            .fold(RegionAttributes::NONE, |attributes, (_, &attribute)| attributes | attribute);
+        machine.set_attributes(first.min(last)..=last.max(first), attributes);
+    }
+
+    // peeking never disturbs anything, and agrees with a read wherever it sees a byte
+    for address in 0..=u16::MAX{
+        if let Some(byte) = machine.peek(address){
+            if let Ok(read) = machine.read(address){
+                assert_eq!(byte, read, "peek and read disagree at ${:04X}", address);
+            }
+        }
+    }
+
+    let mut cpu = W65C02S::default();
+    if cpu.reset(&mut machine).is_err(){
+        return;
+    }
+    for _ in 0..input.steps.min(10_000){
+        if cpu.step(&mut machine).is_err(){
+            break;
+        }
+        assert_eq!(cpu.register(Register::P) & 0x20, 0x20, "bit 5 of P was cleared");
+    }
+});
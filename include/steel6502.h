@@ -0,0 +1,59 @@
+/* The C interface to Steel6502, in the cdylib `cargo build --release` writes to target/release
+ * (libsteel6502.so, libsteel6502.dylib or steel6502.dll). Calls that can fail return -1, and
+ * steel6502_last_error says why. */
+#ifndef STEEL6502_H
+#define STEEL6502_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A 65C02 on a machine of 32KB of RAM and 32KB of ROM. */
+typedef struct Steel6502 Steel6502;
+
+/* Registers, as steel6502_register and steel6502_set_register number them. */
+#define STEEL6502_PC 0
+#define STEEL6502_A  1
+#define STEEL6502_X  2
+#define STEEL6502_Y  3
+#define STEEL6502_SP 4
+#define STEEL6502_P  5
+
+/* Hooks for a range mapped with steel6502_map, given its user pointer and the address accessed. */
+typedef uint8_t (*steel6502_read_hook)(void *user, uint16_t address);
+typedef void (*steel6502_write_hook)(void *user, uint16_t address, uint8_t value);
+
+/* Builds a machine with `rom` in its upper 32KB and resets the CPU. NULL when the ROM does not fit. */
+Steel6502 *steel6502_create(const uint8_t *rom, size_t length);
+void steel6502_destroy(Steel6502 *emulator);
+
+/* Starts again from the reset vector, leaving memory as it is. */
+int steel6502_reset(Steel6502 *emulator);
+/* Executes one instruction; the cycles it took, or -1 when the CPU stopped. */
+int steel6502_step(Steel6502 *emulator);
+
+/* The byte at an address without side effects or hooks, or -1 when nothing is mapped there. */
+int steel6502_read(const Steel6502 *emulator, uint16_t address);
+/* Stores a byte in RAM or ROM, as a debugger would. */
+int steel6502_write(Steel6502 *emulator, uint16_t address, uint8_t value);
+
+uint16_t steel6502_register(const Steel6502 *emulator, int reg);
+int steel6502_set_register(Steel6502 *emulator, int reg, uint16_t value);
+uint64_t steel6502_cycles(const Steel6502 *emulator);
+
+/* Hands CPU accesses from `first` to `last` to the hooks, ahead of RAM, ROM and earlier ranges. Either hook
+ * may be NULL: the range then reads as $FF, or drops writes. */
+int steel6502_map(Steel6502 *emulator, uint16_t first, uint16_t last,
+                  steel6502_read_hook on_read, steel6502_write_hook on_write, void *user);
+
+/* Why the last failing call failed, or NULL; valid until the next failure or steel6502_destroy. */
+const char *steel6502_last_error(const Steel6502 *emulator);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
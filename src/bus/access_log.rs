@@ -0,0 +1,54 @@
+use std::ops::RangeInclusive;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind{
+    Read,
+    Write,
+}
+
+/// A single transaction observed on the bus.
+#[derive(Copy, Clone, Debug)]
+pub struct BusTransaction{
+    pub address: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+    pub pc: u16,    // address of the instruction that caused the access
+}
+
+/// Receiver for logged bus transactions.
+pub trait AccessSink{
+    fn record(&mut self, transaction: &BusTransaction);
+}
+impl<F: FnMut(&BusTransaction)> AccessSink for F{
+    fn record(&mut self, transaction: &BusTransaction) {
+        self(transaction)
+    }
+}
+
+/// Forwards bus transactions to a sink, optionally restricted to a set of address ranges.
+pub struct AccessLogger{
+    sink: Box<dyn AccessSink>,
+    ranges: Vec<RangeInclusive<u16>>,
+}
+impl AccessLogger{
+    /// With no ranges added, every transaction is forwarded.
+    pub fn new(sink: impl AccessSink + 'static) -> Self{
+        Self { sink: Box::new(sink), ranges: Vec::new() }
+    }
+    pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self{
+        self.ranges.push(range);
+        self
+    }
+
+    pub fn log(&mut self, transaction: &BusTransaction){
+        if ranges_accept(&self.ranges, transaction.address){
+            self.sink.record(transaction);
+        }
+    }
+}
+
+/// An empty set of ranges accepts every address.
+#[inline]
+pub(crate) fn ranges_accept(ranges: &[RangeInclusive<u16>], address: u16) -> bool{
+    ranges.is_empty() || ranges.iter().any(|r| r.contains(&address))
+}
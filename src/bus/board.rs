@@ -0,0 +1,496 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+
+use crate::bus::bus::{BusError, Machine, RomImageError, RomPlacement};
+use crate::cpu::w65c02s::W65C02S;
+use crate::devices::ansi_screen::AnsiScreen;
+use crate::devices::audio::WavWriter;
+use crate::devices::ay38910::AY38910;
+use crate::devices::beeper::Beeper;
+use crate::devices::cassette::{Cassette, Tape};
+use crate::devices::char_io::{self, CharInput, CharOutput};
+use crate::devices::cycle_counter::CycleCounter;
+use crate::devices::device::{self, DeviceId};
+use crate::devices::disk_controller::{DiskController, DiskGeometry};
+#[cfg(feature = "video")]
+use crate::devices::framebuffer::{Framebuffer, FramebufferMode};
+use crate::devices::host_services::HostServices;
+use crate::devices::i2c_eeprom::{I2cEeprom, I2cWiring};
+use crate::devices::input_log::InputLog;
+use crate::devices::joystick::{Joystick, JoystickKeys};
+use crate::devices::keyboard::Keyboard;
+use crate::devices::leds::{LedBank, LedDisplay};
+use crate::devices::mc6850::MC6850;
+use crate::devices::midi::{MidiFileWriter, MidiOut, RawMidi};
+use crate::devices::ps2_keyboard::Ps2Keyboard;
+use crate::devices::random::{Random, RandomDevice};
+#[cfg(feature = "video")]
+use crate::devices::raster::RasterPosition;
+use crate::devices::raster::RasterTimer;
+use crate::devices::sd_card::SdCard;
+#[cfg(unix)]
+use crate::devices::serial::PtySerial;
+use crate::devices::serial::{self, SerialLink, TcpSerial};
+use crate::devices::spi::SpiController;
+use crate::devices::w65c22::W65C22;
+use crate::devices::w65c51::W65C51;
+use crate::memory::memory::AccessError;
+
+/// Why an image could not be loaded into a machine, or a device could not be attached to one.
+#[derive(Debug)]
+pub enum BoardError{
+    MissingFile(PathBuf),
+    CouldNotRead(PathBuf),
+    CouldNotWrite(PathBuf),
+    MalformedImage,                 // too short for the layout it is loaded into
+    RomImage(RomImageError),
+    Bus(BusError),
+    CouldNotListen(String),         // the host address, and why listening on it failed
+    CouldNotOpenTerminal(String),
+    #[cfg(feature = "video")]
+    CouldNotOpenWindow(String),
+}
+impl fmt::Display for BoardError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            BoardError::MissingFile(path) => write!(f, "could not find {}", path.display()),
+            BoardError::CouldNotRead(path) => write!(f, "could not read {}", path.display()),
+            BoardError::CouldNotWrite(path) => write!(f, "could not write {}", path.display()),
+            BoardError::MalformedImage => write!(f, "malformed image"),
+            BoardError::RomImage(e) => write!(f, "{}", e),
+            BoardError::Bus(e) => write!(f, "{}", e),
+            BoardError::CouldNotListen(e) => write!(f, "could not listen on {}", e),
+            BoardError::CouldNotOpenTerminal(e) => write!(f, "could not open a terminal: {}", e),
+            #[cfg(feature = "video")]
+            BoardError::CouldNotOpenWindow(e) => write!(f, "could not open a window: {}", e),
+        }
+    }
+}
+impl Error for BoardError{}
+impl From<BusError> for BoardError{
+    fn from(e: BusError) -> Self {
+        BoardError::Bus(e)
+    }
+}
+
+/// Maps the image. Given an entry point, a bare image need not hold the vectors, and one no larger than the ROM
+/// window goes at its start unless asked to be placed at the top. Otherwise an unplaced image is either the ROM
+/// window or the whole 64KB address space, of which the window is the top half.
+pub fn new_machine(rom: &[u8], placement: Option<RomPlacement>, entry: Option<u16>) -> Result<Machine, BoardError>{
+    let rom_window = 0x8000usize;
+    let placement = match (placement, entry){
+        (None, Some(_)) if rom.len() <= rom_window => Some(RomPlacement::WindowStart),
+        _ => placement,
+    };
+
+    match (placement, entry){
+        (Some(placement), Some(entry)) => Machine::new_32k_ram_32k_rom_entered_at(rom, placement, entry).map_err(BoardError::RomImage),
+        (Some(placement), None) => Machine::new_32k_ram_32k_rom_placed(rom, placement).map_err(BoardError::RomImage),
+        // a full address space keeps just its top half, and anything else unplaced has to fill the window
+        (None, _) => match rom.len(){
+            0x10000 => Ok(Machine::new_32k_ram_32k_rom(&rom[rom_window..])?),
+            len if len == rom_window => Ok(Machine::new_32k_ram_32k_rom(rom)?),
+            _ => Err(BoardError::MalformedImage),
+        },
+    }
+}
+
+/// Whether an image is a Commodore `.prg` file, its payload preceded by the little-endian address it loads at.
+pub fn is_prg(path: &Path) -> bool{
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("prg"))
+}
+
+/// Maps the image read from `path`, and returns where it starts if that is not the reset vector. A `.prg` file is
+/// loaded at its load address, and unless it covers the reset vector starts there. Given a generator, RAM powers
+/// up filled from it rather than zeroed.
+pub fn load_image(path: &Path, placement: Option<RomPlacement>, entry: Option<u16>, power_on: Option<&mut Random>) -> Result<(Machine, Option<u16>), BoardError>{
+    let image = fs::read(path).map_err(|_| BoardError::CouldNotRead(path.to_path_buf()))?;
+    if !is_prg(path){
+        let mut machine = new_machine(&image, placement, entry)?;
+        if let Some(random) = power_on{
+            randomize_ram(&mut machine, random)?;
+        }
+        return Ok((machine, entry));
+    }
+
+    let [low, high, payload @ ..] = image.as_slice() else { return Err(BoardError::MalformedImage) };
+    let load_address = u16::from_le_bytes([*low, *high]);
+    let mut machine = Machine::new_32k_ram_32k_rom(&[])?;
+    if let Some(random) = power_on{
+        randomize_ram(&mut machine, random)?;
+    }
+    machine.load_at(load_address, payload)?;
+    let covers_reset = load_address as usize <= W65C02S::RESB_LOW as usize && load_address as usize + payload.len() > W65C02S::RESB_LOW as usize + 1;
+    Ok((machine, entry.or((!covers_reset).then_some(load_address))))
+}
+
+/// Fills RAM from the generator, as real RAM comes up holding whatever it happens to.
+pub fn randomize_ram(machine: &mut Machine, random: &mut Random) -> Result<(), BoardError>{
+    let mut bytes = vec![0u8; machine.ram_contents().len()];
+    random.fill(&mut bytes);
+    Ok(machine.load_ram(&bytes)?)
+}
+
+/// Writes the bytes into RAM from the offset, leaving the rest of it as it is.
+pub fn preload_ram(machine: &mut Machine, offset: u16, bytes: &[u8]) -> Result<(), BoardError>{
+    let mut ram = machine.ram_contents().into_vec();
+    let end = offset as usize + bytes.len();
+    if end > ram.len(){
+        return Err(BusError::from(AccessError::OutOfRange(end)).into());
+    }
+    ram[offset as usize..end].copy_from_slice(bytes);
+    Ok(machine.load_ram(&ram)?)
+}
+
+/// The devices to attach to a machine and what each is connected to on the host, by the address it is mapped at.
+/// None leaves a device out.
+pub struct DeviceOptions{
+    pub char_out: Option<u16>,
+    pub char_in: Option<u16>,              // address of the status register, the character follows it
+    pub console: ConsoleOptions,           // what the two are wired to on the host
+    pub keyboard: Option<u16>,             // likewise
+    pub ps2_keyboard: Option<u16>,         // address of the VIA whose shift register the keyboard feeds
+    pub beeper: Option<(PathBuf, u16)>,    // WAV file the beeper is captured to, and its address
+    pub psg: Option<(PathBuf, u16)>,       // likewise for the sound chip
+    pub tape: Option<TapeOptions>,
+    pub sample_rate: u32,                  // of the WAV files sound is captured to
+    pub midi: Option<MidiOptions>,
+    pub sd_card: Option<(PathBuf, u16)>,   // card image, and the address of the SPI controller it sits behind
+    pub disks: Option<DiskOptions>,
+    pub eeprom: Option<(PathBuf, u16)>,    // EEPROM image, and the address of the VIA it is wired to
+    pub leds: Option<(u16, usize)>,        // address, and the number of 7-segment digits
+    pub joystick: Option<(u16, JoystickKeys)>,
+    pub serial_tcp: Option<(String, SerialChip, u16)>, // host address to listen on, the ACIA bridged to it, and its address
+    pub link: Option<(SerialChip, u16)>,               // the ACIA cabled to the same one in the linked machine
+    pub serial_pty: Option<(SerialChip, u16)>,         // the ACIA attached to a pseudo-terminal, and its address
+    pub irq_controller: Option<u16>,
+    pub ansi_screen: Option<AnsiScreenOptions>,
+    pub vsync: Option<(u16, u32)>,         // address of the raster timer, and its frame rate
+    pub host_services: Option<HostServicesOptions>,
+    pub cycle_counter: Option<u16>,
+    pub rng: Option<u16>,
+    #[cfg(feature = "video")]
+    pub video: Option<VideoOptions>,
+}
+
+pub struct HostServicesOptions{
+    pub address: u16,
+    pub block_file: Option<PathBuf>,       // backs the block commands
+    pub file_directory: Option<PathBuf>,   // where the file commands load and save
+}
+
+pub struct MidiOptions{
+    pub file: Option<PathBuf>,             // standard MIDI file
+    pub port: Option<PathBuf>,             // host MIDI port device, written raw
+    pub address: u16,
+}
+
+pub struct TapeOptions{
+    pub playback: Option<PathBuf>,
+    pub recording: Option<PathBuf>,        // WAV file
+    pub address: u16,
+}
+
+pub struct DiskOptions{
+    pub images: [Option<PathBuf>; 2],      // for drives 0 and 1
+    pub geometry: DiskGeometry,
+    pub address: u16,
+}
+
+pub struct AnsiScreenOptions{
+    pub columns: usize,
+    pub rows: usize,
+    pub color: bool,
+    pub address: u16,
+    pub refresh_rate: u32,
+}
+
+pub enum ConsoleEnd{
+    Stdio,
+    File(PathBuf),
+    Tcp(String),    // address to listen on
+}
+
+/// Where the console's input comes from and its output goes, when not stdin and stdout. Either one being set
+/// adds its half of the console even without `char_in` or `char_out`.
+#[derive(Default)]
+pub struct ConsoleOptions{
+    pub input: Option<ConsoleEnd>,
+    pub output: Option<ConsoleEnd>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SerialChip{
+    W65C51,
+    MC6850,
+}
+impl SerialChip{
+    /// The address the chip is usually mapped at.
+    pub fn default_address(&self) -> u16{
+        match self{
+            SerialChip::W65C51 => W65C51::DEFAULT_ADDRESS,
+            SerialChip::MC6850 => MC6850::DEFAULT_ADDRESS,
+        }
+    }
+}
+
+#[cfg(feature = "video")]
+pub struct VideoOptions{
+    pub mode: FramebufferMode,
+    pub address: u16,
+    pub refresh_rate: u32,
+}
+
+/// The devices `attach_devices` attached that a run goes on looking at.
+pub struct AttachedDevices{
+    pub host_services: Option<DeviceId>,
+    pub framebuffer: Option<DeviceId>,     // None without the video feature
+}
+
+/// Attaches the devices. The inputs the host sends go through `input_log`, `rng` draws from `random`, and `link`
+/// cables an ACIA to one attached to `linked`. The interrupt controller takes the keyboard, the ACIA, the raster
+/// timer and the disk controller on fixed lines, highest priority first.
+///
+/// Ruling out a joystick and a keyboard that would both read the terminal is left to the caller.
+pub fn attach_devices(machine: &mut Machine, devices: &DeviceOptions, linked: Option<&mut Machine>, input_log: &mut Option<InputLog>, random: &mut Random) -> Result<AttachedDevices, BoardError>{
+    attach_console(machine, devices, input_log)?;
+    if let Some((path, address)) = &devices.beeper{
+        let wav = WavWriter::create(path, devices.sample_rate)
+            .map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+        machine.attach_device(*address..=address.saturating_add(3), Beeper::new(wav, Beeper::DEFAULT_CLOCK_RATE));
+    }
+    if let Some((path, address)) = &devices.psg{
+        let wav = WavWriter::create(path, devices.sample_rate)
+            .map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+        machine.attach_device(*address..=address.saturating_add(1), AY38910::new(wav, AY38910::DEFAULT_CLOCK_RATE));
+    }
+
+    if let Some(TapeOptions { playback, recording, address }) = &devices.tape{
+        let mut tape = Tape::new(Cassette::DEFAULT_CLOCK_RATE);
+        if let Some(path) = playback{
+            tape = tape.load(path).map_err(|_| BoardError::CouldNotRead(path.clone()))?;
+        }
+        if let Some(path) = recording{
+            let wav = WavWriter::create(path, devices.sample_rate)
+                .map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+            tape = tape.with_recorder(wav);
+        }
+        machine.attach_device(*address..=address.saturating_add(3), Cassette::new(tape));
+    }
+
+    if let Some(MidiOptions { file, port, address }) = &devices.midi{
+        let mut midi = MidiOut::new(MidiOut::DEFAULT_CLOCK_RATE);
+        if let Some(path) = file{
+            let writer = MidiFileWriter::create(path).map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+            midi = midi.with_sink(writer);
+        }
+        if let Some(path) = port{
+            let raw = RawMidi::open(path).map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+            midi = midi.with_sink(raw);
+        }
+        machine.attach_device(*address..=address.saturating_add(1), midi);
+    }
+
+    if let Some((path, address)) = &devices.sd_card{
+        let card = SdCard::open(path).map_err(|_| BoardError::CouldNotRead(path.clone()))?;
+        let mut spi = SpiController::new();
+        spi.attach(card);
+        machine.attach_device(*address..=address.saturating_add(1), spi);
+    }
+
+    if let Some((path, address)) = &devices.eeprom{
+        let eeprom = I2cEeprom::open(I2cWiring::DEFAULT, path).map_err(|_| BoardError::CouldNotRead(path.clone()))?;
+        let mut via = W65C22::new();
+        via.attach(eeprom);
+        machine.attach_device(*address..=address.saturating_add(15), via);
+    }
+
+    if let Some((address, digits)) = devices.leds{
+        let display = LedDisplay::new(LedBank::stderr(digits, LedBank::DEFAULT_REFRESH_RATE));
+        machine.attach_device(address..=address.saturating_add(2), display);
+    }
+
+    if let Some(disks) = &devices.disks{
+        let mut controller = DiskController::new();
+        for (drive, path) in disks.images.iter().enumerate(){
+            if let Some(path) = path{
+                controller.insert_file(drive, path, disks.geometry).map_err(|_| BoardError::CouldNotRead(path.clone()))?;
+            }
+        }
+        machine.attach_device(disks.address..=disks.address.saturating_add(7), controller);
+    }
+
+    if let Some((listen, chip, address)) = &devices.serial_tcp{
+        let link = TcpSerial::bind(listen.as_str()).map_err(|e| BoardError::CouldNotListen(format!("{}: {}", listen, e)))?;
+        match input_log{
+            Some(log) => attach_serial(machine, *chip, *address, log.tap_link("serial", link)),
+            None => attach_serial(machine, *chip, *address, link),
+        };
+    }
+    #[cfg(unix)]
+    if let Some((chip, address)) = devices.serial_pty{
+        let pty = PtySerial::open().map_err(|e| BoardError::CouldNotOpenTerminal(e.to_string()))?;
+        log::info!("Serial port on {}", pty.path().display());
+        match input_log{
+            Some(log) => attach_serial(machine, chip, address, log.tap_link("serial", pty)),
+            None => attach_serial(machine, chip, address, pty),
+        };
+    }
+
+    if let (Some((chip, address)), Some(other)) = (devices.link, linked){
+        let (ours, theirs) = serial::null_modem();
+        attach_serial(machine, chip, address, ours);
+        attach_serial(other, chip, address, theirs);
+    }
+    if let Some(address) = devices.rng{
+        machine.attach_device(address..=address, RandomDevice::new(random.fork()));
+    }
+    if let Some(address) = devices.cycle_counter{
+        machine.attach_device(address..=address.saturating_add(7), CycleCounter::new());
+    }
+
+    let host_services = match &devices.host_services{
+        Some(HostServicesOptions { address, block_file, file_directory }) => {
+            let mut services = HostServices::stdout();
+            if let Some(path) = block_file{
+                services = services.with_block_file(path).map_err(|_| BoardError::CouldNotWrite(path.clone()))?;
+            }
+            if let Some(path) = file_directory{
+                services = services.with_file_directory(path).map_err(|_| BoardError::MissingFile(path.clone()))?;
+            }
+            Some(machine.attach_device(*address..=address.saturating_add(15), services))
+        },
+        None => None,
+    };
+
+    // displays draw at each vertical blank of the raster when there is one
+    let raster = devices.vsync.map(|(address, frame_rate)| {
+        let timer = RasterTimer::new(RasterTimer::DEFAULT_CLOCK_RATE, frame_rate, RasterTimer::DEFAULT_LINES, RasterTimer::DEFAULT_VISIBLE_LINES);
+        let position = timer.position();
+        machine.attach_device(address..=address.saturating_add(7), timer);
+        position
+    });
+
+    if let Some(screen) = &devices.ansi_screen{
+        let last_address = screen.address + (AnsiScreen::size(screen.columns, screen.rows, screen.color) - 1) as u16;
+        let mut ansi_screen = AnsiScreen::stdout(screen.columns, screen.rows, screen.color, screen.refresh_rate);
+        if let Some(position) = &raster{
+            ansi_screen.sync_to(position.clone());
+        }
+        machine.attach_device(screen.address..=last_address, ansi_screen);
+    }
+
+    #[cfg(feature = "video")]
+    let framebuffer = attach_framebuffer(machine, devices, raster, input_log)?;
+    #[cfg(not(feature = "video"))]
+    let framebuffer: Option<DeviceId> = None;
+
+    if let Some(address) = devices.keyboard && framebuffer.is_none(){
+        let keyboard = Keyboard::terminal_via(|keys| tap_input(input_log, "keyboard", keys)).map_err(|e| BoardError::CouldNotOpenTerminal(e.to_string()))?;
+        machine.attach_device(address..=address.saturating_add(1), keyboard);
+    }
+
+    if let Some((address, keys)) = devices.joystick && framebuffer.is_none(){
+        let joystick = Joystick::terminal(keys).map_err(|e| BoardError::CouldNotOpenTerminal(e.to_string()))?;
+        machine.attach_device(address..=address.saturating_add(1), joystick);
+    }
+
+    if let Some(address) = devices.ps2_keyboard{
+        let keyboard = Ps2Keyboard::terminal_via(|keys| tap_input(input_log, "ps2-keyboard", keys)).map_err(|e| BoardError::CouldNotOpenTerminal(e.to_string()))?;
+        let mut via = W65C22::new();
+        via.attach(keyboard);
+        machine.attach_device(address..=address.saturating_add(15), via);
+    }
+
+    if let Some(address) = devices.irq_controller{
+        machine.attach_irq_controller(address);
+
+        // fixed lines, highest priority first
+        let serial = machine.find_device_id::<W65C51>().or(machine.find_device_id::<MC6850>());
+        let sources = [machine.find_device_id::<Keyboard>(), serial, machine.find_device_id::<RasterTimer>(), machine.find_device_id::<DiskController>()];
+        for (line, id) in sources.into_iter().enumerate(){
+            if let Some(id) = id{
+                machine.route_irq(id, line as u8);
+            }
+        }
+    }
+
+    Ok(AttachedDevices { host_services, framebuffer })
+}
+
+fn tap_input(input_log: &mut Option<InputLog>, name: &str, input: Receiver<u8>) -> Receiver<u8>{
+    match input_log{
+        Some(log) => log.tap(name, input),
+        None => input,
+    }
+}
+
+fn attach_console(machine: &mut Machine, devices: &DeviceOptions, input_log: &mut Option<InputLog>) -> Result<(), BoardError>{
+    let ConsoleOptions { input, output } = &devices.console;
+
+    let tcp = [input, output].into_iter().find_map(|end| match end{
+        Some(ConsoleEnd::Tcp(listen)) => Some(listen),
+        _ => None,
+    });
+    let (mut tcp_input, mut tcp_output) = match tcp{
+        Some(listen) => {
+            let (input, output) = char_io::tcp_console(listen.as_str()).map_err(|e| BoardError::CouldNotListen(format!("{}: {}", listen, e)))?;
+            (Some(input), Some(output))
+        },
+        None => (None, None),
+    };
+
+    if let Some(address) = devices.char_out.or(output.as_ref().map(|_| CharOutput::DEFAULT_ADDRESS)){
+        let device = match output{
+            None | Some(ConsoleEnd::Stdio) => CharOutput::stdout(),
+            Some(ConsoleEnd::File(path)) => CharOutput::new(File::create(path).map_err(|_| BoardError::CouldNotWrite(path.clone()))?),
+            Some(ConsoleEnd::Tcp(_)) => CharOutput::new(tcp_output.take().expect("the TCP console is opened when either end uses it")),
+        };
+        machine.attach_device(address..=address, device);
+    }
+    if let Some(address) = devices.char_in.or(input.as_ref().map(|_| CharInput::DEFAULT_ADDRESS)){
+        let device = match input{
+            None | Some(ConsoleEnd::Stdio) => CharInput::new(tap_input(input_log, "console", device::stdin_bytes())),
+            Some(ConsoleEnd::File(path)) => CharInput::from_bytes(&fs::read(path).map_err(|_| BoardError::CouldNotRead(path.clone()))?),
+            Some(ConsoleEnd::Tcp(_)) => CharInput::new(tap_input(input_log, "console", tcp_input.take().expect("the TCP console is opened when either end uses it"))),
+        };
+        machine.attach_device(address..=address.saturating_add(1), device);
+    }
+
+    Ok(())
+}
+
+/// Attaches an ACIA of the chosen kind, connected to the host through `link`.
+fn attach_serial(machine: &mut Machine, chip: SerialChip, address: u16, link: impl SerialLink + 'static) -> DeviceId{
+    match chip{
+        SerialChip::W65C51 => machine.attach_device(address..=address.saturating_add(3), W65C51::new(link)),
+        SerialChip::MC6850 => machine.attach_device(address..=address.saturating_add(1), MC6850::new(link)),
+    }
+}
+
+/// Opens the window and maps its video memory. A keyboard asked for alongside it takes its keys from the window.
+#[cfg(feature = "video")]
+fn attach_framebuffer(machine: &mut Machine, devices: &DeviceOptions, raster: Option<RasterPosition>, input_log: &mut Option<InputLog>) -> Result<Option<DeviceId>, BoardError>{
+    let Some(video) = &devices.video else { return Ok(None) };
+
+    let mut framebuffer = Framebuffer::new(video.mode, video.refresh_rate).map_err(|e| BoardError::CouldNotOpenWindow(e.to_string()))?;
+    if let Some(position) = raster{
+        framebuffer.sync_to(position);
+    }
+    if let Some(address) = devices.keyboard{
+        let (keys, window_keys) = std::sync::mpsc::channel();
+        framebuffer.forward_keys(keys);
+        machine.attach_device(address..=address.saturating_add(1), Keyboard::new(tap_input(input_log, "keyboard", window_keys)));
+    }
+    if let Some((address, keys)) = devices.joystick{
+        let joystick = Joystick::new();
+        framebuffer.drive_joystick(joystick.buttons(), keys);
+        machine.attach_device(address..=address.saturating_add(1), joystick);
+    }
+
+    let last_address = video.address + (video.mode.size() - 1) as u16;
+    Ok(Some(machine.attach_device(video.address..=last_address, framebuffer)))
+}
@@ -1,77 +1,1302 @@
-use crate::memory::memory::{Indexed, RAMSegment, ROMSegment};
+use std::error::Error;
+use std::fmt;
+use std::ops::{BitOr, Bound, Range, RangeBounds, RangeInclusive};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::w65c02s::{DecodedInstruction, W65C02S};
+use crate::bus::access_log::{AccessKind, AccessLogger, BusTransaction};
+use crate::bus::decode_cache::{Cached, DecodeCache};
+use crate::devices::apple1_terminal::Apple1Terminal;
+use crate::devices::device::{Device, DeviceId};
+use crate::devices::hd44780::{HD44780, LcdWiring};
+use crate::devices::irq_controller::IrqController;
+use crate::devices::known::KnownDevice;
+use crate::devices::w65c21::W65C21;
+use crate::devices::w65c22::W65C22;
+use crate::memory::memory::{AccessError, Indexed, MemoryPage, RAMSegment, RAMSnapshot, ROMSegment};
+
+/// A bus access that could not be carried out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BusError{
+    UnmappedRead(u16),
+    UnmappedWrite(u16),
+    ReadFromWriteOnly(u16),
+    WriteToReadOnly(u16),
+    WriteToRom(u16),
+    Segment(AccessError),
+    ReplayDiverged(u16),    // the access is not the one a `Replayer`'s recording has next
+    ReversedMapping(u8, u8),    // first and last page of a mapping whose range runs backwards
+}
+impl fmt::Display for BusError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            BusError::UnmappedRead(address) => write!(f, "attempted to read from unmapped memory at address {:04X}", address),
+            BusError::UnmappedWrite(address) => write!(f, "attempted to write to unmapped memory at address {:04X}", address),
+            BusError::ReadFromWriteOnly(address) => write!(f, "attempted to read from write-only memory at address {:04X}", address),
+            BusError::WriteToReadOnly(address) => write!(f, "attempted to write to read-only memory at address {:04X}", address),
+            BusError::WriteToRom(address) => write!(f, "attempted to write to ROM at address {:04X}", address),
+            BusError::Segment(e) => write!(f, "segment access failed: {}", e),
+            BusError::ReplayDiverged(address) => write!(f, "access at address {:04X} does not match the recording", address),
+            BusError::ReversedMapping(first, last) => write!(f, "mapping runs backwards, from page {:02X} to page {:02X}", first, last),
+        }
+    }
+}
+impl BusError{
+    /// The address the access was made to, if it was to one.
+    pub fn address(&self) -> Option<u16>{
+        match self{
+            BusError::UnmappedRead(address) | BusError::UnmappedWrite(address) | BusError::ReadFromWriteOnly(address)
+                | BusError::WriteToReadOnly(address) | BusError::WriteToRom(address) | BusError::ReplayDiverged(address) => Some(*address),
+            BusError::Segment(_) | BusError::ReversedMapping(..) => None,
+        }
+    }
+}
+impl Error for BusError{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self{
+            BusError::Segment(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<AccessError> for BusError{
+    fn from(e: AccessError) -> Self {
+        BusError::Segment(e)
+    }
+}
 
 pub trait Bus{
-    fn read(&mut self, address: u16) -> u8;
-    fn write(&mut self, address: u16, val: u8);
+    fn read(&mut self, address: u16) -> Result<u8, BusError>;
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError>;
+
+    /// Attributes of the region mapped at the given address. Buses without regions report none.
+    fn attributes(&self, _address: u16) -> RegionAttributes{
+        RegionAttributes::NONE
+    }
+
+    /// Called by the CPU before each opcode fetch with the address of the instruction about to run.
+    fn begin_instruction(&mut self, _pc: u16){ }
+    /// The instruction at `address` already decoded, from a bus that keeps those of memory that cannot change,
+    /// for the CPU to execute without fetching its bytes. None has it fetched through `read`, as by default.
+    fn decoded_instruction(&mut self, _address: u16) -> Option<DecodedInstruction>{
+        None
+    }
+    /// Called by the CPU after each instruction with the number of cycles it took.
+    fn tick(&mut self, _cycles: u32){ }
+    /// Level of the (active low, wired-OR) IRQ line; true while any source asserts it.
+    fn irq(&self) -> bool{
+        false
+    }
+    /// Level of the NMI line; true while any source asserts it. The CPU responds to it becoming asserted.
+    fn nmi(&self) -> bool{
+        false
+    }
+    /// Level of the RESET line; true while any source asserts it. The CPU responds to it becoming asserted, ahead
+    /// of any NMI or IRQ.
+    fn reset(&self) -> bool{
+        false
+    }
+    /// Whether ticking the bus does nothing and neither interrupt line can be asserted, so that the CPU may skip
+    /// both between instructions. Buses that cannot tell say no.
+    fn is_quiet(&self) -> bool{
+        false
+    }
+
+    //#GROUP: little-endian helpers, all wrapping at the top of the address space
+    fn read_u16(&mut self, address: u16) -> Result<u16, BusError>{
+        let low = self.read(address)? as u16;
+        let high = self.read(address.wrapping_add(1))? as u16;
+
+        Ok((high << 8) | low)
+    }
+    fn write_u16(&mut self, address: u16, val: u16) -> Result<(), BusError>{
+        self.write(address, (val & 0xff) as u8)?;
+        self.write(address.wrapping_add(1), (val >> 8) as u8)
+    }
+    fn read_u32(&mut self, address: u16) -> Result<u32, BusError>{
+        let low = self.read_u16(address)? as u32;
+        let high = self.read_u16(address.wrapping_add(2))? as u32;
+
+        Ok((high << 16) | low)
+    }
+    fn write_u32(&mut self, address: u16, val: u32) -> Result<(), BusError>{
+        self.write_u16(address, (val & 0xffff) as u16)?;
+        self.write_u16(address.wrapping_add(2), (val >> 16) as u16)
+    }
+
+    /// Reads a pointer stored in zero page. The high byte of a pointer at $FF comes from $00, as on the 6502.
+    fn read_zp_pointer(&mut self, zp_address: u8) -> Result<u16, BusError>{
+        let low = self.read(zp_address as u16)? as u16;
+        let high = self.read(zp_address.wrapping_add(1) as u16)? as u16;
+
+        Ok((high << 8) | low)
+    }
+    /// Reads the byte that the pointer stored at `pointer` refers to.
+    fn read_indirect(&mut self, pointer: u16) -> Result<u8, BusError>{
+        let target = self.read_u16(pointer)?;
+        self.read(target)
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B{
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        (**self).read(address)
+    }
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError> {
+        (**self).write(address, val)
+    }
+    fn attributes(&self, address: u16) -> RegionAttributes {
+        (**self).attributes(address)
+    }
+    fn begin_instruction(&mut self, pc: u16) {
+        (**self).begin_instruction(pc)
+    }
+    fn decoded_instruction(&mut self, address: u16) -> Option<DecodedInstruction> {
+        (**self).decoded_instruction(address)
+    }
+    fn tick(&mut self, cycles: u32) {
+        (**self).tick(cycles)
+    }
+    fn irq(&self) -> bool {
+        (**self).irq()
+    }
+    fn nmi(&self) -> bool {
+        (**self).nmi()
+    }
+    fn reset(&self) -> bool {
+        (**self).reset()
+    }
+    fn is_quiet(&self) -> bool {
+        (**self).is_quiet()
+    }
+}
+
+/// Set of access attributes carried by a mapped region of the address space.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegionAttributes(u8);
+impl RegionAttributes{
+    pub const NONE: Self = Self(0);
+    pub const READ_ONLY: Self = Self(0b0000_0001);      // writes are rejected
+    pub const WRITE_ONLY: Self = Self(0b0000_0010);     // reads are rejected
+    pub const NO_EXECUTE: Self = Self(0b0000_0100);     // opcode fetches are rejected
+    pub const SIDE_EFFECTS: Self = Self(0b0000_1000);   // reading changes device state, so it should not be inspected
+
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool{
+        (self.0 & other.0) == other.0
+    }
+    #[inline]
+    pub const fn union(self, other: Self) -> Self{
+        Self(self.0 | other.0)
+    }
+}
+impl BitOr for RegionAttributes{
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 enum Page{
     Unmapped,
     RAM {page_relative: usize},
-    ROM {page_relative: usize},
+    ROM {segment: usize, page_relative: usize},
     //IODevice,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SegmentKind{
+    RAM,
+    ROM(usize),     // index of the ROM segment within the machine
+}
+
+/// A run of pages decoded to a segment. Where mappings overlap, the one with the highest
+/// priority decodes the page; ties go to the mapping added last.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mapping{
+    kind: SegmentKind,
+    pages: RangeInclusive<u8>,
+    segment_page: usize,    // page within the segment that the first mapped page decodes to
+    priority: u8,
+    write_through: bool,    // reads decode here, writes fall through to the next mapping below
+}
+impl Mapping{
+    pub fn new(kind: SegmentKind, pages: RangeInclusive<u8>, segment_page: usize) -> Self{
+        Self { kind, pages, segment_page, priority: 0, write_through: false }
+    }
+    pub fn with_priority(mut self, priority: u8) -> Self{
+        self.priority = priority;
+        self
+    }
+    pub fn write_through(mut self) -> Self{
+        self.write_through = true;
+        self
+    }
+
+    fn decode(&self, page: u8) -> Page{
+        let page_relative = self.segment_page + (page - self.pages.start()) as usize;
+        match self.kind{
+            SegmentKind::RAM => Page::RAM { page_relative },
+            SegmentKind::ROM(segment) => Page::ROM { segment, page_relative },
+        }
+    }
+}
+
+/// Where a ROM image smaller than its window is placed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RomPlacement{
+    #[default]
+    WindowStart,    // first byte at the start of the window
+    AlignToVectors, // last byte at $FFFF, as with a small EEPROM decoded at the top of memory
+}
+
+#[derive(Debug)]
+pub enum RomImageError{
+    TooLarge(usize),
+    VectorsNotInImage,
+    ResetVectorOutsideImage(u16),
+    EntryOutsideImage(u16),
+    Bus(BusError),
+}
+impl fmt::Display for RomImageError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            RomImageError::TooLarge(size) => write!(f, "image of {} bytes is larger than the ROM", size),
+            RomImageError::VectorsNotInImage => write!(f, "the vectors are not in the image"),
+            RomImageError::ResetVectorOutsideImage(address) => write!(f, "reset vector points outside the image, to {:04X}", address),
+            RomImageError::EntryOutsideImage(address) => write!(f, "entry is outside the image, at {:04X}", address),
+            RomImageError::Bus(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A byte that differs between two machines. `None` is an address that is not mapped, or not inspectable, in that machine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryDifference{
+    pub address: u16,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+}
+impl fmt::Display for MemoryDifference{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let byte = |val: Option<u8>| val.map_or("unmapped".to_string(), |val| format!("0x{:02X}", val));
+        write!(f, "byte ${:04X} expected {} got {}", self.address, byte(self.expected), byte(self.actual))
+    }
+}
+
+type ReadHandler = Box<dyn FnMut(u16) -> u8>;
+type WriteHandler = Box<dyn FnMut(u16, u8)>;
+
+/// Host closures standing in for a device at a handful of addresses.
+struct RegisterHandler{
+    base: u16,
+    on_read: ReadHandler,
+    on_write: WriteHandler,
+}
+impl Device for RegisterHandler{
+    fn read(&mut self, offset: u16) -> u8 {
+        (self.on_read)(self.base.wrapping_add(offset))
+    }
+    fn write(&mut self, offset: u16, val: u8) {
+        (self.on_write)(self.base.wrapping_add(offset), val)
+    }
+}
+
+struct MappedDevice{
+    addresses: RangeInclusive<u16>,
+    device: KnownDevice,
+    irq_route: IrqRoute,
+}
+
+/// Where a device's IRQ output goes.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum IrqRoute{
+    Cpu,                // straight to the CPU's IRQ line
+    Controller(u8),     // to a line of the interrupt controller
+    Unconnected,        // nowhere, as on boards that leave the pin open
+}
+
+/// A machine's RAM and ROM as seen by a device accessing memory directly (DMA). Attributes apply as they do to
+/// the CPU, but other devices cannot be reached and nothing is logged.
+pub struct GuestMemory<'a>{
+    ram: &'a mut RAMSegment,
+    roms: &'a [ROMSegment],
+    read_map: &'a [Page; 256],
+    write_map: &'a [Page; 256],
+    attributes: &'a [RegionAttributes; 256],
+}
+impl GuestMemory<'_>{
+    pub fn read(&self, address: u16) -> Result<u8, BusError>{
+        let (page, offset) = split_address(address);
+        if self.attributes[page].contains(RegionAttributes::WRITE_ONLY){
+            return Err(BusError::ReadFromWriteOnly(address));
+        }
+
+        match self.read_map[page]{
+            Page::ROM { segment, page_relative } => Ok(self.roms[segment].peek_page_offset(page_relative, offset)),
+            Page::RAM { page_relative } => Ok(self.ram.peek_page_offset(page_relative, offset)),
+            Page::Unmapped => Err(BusError::UnmappedRead(address)),
+        }
+    }
+    pub fn write(&mut self, address: u16, val: u8) -> Result<(), BusError>{
+        let (page, offset) = split_address(address);
+        if self.attributes[page].contains(RegionAttributes::READ_ONLY){
+            return Err(BusError::WriteToReadOnly(address));
+        }
+
+        match self.write_map[page]{
+            Page::RAM { page_relative } => self.ram.write_page_offset(page_relative, offset, val),
+            Page::ROM { .. } => return Err(BusError::WriteToRom(address)),
+            Page::Unmapped => return Err(BusError::UnmappedWrite(address)),
+        }
+
+        Ok(())
+    }
+}
+
 fn split_address(address: u16) -> (usize, u8){
     ((address >> 8) as usize, (address & 0xff) as u8)
 }
+/// Resolves a range of addresses into a half-open span wide enough to reach the end of the address space.
+fn address_span(range: impl RangeBounds<u16>) -> Range<u32>{
+    let start = match range.start_bound(){
+        Bound::Included(&s) => s as u32,
+        Bound::Excluded(&s) => s as u32 + 1,
+        Bound::Unbounded => 0x0000,
+    };
+    let end = match range.end_bound(){
+        Bound::Included(&e) => e as u32 + 1,
+        Bound::Excluded(&e) => e as u32,
+        Bound::Unbounded => 0x10000,
+    };
+
+    start..end
+}
+
+/// What a machine holds: its RAM and ROM, how they are mapped, the page attributes, and the state of each device
+/// that saves one, in the order they were attached. The decoded page tables are rebuilt from the mappings.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+pub struct MachineState{
+    roms: Vec<ROMSegment>,
+    ram: RAMSegment,
+    mappings: Vec<Mapping>,
+    #[serde(with = "crate::memory::array")]
+    attributes: [RegionAttributes; 256],
+    devices: Vec<Option<serde_json::Value>>,
+}
 
 pub struct Machine{
-    rom: ROMSegment,
+    roms: Vec<ROMSegment>,
     ram: RAMSegment,
 
-    page_map: [Page; 256],
+    mappings: Vec<Mapping>,
+    read_map: [Page; 256],
+    write_map: [Page; 256],
+    attributes: [RegionAttributes; 256],
+
+    devices: Vec<MappedDevice>,
+    device_pages: [bool; 256],      // pages with at least one device, so other pages skip the search
+    irq_controller: Option<DeviceId>,
+
+    loggers: Vec<AccessLogger>,
+    instruction_pc: u16,
+    decode_cache: DecodeCache,      // instructions decoded from ROM
+    low_pages: [Option<usize>; 2],  // RAM page behind the zero page and the stack, while nothing else can get in the way
 }
 impl Machine{
     /// ram pages: 0x00 -> 0x7f, total address space: 0x0000 -> 0x7fff (32kb)
     /// rom pages: 0x80 -> 0xff, total address space: 0x8000 -> 0xffff (32kb)
-    pub fn new_32k_ram_32k_rom(rom_image: &[u8]) -> Self{
-        let ram = RAMSegment::new(128);
-        let mut rom = ROMSegment::new(128);
-        match rom.load(rom_image){
-            Ok(_) => {},
-            Err(_) => panic!("ROM image ({:X} bytes) exceeded size of ROM ({:X} bytes)", rom_image.len(), rom.len()),
+    pub fn new_32k_ram_32k_rom(rom_image: &[u8]) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(128);
+        machine.add_rom_window(0x80, 128, rom_image)?;
+
+        Ok(machine)
+    }
+
+    /// ram pages: 0x00 -> 0x3f, total address space: 0x0000 -> 0x3fff (16kb)
+    /// i/o hole:  0x40 -> 0xbf, unmapped and non-executable, for registers added with `map_register(s)`
+    /// rom pages: 0xc0 -> 0xff, total address space: 0xc000 -> 0xffff (16kb)
+    pub fn new_16k_ram_16k_rom(rom_image: &[u8]) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(64);
+        machine.set_attributes(0x40..=0xbf, RegionAttributes::NO_EXECUTE);
+        machine.add_rom_window(0xc0, 64, rom_image)?;
+
+        Ok(machine)
+    }
+
+    /// ram pages: 0x00 -> 0xbf, total address space: 0x0000 -> 0xbfff (48kb)
+    /// i/o hole:  0xc0 -> 0xdf, unmapped and non-executable, for registers added with `map_register(s)`
+    /// rom pages: 0xe0 -> 0xff, total address space: 0xe000 -> 0xffff (8kb monitor)
+    pub fn new_48k_ram_8k_rom(monitor_image: &[u8]) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(192);
+        machine.set_attributes(0xc0..=0xdf, RegionAttributes::NO_EXECUTE);
+        machine.add_rom_window(0xe0, 32, monitor_image)?;
+
+        Ok(machine)
+    }
+
+    /// Ben Eater's breadboard computer.
+    /// ram pages: 0x00 -> 0x3f, total address space: 0x0000 -> 0x3fff (16kb)
+    /// via:       0x6000 -> 0x7fff, registers mirrored every 16 bytes, with an HD44780 LCD on its ports
+    /// rom pages: 0x80 -> 0xff, total address space: 0x8000 -> 0xffff (32kb)
+    pub fn new_ben_eater(rom_image: &[u8]) -> Result<Self, BusError>{
+        Self::ben_eater(rom_image, LcdWiring::BEN_EATER)
+    }
+    /// Ben Eater's breadboard computer as later rewired, with the LCD on port B alone, driven in 4-bit mode.
+    pub fn new_ben_eater_4bit(rom_image: &[u8]) -> Result<Self, BusError>{
+        Self::ben_eater(rom_image, LcdWiring::BEN_EATER_4BIT)
+    }
+    fn ben_eater(rom_image: &[u8], wiring: LcdWiring) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(64);
+        machine.add_rom_window(0x80, 128, rom_image)?;
+
+        let mut via = W65C22::new();
+        via.attach(HD44780::new(wiring));
+        machine.attach_device(0x6000..=0x7fff, via);
+
+        Ok(machine)
+    }
+
+    /// Apple 1 with its memory expanded to the full 4K BASIC setup.
+    /// ram pages: 0x00 -> 0xef, total address space: 0x0000 -> 0xefff (60kb), leaving room for BASIC at 0xe000
+    /// pia:       0xd010 -> 0xd013, keyboard on port A and display on port B, connected to the host's stdin/stdout
+    ///            its IRQ outputs are not wired to the CPU, as on the real board, where Wozmon enables them anyway
+    /// rom pages: 0xff, total address space: 0xff00 -> 0xffff (256 byte monitor, e.g. Wozmon)
+    pub fn new_apple1(monitor_image: &[u8]) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(0xf0);
+        machine.add_rom_window(0xff, 1, monitor_image)?;
+
+        let mut pia = W65C21::new();
+        pia.attach(Apple1Terminal::stdio());
+        let pia = machine.attach_device(0xd010..=0xd013, pia);
+        machine.disconnect_irq(pia);
+
+        Ok(machine)
+    }
+
+    /// ram pages: 0x00 -> 0xff, total address space: 0x0000 -> 0xffff (64kb)
+    /// The image is loaded from address 0x0000, vectors included, and stays writable; the usual setup for CPU test suites.
+    pub fn new_64k_ram(image: &[u8]) -> Result<Self, BusError>{
+        let mut machine = Self::with_ram(256);
+        machine.load_ram(image)?;
+
+        Ok(machine)
+    }
+
+    /// Maps a fixed-size, read-only ROM window with the image loaded from its start.
+    fn add_rom_window(&mut self, first_page: u8, num_pages: usize, rom_image: &[u8]) -> Result<(), BusError>{
+        let mut rom = ROMSegment::new(num_pages);
+        rom.load(rom_image)?;
+
+        let last_page = first_page + (num_pages - 1) as u8;
+        let segment = self.roms.len();
+        self.roms.push(rom);
+        self.map(Mapping::new(SegmentKind::ROM(segment), first_page..=last_page, 0))?;
+        self.protect_rom(first_page..=last_page);
+
+        Ok(())
+    }
+
+    /// Same layout as `new_32k_ram_32k_rom`, but accepts images smaller than the ROM window and checks that
+    /// the reset vector is part of the image and points back into it.
+    pub fn new_32k_ram_32k_rom_placed(rom_image: &[u8], placement: RomPlacement) -> Result<Self, RomImageError>{
+        Self::place_32k_rom(rom_image, placement, None)
+    }
+    /// Same as `new_32k_ram_32k_rom_placed`, for an image without vectors that is entered at `entry` instead;
+    /// only checks that `entry` is part of the image.
+    pub fn new_32k_ram_32k_rom_entered_at(rom_image: &[u8], placement: RomPlacement, entry: u16) -> Result<Self, RomImageError>{
+        Self::place_32k_rom(rom_image, placement, Some(entry))
+    }
+    fn place_32k_rom(rom_image: &[u8], placement: RomPlacement, entry: Option<u16>) -> Result<Self, RomImageError>{
+        const WINDOW_START: usize = 0x8000;
+        const WINDOW_SIZE: usize = 0x8000;
+
+        if rom_image.len() > WINDOW_SIZE{
+            return Err(RomImageError::TooLarge(rom_image.len()));
+        }
+
+        let offset = match placement{
+            RomPlacement::WindowStart => 0,
+            RomPlacement::AlignToVectors => WINDOW_SIZE - rom_image.len(),
+        };
+        let image_span = (WINDOW_START + offset)..(WINDOW_START + offset + rom_image.len());
+
+        match entry{
+            Some(entry) => {
+                if !image_span.contains(&(entry as usize)){
+                    return Err(RomImageError::EntryOutsideImage(entry));
+                }
+            },
+            None => {
+                let reset_low = W65C02S::RESB_LOW as usize;
+                if !image_span.contains(&reset_low) || !image_span.contains(&(reset_low + 1)){
+                    return Err(RomImageError::VectorsNotInImage);
+                }
+                let entry = u16::from_le_bytes([rom_image[reset_low - image_span.start], rom_image[reset_low + 1 - image_span.start]]);
+                if !image_span.contains(&(entry as usize)){
+                    return Err(RomImageError::ResetVectorOutsideImage(entry));
+                }
+            },
+        }
+
+        let mut placed = vec![0u8; offset];
+        placed.extend_from_slice(rom_image);
+
+        Self::new_32k_ram_32k_rom(&placed).map_err(RomImageError::Bus)
+    }
+
+    /// RAM mapped from page 0x00 upwards, everything above it left unmapped for ROMs to be added.
+    /// Panics if `ram_pages` is more than the 256 pages of the address space.
+    pub fn with_ram(ram_pages: usize) -> Self{
+        assert!(ram_pages <= 256, "{} pages of RAM do not fit in the address space", ram_pages);
+        let mut machine = Self::with_unmapped_ram(ram_pages);
+        if ram_pages > 0{
+            machine.map(Mapping::new(SegmentKind::RAM, 0x00..=((ram_pages - 1) as u8), 0))
+                .expect("RAM segment is created with exactly the mapped number of pages");
+        }
+
+        machine
+    }
+    /// A RAM segment that nothing decodes to yet; map its pages wherever the board puts them with `map`.
+    pub fn with_unmapped_ram(ram_pages: usize) -> Self{
+        Self {
+            ram: RAMSegment::new(ram_pages),
+            roms: Vec::new(),
+            mappings: Vec::new(),
+            read_map: [Page::Unmapped; 256],
+            write_map: [Page::Unmapped; 256],
+            attributes: [RegionAttributes::NONE; 256],
+            devices: Vec::new(),
+            device_pages: [false; 256],
+            irq_controller: None,
+            loggers: Vec::new(),
+            instruction_pc: 0,
+            decode_cache: DecodeCache::new(),
+            low_pages: [None; 2],
+        }
+    }
+
+    /// Adds a ROM segment sized to the image (rounded up to whole pages) and maps it read-only starting at
+    /// the given page. Returns the index of the new segment.
+    pub fn add_rom(&mut self, first_page: u8, image: &[u8]) -> Result<usize, BusError>{
+        let num_pages = image.len().div_ceil(MemoryPage::SIZE);
+        // checked before the last page is worked out, which an empty image has none of
+        if num_pages == 0 || first_page as usize + num_pages > 0x100{
+            return Err(AccessError::OutOfRange((first_page as usize * MemoryPage::SIZE) + image.len()).into());
+        }
+        let last_page = first_page as usize + num_pages - 1;
+
+        let mut rom = ROMSegment::new(num_pages);
+        rom.load(image)?;
+
+        let segment = self.roms.len();
+        self.roms.push(rom);
+        self.map(Mapping::new(SegmentKind::ROM(segment), first_page..=(last_page as u8), 0))?;
+        self.protect_rom(first_page..=(last_page as u8));
+
+        Ok(segment)
+    }
+    /// Marks the pages of a freshly mapped ROM read-only, except those whose writes still decode to RAM through
+    /// a mapping of higher priority, which keep their attributes.
+    fn protect_rom(&mut self, pages: RangeInclusive<u8>){
+        for page in pages{
+            if !matches!(self.write_map[page as usize], Page::RAM { .. }){
+                let attributes = self.attributes[page as usize] | RegionAttributes::READ_ONLY;
+                self.set_attributes(page..=page, attributes);
+            }
+        }
+    }
+
+    /// Adds a mapping on top of the existing ones and re-decodes the pages it covers.
+    pub fn map(&mut self, mapping: Mapping) -> Result<(), BusError>{
+        let segment_pages = match mapping.kind{
+            SegmentKind::RAM => self.ram.len() / MemoryPage::SIZE,
+            SegmentKind::ROM(segment) => self.roms.get(segment).map_or(0, |r| r.len() / MemoryPage::SIZE),
+        };
+        if mapping.pages.start() > mapping.pages.end(){
+            return Err(BusError::ReversedMapping(*mapping.pages.start(), *mapping.pages.end()));
+        }
+        let mapped_pages = (mapping.pages.end() - mapping.pages.start()) as usize + 1;
+        if mapping.segment_page + mapped_pages > segment_pages{
+            return Err(AccessError::OutOfRange((mapping.segment_page + mapped_pages) * MemoryPage::SIZE).into());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(kind = ?mapping.kind, pages = ?mapping.pages, segment_page = mapping.segment_page, priority = mapping.priority, "mapped");
+        let pages = mapping.pages.clone();
+        self.mappings.push(mapping);
+        for page in pages{
+            self.decode_page(page);
+        }
+
+        Ok(())
+    }
+    fn decode_page(&mut self, page: u8){
+        let mut read: Option<&Mapping> = None;
+        let mut write: Option<&Mapping> = None;
+
+        // walk newest first so that ties in priority go to the most recent mapping
+        for mapping in self.mappings.iter().rev().filter(|m| m.pages.contains(&page)){
+            if read.is_none_or(|r| mapping.priority > r.priority){
+                read = Some(mapping);
+            }
+            if !mapping.write_through && write.is_none_or(|w| mapping.priority > w.priority){
+                write = Some(mapping);
+            }
         }
 
-        let mut map = [Page::Unmapped; 256];
+        self.read_map[page as usize] = read.map_or(Page::Unmapped, |m| m.decode(page));
+        self.decode_cache.empty(page as usize);
+        self.write_map[page as usize] = write.map_or(Page::Unmapped, |m| m.decode(page));
+        self.update_low_pages();
+    }
+    /// Works out which of the zero page and the stack are plain RAM, read and written at the same page with no
+    /// device, attribute or logger to go through, so that `read` and `write` can reach them directly.
+    fn update_low_pages(&mut self){
+        for page in 0..self.low_pages.len(){
+            let guarded = self.device_pages[page] || !self.loggers.is_empty()
+                || self.attributes[page].contains(RegionAttributes::READ_ONLY)
+                || self.attributes[page].contains(RegionAttributes::WRITE_ONLY);
+            self.low_pages[page] = match (self.read_map[page], self.write_map[page]){
+                (Page::RAM { page_relative }, Page::RAM { page_relative: written }) if page_relative == written && !guarded => Some(page_relative),
+                _ => None,
+            };
+        }
+    }
 
-        // init ram in page_map
-        for page in 0x00usize..=0x7fusize{
-            map[page] = Page::RAM { page_relative: page };
+    /// Replaces the attributes of every page in the given range (inclusive, by page number).
+    pub fn set_attributes(&mut self, pages: RangeInclusive<u8>, attributes: RegionAttributes){
+        #[cfg(feature = "tracing")]
+        tracing::debug!(pages = ?pages, attributes = ?attributes, "attributes set");
+        for page in pages{
+            self.attributes[page as usize] = attributes;
+            self.decode_cache.empty(page as usize);
         }
+        self.update_low_pages();
+    }
 
-        // init ram in page_map
-        for page in 0x80usize..=0xffusize{
-            map[page] = Page::ROM { page_relative: page - 0x80 };
+    /// Routes CPU accesses to a single address to host closures, ahead of any mapping or attribute.
+    pub fn map_register(&mut self, address: u16, mut on_read: impl FnMut() -> u8 + 'static, mut on_write: impl FnMut(u8) + 'static){
+        self.map_registers(address..=address, move |_| on_read(), move |_, val| on_write(val));
+    }
+    /// Routes CPU accesses within a range to host closures, which receive the accessed address.
+    /// Later handlers take precedence over earlier ones where they overlap.
+    pub fn map_registers(&mut self, addresses: RangeInclusive<u16>, on_read: impl FnMut(u16) -> u8 + 'static, on_write: impl FnMut(u16, u8) + 'static){
+        let base = *addresses.start();
+        self.attach_device(addresses, RegisterHandler { base, on_read: Box::new(on_read), on_write: Box::new(on_write) });
+    }
+
+    /// Maps a device over a range of addresses, ahead of any mapping or attribute. Devices attached later
+    /// take precedence over earlier ones where they overlap.
+    pub fn attach_device(&mut self, addresses: RangeInclusive<u16>, device: impl Device) -> DeviceId{
+        for page in split_address(*addresses.start()).0..=split_address(*addresses.end()).0{
+            self.device_pages[page] = true;
+            self.decode_cache.empty(page);
         }
+        self.update_low_pages();
 
-        Self { ram: ram, rom: rom, page_map: map }
+        self.devices.push(MappedDevice { addresses, device: KnownDevice::new(device), irq_route: IrqRoute::Cpu });
+        DeviceId(self.devices.len() - 1)
+    }
+    pub fn device<T: Device>(&self, id: DeviceId) -> Option<&T>{
+        self.devices.get(id.0)?.device.as_any().downcast_ref::<T>()
+    }
+    pub fn device_mut<T: Device>(&mut self, id: DeviceId) -> Option<&mut T>{
+        self.devices.get_mut(id.0)?.device.as_any_mut().downcast_mut::<T>()
+    }
+    /// The first attached device of the given type.
+    pub fn find_device<T: Device>(&self) -> Option<&T>{
+        self.devices.iter().find_map(|d| d.device.as_any().downcast_ref::<T>())
+    }
+    pub fn find_device_mut<T: Device>(&mut self) -> Option<&mut T>{
+        self.devices.iter_mut().find_map(|d| d.device.as_any_mut().downcast_mut::<T>())
+    }
+    pub fn find_device_id<T: Device>(&self) -> Option<DeviceId>{
+        self.devices.iter().position(|d| d.device.as_any().is::<T>()).map(DeviceId)
+    }
+
+    /// Maps an interrupt controller that devices' IRQs can then be routed through with `route_irq`.
+    /// Replaces the routing target of any controller attached before.
+    pub fn attach_irq_controller(&mut self, address: u16) -> DeviceId{
+        let id = self.attach_device(address..=address.saturating_add(3), IrqController::new());
+        self.irq_controller = Some(id);
+        id
+    }
+    /// Routes a device's IRQ through line 0-7 of the interrupt controller instead of straight to the CPU.
+    /// Returns false, changing nothing, if there is no such device or line.
+    pub fn route_irq(&mut self, id: DeviceId, line: u8) -> bool{
+        match self.devices.get_mut(id.0){
+            Some(mapped) if line < 8 && Some(id) != self.irq_controller => {
+                mapped.irq_route = IrqRoute::Controller(line);
+                true
+            },
+            _ => false,
+        }
+    }
+    /// Leaves a device's IRQ output unconnected, so that it never interrupts the CPU, as on boards that do not
+    /// wire it. Returns false if there is no such device.
+    pub fn disconnect_irq(&mut self, id: DeviceId) -> bool{
+        match self.devices.get_mut(id.0){
+            Some(mapped) => {
+                mapped.irq_route = IrqRoute::Unconnected;
+                true
+            },
+            None => false,
+        }
     }
 
-    pub fn load_ram(&mut self, bytes: &[u8]){
-        self.ram.load(bytes);
+    #[inline]
+    fn device_for(&mut self, address: u16) -> Option<(&mut KnownDevice, u16)>{
+        if !self.device_pages[split_address(address).0]{
+            return None;
+        }
+
+        self.devices.iter_mut().rev()
+            .find(|d| d.addresses.contains(&address))
+            .map(|d| (&mut d.device, address - d.addresses.start()))
+    }
+
+    /// Decodes the instruction at `address` into the cache, if it is in ROM that reads without side effects and
+    /// lies within one page, and no logger has to see it fetched.
+    fn decode_for_cache(&mut self, address: u16) -> Option<DecodedInstruction>{
+        let (page, offset) = split_address(address);
+        if !self.loggers.is_empty() || !matches!(self.read_map[page], Page::ROM { .. }){
+            self.decode_cache.set_uncached(page);
+            return None;
+        }
+
+        let byte = |i: u16| self.peek(address.wrapping_add(i)).filter(|_| offset as u16 + i <= 0xff);
+        let decoded = byte(0)
+            .map(|opcode| DecodedInstruction::decode([opcode, byte(1).unwrap_or(0), byte(2).unwrap_or(0)]))
+            .filter(|decoded| (1..decoded.length() as u16).all(|i| byte(i).is_some()));
+        self.decode_cache.insert(address, decoded);
+        decoded
+    }
+
+    /// Changes whenever instructions decoded from ROM are dropped, so that code compiled from them can tell it
+    /// is stale.
+    #[cfg(feature = "jit")]
+    pub(crate) fn decode_generation(&self) -> u64{
+        self.decode_cache.generation()
+    }
+
+    /// Streams every subsequent bus transaction accepted by the logger to its sink.
+    pub fn attach_logger(&mut self, logger: AccessLogger){
+        // fetches the cache would save must reach the logger
+        self.decode_cache.empty_all();
+        self.loggers.push(logger);
+        self.update_low_pages();
+    }
+    /// Logs a fault on the way to the CPU, with the instruction that caused it.
+    fn fault(&self, error: BusError) -> BusError{
+        log::debug!("${:04X}: {}", self.instruction_pc, error);
+        error
+    }
+    fn log_access(&mut self, address: u16, value: u8, kind: AccessKind){
+        if self.loggers.is_empty(){
+            return;
+        }
+
+        let transaction = BusTransaction { address, value, kind, pc: self.instruction_pc };
+        for logger in self.loggers.iter_mut(){
+            logger.log(&transaction);
+        }
+    }
+
+    /// Writes `image` from `address` on, into RAM and ROM alike, as the payload of a Commodore `.prg` file is loaded.
+    pub fn load_at(&mut self, address: u16, image: &[u8]) -> Result<(), BusError>{
+        if address as usize + image.len() > 0x10000{
+            return Err(AccessError::OutOfRange(address as usize + image.len()).into());
+        }
+        for (address, byte) in (address..=u16::MAX).zip(image){
+            self.poke(address, *byte)?;
+        }
+        Ok(())
+    }
+    pub fn load_ram(&mut self, bytes: &[u8]) -> Result<(), BusError>{
+        Ok(self.ram.load(bytes)?)
     }
     pub fn ram_contents(&self) -> Box<[u8]>{
         self.ram.contents()
     }
+    /// Reads a byte without any bus side effects. Unmapped, write-only and side-effecting pages read as `None`.
+    pub fn peek(&self, address: u16) -> Option<u8>{
+        let (page, offset) = split_address(address);
+        if self.device_pages[page] && self.devices.iter().any(|d| d.addresses.contains(&address)){
+            return None;
+        }
+        if self.attributes[page].contains(RegionAttributes::WRITE_ONLY) || self.attributes[page].contains(RegionAttributes::SIDE_EFFECTS){
+            return None;
+        }
+
+        match self.read_map[page]{
+            Page::ROM { segment, page_relative } => Some(self.roms[segment].peek_page_offset(page_relative, offset)),
+            Page::RAM { page_relative } => Some(self.ram.peek_page_offset(page_relative, offset)),
+            Page::Unmapped => None,
+        }
+    }
+    /// Stores a byte into whichever segment the CPU would read the address from, ROM included.
+    /// Bypasses attributes and access loggers.
+    pub fn poke(&mut self, address: u16, val: u8) -> Result<(), BusError>{
+        let (page, offset) = split_address(address);
+        match self.read_map[page]{
+            Page::ROM { segment, page_relative } => {
+                self.roms[segment].program_page_offset(page_relative, offset, val);
+                // the ROM page may be mapped at more than one place
+                for (other, decoded) in self.read_map.iter().enumerate(){
+                    if matches!(decoded, Page::ROM { segment: s, page_relative: p } if *s == segment && *p == page_relative){
+                        self.decode_cache.empty(other);
+                    }
+                }
+            },
+            Page::RAM { page_relative } => self.ram.write_page_offset(page_relative, offset, val),
+            Page::Unmapped => return Err(BusError::UnmappedWrite(address)),
+        }
+
+        Ok(())
+    }
+    pub fn fill(&mut self, range: impl RangeBounds<u16>, val: u8) -> Result<(), BusError>{
+        for address in address_span(range){
+            self.poke(address as u16, val)?;
+        }
+
+        Ok(())
+    }
+    /// Copies the bytes in `src` so that they start at `dst`. Overlapping ranges copy as if through a temporary buffer.
+    pub fn copy(&mut self, src: impl RangeBounds<u16>, dst: u16) -> Result<(), BusError>{
+        let bytes = address_span(src)
+            .map(|address| self.peek(address as u16).ok_or(BusError::UnmappedRead(address as u16)))
+            .collect::<Result<Vec<u8>, BusError>>()?;
+        if dst as usize + bytes.len() > 0x10000{
+            return Err(AccessError::OutOfRange(dst as usize + bytes.len()).into());
+        }
+
+        for (i, byte) in bytes.into_iter().enumerate(){
+            self.poke(dst + i as u16, byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every address whose contents differ, treating `self` as the actual state and `expected` as the golden one.
+    /// An address inspectable in only one of the machines differs too.
+    pub fn diff(&self, expected: &Machine) -> Vec<MemoryDifference>{
+        (0x0000..=0xffffu16).filter_map(|address| {
+            let (actual, expected) = (self.peek(address), expected.peek(address));
+            (actual != expected).then_some(MemoryDifference { address, expected, actual })
+        }).collect()
+    }
+
+    /// 64-bit FNV-1a digest of the range. Every address counts, one that can be inspected as a 1 and its byte and
+    /// one that cannot as a 0, so that layouts differing only in their gaps hash apart. The hash is stable across
+    /// builds and platforms, so it can be hard-coded in test assertions.
+    pub fn hash_range(&self, range: impl RangeBounds<u16>) -> u64{
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let fold = |hash: u64, byte: u8| (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        address_span(range).fold(FNV_OFFSET_BASIS, |hash, address| match self.peek(address as u16){
+            Some(byte) => fold(fold(hash, 1), byte),
+            None => fold(hash, 0),
+        })
+    }
+
+    pub fn snapshot_ram(&self) -> RAMSnapshot{
+        self.ram.snapshot()
+    }
+    pub fn restore_ram(&mut self, snapshot: &RAMSnapshot){
+        self.ram.restore(snapshot);
+    }
+
+    pub fn has_devices(&self) -> bool{
+        !self.devices.is_empty()
+    }
+    /// The state of each device in the order they were attached, None for one that does not save its own.
+    #[cfg(feature = "serde")]
+    pub fn device_states(&self) -> Vec<Option<serde_json::Value>>{
+        self.devices.iter().map(|mapped| mapped.device.save_state()).collect()
+    }
+    /// Puts back what `device_states` returned on a machine with the same devices attached in the same order.
+    #[cfg(feature = "serde")]
+    pub fn restore_device_states(&mut self, states: Vec<Option<serde_json::Value>>) -> Result<(), serde_json::Error>{
+        if states.len() != self.devices.len(){
+            return Err(serde::de::Error::custom(format!("the state has {} devices, the machine {}", states.len(), self.devices.len())));
+        }
+        for (mapped, device) in self.devices.iter_mut().zip(states){
+            if let Some(device) = device{
+                mapped.device.load_state(device)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn state(&self) -> MachineState{
+        MachineState {
+            roms: self.roms.clone(),
+            ram: self.ram.clone(),
+            mappings: self.mappings.clone(),
+            attributes: self.attributes,
+            devices: self.device_states(),
+        }
+    }
+    /// Puts back a state taken from a machine built the same way, with the same devices attached in the same
+    /// order.
+    #[cfg(feature = "serde")]
+    pub fn restore_state(&mut self, state: MachineState) -> Result<(), serde_json::Error>{
+        self.restore_device_states(state.devices)?;
+
+        self.roms = state.roms;
+        self.ram = state.ram;
+        self.mappings = state.mappings;
+        self.attributes = state.attributes;
+        for page in 0..=u8::MAX{
+            self.decode_page(page);
+        }
+        Ok(())
+    }
 }
 impl Bus for Machine{
-    fn read(&mut self, address: u16) -> u8 {
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        // zero page and stack traffic is most of what real programs do
+        if address < 0x200 && let Some(page_relative) = self.low_pages[(address >> 8) as usize]{
+            return Ok(self.ram.read_page_offset(page_relative, address as u8));
+        }
+        if let Some((device, offset)) = self.device_for(address){
+            let val = device.read(offset);
+            log::trace!("{} read ${:04X}: ${:02X}", device.name(), address, val);
+
+            self.log_access(address, val, AccessKind::Read);
+            return Ok(val);
+        }
+
         let (page, offset) = split_address(address);
-        match self.page_map[page]{
-            Page::ROM { page_relative } => self.rom.read_page_offset(page_relative, offset),
-            Page::RAM { page_relative } => self.ram.read_page_offset(page_relative, offset),
-            Page::Unmapped => panic!("Attempted to read from unmapped memory at address {:X}", address),
+        if self.attributes[page].contains(RegionAttributes::WRITE_ONLY){
+            return Err(self.fault(BusError::ReadFromWriteOnly(address)));
         }
+
+        let val = match self.read_map[page]{
+            Page::ROM { segment, page_relative } => self.roms[segment].read_page_offset(page_relative, offset),
+            Page::RAM { page_relative } => self.ram.read_page_offset(page_relative, offset),
+            Page::Unmapped => return Err(self.fault(BusError::UnmappedRead(address))),
+        };
+
+        self.log_access(address, val, AccessKind::Read);
+        Ok(val)
     }
 
-    fn write(&mut self, address: u16, val: u8){
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError>{
+        if address < 0x200 && let Some(page_relative) = self.low_pages[(address >> 8) as usize]{
+            self.ram.write_page_offset(page_relative, address as u8, val);
+            return Ok(());
+        }
+        if let Some((device, offset)) = self.device_for(address){
+            device.write(offset, val);
+            log::trace!("{} write ${:04X}: ${:02X}", device.name(), address, val);
+
+            self.log_access(address, val, AccessKind::Write);
+            return Ok(());
+        }
+
         let (page, offset) = split_address(address);
-        match self.page_map[page]{
+        if self.attributes[page].contains(RegionAttributes::READ_ONLY){
+            return Err(self.fault(BusError::WriteToReadOnly(address)));
+        }
+
+        match self.write_map[page]{
             Page::RAM { page_relative } => self.ram.write_page_offset(page_relative, offset, val),
-            Page::ROM { page_relative: _ } => panic!("Attempted to write to ROM at address {:X}", address),
-            Page::Unmapped => panic!("Attempted to write to Unmapped memory at address {:X}", address),
+            Page::ROM { .. } => return Err(self.fault(BusError::WriteToRom(address))),
+            Page::Unmapped => return Err(self.fault(BusError::UnmappedWrite(address))),
+        }
+
+        self.log_access(address, val, AccessKind::Write);
+        Ok(())
+    }
+
+    fn attributes(&self, address: u16) -> RegionAttributes{
+        self.attributes[split_address(address).0]
+    }
+
+    fn begin_instruction(&mut self, pc: u16){
+        self.instruction_pc = pc;
+    }
+    #[inline]
+    fn decoded_instruction(&mut self, address: u16) -> Option<DecodedInstruction>{
+        match self.decode_cache.get(address){
+            Cached::Decoded(decoded) => Some(decoded),
+            Cached::Uncached => None,
+            Cached::Unknown => self.decode_for_cache(address),
         }
     }
-}
\ No newline at end of file
+
+    fn tick(&mut self, cycles: u32){
+        let mut memory = GuestMemory {
+            ram: &mut self.ram,
+            roms: &self.roms,
+            read_map: &self.read_map,
+            write_map: &self.write_map,
+            attributes: &self.attributes,
+        };
+        for mapped in self.devices.iter_mut(){
+            {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::trace_span!("tick", device = mapped.device.name(), cycles).entered();
+                mapped.device.tick(cycles);
+            }
+            // the devices that transfer report their own transfers, so an idle one costs nothing here
+            mapped.device.dma(&mut memory);
+        }
+
+        if let Some(id) = self.irq_controller{
+            let lines = self.devices.iter()
+                .filter_map(|d| match d.irq_route{
+                    IrqRoute::Controller(line) if d.device.irq() => Some(line),
+                    _ => None,
+                })
+                .fold(0u8, |lines, line| lines | (1 << line));
+            if let Some(controller) = self.device_mut::<IrqController>(id){
+                controller.set_lines(lines);
+            }
+        }
+    }
+
+    fn irq(&self) -> bool{
+        // routed devices reach the CPU through the controller, which is itself a device
+        self.devices.iter().any(|d| d.irq_route == IrqRoute::Cpu && d.device.irq())
+    }
+    fn nmi(&self) -> bool{
+        self.devices.iter().any(|d| d.device.nmi())
+    }
+    fn reset(&self) -> bool{
+        self.devices.iter().any(|d| d.device.reset())
+    }
+    // devices are all that tick and interrupt
+    fn is_quiet(&self) -> bool{
+        self.devices.is_empty()
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::bus::access_log::BusTransaction;
+    use crate::cpu::assembler;
+    use crate::cpu::bench;
+    use crate::cpu::w65c02s::CpuError;
+
+    // LDA #$11, STA $10, BRA back to the LDA: three instructions that store the byte at $8001 to $10 again and again
+    const STORE_LOOP: [u8; 6] = [0xa9, 0x11, 0x85, 0x10, 0x80, 0xfa];
+
+    /// A 32KB RAM, 32KB ROM machine with `program` at $8000, and a CPU reset onto it.
+    fn rom_machine(program: &[u8]) -> (W65C02S, Machine){
+        let mut rom = vec![0xea; 0x8000];
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x7ffc..0x7ffe].copy_from_slice(&[0x00, 0x80]);
+        let mut machine = Machine::new_32k_ram_32k_rom(&rom).unwrap();
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut machine).unwrap();
+        (cpu, machine)
+    }
+    fn run(cpu: &mut W65C02S, machine: &mut Machine, steps: usize){
+        for _ in 0..steps{
+            cpu.step(machine).unwrap();
+        }
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn reversed_mapping_is_an_error(){
+        let mut machine = Machine::with_unmapped_ram(16);
+        let mapping = Mapping::new(SegmentKind::RAM, 0x0f..=0x00, 0);
+        assert_eq!(machine.map(mapping), Err(BusError::ReversedMapping(0x0f, 0x00)));
+    }
+
+    #[test]
+    fn rom_is_read_only_unless_ram_takes_its_writes(){
+        let mut machine = Machine::with_ram(0x80);
+        machine.add_rom(0x80, &[0xea; 0x200]).unwrap();
+        assert_eq!(machine.write(0x8000, 0x42), Err(BusError::WriteToReadOnly(0x8000)));
+
+        let mut machine = Machine::with_unmapped_ram(0x100);
+        machine.map(Mapping::new(SegmentKind::RAM, 0x00..=0xff, 0).with_priority(1)).unwrap();
+        machine.add_rom(0x80, &[0xea; 0x200]).unwrap();
+        assert!(!machine.attributes(0x8000).contains(RegionAttributes::READ_ONLY));
+        assert_eq!(machine.write(0x8000, 0x42), Ok(()));
+        assert_eq!(machine.read(0x8000), Ok(0x42));
+    }
+
+    #[test]
+    fn rom_must_hold_bytes_and_end_by_ffff(){
+        let mut machine = Machine::with_ram(0x80);
+        assert_eq!(machine.add_rom(0x00, &[]), Err(AccessError::OutOfRange(0).into()));
+        assert_eq!(machine.add_rom(0x80, &[]), Err(AccessError::OutOfRange(0x8000).into()));
+        assert_eq!(machine.add_rom(0xff, &[0xea; 0x101]), Err(AccessError::OutOfRange(0x10001).into()));
+        assert!(machine.add_rom(0xff, &[0xea; 0x100]).is_ok());
+    }
+
+    #[test]
+    fn hash_counts_unmapped_addresses(){
+        // the same bytes, one page apart across a gap
+        let mut low = Machine::with_unmapped_ram(0x100);
+        low.map(Mapping::new(SegmentKind::RAM, 0x00..=0x00, 0)).unwrap();
+        let mut high = Machine::with_unmapped_ram(0x100);
+        high.map(Mapping::new(SegmentKind::RAM, 0x01..=0x01, 1)).unwrap();
+
+        assert_ne!(low.hash_range(0x0000..=0x01ff), high.hash_range(0x0000..=0x01ff));
+        assert_eq!(low.hash_range(0x0000..=0x00ff), high.hash_range(0x0100..=0x01ff));
+    }
+
+    #[test]
+    fn diff_reports_addresses_mapped_in_one_machine(){
+        let mut actual = Machine::with_ram(0x80);
+        let expected = Machine::with_ram(0x81);
+        actual.poke(0x0010, 0x42).unwrap();
+
+        assert_eq!(actual.diff(&expected), vec![MemoryDifference { address: 0x0010, expected: Some(0x00), actual: Some(0x42) }]
+            .into_iter()
+            .chain((0x8000..=0x80ff).map(|address| MemoryDifference { address, expected: Some(0x00), actual: None }))
+            .collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn more_ram_than_the_address_space_panics(){
+        Machine::with_ram(257);
+    }
+
+    #[test]
+    fn poked_rom_is_decoded_again(){
+        let (mut cpu, mut machine) = rom_machine(&STORE_LOOP);
+        run(&mut cpu, &mut machine, 3);
+        assert_eq!(machine.peek(0x0010), Some(0x11));
+        assert!(matches!(machine.decode_cache.get(0x8000), Cached::Decoded(_)));
+
+        machine.poke(0x8001, 0x22).unwrap();
+        assert!(matches!(machine.decode_cache.get(0x8000), Cached::Unknown));
+        run(&mut cpu, &mut machine, 3);
+        assert_eq!(machine.peek(0x0010), Some(0x22));
+    }
+
+    #[test]
+    fn remapped_rom_is_decoded_again(){
+        let (mut cpu, mut machine) = rom_machine(&STORE_LOOP);
+        run(&mut cpu, &mut machine, 3);
+
+        let mut patched = STORE_LOOP;
+        patched[1] = 0x33;
+        machine.add_rom(0x80, &patched).unwrap();
+        run(&mut cpu, &mut machine, 3);
+        assert_eq!(machine.peek(0x0010), Some(0x33));
+    }
+
+    #[test]
+    fn new_attributes_empty_the_cache(){
+        let (mut cpu, mut machine) = rom_machine(&STORE_LOOP);
+        run(&mut cpu, &mut machine, 3);
+
+        // a page whose reads have side effects cannot be peeked, so is fetched through the bus from now on
+        machine.set_attributes(0x80..=0x80, RegionAttributes::READ_ONLY | RegionAttributes::SIDE_EFFECTS);
+        assert!(matches!(machine.decode_cache.get(0x8000), Cached::Unknown));
+        run(&mut cpu, &mut machine, 3);
+        assert!(matches!(machine.decode_cache.get(0x8000), Cached::Uncached));
+        assert_eq!(machine.peek(0x0010), Some(0x11));
+    }
+
+    #[test]
+    fn device_over_rom_is_read_through_the_bus(){
+        let (mut cpu, mut machine) = rom_machine(&STORE_LOOP);
+        run(&mut cpu, &mut machine, 3);
+
+        machine.map_register(0x8001, || 0x44, |_| {});
+        run(&mut cpu, &mut machine, 3);
+        assert_eq!(machine.peek(0x0010), Some(0x44));
+    }
+
+    #[test]
+    fn cached_execution_matches_uncached(){
+        let workload = assembler::assemble(bench::WORKLOAD).unwrap();
+        let mut cached = Machine::new_32k_ram_32k_rom(&workload.image).unwrap();
+        // a logger has to see every fetch, so nothing is kept for the machine it is attached to
+        let mut uncached = Machine::new_32k_ram_32k_rom(&workload.image).unwrap();
+        uncached.attach_logger(AccessLogger::new(|_: &BusTransaction| {}));
+
+        let (mut cached_cpu, mut uncached_cpu) = (W65C02S::default(), W65C02S::default());
+        cached_cpu.reset(&mut cached).unwrap();
+        uncached_cpu.reset(&mut uncached).unwrap();
+        run(&mut cached_cpu, &mut cached, 20_000);
+        run(&mut uncached_cpu, &mut uncached, 20_000);
+
+        assert!(matches!(cached.decode_cache.get(0x8000), Cached::Decoded(_)));
+        assert!(matches!(uncached.decode_cache.get(0x8000), Cached::Uncached));
+        assert!(cached_cpu.diff(&uncached_cpu).is_empty());
+        assert_eq!(cached_cpu.cycles(), uncached_cpu.cycles());
+        assert!(cached.diff(&uncached).is_empty());
+    }
+
+    #[test]
+    fn devices_in_the_zero_page_and_stack_see_their_accesses(){
+        let mut machine = Machine::with_ram(0x80);
+        assert_eq!(machine.low_pages, [Some(0), Some(1)]);
+
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let log = written.clone();
+        machine.map_registers(0x0010..=0x0010, |_| 0x42, move |address, val| log.borrow_mut().push((address, val)));
+        let log = written.clone();
+        machine.map_registers(0x01ff..=0x01ff, |_| 0x43, move |address, val| log.borrow_mut().push((address, val)));
+        assert_eq!(machine.low_pages, [None, None]);
+
+        machine.write(0x0010, 0x01).unwrap();
+        machine.write(0x01ff, 0x02).unwrap();
+        assert_eq!(machine.read(0x0010), Ok(0x42));
+        assert_eq!(machine.read(0x01ff), Ok(0x43));
+        assert_eq!(*written.borrow(), [(0x0010, 0x01), (0x01ff, 0x02)]);
+
+        // the rest of the pages is still RAM
+        machine.write(0x0011, 0x05).unwrap();
+        assert_eq!(machine.read(0x0011), Ok(0x05));
+    }
+
+    #[test]
+    fn loggers_see_zero_page_and_stack_accesses(){
+        let mut machine = Machine::with_ram(0x80);
+        let logged = Rc::new(RefCell::new(Vec::new()));
+        let log = logged.clone();
+        machine.attach_logger(AccessLogger::new(move |t: &BusTransaction| log.borrow_mut().push((t.address, t.value, t.kind))));
+        assert_eq!(machine.low_pages, [None, None]);
+
+        machine.write(0x0010, 0x01).unwrap();
+        machine.read(0x0010).unwrap();
+        machine.write(0x01fd, 0x02).unwrap();
+        assert_eq!(*logged.borrow(), [(0x0010, 0x01, AccessKind::Write), (0x0010, 0x01, AccessKind::Read), (0x01fd, 0x02, AccessKind::Write)]);
+    }
+
+    #[test]
+    fn protected_zero_page_and_stack_fault(){
+        let (mut cpu, mut machine) = rom_machine(&STORE_LOOP);
+        machine.set_attributes(0x00..=0x00, RegionAttributes::READ_ONLY);
+        machine.set_attributes(0x01..=0x01, RegionAttributes::WRITE_ONLY);
+        assert_eq!(machine.low_pages, [None, None]);
+
+        assert_eq!(machine.write(0x0010, 0x01), Err(BusError::WriteToReadOnly(0x0010)));
+        assert_eq!(machine.read(0x01ff), Err(BusError::ReadFromWriteOnly(0x01ff)));
+
+        cpu.step(&mut machine).unwrap();
+        assert!(matches!(cpu.step(&mut machine), Err(CpuError::Bus(BusError::WriteToReadOnly(0x0010)))));
+
+        machine.set_attributes(0x00..=0x01, RegionAttributes::NONE);
+        assert_eq!(machine.low_pages, [Some(0), Some(1)]);
+    }
+}
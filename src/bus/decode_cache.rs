@@ -0,0 +1,85 @@
+#[cfg(feature = "jit")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cpu::w65c02s::DecodedInstruction;
+
+/// The next generation to hand out, shared by every cache so that no two are ever in the same one.
+#[cfg(feature = "jit")]
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// What the cache knows of the instruction at an address.
+#[derive(Copy, Clone)]
+pub(crate) enum Cached{
+    Decoded(DecodedInstruction),
+    Uncached,           // to be fetched through the bus every time
+    Unknown,            // not looked at since its page was last emptied
+}
+
+enum CachedPage{
+    Unknown,
+    Uncached,
+    Decoded(Box<[Cached; 256]>),
+}
+
+/// Instructions a `Machine` decoded from ROM, kept by address so that code running from ROM is not fetched and
+/// decoded again on every pass. The machine decides what may be kept, and empties the pages whose mapping,
+/// attributes or devices change.
+pub(crate) struct DecodeCache{
+    pages: Vec<CachedPage>,     // by page number, allocated on the first instruction kept in them
+    #[cfg(feature = "jit")]
+    generation: u64,            // changes whenever anything kept is dropped, for code compiled from it
+}
+impl DecodeCache{
+    pub(crate) fn new() -> Self{
+        Self {
+            pages: (0..256).map(|_| CachedPage::Unknown).collect(),
+            #[cfg(feature = "jit")]
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, address: u16) -> Cached{
+        match &self.pages[(address >> 8) as usize]{
+            CachedPage::Decoded(slots) => slots[(address & 0xff) as usize],
+            CachedPage::Uncached => Cached::Uncached,
+            CachedPage::Unknown => Cached::Unknown,
+        }
+    }
+    /// Keeps what the instruction at `address` decoded to, None to fetch it through the bus every time.
+    pub(crate) fn insert(&mut self, address: u16, decoded: Option<DecodedInstruction>){
+        let page = &mut self.pages[(address >> 8) as usize];
+        if !matches!(page, CachedPage::Decoded(_)){
+            *page = CachedPage::Decoded(Box::new([Cached::Unknown; 256]));
+        }
+        if let CachedPage::Decoded(slots) = page{
+            slots[(address & 0xff) as usize] = decoded.map_or(Cached::Uncached, Cached::Decoded);
+        }
+    }
+    /// Has every instruction of the page fetched through the bus, until it is emptied.
+    pub(crate) fn set_uncached(&mut self, page: usize){
+        self.pages[page] = CachedPage::Uncached;
+    }
+
+    pub(crate) fn empty(&mut self, page: usize){
+        self.pages[page] = CachedPage::Unknown;
+        #[cfg(feature = "jit")]
+        self.next_generation();
+    }
+    pub(crate) fn empty_all(&mut self){
+        self.pages.fill_with(|| CachedPage::Unknown);
+        #[cfg(feature = "jit")]
+        self.next_generation();
+    }
+
+    /// Which state of the cache this is. Anything built on instructions it held is still good while it stays the
+    /// same; it is unique to the cache, so a generation from another one never matches.
+    #[cfg(feature = "jit")]
+    pub(crate) fn generation(&self) -> u64{
+        self.generation
+    }
+    #[cfg(feature = "jit")]
+    fn next_generation(&mut self){
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
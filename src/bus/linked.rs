@@ -0,0 +1,44 @@
+use crate::bus::bus::Machine;
+use crate::cpu::w65c02s::{CpuError, Mnemonic, W65C02S};
+
+/// A second machine with its own CPU, run in lockstep alongside another so the two can talk through linked
+/// devices, such as UARTs joined by a null-modem cable. The owner of the first machine steps it as usual and
+/// calls `catch_up` with its cycle count; the second machine then runs until it is level, so neither is ever
+/// more than an instruction ahead of the other.
+pub struct LinkedMachine{
+    cpu: W65C02S,
+    machine: Machine,
+    halted: bool,
+}
+impl LinkedMachine{
+    /// Resets the CPU from the machine's reset vector.
+    pub fn new(mut machine: Machine) -> Result<Self, CpuError>{
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut machine)?;
+        Ok(Self { cpu, machine, halted: false })
+    }
+
+    pub fn cpu(&self) -> &W65C02S{
+        &self.cpu
+    }
+    pub fn machine(&self) -> &Machine{
+        &self.machine
+    }
+    pub fn machine_mut(&mut self) -> &mut Machine{
+        &mut self.machine
+    }
+    /// Whether the machine has stopped at a `BRK`. A halted machine is no longer stepped.
+    pub fn halted(&self) -> bool{
+        self.halted
+    }
+
+    /// Runs instructions until the machine's CPU has spent at least `cycles` cycles.
+    pub fn catch_up(&mut self, cycles: u64) -> Result<(), CpuError>{
+        while !self.halted && self.cpu.cycles() < cycles{
+            if let Mnemonic::BRK = self.cpu.step(&mut self.machine)?{
+                self.halted = true;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::bus::bus::{BusError, Machine, Mapping, SegmentKind};
+use crate::devices::w65c21::W65C21;
+use crate::devices::w65c22::W65C22;
+use crate::memory::memory::MemoryPage;
+
+/// A board described in a TOML file rather than by one of the fixed layouts:
+///
+/// ```toml
+/// clock = "1MHz"
+/// entry = 0x8000          # start here instead of at the reset vector
+/// halt = [0x8003]         # stop when the CPU reaches any of these
+///
+/// [[ram]]
+/// start = 0x0000
+/// size = 0x8000
+///
+/// [[rom]]
+/// file = "monitor.bin"    # relative to the configuration file
+/// start = 0xe000
+/// size = 0x2000           # optional, the image is padded to fill it
+///
+/// [devices]
+/// char-out = 0xf001
+/// via = 0x6000
+/// ```
+///
+/// Regions start and end on page boundaries; where they overlap, the one listed last decodes the page.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineConfig{
+    pub clock: Option<String>,      // such as "1MHz"
+    pub entry: Option<u16>,
+    #[serde(default)]
+    pub halt: Vec<u16>,
+    #[serde(default)]
+    ram: Vec<RamRegion>,
+    #[serde(default)]
+    rom: Vec<RomRegion>,
+    #[serde(default)]
+    pub devices: DeviceAddresses,
+
+    #[serde(skip)]
+    directory: PathBuf,             // ROM files are found relative to it
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RamRegion{
+    start: u16,
+    size: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomRegion{
+    file: PathBuf,
+    start: u16,
+    size: Option<u32>,
+}
+
+/// Base addresses of the devices on the board, named after the command-line flags that add them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DeviceAddresses{
+    pub char_out: Option<u16>,
+    pub char_in: Option<u16>,
+    pub keyboard: Option<u16>,
+    pub ps2_keyboard: Option<u16>,
+    pub irq_controller: Option<u16>,
+    pub cycle_counter: Option<u16>,
+    pub rng: Option<u16>,
+    pub host_services: Option<u16>,
+    pub via: Option<u16>,                   // a W65C22 with nothing on its ports
+    pub pia: Option<u16>,                   // a W65C21 likewise
+    pub disk_controller: Option<u16>,       // with its drives empty unless --disk fills them
+
+    // devices that need more than an address, such as an image or what they are wired to, named so that
+    // placing one is refused rather than taken for a misspelling
+    acia: Option<toml::Value>,
+    lcd: Option<toml::Value>,
+    sd_card: Option<toml::Value>,
+    eeprom: Option<toml::Value>,
+}
+impl DeviceAddresses{
+    /// The first of the devices a configuration cannot set up.
+    fn needing_setup(&self) -> Option<&'static str>{
+        [("acia", self.acia.is_some()), ("lcd", self.lcd.is_some()), ("sd-card", self.sd_card.is_some()), ("eeprom", self.eeprom.is_some())]
+            .into_iter()
+            .find_map(|(key, placed)| placed.then_some(key))
+    }
+}
+
+#[derive(Debug)]
+pub enum MachineConfigError{
+    Io(PathBuf, io::Error),
+    Parse(toml::de::Error),
+    Misaligned{ start: u16, size: u32 },    // a region that does not cover whole pages
+    OutsideAddressSpace{ start: u16, size: u32 },
+    RomTooLarge(PathBuf),                   // the image does not fit the size given for its region
+    NeedsSetup(&'static str),               // a device the file cannot place, by its key
+    Bus(BusError),
+}
+impl fmt::Display for MachineConfigError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            MachineConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            MachineConfigError::Parse(e) => write!(f, "{}", e),
+            MachineConfigError::Misaligned { start, size } => write!(f, "region of {:#X} bytes at ${:04X} does not cover whole pages", size, start),
+            MachineConfigError::OutsideAddressSpace { start, size } => write!(f, "region of {:#X} bytes at ${:04X} does not fit the address space", size, start),
+            MachineConfigError::RomTooLarge(path) => write!(f, "{} is larger than its region", path.display()),
+            MachineConfigError::NeedsSetup(key) => write!(f, "{} needs more than an address to set up, and cannot be placed from a configuration file", key),
+            MachineConfigError::Bus(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl MachineConfig{
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MachineConfigError>{
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| MachineConfigError::Io(path.to_path_buf(), e))?;
+        let mut config = Self::parse(&text)?;
+        config.directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        Ok(config)
+    }
+    fn parse(text: &str) -> Result<Self, MachineConfigError>{
+        let config: Self = toml::from_str(text).map_err(MachineConfigError::Parse)?;
+        match config.devices.needing_setup(){
+            Some(key) => Err(MachineConfigError::NeedsSetup(key)),
+            None => Ok(config),
+        }
+    }
+
+    /// Maps the RAM and ROM regions, and attaches the devices that need nothing from the host. The RAM of every
+    /// region lives in the one segment, in the order listed, so the RAM dump is the regions one after the other.
+    pub fn build(&self) -> Result<Machine, MachineConfigError>{
+        let mut ram_pages = Vec::new();
+        for region in &self.ram{
+            ram_pages.push(pages(region.start, region.size)?);
+        }
+
+        let mut machine = Machine::with_unmapped_ram(ram_pages.iter().map(|p| p.len()).sum());
+        let mut segment_page = 0;
+        for pages in ram_pages{
+            let num_pages = pages.len();
+            let (first, last) = (pages.start as u8, (pages.end - 1) as u8);
+            machine.map(Mapping::new(SegmentKind::RAM, first..=last, segment_page)).map_err(MachineConfigError::Bus)?;
+            segment_page += num_pages;
+        }
+
+        for region in &self.rom{
+            let path = self.directory.join(&region.file);
+            let mut image = fs::read(&path).map_err(|e| MachineConfigError::Io(path.clone(), e))?;
+            let size = region.size.unwrap_or((image.len() as u32).next_multiple_of(MemoryPage::SIZE as u32));
+            if image.len() > size as usize{
+                return Err(MachineConfigError::RomTooLarge(path));
+            }
+            let pages = pages(region.start, size)?;
+            image.resize(size as usize, 0);
+            machine.add_rom(pages.start as u8, &image).map_err(MachineConfigError::Bus)?;
+        }
+
+        if let Some(address) = self.devices.via{
+            machine.attach_device(address..=address.saturating_add(15), W65C22::new());
+        }
+        if let Some(address) = self.devices.pia{
+            machine.attach_device(address..=address.saturating_add(3), W65C21::new());
+        }
+
+        Ok(machine)
+    }
+}
+
+/// The pages a region covers.
+fn pages(start: u16, size: u32) -> Result<Range<usize>, MachineConfigError>{
+    let page_size = MemoryPage::SIZE as u32;
+    if !(start as u32).is_multiple_of(page_size) || !size.is_multiple_of(page_size){
+        return Err(MachineConfigError::Misaligned { start, size });
+    }
+    if size == 0 || start as u32 + size > 0x10000{
+        return Err(MachineConfigError::OutsideAddressSpace { start, size });
+    }
+
+    let first = start as usize / MemoryPage::SIZE;
+    Ok(first..(first + (size / page_size) as usize))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::bus::bus::Bus;
+
+    #[test]
+    fn places_a_via_and_a_pia(){
+        let config = MachineConfig::parse("[[ram]]\nstart = 0\nsize = 0x4000\n[devices]\nvia = 0x6000\npia = 0xd010\n").unwrap();
+        let mut machine = config.build().unwrap();
+
+        // the data direction registers, which read back what was written
+        machine.write(0x6003, 0x5a).unwrap();
+        assert_eq!(machine.read(0x6003), Ok(0x5a));
+        machine.write(0xd010, 0xa5).unwrap();
+        assert_eq!(machine.read(0xd010), Ok(0xa5));
+    }
+
+    #[test]
+    fn refuses_devices_that_need_setting_up(){
+        let result = MachineConfig::parse("[devices]\nvia = 0x6000\nsd-card = 0x7f00\n");
+        assert!(matches!(result, Err(MachineConfigError::NeedsSetup("sd-card"))));
+    }
+}
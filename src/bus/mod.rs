@@ -1 +1,10 @@
-pub mod bus;
\ No newline at end of file
+pub mod bus;
+pub mod access_log;
+pub mod probe;
+pub mod linked;
+pub mod result_watch;
+pub mod machine_config;
+pub mod static_machine;
+pub mod replay;
+pub mod decode_cache;
+pub mod board;
\ No newline at end of file
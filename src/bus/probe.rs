@@ -0,0 +1,86 @@
+use std::ops::RangeInclusive;
+
+use crate::bus::access_log::{ranges_accept, AccessKind, BusTransaction};
+use crate::bus::bus::{Bus, BusError, RegionAttributes};
+
+/// Transparent wrapper that records every access forwarded to the inner bus,
+/// optionally restricted to a set of address ranges.
+pub struct BusProbe<B: Bus>{
+    inner: B,
+    ranges: Vec<RangeInclusive<u16>>,
+    transactions: Vec<BusTransaction>,
+    instruction_pc: u16,
+}
+impl<B: Bus> BusProbe<B>{
+    pub fn new(inner: B) -> Self{
+        Self { inner, ranges: Vec::new(), transactions: Vec::new(), instruction_pc: 0 }
+    }
+    pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self{
+        self.ranges.push(range);
+        self
+    }
+
+    pub fn transactions(&self) -> &[BusTransaction]{
+        &self.transactions
+    }
+    /// Hands over the recorded transactions, leaving the buffer empty.
+    pub fn take_transactions(&mut self) -> Vec<BusTransaction>{
+        std::mem::take(&mut self.transactions)
+    }
+    pub fn clear(&mut self){
+        self.transactions.clear();
+    }
+
+    pub fn inner(&self) -> &B{
+        &self.inner
+    }
+    pub fn inner_mut(&mut self) -> &mut B{
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> B{
+        self.inner
+    }
+
+    fn record(&mut self, address: u16, value: u8, kind: AccessKind){
+        if ranges_accept(&self.ranges, address){
+            self.transactions.push(BusTransaction { address, value, kind, pc: self.instruction_pc });
+        }
+    }
+}
+impl<B: Bus> Bus for BusProbe<B>{
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        let val = self.inner.read(address)?;
+
+        self.record(address, val, AccessKind::Read);
+        Ok(val)
+    }
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError> {
+        self.inner.write(address, val)?;
+
+        self.record(address, val, AccessKind::Write);
+        Ok(())
+    }
+
+    fn attributes(&self, address: u16) -> RegionAttributes {
+        self.inner.attributes(address)
+    }
+    fn begin_instruction(&mut self, pc: u16) {
+        self.instruction_pc = pc;
+        self.inner.begin_instruction(pc);
+    }
+    fn tick(&mut self, cycles: u32) {
+        self.inner.tick(cycles);
+    }
+    fn irq(&self) -> bool {
+        self.inner.irq()
+    }
+    fn nmi(&self) -> bool {
+        self.inner.nmi()
+    }
+    fn reset(&self) -> bool {
+        self.inner.reset()
+    }
+    fn is_quiet(&self) -> bool {
+        self.inner.is_quiet()
+    }
+}
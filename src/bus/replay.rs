@@ -0,0 +1,289 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bus::bus::{Bus, BusError, RegionAttributes};
+
+/// Something that went between the CPU and the bus, stamped with the cycles the bus had been ticked by before
+/// it, counted from the start of the recording.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Transaction{
+    Read{ cycle: u64, address: u16, result: Result<u8, BusError> },
+    Write{ cycle: u64, address: u16, value: u8, result: Result<(), BusError> },
+    /// The IRQ line went to `level` since it was last sampled, which it is after every tick; it starts low.
+    Irq{ cycle: u64, level: bool },
+    Nmi{ cycle: u64, level: bool },
+    Reset{ cycle: u64, level: bool },
+}
+
+/// What a `Recorder` captured, for a `Replayer` to feed back.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Recording{
+    pub transactions: Vec<Transaction>,
+}
+
+/// Transparent wrapper that records every access to the inner bus, with its outcome, and every change of its
+/// interrupt and reset lines, so that the run can be replayed without the bus.
+pub struct Recorder<B: Bus>{
+    inner: B,
+    recording: Recording,
+    cycle: u64,
+    irq: bool,      // levels at the last tick
+    nmi: bool,
+    reset: bool,
+}
+impl<B: Bus> Recorder<B>{
+    pub fn new(inner: B) -> Self{
+        Self { inner, recording: Recording::default(), cycle: 0, irq: false, nmi: false, reset: false }
+    }
+
+    pub fn recording(&self) -> &Recording{
+        &self.recording
+    }
+    pub fn inner(&self) -> &B{
+        &self.inner
+    }
+    pub fn inner_mut(&mut self) -> &mut B{
+        &mut self.inner
+    }
+    pub fn into_parts(self) -> (B, Recording){
+        (self.inner, self.recording)
+    }
+}
+impl<B: Bus> Bus for Recorder<B>{
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        let result = self.inner.read(address);
+
+        self.recording.transactions.push(Transaction::Read { cycle: self.cycle, address, result });
+        result
+    }
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError> {
+        let result = self.inner.write(address, val);
+
+        self.recording.transactions.push(Transaction::Write { cycle: self.cycle, address, value: val, result });
+        result
+    }
+
+    fn attributes(&self, address: u16) -> RegionAttributes {
+        self.inner.attributes(address)
+    }
+    fn begin_instruction(&mut self, pc: u16) {
+        self.inner.begin_instruction(pc);
+    }
+    // the CPU looks at the lines right after ticking the bus, so what they are then is what it sees
+    fn tick(&mut self, cycles: u32) {
+        self.inner.tick(cycles);
+        self.cycle += cycles as u64;
+
+        let (irq, nmi, reset) = (self.inner.irq(), self.inner.nmi(), self.inner.reset());
+        if irq != self.irq{
+            self.irq = irq;
+            self.recording.transactions.push(Transaction::Irq { cycle: self.cycle, level: irq });
+        }
+        if nmi != self.nmi{
+            self.nmi = nmi;
+            self.recording.transactions.push(Transaction::Nmi { cycle: self.cycle, level: nmi });
+        }
+        if reset != self.reset{
+            self.reset = reset;
+            self.recording.transactions.push(Transaction::Reset { cycle: self.cycle, level: reset });
+        }
+    }
+    fn irq(&self) -> bool {
+        self.inner.irq()
+    }
+    fn nmi(&self) -> bool {
+        self.inner.nmi()
+    }
+    fn reset(&self) -> bool {
+        self.inner.reset()
+    }
+}
+
+/// Where a replayed run parted ways with the recording: the transaction expected next, None past the end of
+/// the recording, and what the CPU did instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Divergence{
+    pub index: usize,
+    pub expected: Option<Transaction>,
+    pub actual: Transaction,
+}
+impl fmt::Display for Divergence{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expected{
+            Some(expected) => write!(f, "transaction {} was {:?}, where the recording has {:?}", self.index, self.actual, expected),
+            None => write!(f, "transaction {} was {:?}, past the end of the recording", self.index, self.actual),
+        }
+    }
+}
+
+/// A bus with nothing behind it that answers the CPU from a recording: reads return what they returned when it
+/// was made, writes are checked against it, and the interrupt lines change when they did. A run of the same
+/// program from the same state then goes as the recorded one did, at the speed of the CPU alone.
+///
+/// Every access must match the next one recorded, address, value and cycle; the first that does not is kept
+/// as the divergence, and it and every access after it fail with `BusError::ReplayDiverged`. Attributes are
+/// not recorded, so none apply.
+pub struct Replayer{
+    recording: Recording,
+    next: usize,
+    cycle: u64,
+    irq: bool,
+    nmi: bool,
+    reset: bool,
+    divergence: Option<Divergence>,
+}
+impl Replayer{
+    pub fn new(recording: Recording) -> Self{
+        Self { recording, next: 0, cycle: 0, irq: false, nmi: false, reset: false, divergence: None }
+    }
+
+    pub fn divergence(&self) -> Option<&Divergence>{
+        self.divergence.as_ref()
+    }
+    /// Whether every transaction of the recording has been replayed, without a divergence.
+    pub fn finished(&self) -> bool{
+        self.divergence.is_none() && self.next == self.recording.transactions.len()
+    }
+
+    /// Takes the next transaction if it is `actual`, and keeps the divergence otherwise.
+    fn expect(&mut self, actual: Transaction, matches: impl FnOnce(&Transaction) -> bool) -> Option<Transaction>{
+        if self.divergence.is_some(){
+            return None;
+        }
+
+        let expected = self.recording.transactions.get(self.next).copied();
+        match expected{
+            Some(expected) if matches(&expected) => {
+                self.next += 1;
+                Some(expected)
+            },
+            _ => {
+                log::debug!("replay diverged at transaction {}", self.next);
+                self.divergence = Some(Divergence { index: self.next, expected, actual });
+                None
+            },
+        }
+    }
+}
+impl Bus for Replayer{
+    fn read(&mut self, address: u16) -> Result<u8, BusError> {
+        let cycle = self.cycle;
+        let actual = Transaction::Read { cycle, address, result: Err(BusError::ReplayDiverged(address)) };
+        match self.expect(actual, |t| matches!(t, Transaction::Read { cycle: c, address: a, .. } if *c == cycle && *a == address)){
+            Some(Transaction::Read { result, .. }) => result,
+            _ => Err(BusError::ReplayDiverged(address)),
+        }
+    }
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError> {
+        let cycle = self.cycle;
+        let actual = Transaction::Write { cycle, address, value: val, result: Err(BusError::ReplayDiverged(address)) };
+        match self.expect(actual, |t| matches!(t, Transaction::Write { cycle: c, address: a, value: v, .. } if *c == cycle && *a == address && *v == val)){
+            Some(Transaction::Write { result, .. }) => result,
+            _ => Err(BusError::ReplayDiverged(address)),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycle += cycles as u64;
+
+        while self.divergence.is_none(){
+            match self.recording.transactions.get(self.next){
+                Some(Transaction::Irq { cycle, level }) if *cycle == self.cycle => self.irq = *level,
+                Some(Transaction::Nmi { cycle, level }) if *cycle == self.cycle => self.nmi = *level,
+                Some(Transaction::Reset { cycle, level }) if *cycle == self.cycle => self.reset = *level,
+                _ => break,
+            }
+            self.next += 1;
+        }
+    }
+    fn irq(&self) -> bool {
+        self.irq
+    }
+    fn nmi(&self) -> bool {
+        self.nmi
+    }
+    fn reset(&self) -> bool {
+        self.reset
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::bus::bus::Machine;
+    use crate::cpu::assembler;
+    use crate::cpu::w65c02s::{CpuError, Mnemonic, W65C02S};
+
+    /// Copies eight reads of a register that counts up by 7 into $0200, then stops at a `BRK`.
+    const PROGRAM: &str = "
+        .org $8000
+reset:  LDX #0
+copy:   LDA $6000
+        STA $0200,X
+        INX
+        CPX #8
+        BNE copy
+        BRK
+        .org $fffc
+        .word reset, reset
+";
+
+    /// Records a run of `PROGRAM` on a machine with the counting register mapped at $6000.
+    fn record() -> Recording{
+        let assembly = assembler::assemble(PROGRAM).unwrap();
+        let mut machine = Machine::new_32k_ram_32k_rom(&assembly.windowed(0x8000, 0x8000).unwrap()).unwrap();
+        let mut count = 0u8;
+        machine.map_register(0x6000, move || { count = count.wrapping_add(7); count }, |_| {});
+
+        let mut recorder = Recorder::new(machine);
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut recorder).unwrap();
+        while !matches!(cpu.step(&mut recorder).unwrap(), Mnemonic::BRK) {}
+
+        let (machine, recording) = recorder.into_parts();
+        assert_eq!(machine.peek(0x0207), Some(56));
+        recording
+    }
+    /// Replays `PROGRAM` to its `BRK` or the first error.
+    fn replay(replayer: &mut Replayer) -> Result<(), CpuError>{
+        let mut cpu = W65C02S::default();
+        cpu.reset(replayer)?;
+        while !matches!(cpu.step(replayer)?, Mnemonic::BRK) {}
+        Ok(())
+    }
+
+    #[test]
+    fn recorded_run_replays_to_the_end(){
+        let mut replayer = Replayer::new(record());
+        replay(&mut replayer).unwrap();
+        assert!(replayer.finished());
+        assert_eq!(replayer.divergence(), None);
+    }
+
+    #[test]
+    fn changed_write_is_the_divergence(){
+        let mut recording = record();
+        let (index, original) = recording.transactions.iter().enumerate()
+            .find_map(|(i, t)| match t{
+                Transaction::Write { address: 0x0203, .. } => Some((i, *t)),
+                _ => None,
+            })
+            .unwrap();
+        let Transaction::Write { cycle, address, value, result } = original else { unreachable!() };
+        let expected = Transaction::Write { cycle, address, value: value ^ 0xff, result };
+        recording.transactions[index] = expected;
+
+        let mut replayer = Replayer::new(recording);
+        assert!(matches!(replay(&mut replayer), Err(CpuError::Bus(BusError::ReplayDiverged(0x0203)))));
+        assert!(!replayer.finished());
+        assert_eq!(replayer.divergence(), Some(&Divergence {
+            index,
+            expected: Some(expected),
+            actual: Transaction::Write { cycle, address, value, result: Err(BusError::ReplayDiverged(0x0203)) },
+        }));
+    }
+}
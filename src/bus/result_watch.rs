@@ -0,0 +1,54 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::bus::access_log::{AccessKind, AccessLogger, BusTransaction};
+use crate::bus::bus::Machine;
+
+/// How a test program graded itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict{
+    Pass,
+    /// The error code is the byte at the error address, when there is one.
+    Fail{ error_code: Option<u8> },
+}
+
+/// Watches the byte a test program writes its result to, the convention most 6502 validation programs follow:
+/// one value means every test passed, another that one failed, and anything else written there, such as a
+/// progress marker, is ignored. A failing program may also leave an error code in another byte.
+pub struct ResultWatch{
+    written: Rc<Cell<Option<u8>>>,  // last value written to the result address
+    pass: u8,
+    fail: u8,
+    error_address: Option<u16>,
+}
+impl ResultWatch{
+    pub const DEFAULT_PASS: u8 = 0x01;
+    pub const DEFAULT_FAIL: u8 = 0xff;
+
+    /// Starts watching writes to `address` on the machine's bus.
+    pub fn attach(machine: &mut Machine, address: u16, pass: u8, fail: u8) -> Self{
+        let written = Rc::new(Cell::new(None));
+        let sink = written.clone();
+        machine.attach_logger(AccessLogger::new(move |transaction: &BusTransaction| {
+            if transaction.kind == AccessKind::Write{
+                sink.set(Some(transaction.value));
+            }
+        }).with_range(address..=address));
+
+        Self { written, pass, fail, error_address: None }
+    }
+    /// Reports the byte at `address` as the error code of a failure.
+    pub fn with_error_address(mut self, address: u16) -> Self{
+        self.error_address = Some(address);
+        self
+    }
+
+    /// The verdict once the program has written one.
+    pub fn verdict(&self, machine: &Machine) -> Option<Verdict>{
+        match self.written.get()?{
+            value if value == self.pass => Some(Verdict::Pass),
+            value if value == self.fail => Some(Verdict::Fail { error_code: self.error_address.and_then(|a| machine.peek(a)) }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::bus::bus::{Bus, BusError, RegionAttributes};
+use crate::memory::memory::AccessError;
+
+/// A machine of `RAM` bytes of RAM from $0000 up and `ROM` bytes of ROM ending at $FFFF, held in arrays of their
+/// own rather than in pages on the heap, with whatever lies between unmapped. Nothing is allocated to build or
+/// run it, so it can live in a `static` or on the stack. It has no devices, mappings or loggers; hosts that
+/// need them use a `Machine`.
+///
+/// `StaticMachine<0x8000, 0x8000>` has the layout of `Machine::new_32k_ram_32k_rom`.
+#[derive(Clone)]
+pub struct StaticMachine<const RAM: usize, const ROM: usize>{
+    ram: [u8; RAM],
+    rom: [u8; ROM],
+}
+impl<const RAM: usize, const ROM: usize> StaticMachine<RAM, ROM>{
+    /// Where the ROM starts, checked when the machine is built to leave room for the RAM below it.
+    pub const ROM_START: usize = {
+        assert!(RAM + ROM <= 0x10000, "RAM and ROM do not fit in 64KB together");
+        0x10000 - ROM
+    };
+
+    /// A machine with cleared RAM and the given ROM, for building one in a `static`.
+    pub const fn new(rom: [u8; ROM]) -> Self{
+        // names the constant so that sizes that do not fit fail to compile here
+        let _ = Self::ROM_START;
+        Self { ram: [0; RAM], rom }
+    }
+    /// Loads `rom_image` from the start of the ROM on, leaving the rest of it cleared, as
+    /// `Machine::new_32k_ram_32k_rom` does with a shorter image.
+    pub fn from_image(rom_image: &[u8]) -> Result<Self, BusError>{
+        if rom_image.len() > ROM{
+            return Err(AccessError::OutOfRange(ROM).into());
+        }
+
+        let mut machine = Self::new([0; ROM]);
+        machine.rom[..rom_image.len()].copy_from_slice(rom_image);
+        Ok(machine)
+    }
+
+    pub fn ram(&self) -> &[u8; RAM]{
+        &self.ram
+    }
+    pub fn ram_mut(&mut self) -> &mut [u8; RAM]{
+        &mut self.ram
+    }
+    pub fn rom(&self) -> &[u8; ROM]{
+        &self.rom
+    }
+    /// The ROM, for a debugger to patch; the CPU cannot write it.
+    pub fn rom_mut(&mut self) -> &mut [u8; ROM]{
+        &mut self.rom
+    }
+}
+
+impl<const RAM: usize, const ROM: usize> Bus for StaticMachine<RAM, ROM>{
+    #[inline]
+    fn read(&mut self, address: u16) -> Result<u8, BusError>{
+        let address_index = address as usize;
+        if address_index < RAM{
+            Ok(self.ram[address_index])
+        }
+        else if address_index >= Self::ROM_START{
+            Ok(self.rom[address_index - Self::ROM_START])
+        }
+        else{
+            Err(BusError::UnmappedRead(address))
+        }
+    }
+    #[inline]
+    fn write(&mut self, address: u16, val: u8) -> Result<(), BusError>{
+        let address_index = address as usize;
+        if address_index < RAM{
+            self.ram[address_index] = val;
+            Ok(())
+        }
+        else if address_index >= Self::ROM_START{
+            Err(BusError::WriteToReadOnly(address))
+        }
+        else{
+            Err(BusError::UnmappedWrite(address))
+        }
+    }
+
+    fn attributes(&self, address: u16) -> RegionAttributes{
+        if address as usize >= Self::ROM_START { RegionAttributes::READ_ONLY } else { RegionAttributes::NONE }
+    }
+    // without devices, nothing ticks or interrupts
+    fn is_quiet(&self) -> bool{
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::cpu::w65c02s::{Mnemonic, Register, W65C02S};
+
+    #[test]
+    fn runs_to_brk(){
+        // LDA #$42, STA $0200, BRK, with the reset vector at $8000
+        let mut rom = [0xea; 0x8000];
+        rom[..6].copy_from_slice(&[0xa9, 0x42, 0x8d, 0x00, 0x02, 0x00]);
+        rom[0x7ffc..0x7ffe].copy_from_slice(&[0x00, 0x80]);
+        let mut machine = StaticMachine::<0x8000, 0x8000>::new(rom);
+
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut machine).unwrap();
+        let mut steps = 0;
+        while !matches!(cpu.step(&mut machine).unwrap(), Mnemonic::BRK){
+            steps += 1;
+            assert!(steps < 10, "no BRK by ${:04X}", cpu.register(Register::PC));
+        }
+        assert_eq!(machine.ram()[0x0200], 0x42);
+        assert_eq!(machine.write(0x8000, 0x00), Err(BusError::WriteToReadOnly(0x8000)));
+    }
+}
@@ -0,0 +1,534 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use steel6502::bus::board::SerialChip;
+use steel6502::cpu::save_state::AutosaveInterval;
+use steel6502::devices::audio;
+use steel6502::devices::disk_controller::DiskGeometry;
+use steel6502::devices::joystick::JoystickKeys;
+use steel6502::devices::leds::LedBank;
+use steel6502::cpu::symbols::parse_address;
+use steel6502::runner::options::{parse_autosave_interval, parse_byte, parse_disk_geometry, parse_dump_every, parse_duration, parse_frequency, parse_range, parse_size, DumpEvery, DumpFormat, StatsMode, TraceFormat};
+
+/// Emulates a W65C02S machine: runs ROM and memory images with memory-mapped devices, and assembles and
+/// disassembles them.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true, arg_required_else_help = true)]
+pub struct Cli{
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Log more of what the machine does to stderr: device activity, interrupts and bus faults, and with `-vv`
+    /// every access to a device.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Log only warnings and errors.
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
+    pub quiet: bool,
+    /// How an error that stops the program is written to stderr.
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = ErrorFormat::Text, global = true)]
+    pub errors: ErrorFormat,
+
+    /// Without a subcommand, the images are run as with `run`.
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command{
+    /// Run images until they stop.
+    Run(RunArgs),
+    /// Run an image under a debugger.
+    ///
+    /// The debugger takes commands on stdin and stops before the first instruction; `help` lists the commands.
+    Debug(RunArgs),
+    /// Run images as tests, each to a pass or a failure.
+    ///
+    /// An image passes by reaching a `--halt-addr`, writing a pass to `--result-addr` or exiting with status 0
+    /// through the host services, and its RAM matching `--compare`. Anything else fails it.
+    ///
+    /// A directory is searched for the standard test suites instead: Tom Harte's single-step vectors as
+    /// `*.json` files named after their opcode, and Klaus Dormann's 64KB functional test images as `*.bin` files,
+    /// run from `$0400` or `--entry` until they trap. The success trap is read from the `.lst` listing beside
+    /// an image, or given with `--halt-addr`. Each directory of vectors prints a pass/fail matrix by opcode.
+    Test(RunArgs),
+    /// Run an image until it stops and check its final state against a golden save state.
+    ///
+    /// The registers, the cycle and instruction counts and every byte of RAM must match; what differs is listed.
+    /// `--update` writes the golden state instead, from the run.
+    Verify(VerifyArgs),
+    /// List the instructions in an image.
+    Disasm(DisasmArgs),
+    /// Assemble 65C02 source into an image.
+    Asm(AsmArgs),
+    /// Run a workload flat out for a while and report how fast the emulator went.
+    ///
+    /// Without an image, a workload built in runs, the same on every machine, so that the speed can be
+    /// compared between builds and hosts.
+    Bench(BenchArgs),
+}
+
+/// Everything that sets up and runs a machine, shared by `run`, `debug`, `test` and `verify`.
+#[derive(Args)]
+#[command(group(ArgGroup::new("serial").multiple(false)))]
+pub struct RunArgs{
+    /// ROM, 64KB memory or `.prg` images, run one after another.
+    #[arg(value_name = "IMAGE", required_unless_present = "machine")]
+    pub images: Vec<PathBuf>,
+
+    /// Directory the RAM dumps and reports are written to.
+    #[arg(short = 'o', long = "output-dir", value_name = "DIR", help_heading = "Machine")]
+    pub output_dir: Option<PathBuf>,
+    /// TOML file describing the board, in place of an image.
+    #[arg(long, value_name = "FILE", conflicts_with = "images", help_heading = "Machine")]
+    pub machine: Option<PathBuf>,
+    /// Place a ROM image smaller than 32KB so that it ends at the vectors.
+    #[arg(long, help_heading = "Machine")]
+    pub place_top: bool,
+    /// Start at this address or label instead of the reset vector.
+    #[arg(long, value_name = "ADDR", help_heading = "Machine")]
+    pub entry: Option<String>,
+    /// Pace execution to a clock rate, such as 1MHz.
+    #[arg(long, value_name = "FREQ", value_parser = frequency, help_heading = "Machine")]
+    pub clock: Option<u64>,
+    /// Run flat out, even with a clock given.
+    #[arg(long, help_heading = "Machine")]
+    pub unlimited: bool,
+    /// Pick up a run from a save state, in place of the reset.
+    #[arg(long, value_name = "FILE", help_heading = "Machine")]
+    pub resume: Option<PathBuf>,
+    /// Seed for everything random in a run, so that it can be repeated; without it one is picked and printed.
+    #[arg(long, value_name = "N", help_heading = "Machine")]
+    pub seed: Option<u64>,
+    /// Power RAM up filled with random bytes, as real RAM does, instead of zeroed.
+    #[arg(long, help_heading = "Machine")]
+    pub random_ram: bool,
+    /// Load RAM from a file before the reset, as <file>[:<offset>], such as data tables a program expects.
+    #[arg(long, value_name = "FILE", help_heading = "Machine")]
+    pub ram_image: Option<String>,
+    /// Carry on past opcodes the W65C02S does not define, as the NOPs the chip runs them as, instead of stopping.
+    #[arg(long, help_heading = "Machine")]
+    pub invalid_nop: bool,
+
+    /// Map the character output, at $F001.
+    #[arg(long, help_heading = "Console")]
+    pub char_out: bool,
+    /// The character output, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Console")]
+    pub char_out_addr: Option<u16>,
+    /// Map the character input, at $F004.
+    #[arg(long, help_heading = "Console")]
+    pub char_in: bool,
+    /// The character input, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Console")]
+    pub char_in_addr: Option<u16>,
+    /// Wire both halves of the console to `stdio` or to `tcp:<host:port>`.
+    #[arg(long, value_name = "END", help_heading = "Console")]
+    pub console: Option<String>,
+    /// Feed the console input from a file.
+    #[arg(long, value_name = "FILE", help_heading = "Console")]
+    pub console_in: Option<PathBuf>,
+    /// Write the console output to a file.
+    #[arg(long, value_name = "FILE", help_heading = "Console")]
+    pub console_out: Option<PathBuf>,
+
+    /// Map a keyboard fed from the terminal, at $F010.
+    #[arg(long, conflicts_with_all = ["ps2_keyboard", "ps2_addr"], help_heading = "Devices")]
+    pub keyboard: bool,
+    /// The keyboard, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, conflicts_with_all = ["ps2_keyboard", "ps2_addr"], help_heading = "Devices")]
+    pub keyboard_addr: Option<u16>,
+    /// Map a VIA with a PS/2 keyboard on its shift register, at $F0A0.
+    #[arg(long, help_heading = "Devices")]
+    pub ps2_keyboard: bool,
+    /// The PS/2 keyboard's VIA, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub ps2_addr: Option<u16>,
+    /// Capture the beeper to a WAV file.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub beeper: Option<PathBuf>,
+    /// The beeper, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "beeper", help_heading = "Devices")]
+    pub beeper_addr: Option<u16>,
+    /// Capture the sound chip to a WAV file.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub psg: Option<PathBuf>,
+    /// The sound chip, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "psg", help_heading = "Devices")]
+    pub psg_addr: Option<u16>,
+    /// Sample rate of the WAV files sound is captured to.
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..=audio::MAX_SAMPLE_RATE as i64), default_value_t = audio::DEFAULT_SAMPLE_RATE, help_heading = "Devices")]
+    pub sample_rate: u32,
+    /// Play a tape from a WAV file.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub tape_in: Option<PathBuf>,
+    /// Record the tape to a WAV file.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub tape_out: Option<PathBuf>,
+    /// The cassette interface, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub tape_addr: Option<u16>,
+    /// Write the MIDI output to a standard MIDI file.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub midi_file: Option<PathBuf>,
+    /// Write the MIDI output raw to a host MIDI port.
+    #[arg(long, value_name = "PORT", help_heading = "Devices")]
+    pub midi_out: Option<PathBuf>,
+    /// The MIDI port, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub midi_addr: Option<u16>,
+    /// Put an SD card image behind an SPI controller.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub sd: Option<PathBuf>,
+    /// The SPI controller, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "sd", help_heading = "Devices")]
+    pub sd_addr: Option<u16>,
+    /// Put a disk image in drive 0.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub disk: Option<PathBuf>,
+    /// Put a disk image in drive 1.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub disk2: Option<PathBuf>,
+    /// Layout of the disk images, as <tracks>x<sectors>x<sector size>.
+    #[arg(long, value_name = "GEOMETRY", value_parser = disk_geometry, help_heading = "Devices")]
+    pub disk_geometry: Option<DiskGeometry>,
+    /// The disk controller, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub disk_addr: Option<u16>,
+    /// Wire an I2C EEPROM image to a VIA.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub eeprom: Option<PathBuf>,
+    /// The EEPROM's VIA, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "eeprom", help_heading = "Devices")]
+    pub eeprom_addr: Option<u16>,
+    /// Show LEDs and 7-segment digits, at $F0F0.
+    #[arg(long, help_heading = "Devices")]
+    pub leds: bool,
+    /// The LEDs, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub leds_addr: Option<u16>,
+    /// Number of 7-segment digits.
+    #[arg(long, value_name = "N", value_parser = digits, help_heading = "Devices")]
+    pub digits: Option<usize>,
+    /// Map a joystick driven by keys, at $F0F8.
+    #[arg(long, help_heading = "Devices")]
+    pub joystick: bool,
+    /// The joystick, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub joystick_addr: Option<u16>,
+    /// Keys for up, down, left, right and fire, such as `wsadf`.
+    #[arg(long, value_name = "KEYS", value_parser = joystick_keys, help_heading = "Devices")]
+    pub joystick_keys: Option<JoystickKeys>,
+    /// Bridge an ACIA to a TCP port.
+    #[arg(long, value_name = "HOST:PORT", group = "serial", help_heading = "Serial")]
+    pub serial_tcp: Option<String>,
+    /// Cable an ACIA to one in a second machine running this image.
+    #[arg(long, value_name = "IMAGE", group = "serial", help_heading = "Serial")]
+    pub link: Option<PathBuf>,
+    /// Attach an ACIA to a pseudo-terminal.
+    #[arg(long, group = "serial", help_heading = "Serial")]
+    pub serial_pty: bool,
+    /// The ACIA: 6551 or 6850.
+    #[arg(long, value_name = "CHIP", value_parser = serial_chip(), requires = "serial", help_heading = "Serial")]
+    pub serial_chip: Option<SerialChip>,
+    /// The ACIA, at this address instead of the chip's usual one.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "serial", help_heading = "Serial")]
+    pub serial_addr: Option<u16>,
+    /// Map an interrupt controller, at $F060.
+    #[arg(long, help_heading = "Devices")]
+    pub irq_controller: bool,
+    /// The interrupt controller, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub irq_controller_addr: Option<u16>,
+    /// Draw a text screen in the terminal, of <columns>x<rows>.
+    #[arg(long, value_name = "SIZE", value_parser = size, help_heading = "Devices")]
+    pub ansi_screen: Option<(usize, usize)>,
+    /// Give the screen a color byte per character.
+    #[arg(long, requires = "ansi_screen", help_heading = "Devices")]
+    pub ansi_color: bool,
+    /// The screen memory, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "ansi_screen", help_heading = "Devices")]
+    pub ansi_addr: Option<u16>,
+    /// How often the screens redraw.
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..), help_heading = "Devices")]
+    pub refresh: Option<u32>,
+    /// Map a raster timer raising vertical blank, at $F0B0.
+    #[arg(long, help_heading = "Devices")]
+    pub vsync: bool,
+    /// The raster timer, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub vsync_addr: Option<u16>,
+    /// Frames a second of the raster timer.
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..), help_heading = "Devices")]
+    pub frame_rate: Option<u32>,
+    /// Map the host services, at $F070.
+    #[arg(long, help_heading = "Devices")]
+    pub host_services: bool,
+    /// The host services, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub host_services_addr: Option<u16>,
+    /// File backing the block commands of the host services.
+    #[arg(long, value_name = "FILE", help_heading = "Devices")]
+    pub host_block_file: Option<PathBuf>,
+    /// Directory the file commands of the host services load and save in.
+    #[arg(long, value_name = "DIR", help_heading = "Devices")]
+    pub host_dir: Option<PathBuf>,
+    /// Map a random number generator, at $F028.
+    #[arg(long, help_heading = "Devices")]
+    pub rng: bool,
+    /// The random number generator, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub rng_addr: Option<u16>,
+    /// Map a cycle counter, at $F0B8.
+    #[arg(long, help_heading = "Devices")]
+    pub cycle_counter: bool,
+    /// The cycle counter, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Devices")]
+    pub cycle_counter_addr: Option<u16>,
+    /// Show a framebuffer in a window, `text:<columns>x<rows>` or `bitmap:<width>x<height>`. Needs the `video`
+    /// feature.
+    #[arg(long, value_name = "MODE", help_heading = "Devices")]
+    pub video: Option<String>,
+    /// The video memory, at this address instead.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "video", help_heading = "Devices")]
+    pub video_addr: Option<u16>,
+
+    /// Watch this address for the program's verdict.
+    #[arg(long, value_name = "ADDR", value_parser = address, help_heading = "Stopping")]
+    pub result_addr: Option<u16>,
+    /// Value written there on a pass.
+    #[arg(long, value_name = "BYTE", value_parser = byte, requires = "result_addr", help_heading = "Stopping")]
+    pub result_pass: Option<u8>,
+    /// Value written there on a failure.
+    #[arg(long, value_name = "BYTE", value_parser = byte, requires = "result_addr", help_heading = "Stopping")]
+    pub result_fail: Option<u8>,
+    /// Address of the error code reported with a failure.
+    #[arg(long, value_name = "ADDR", value_parser = address, requires = "result_addr", help_heading = "Stopping")]
+    pub result_error_addr: Option<u16>,
+    /// Stop after this many instructions.
+    #[arg(long, value_name = "N", help_heading = "Stopping")]
+    pub max_instructions: Option<u64>,
+    /// Stop at the first instruction boundary past this many cycles.
+    #[arg(long, value_name = "N", help_heading = "Stopping")]
+    pub max_cycles: Option<u64>,
+    /// Stop an image that has run this long, such as `30s` or `500ms`, and go on to the next.
+    #[arg(long, value_name = "TIME", value_parser = duration, help_heading = "Stopping")]
+    pub timeout: Option<Duration>,
+    /// Stop when the CPU reaches this address or label. Can be given more than once.
+    #[arg(long, value_name = "ADDR", help_heading = "Stopping")]
+    pub halt_addr: Vec<String>,
+
+    /// Drive the run from a Rhai script, called back on breakpoints, memory writes and timer ticks. Needs the
+    /// `scripting` feature.
+    #[arg(long, value_name = "FILE", help_heading = "Scripting")]
+    pub script: Option<PathBuf>,
+    /// Serve the state of the run and control of it over HTTP and a WebSocket, on <host:port>. Needs the
+    /// `remote` feature.
+    #[arg(long, value_name = "ADDR", help_heading = "Scripting")]
+    pub remote: Option<String>,
+
+    /// Record the input the host sends the machine.
+    #[arg(long, value_name = "FILE", conflicts_with = "replay_input", help_heading = "Output")]
+    pub record_input: Option<PathBuf>,
+    /// Replay recorded input in place of the host's.
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub replay_input: Option<PathBuf>,
+    /// Fingerprint every instruction to a file, for `--check-fingerprints` to hold a later run to.
+    #[arg(long, value_name = "FILE", conflicts_with = "check_fingerprints", help_heading = "Output")]
+    pub record_fingerprints: Option<PathBuf>,
+    /// Check that the run does exactly what the recorded one did, stopping where it parts ways.
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub check_fingerprints: Option<PathBuf>,
+    /// Format of the RAM dump.
+    #[arg(long, value_name = "FORMAT", value_parser = dump_format(), default_value = "raw", help_heading = "Output")]
+    pub dump_format: DumpFormat,
+    /// Also dump a range of RAM every so many cycles, or instructions with `i`, to numbered files, as
+    /// <interval>:<start>-<end>, such as `1000000:0200-03FF`.
+    #[arg(long, value_name = "EVERY", value_parser = dump_every, help_heading = "Output")]
+    pub dump_every: Option<DumpEvery>,
+    /// Name the RAM dumps from a template of `{name}`, `{kind}`, `{ext}` and `{date}`, which may hold directories.
+    #[arg(long, value_name = "TEMPLATE", default_value = "{name}_{kind}.{ext}", help_heading = "Output")]
+    pub out_template: String,
+    /// Also write the final registers and state, as a save state `--resume` picks up, named as the dumps are.
+    #[arg(long, help_heading = "Output")]
+    pub dump_state: bool,
+    /// Dump just this range of RAM, as <start>-<end>.
+    #[arg(long, value_name = "RANGE", value_parser = range, help_heading = "Output")]
+    pub dump_range: Option<(u16, u16)>,
+    /// Write the final state as a report.
+    #[arg(long, value_name = "FORMAT", value_enum, help_heading = "Output")]
+    pub report: Option<ReportFormat>,
+    /// Write where the run spent its time to a file.
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub profile: Option<PathBuf>,
+    /// Write which ROM addresses ran, as an annotated listing and a JSON summary beside the RAM dump.
+    #[arg(long, help_heading = "Output")]
+    pub coverage: bool,
+    /// Save the state every so often, such as `5s`, `500ms` or `20M` cycles, rotating through three files.
+    #[arg(long, value_name = "INTERVAL", value_parser = autosave_interval, help_heading = "Output")]
+    pub autosave: Option<AutosaveInterval>,
+    /// Print how fast each image ran at the end, or with `--stats=live` also on a status line every second.
+    #[arg(long, value_name = "WHEN", value_parser = stats_mode(), num_args = 0..=1, require_equals = true, default_missing_value = "summary", help_heading = "Output")]
+    pub stats: Option<StatsMode>,
+    /// Check the final RAM against a golden file, as <file>[:<start>-<end>].
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub compare: Option<String>,
+    /// Label file naming addresses in traces and address flags.
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub symbols: Option<PathBuf>,
+    /// Trace every instruction to a file.
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub trace: Option<PathBuf>,
+    /// Begin tracing at this address or label.
+    #[arg(long, value_name = "ADDR", requires = "trace", help_heading = "Output")]
+    pub trace_start: Option<String>,
+    /// End tracing at this address or label.
+    #[arg(long, value_name = "ADDR", requires = "trace", help_heading = "Output")]
+    pub trace_stop: Option<String>,
+    /// Lay the trace out as Steel6502 does, or as VICE's monitor does.
+    #[arg(long, value_name = "FORMAT", value_parser = trace_format(), default_value = "steel", requires = "trace", help_heading = "Output")]
+    pub trace_format: TraceFormat,
+    /// Print a register, `byte:<addr>` or `word:<addr>` when it changes. Can be given more than once.
+    #[arg(long, value_name = "WATCH", help_heading = "Output")]
+    pub watch: Vec<String>,
+    /// Print the watches every N instructions instead.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), requires = "watch", help_heading = "Output")]
+    pub watch_every: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs{
+    /// Golden save state the final state must match.
+    #[arg(long, value_name = "FILE")]
+    pub against: PathBuf,
+    /// Write the golden state from this run instead of checking against it.
+    #[arg(long)]
+    pub update: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Args)]
+pub struct BenchArgs{
+    /// Image to run in place of the built-in workload; one ending in `BRK` is started again.
+    pub image: Option<PathBuf>,
+    /// How long to run for, such as `10s`.
+    #[arg(long, value_name = "TIME", value_parser = duration, default_value = "5s")]
+    pub time: Duration,
+    /// Place a ROM image smaller than 32KB so that it ends at the vectors.
+    #[arg(long)]
+    pub place_top: bool,
+    /// Run hot code from ROM compiled to native code. Needs the `jit` feature.
+    #[arg(long)]
+    pub jit: bool,
+}
+
+#[derive(Args)]
+pub struct DisasmArgs{
+    pub image: PathBuf,
+    /// Address of the first byte of the image; by default the image ends at $FFFF.
+    #[arg(long, value_name = "ADDR")]
+    pub org: Option<String>,
+    /// Label file naming addresses.
+    #[arg(long, value_name = "FILE")]
+    pub symbols: Option<PathBuf>,
+    /// List just these addresses, as <start>-<end>.
+    #[arg(long, value_name = "RANGE", value_parser = range, conflicts_with = "source")]
+    pub range: Option<(u16, u16)>,
+    /// Write source that assembles back into the image, with labels, in place of the listing.
+    #[arg(long)]
+    pub source: bool,
+    /// Trace of a run of the image, from `--trace`, telling its code from its data for `--source`.
+    #[arg(long, value_name = "FILE", requires = "source")]
+    pub trace: Option<PathBuf>,
+    /// Write the listing to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct AsmArgs{
+    pub source: PathBuf,
+    /// The image; by default the source's name with `.bin`.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Write the source with the address and bytes of each line.
+    #[arg(long, value_name = "FILE")]
+    pub listing: Option<PathBuf>,
+    /// Write a label file of the symbols, for `--symbols`.
+    #[arg(long, value_name = "FILE")]
+    pub labels: Option<PathBuf>,
+    /// Address of the first byte of the image; by default the start of the ROM window.
+    #[arg(long, value_name = "ADDR", value_parser = address, default_value = "$8000")]
+    pub origin: u16,
+    /// Length of the image in bytes; by default the ROM window, so that `run` loads the image as it is.
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..=0x10000), default_value_t = 0x8000)]
+    pub size: u32,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ReportFormat{
+    Json,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat{
+    Text,
+    Json,       // one object on a line, for tools
+}
+
+
+fn address(text: &str) -> Result<u16, String>{
+    parse_address(text).ok_or(format!("`{}` is not an address, such as $F001", text))
+}
+fn byte(text: &str) -> Result<u8, String>{
+    parse_byte(text).ok_or(format!("`{}` is not a byte, such as $FF", text))
+}
+fn range(text: &str) -> Result<(u16, u16), String>{
+    parse_range(text).ok_or(format!("`{}` is not a range of addresses, such as 0200-02FF", text))
+}
+fn frequency(text: &str) -> Result<u64, String>{
+    parse_frequency(text).ok_or(format!("`{}` is not a frequency, such as 1MHz", text))
+}
+fn duration(text: &str) -> Result<Duration, String>{
+    parse_duration(text).filter(|d| !d.is_zero()).ok_or(format!("`{}` is not a length of time, such as 30s or 500ms", text))
+}
+fn dump_every(text: &str) -> Result<DumpEvery, String>{
+    parse_dump_every(text).ok_or(format!("`{}` is not an interval and a range, such as 1000000:0200-03FF or 50000i:0200-03FF", text))
+}
+fn autosave_interval(text: &str) -> Result<AutosaveInterval, String>{
+    parse_autosave_interval(text).ok_or(format!("`{}` is not an interval, such as 5s or 20M cycles", text))
+}
+fn size(text: &str) -> Result<(usize, usize), String>{
+    parse_size(text).ok_or(format!("`{}` is not a size, such as 40x25", text))
+}
+fn disk_geometry(text: &str) -> Result<DiskGeometry, String>{
+    parse_disk_geometry(text).ok_or(format!("`{}` is not a disk geometry, such as 35x16x256", text))
+}
+fn digits(text: &str) -> Result<usize, String>{
+    text.parse().ok().filter(|d| *d <= LedBank::MAX_DIGITS).ok_or(format!("`{}` is not a count of digits, up to {}", text, LedBank::MAX_DIGITS))
+}
+fn joystick_keys(text: &str) -> Result<JoystickKeys, String>{
+    JoystickKeys::parse(text).ok_or(format!("`{}` is not five keys, for up, down, left, right and fire", text))
+}
+fn serial_chip() -> impl TypedValueParser<Value = SerialChip>{
+    PossibleValuesParser::new(["6551", "6850"]).map(|chip| if chip == "6551" { SerialChip::W65C51 } else { SerialChip::MC6850 })
+}
+fn dump_format() -> impl TypedValueParser<Value = DumpFormat>{
+    PossibleValuesParser::new(["raw", "hexdump", "ihex", "srec"]).map(|format| match format.as_str(){
+        "hexdump" => DumpFormat::Hexdump,
+        "ihex" => DumpFormat::Ihex,
+        "srec" => DumpFormat::Srec,
+        _ => DumpFormat::Raw,
+    })
+}
+fn stats_mode() -> impl TypedValueParser<Value = StatsMode>{
+    PossibleValuesParser::new(["summary", "live"]).map(|mode| if mode == "summary" { StatsMode::Summary } else { StatsMode::Live })
+}
+fn trace_format() -> impl TypedValueParser<Value = TraceFormat>{
+    PossibleValuesParser::new(["steel", "vice"]).map(|format| if format == "steel" { TraceFormat::Steel } else { TraceFormat::Vice })
+}
@@ -0,0 +1,625 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::cpu::w65c02s::{AddressingMode, W65C02S};
+
+// byte written to the gaps between the stretches of code and data in the image, as an erased EPROM reads
+const FILL_BYTE: u8 = 0xff;
+
+/// The output of `assemble`: an image running from the lowest address written to the highest, with a listing
+/// of the source beside the bytes each line produced and the value of every label and constant.
+#[derive(Clone, Debug)]
+pub struct Assembly{
+    pub origin: u16,                        // address of the first byte of the image
+    pub image: Vec<u8>,
+    pub listing: String,
+    pub symbols: BTreeMap<String, u16>,
+}
+impl Assembly{
+    /// The symbols as a VICE label file, which `SymbolTable` reads back.
+    pub fn labels(&self) -> String{
+        self.symbols.iter().map(|(name, address)| format!("al C:{:04X} .{}\n", address, name)).collect()
+    }
+    /// The image laid out in a window of `size` bytes from `origin`, the rest of the window filled as the gaps
+    /// are; None when the source writes outside the window.
+    pub fn windowed(&self, origin: u16, size: usize) -> Option<Vec<u8>>{
+        if origin as usize + size > 0x10000{
+            return None;
+        }
+        let mut window = vec![FILL_BYTE; size];
+        if self.image.is_empty(){
+            return Some(window);
+        }
+        let start = (self.origin as usize).checked_sub(origin as usize)?;
+        window.get_mut(start..start + self.image.len())?.copy_from_slice(&self.image);
+        Some(window)
+    }
+}
+
+#[derive(Debug)]
+pub struct AssemblyError{
+    pub line: usize,                        // counted from 1
+    pub kind: AssemblyErrorKind,
+}
+
+#[derive(Debug)]
+pub enum AssemblyErrorKind{
+    UnknownInstruction(String),
+    UnknownDirective(String),
+    InvalidOperand(String),                 // not an addressing mode the instruction has
+    InvalidExpression(String),
+    UndefinedSymbol(String),
+    DuplicateSymbol(String),
+    ValueOutOfRange(i64),
+    BranchOutOfRange(i64),                  // the distance to the target
+    Overlap(u16),                           // two lines write this address
+    OutsideAddressSpace,
+}
+impl fmt::Display for AssemblyError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind{
+            AssemblyErrorKind::UnknownInstruction(name) => write!(f, "unknown instruction {}", name),
+            AssemblyErrorKind::UnknownDirective(name) => write!(f, "unknown directive {}", name),
+            AssemblyErrorKind::InvalidOperand(operand) => write!(f, "invalid operand {}", operand),
+            AssemblyErrorKind::InvalidExpression(text) => write!(f, "invalid expression {}", text),
+            AssemblyErrorKind::UndefinedSymbol(name) => write!(f, "undefined symbol {}", name),
+            AssemblyErrorKind::DuplicateSymbol(name) => write!(f, "{} is defined twice", name),
+            AssemblyErrorKind::ValueOutOfRange(value) => write!(f, "value {} does not fit", value),
+            AssemblyErrorKind::BranchOutOfRange(distance) => write!(f, "branch of {} bytes is out of range", distance),
+            AssemblyErrorKind::Overlap(address) => write!(f, "${:04X} is written twice", address),
+            AssemblyErrorKind::OutsideAddressSpace => write!(f, "code runs past $FFFF"),
+        }
+    }
+}
+
+/// What one line of source asks for, besides the label it may define.
+enum Statement<'a>{
+    Constant(&'a str),                      // `name = value`, the name being the label
+    Org(&'a str),
+    Bytes(Vec<&'a str>),                    // expressions and quoted strings
+    Words(Vec<&'a str>),
+    Fill(&'a str, Option<&'a str>),         // count, and the byte to fill with
+    Instruction(&'a str, &'a str),          // mnemonic and operand
+}
+
+struct Line<'a>{
+    text: &'a str,
+    label: Option<&'a str>,
+    statement: Option<Statement<'a>>,
+}
+
+/// Assembles 65C02 source in the common syntax, taking the opcodes from the CPU's own table:
+///
+/// ```text
+/// char_out = $f001        ; constants, with `=` or `EQU`
+///         .org $8000      ; also `*= $8000`
+/// start:  LDX #0
+/// loop:   LDA message,X
+///         BEQ done
+///         STA char_out
+///         INX
+///         BRA loop
+/// done:   BRA done
+/// message: .byte "Hi", 0  ; also .word and .fill <count>[, <byte>], or .res as ca65 spells it
+/// ```
+///
+/// `.setcpu` is accepted and ignored, so that source written for ca65 assembles too.
+///
+/// Numbers are `$ff`, `%1010`, `'c'` or decimal; expressions add and subtract them, labels and `*` (the address
+/// of the line), and `<` or `>` in front takes the low or high byte. Operands whose value is known by the time
+/// they are reached and fits in a byte use zero page addressing where the instruction has it.
+pub fn assemble(source: &str) -> Result<Assembly, AssemblyError>{
+    let lines = source.lines().enumerate()
+        .map(|(i, text)| parse_line(text).map_err(|kind| AssemblyError { line: i + 1, kind }))
+        .collect::<Result<Vec<Line>, AssemblyError>>()?;
+
+    // the first pass places the labels, deciding the size of each instruction as it goes
+    let mut symbols = BTreeMap::new();
+    let mut defined = HashSet::new();
+    let mut opcodes = vec![None; lines.len()];
+    let mut pc = 0u32;
+    for (i, line) in lines.iter().enumerate(){
+        let error = |kind| AssemblyError { line: i + 1, kind };
+        if let Some(label) = line.label{
+            if !defined.insert(label){
+                return Err(error(AssemblyErrorKind::DuplicateSymbol(label.to_string())));
+            }
+            let value = match &line.statement{
+                Some(Statement::Constant(value)) => evaluate(value, &symbols, pc, false).map_err(error)?,
+                _ => Some(pc as i64),
+            };
+            if let Some(value) = value{
+                symbols.insert(label.to_string(), value as u16);
+            }
+        }
+
+        match &line.statement{
+            Some(Statement::Org(address)) => pc = known(evaluate(address, &symbols, pc, false), address).and_then(word).map_err(error)? as u32,
+            Some(Statement::Bytes(items)) => pc += items.iter().map(|item| string_bytes(item).map_or(1, |s| s.len() as u32)).sum::<u32>(),
+            Some(Statement::Words(items)) => pc += 2 * items.len() as u32,
+            Some(Statement::Fill(count, _)) => pc += known(evaluate(count, &symbols, pc, false), count).and_then(word).map_err(error)? as u32,
+            Some(Statement::Instruction(mnemonic, operand)) => {
+                let opcode = select_opcode(mnemonic, operand, &symbols, pc).map_err(error)?;
+                opcodes[i] = Some(opcode);
+                pc += 1 + operation_mode(opcode).num_operand_bytes() as u32;
+            },
+            Some(Statement::Constant(_)) | None => (),
+        }
+        if pc > 0x10000{
+            return Err(error(AssemblyErrorKind::OutsideAddressSpace));
+        }
+    }
+
+    // the second encodes every line, now that all the labels are known
+    let mut memory: BTreeMap<u16, u8> = BTreeMap::new();
+    let mut listing = String::new();
+    pc = 0;
+    for (i, line) in lines.iter().enumerate(){
+        let error = |kind| AssemblyError { line: i + 1, kind };
+        let value = |text: &str, pc: u32| known(evaluate(text, &symbols, pc, true), text);
+        let mut bytes = Vec::new();
+        match &line.statement{
+            Some(Statement::Constant(text)) => {
+                let constant = value(text, pc).and_then(word).map_err(error)?;
+                symbols.insert(line.label.unwrap_or_default().to_string(), constant);
+            },
+            Some(Statement::Org(address)) => pc = value(address, pc).map_err(error)? as u32,
+            Some(Statement::Bytes(items)) => for item in items{
+                match string_bytes(item){
+                    Some(string) => bytes.extend(string),
+                    None => bytes.push(value(item, pc).and_then(byte).map_err(error)?),
+                }
+            },
+            Some(Statement::Words(items)) => for item in items{
+                bytes.extend(value(item, pc).and_then(word).map_err(error)?.to_le_bytes());
+            },
+            Some(Statement::Fill(count, fill)) => {
+                let fill = fill.map(|fill| value(fill, pc).and_then(byte)).transpose().map_err(error)?;
+                bytes.resize(value(count, pc).and_then(word).map_err(error)? as usize, fill.unwrap_or(0));
+            },
+            Some(Statement::Instruction(_, operand)) => {
+                let opcode = opcodes[i].unwrap_or_default();
+                bytes.push(opcode);
+                bytes.extend(encode_operand(operation_mode(opcode), operand, &symbols, pc).map_err(error)?);
+            },
+            None => (),
+        }
+
+        let address = pc as u16;
+        for (offset, &b) in bytes.iter().enumerate(){
+            let address = address.wrapping_add(offset as u16);
+            if memory.insert(address, b).is_some(){
+                return Err(error(AssemblyErrorKind::Overlap(address)));
+            }
+        }
+        listing.push_str(&listing_line(address, &bytes, line.text));
+        pc += bytes.len() as u32;
+    }
+
+    let (origin, last) = match (memory.first_key_value(), memory.last_key_value()){
+        (Some((&first, _)), Some((&last, _))) => (first, last),
+        _ => (0, 0),
+    };
+    let mut image = vec![FILL_BYTE; if memory.is_empty() { 0 } else { (last - origin) as usize + 1 }];
+    for (address, b) in memory{
+        image[(address - origin) as usize] = b;
+    }
+    Ok(Assembly { origin, image, listing, symbols })
+}
+
+/// The address and up to three bytes in front of the source line, as the disassembler lays them out, with
+/// longer runs of data carried on to lines of their own.
+fn listing_line(address: u16, bytes: &[u8], text: &str) -> String{
+    let hex = |chunk: &[u8]| chunk.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+    let mut chunks = bytes.chunks(3);
+    let mut listing = match chunks.next(){
+        Some(chunk) => format!("{:04X}  {:<8}  {}\n", address, hex(chunk), text),
+        None => format!("{:16}{}\n", "", text),
+    };
+    for (i, chunk) in chunks.enumerate(){
+        listing.push_str(&format!("{:04X}  {}\n", address.wrapping_add(3 * (i as u16 + 1)), hex(chunk)));
+    }
+    listing
+}
+
+fn parse_line(text: &str) -> Result<Line<'_>, AssemblyErrorKind>{
+    let code = strip_comment(text).trim();
+    let mut line = Line { text, label: None, statement: None };
+
+    let (first, rest) = split_word(code);
+    let rest = if let Some(label) = first.strip_suffix(':'){
+        line.label = Some(label);
+        rest
+    }
+    else{
+        code
+    };
+    if rest.is_empty(){
+        return Ok(line);
+    }
+
+    let (word, operand) = split_word(rest);
+    let (equ, value) = split_word(operand);
+    if let Some(address) = rest.strip_prefix("*="){
+        line.statement = Some(Statement::Org(address.trim()));
+    }
+    else if let Some(value) = operand.strip_prefix('='){
+        line.label = Some(word);
+        line.statement = Some(Statement::Constant(value.trim()));
+    }
+    else if equ.eq_ignore_ascii_case("equ"){
+        line.label = Some(word);
+        line.statement = Some(Statement::Constant(value));
+    }
+    else if word.eq_ignore_ascii_case(".setcpu"){
+        // for source written for ca65; there is only the one CPU
+        return Ok(line);
+    }
+    else if let Some(directive) = word.strip_prefix('.'){
+        let items = split_items(operand);
+        line.statement = Some(match directive.to_ascii_lowercase().as_str(){
+            "org" => Statement::Org(operand),
+            "byte" | "db" => Statement::Bytes(items),
+            "word" | "dw" => Statement::Words(items),
+            "fill" | "res" if matches!(items.len(), 1 | 2) => Statement::Fill(items[0], items.get(1).copied()),
+            "fill" | "res" => return Err(AssemblyErrorKind::InvalidOperand(operand.to_string())),
+            _ => return Err(AssemblyErrorKind::UnknownDirective(word.to_string())),
+        });
+    }
+    else{
+        line.statement = Some(Statement::Instruction(word, operand));
+    }
+    Ok(line)
+}
+
+fn split_word(text: &str) -> (&str, &str){
+    match text.split_once(char::is_whitespace){
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// Everything before a `;` that is not inside quotes.
+fn strip_comment(text: &str) -> &str{
+    let mut quote = None;
+    for (i, c) in text.char_indices(){
+        match (c, quote){
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (';', None) => return &text[..i],
+            _ => (),
+        }
+    }
+    text
+}
+
+/// The comma-separated items of a directive, leaving commas inside quotes alone.
+fn split_items(text: &str) -> Vec<&str>{
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices(){
+        match (c, quote){
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (',', None) => {
+                items.push(text[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    if !text.trim().is_empty(){
+        items.push(text[start..].trim());
+    }
+    items
+}
+
+fn string_bytes(item: &str) -> Option<&[u8]>{
+    item.strip_prefix('"')?.strip_suffix('"').map(str::as_bytes)
+}
+
+/// The value of an expression, or None in the first pass when it names a symbol not yet defined.
+fn evaluate(text: &str, symbols: &BTreeMap<String, u16>, pc: u32, final_pass: bool) -> Result<Option<i64>, AssemblyErrorKind>{
+    let invalid = || AssemblyErrorKind::InvalidExpression(text.to_string());
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix('<'){
+        return Ok(evaluate(rest, symbols, pc, final_pass)?.map(|value| value & 0xff));
+    }
+    if let Some(rest) = text.strip_prefix('>'){
+        return Ok(evaluate(rest, symbols, pc, final_pass)?.map(|value| (value >> 8) & 0xff));
+    }
+
+    let mut total = Some(0i64);
+    let mut rest = text;
+    let mut sign = 1;
+    loop{
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('-'){
+            sign = -sign;
+            rest = after;
+            continue;
+        }
+        let end = term_length(rest).ok_or_else(invalid)?;
+        let term = match &rest[..end]{
+            "*" => Some(pc as i64),
+            term => term_value(term, symbols, final_pass).ok_or_else(invalid)??,
+        };
+        total = total.zip(term).map(|(total, term)| total + sign * term);
+
+        rest = rest[end..].trim_start();
+        if rest.is_empty(){
+            return Ok(total);
+        }
+        sign = match rest.as_bytes()[0]{
+            b'+' => 1,
+            b'-' => -1,
+            _ => return Err(invalid()),
+        };
+        rest = &rest[1..];
+    }
+}
+
+fn term_length(text: &str) -> Option<usize>{
+    if text.starts_with('*'){
+        return Some(1);
+    }
+    if text.starts_with('\''){
+        return text.char_indices().nth(2).filter(|(_, c)| *c == '\'').map(|(i, _)| i + 1);
+    }
+    let length = text.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '%'))).unwrap_or(text.len());
+    (length > 0).then_some(length)
+}
+
+/// None when the term is not a number or a name; Some(Err) for a name that is not defined in the last pass.
+fn term_value(term: &str, symbols: &BTreeMap<String, u16>, final_pass: bool) -> Option<Result<Option<i64>, AssemblyErrorKind>>{
+    let number = if let Some(digits) = term.strip_prefix('$'){
+        i64::from_str_radix(digits, 16).ok()
+    }
+    else if let Some(digits) = term.strip_prefix('%'){
+        i64::from_str_radix(digits, 2).ok()
+    }
+    else if let Some(character) = term.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')){
+        character.chars().next().map(|c| c as i64)
+    }
+    else if term.starts_with(|c: char| c.is_ascii_digit()){
+        term.parse().ok()
+    }
+    else{
+        return match symbols.get(term){
+            Some(&value) => Some(Ok(Some(value as i64))),
+            None if final_pass => Some(Err(AssemblyErrorKind::UndefinedSymbol(term.to_string()))),
+            None => Some(Ok(None)),
+        };
+    };
+    number.map(|n| Ok(Some(n)))
+}
+
+fn known(value: Result<Option<i64>, AssemblyErrorKind>, text: &str) -> Result<i64, AssemblyErrorKind>{
+    value?.ok_or_else(|| AssemblyErrorKind::UndefinedSymbol(text.trim().to_string()))
+}
+fn byte(value: i64) -> Result<u8, AssemblyErrorKind>{
+    if (-128..=255).contains(&value) { Ok(value as u8) } else { Err(AssemblyErrorKind::ValueOutOfRange(value)) }
+}
+fn word(value: i64) -> Result<u16, AssemblyErrorKind>{
+    if (-32768..=65535).contains(&value) { Ok(value as u16) } else { Err(AssemblyErrorKind::ValueOutOfRange(value)) }
+}
+
+/// The forms an operand can take, told apart by its punctuation.
+enum OperandSyntax<'a>{
+    None,
+    Accumulator,
+    Immediate(&'a str),
+    IndexedIndirect(&'a str),       // (a,X)
+    IndirectIndexed(&'a str),       // (zp),Y
+    Indirect(&'a str),              // (a)
+    IndexedX(&'a str),
+    IndexedY(&'a str),
+    Pair(&'a str, &'a str),         // zp,target of BBR and BBS
+    Plain(&'a str),
+}
+
+fn operand_syntax(operand: &str) -> OperandSyntax<'_>{
+    let operand = operand.trim();
+    if operand.is_empty(){
+        OperandSyntax::None
+    }
+    else if operand.eq_ignore_ascii_case("a"){
+        OperandSyntax::Accumulator
+    }
+    else if let Some(value) = operand.strip_prefix('#'){
+        OperandSyntax::Immediate(value)
+    }
+    else if let Some(inner) = operand.strip_prefix('(').and_then(|o| o.strip_suffix(')')) && let Some(address) = indexed(inner, "x"){
+        OperandSyntax::IndexedIndirect(address)
+    }
+    else if let Some(pointer) = indexed(operand, "y").and_then(|o| o.strip_prefix('(')).and_then(|o| o.strip_suffix(')')){
+        OperandSyntax::IndirectIndexed(pointer)
+    }
+    else if let Some(inner) = operand.strip_prefix('(').and_then(|o| o.strip_suffix(')')){
+        OperandSyntax::Indirect(inner)
+    }
+    else if let Some(address) = indexed(operand, "x"){
+        OperandSyntax::IndexedX(address)
+    }
+    else if let Some(address) = indexed(operand, "y"){
+        OperandSyntax::IndexedY(address)
+    }
+    else if let Some((zero_page, target)) = split_items(operand).split_first().filter(|(_, rest)| rest.len() == 1).map(|(first, rest)| (*first, rest[0])){
+        OperandSyntax::Pair(zero_page, target)
+    }
+    else{
+        OperandSyntax::Plain(operand)
+    }
+}
+
+/// The expression in front of `,X` or `,Y`.
+fn indexed<'a>(text: &'a str, register: &str) -> Option<&'a str>{
+    text.rsplit_once(',').filter(|(_, index)| index.trim().eq_ignore_ascii_case(register)).map(|(expression, _)| expression.trim())
+}
+
+fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<u8>{
+    (0..=255u8).find(|&opcode| W65C02S::operation(opcode)
+        .is_some_and(|operation| operation.addressing_mode() == mode && operation.mnemonic().to_string().eq_ignore_ascii_case(mnemonic)))
+}
+
+fn operation_mode(opcode: u8) -> AddressingMode{
+    W65C02S::operation(opcode).map_or(AddressingMode::Implied, |operation| operation.addressing_mode())
+}
+
+/// Picks the addressing mode, and so the opcode, from the operand's syntax and, between zero page and absolute
+/// forms, from whether its value is known yet and fits in a byte.
+fn select_opcode(mnemonic: &str, operand: &str, symbols: &BTreeMap<String, u16>, pc: u32) -> Result<u8, AssemblyErrorKind>{
+    if !(0..=255u8).any(|opcode| W65C02S::operation(opcode).is_some_and(|operation| operation.mnemonic().to_string().eq_ignore_ascii_case(mnemonic))){
+        return Err(AssemblyErrorKind::UnknownInstruction(mnemonic.to_string()));
+    }
+    let sized = |expression: &str, zero_page: AddressingMode, absolute: AddressingMode| -> Result<Option<u8>, AssemblyErrorKind>{
+        let fits = evaluate(expression, symbols, pc, false)?.is_some_and(|value| (0..=255).contains(&value));
+        let zero_page = find_opcode(mnemonic, zero_page);
+        let absolute = find_opcode(mnemonic, absolute);
+        Ok(if fits { zero_page.or(absolute) } else { absolute.or(zero_page) })
+    };
+
+    let opcode = match operand_syntax(operand){
+        OperandSyntax::None => [AddressingMode::Implied, AddressingMode::Stack, AddressingMode::Accumulator].into_iter().find_map(|mode| find_opcode(mnemonic, mode)),
+        OperandSyntax::Accumulator => find_opcode(mnemonic, AddressingMode::Accumulator),
+        OperandSyntax::Immediate(_) => find_opcode(mnemonic, AddressingMode::Immediate),
+        OperandSyntax::IndexedIndirect(address) => sized(address, AddressingMode::ZeroPageIndexedIndirect, AddressingMode::AbsoluteIndexedIndirect)?,
+        OperandSyntax::IndirectIndexed(_) => find_opcode(mnemonic, AddressingMode::ZeroPageIndirectIndexedY),
+        OperandSyntax::Indirect(address) => sized(address, AddressingMode::ZeroPageIndirect, AddressingMode::AbsoluteIndirect)?,
+        OperandSyntax::IndexedX(address) => sized(address, AddressingMode::ZeroPageIndexedX, AddressingMode::AbsoluteIndexedX)?,
+        OperandSyntax::IndexedY(address) => sized(address, AddressingMode::ZeroPageIndexedY, AddressingMode::AbsoluteIndexedY)?,
+        OperandSyntax::Pair(..) => find_opcode(mnemonic, AddressingMode::ZeroPageRelative),
+        OperandSyntax::Plain(address) => match find_opcode(mnemonic, AddressingMode::ProgramCounterRelative){
+            Some(opcode) => Some(opcode),
+            None => sized(address, AddressingMode::ZeroPage, AddressingMode::Absolute)?,
+        },
+    };
+    opcode.ok_or_else(|| AssemblyErrorKind::InvalidOperand(operand.to_string()))
+}
+
+fn encode_operand(mode: AddressingMode, operand: &str, symbols: &BTreeMap<String, u16>, pc: u32) -> Result<Vec<u8>, AssemblyErrorKind>{
+    let value = |text: &str| known(evaluate(text, symbols, pc, true), text);
+    let branch = |target: &str, length: u32| -> Result<u8, AssemblyErrorKind>{
+        let distance = value(target)? - (pc + length) as i64;
+        if (-128..=127).contains(&distance) { Ok(distance as u8) } else { Err(AssemblyErrorKind::BranchOutOfRange(distance)) }
+    };
+
+    let expression = match operand_syntax(operand){
+        OperandSyntax::None | OperandSyntax::Accumulator => return Ok(Vec::new()),
+        OperandSyntax::Pair(zero_page, target) => return Ok(vec![value(zero_page).and_then(byte)?, branch(target, 3)?]),
+        OperandSyntax::Immediate(e) | OperandSyntax::IndexedIndirect(e) | OperandSyntax::IndirectIndexed(e) | OperandSyntax::Indirect(e) |
+        OperandSyntax::IndexedX(e) | OperandSyntax::IndexedY(e) | OperandSyntax::Plain(e) => e,
+    };
+    match mode{
+        AddressingMode::ProgramCounterRelative => Ok(vec![branch(expression, 2)?]),
+        mode if mode.num_operand_bytes() == 2 => Ok(value(expression).and_then(word)?.to_le_bytes().to_vec()),
+        _ => {
+            let value = value(expression)?;
+            // a zero page operand is an address, so cannot be negative as an immediate byte can
+            if mode != AddressingMode::Immediate && !(0..=255).contains(&value){
+                return Err(AssemblyErrorKind::ValueOutOfRange(value));
+            }
+            Ok(vec![byte(value)?])
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::bus::board;
+    use crate::cpu::disassembler;
+    use crate::cpu::w65c02s::{DecodedInstruction, Mnemonic};
+
+    /// Assembles one instruction at $8000, returning its bytes.
+    fn assemble_one(text: &str) -> Result<Vec<u8>, AssemblyErrorKind>{
+        let assembly = assemble(&format!("        .org $8000\n        {}\n", text)).map_err(|e| e.kind)?;
+        Ok(assembly.image)
+    }
+
+    #[test]
+    fn every_opcode_round_trips(){
+        for opcode in 0..=255u8{
+            let Some(operation) = W65C02S::operation(opcode) else { continue };
+            // zero page operands stay below $100, and branches land near enough to reach
+            let bytes = [opcode, 0x34, 0x12];
+            let disassembly = disassembler::disassemble(0x8000, |address| bytes.get(address.wrapping_sub(0x8000) as usize).copied());
+
+            let assembled = assemble_one(&disassembly.text).unwrap_or_else(|e| panic!("${:02X} {}: {:?}", opcode, disassembly.text, e));
+            assert_eq!(assembled, disassembly.bytes, "${:02X} {}", opcode, disassembly.text);
+
+            let mut padded = [0; 3];
+            padded[..assembled.len()].copy_from_slice(&assembled);
+            let decoded = DecodedInstruction::decode(padded);
+            assert_eq!(decoded.length() as usize, assembled.len(), "${:02X} {}", opcode, disassembly.text);
+            assert_eq!(decoded.operation().addressing_mode(), operation.addressing_mode(), "${:02X} {}", opcode, disassembly.text);
+            assert_eq!(decoded.operation().mnemonic().to_string(), operation.mnemonic().to_string(), "${:02X} {}", opcode, disassembly.text);
+            assert_eq!(decoded.operand()[..assembled.len() - 1], assembled[1..], "${:02X} {}", opcode, disassembly.text);
+        }
+    }
+
+    #[test]
+    fn stz_has_the_modes_of_the_data_sheet(){
+        assert_eq!(assemble_one("STZ $12").unwrap(), [0x64, 0x12]);
+        assert_eq!(assemble_one("STZ $12,X").unwrap(), [0x74, 0x12]);
+        assert_eq!(assemble_one("STZ $1234").unwrap(), [0x9c, 0x34, 0x12]);
+        assert_eq!(assemble_one("STZ $1234,X").unwrap(), [0x9e, 0x34, 0x12]);
+        assert!(matches!(assemble_one("STZ $1234,Y"), Err(AssemblyErrorKind::InvalidOperand(_))));
+
+        let decoded = DecodedInstruction::decode([0x9c, 0x34, 0x12]);
+        assert!(matches!(decoded.operation().mnemonic(), Mnemonic::STZ));
+        assert_eq!(decoded.operation().addressing_mode(), AddressingMode::Absolute);
+    }
+
+    #[test]
+    fn assembled_rom_runs_as_it_is(){
+        let source = "
+        .org $8000
+reset:  LDX #0
+copy:   LDA message,X
+        BEQ done
+        STA $0200,X
+        INX
+        BNE copy
+done:   BRA done
+message: .byte \"Hi!\", 0
+        .org $fffc
+        .word reset, reset
+";
+        let assembly = assemble(source).unwrap();
+        let image = assembly.windowed(0x8000, 0x8000).unwrap();
+        assert_eq!(image.len(), 0x8000);
+
+        let mut machine = board::new_machine(&image, None, None).unwrap();
+        let mut cpu = W65C02S::default();
+        cpu.reset(&mut machine).unwrap();
+        for _ in 0..100{
+            cpu.step(&mut machine).unwrap();
+        }
+        assert_eq!(&machine.ram_contents()[0x200..0x203], b"Hi!");
+    }
+
+    #[test]
+    fn code_outside_the_window_is_not_windowed(){
+        let assembly = assemble("        .org $0200\n        NOP\n").unwrap();
+        assert!(assembly.windowed(0x8000, 0x8000).is_none());
+        assert!(assembly.windowed(0x8000, 0x10000).is_none());
+        assert_eq!(assembly.windowed(0x0200, 2).unwrap(), [0xea, FILL_BYTE]);
+    }
+
+    #[test]
+    fn unplaced_images_fill_the_window_or_the_address_space(){
+        let mut image = vec![FILL_BYTE; 0x8000];
+        image[0x7ffc..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        assert!(board::new_machine(&image, None, None).is_ok());
+
+        let mut full = vec![0; 0x8000];
+        full.extend(&image);
+        assert!(board::new_machine(&full, None, None).is_ok());
+
+        assert!(matches!(board::new_machine(&image[..0x4000], None, None), Err(board::BoardError::MalformedImage)));
+        assert!(matches!(board::new_machine(&full[..0xc000], None, None), Err(board::BoardError::MalformedImage)));
+    }
+}
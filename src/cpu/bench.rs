@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use crate::bus::bus::Machine;
+#[cfg(feature = "jit")]
+use crate::cpu::jit::Jit;
+use crate::cpu::limits::RunLimits;
+use crate::cpu::speed::{Speed, SpeedMeter};
+use crate::cpu::w65c02s::{CpuError, W65C02S};
+
+/// The workload `bench` runs without an image: filling, summing and copying a page, a shift-and-add multiply
+/// called as a subroutine, decimal adds and stack traffic, over and over. It covers most addressing modes, so
+/// its speed is not that of one instruction.
+pub const WORKLOAD: &str = "
+        .org $8000
+start:  LDX #$FF
+        TXS
+outer:  LDY #0
+fill:   TYA
+        STA $0200,Y
+        INY
+        BNE fill
+        LDX #0
+        CLC
+        LDA #0
+sum:    ADC $0200,X
+        EOR $10
+        STA $10
+        INX
+        BNE sum
+        JSR multiply
+        LDA #$00
+        STA $20
+        STA $22
+        LDA #$02
+        STA $21
+        LDA #$03
+        STA $23
+        LDY #0
+copy:   LDA ($20),Y
+        STA ($22),Y
+        INY
+        BNE copy
+        SED
+        LDA #0
+        LDX #$20
+decimal: CLC
+        ADC #1
+        DEX
+        BNE decimal
+        CLD
+        LDX #$10
+stack:  PHA
+        PHX
+        PLX
+        PLA
+        DEX
+        BNE stack
+        JMP outer
+
+multiply: LDA #$5A
+        STA $30
+        LDA #$3C
+        STA $31
+        LDA #0
+        STA $33
+        LDX #8
+shift:  LSR $31
+        BCC next
+        CLC
+        ADC $30
+next:   ROR A
+        ROR $33
+        DEX
+        BNE shift
+        STA $32
+        RTS
+
+        .org $FFFA
+        .word start, start, start
+";
+
+/// Runs the machine flat out for `length` of wall time and measures how fast it went. A program that ends in
+/// a `BRK` is started again from `entry`, so that a short one still fills the time.
+pub fn run(cpu: &mut W65C02S, machine: &mut Machine, entry: u16, length: Duration) -> Result<Speed, CpuError>{
+    let meter = SpeedMeter::new(cpu);
+    let started = Instant::now();
+    loop{
+        let limits = RunLimits { timeout: Some(length.saturating_sub(started.elapsed())), ..RunLimits::NONE };
+        match cpu.run(machine, &limits)?{
+            Some(_) => return Ok(meter.finish(cpu)),
+            None => cpu.reset_to(entry),
+        }
+    }
+}
+
+/// `run` with hot code compiled by `jit`, which keeps what it compiled for the next run on the same machine.
+#[cfg(feature = "jit")]
+pub fn run_jit(jit: &mut Jit, cpu: &mut W65C02S, machine: &mut Machine, entry: u16, length: Duration) -> Result<Speed, CpuError>{
+    let meter = SpeedMeter::new(cpu);
+    let started = Instant::now();
+    loop{
+        let limits = RunLimits { timeout: Some(length.saturating_sub(started.elapsed())), ..RunLimits::NONE };
+        match jit.run(cpu, machine, &limits)?{
+            Some(_) => return Ok(meter.finish(cpu)),
+            None => cpu.reset_to(entry),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::bus::bus::{Bus, Machine, RegionAttributes};
+use crate::cpu::disassembler;
+use crate::cpu::symbols::SymbolTable;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+// runs of a byte at least this long that nothing executed are listed as one line, such as unused ROM
+const FILL_RUN: usize = 16;
+
+/// Marks every address an instruction started at, for a report of how much of the ROM a run exercised.
+///
+/// The report covers the read-only pages of the machine, or, when it has none, the pages that any instruction
+/// ran in. A byte counts as executed when it is part of an instruction that ran, its opcode or its operand.
+pub struct Coverage{
+    executed: Vec<bool>,    // indexed by address
+}
+
+/// How much of the ROM a run executed, for tools to read.
+#[derive(Debug, Serialize)]
+pub struct CoverageSummary{
+    image: String,
+    regions: Vec<Span>,
+    bytes: usize,                   // readable bytes in the regions
+    executed_bytes: usize,
+    percent: f64,
+    instructions: usize,            // distinct addresses an instruction ran at
+    not_executed: Vec<Span>,
+    routines: Vec<Routine>,         // a stretch from each symbol in the regions to the next
+}
+
+#[derive(Debug, Serialize)]
+struct Span{
+    start: u16,
+    end: u16,           // included
+}
+
+#[derive(Debug, Serialize)]
+struct Routine{
+    name: String,
+    address: u16,
+    entered: bool,      // an instruction ran at the address
+    bytes: usize,
+    executed_bytes: usize,
+}
+
+/// The addresses a report covers and which of their bytes ran, worked out once for the listing and the summary.
+struct Analysis{
+    regions: Vec<(u16, u16)>,       // runs of whole pages, both ends included
+    covered: Vec<bool>,             // indexed by address
+}
+
+impl Coverage{
+    pub fn new() -> Self{
+        Self { executed: vec![false; 0x10000] }
+    }
+
+    /// Call before each step of the CPU.
+    pub fn before(&mut self, cpu: &W65C02S){
+        self.executed[cpu.register(Register::PC) as usize] = true;
+    }
+
+    /// Every address of the covered regions, with `+` before the instructions that ran and `-` before the bytes
+    /// that did not, disassembled from where they start and with each symbol on a `name:` line above it.
+    pub fn listing(&self, machine: &Machine, symbols: &SymbolTable) -> String{
+        let analysis = self.analyse(machine);
+        let summary = self.summary("", machine, symbols);
+        let mut listing = format!("; {} of {} bytes executed ({:.1}%); + executed, - not executed\n",
+            summary.executed_bytes, summary.bytes, summary.percent);
+
+        for &(first, last) in &analysis.regions{
+            let (first, last) = (first as usize, last as usize);
+            let mut address = first;
+            while address <= last{
+                if let Some(name) = symbols.name_of(address as u16){
+                    let _ = writeln!(listing, "{}:", name);
+                }
+                if self.executed[address]{
+                    let instruction = disassembler::disassemble_with_symbols(address as u16, |a| machine.peek(a), symbols);
+                    let _ = writeln!(listing, "+ {}", instruction);
+                    address += instruction.bytes.len().max(1);
+                    continue;
+                }
+
+                // a long run of one byte, unbroken by a symbol or an instruction that ran, is listed as one line
+                let byte = machine.peek(address as u16);
+                let run = (address..=last)
+                    .take_while(|&a| machine.peek(a as u16) == byte && !self.executed[a] && (a == address || symbols.name_of(a as u16).is_none()))
+                    .count();
+                if run >= FILL_RUN || byte.is_none(){
+                    let end = address + run - 1;
+                    let _ = match byte{
+                        Some(byte) => writeln!(listing, "- {:04X}-{:04X}  {} bytes of ${:02X}", address, end, run, byte),
+                        None => writeln!(listing, "  {:04X}-{:04X}  not readable", address, end),
+                    };
+                    address += run;
+                    continue;
+                }
+
+                // bytes that did not run may be data, so stop short of the next instruction that did
+                let read = |a: u16| machine.peek(a).filter(|_| a as usize == address || ((address..=last).contains(&(a as usize)) && !self.executed[a as usize]));
+                let instruction = disassembler::disassemble_with_symbols(address as u16, read, symbols);
+                let _ = writeln!(listing, "- {}", instruction);
+                address += instruction.bytes.len().max(1);
+            }
+        }
+        listing
+    }
+
+    pub fn summary(&self, image: &str, machine: &Machine, symbols: &SymbolTable) -> CoverageSummary{
+        let analysis = self.analyse(machine);
+        let addresses = || analysis.regions.iter().flat_map(|&(first, last)| first..=last).filter(|&a| machine.peek(a).is_some());
+
+        let bytes = addresses().count();
+        let executed_bytes = addresses().filter(|&a| analysis.covered[a as usize]).count();
+        let mut not_executed = Vec::<Span>::new();
+        for address in addresses().filter(|&a| !analysis.covered[a as usize]){
+            match not_executed.last_mut(){
+                Some(span) if span.end.wrapping_add(1) == address => span.end = address,
+                _ => not_executed.push(Span { start: address, end: address }),
+            }
+        }
+
+        let mut routines = Vec::new();
+        for &(first, last) in &analysis.regions{
+            let named = (first..=last).filter(|&a| symbols.name_of(a).is_some()).collect::<Vec<u16>>();
+            for (i, &address) in named.iter().enumerate(){
+                let end = named.get(i + 1).map_or(last, |next| next - 1);
+                let span = (address..=end).filter(|&a| machine.peek(a).is_some());
+                routines.push(Routine {
+                    name: symbols.name_of(address).unwrap_or_default().to_string(),
+                    address,
+                    entered: self.executed[address as usize],
+                    bytes: span.clone().count(),
+                    executed_bytes: span.filter(|&a| analysis.covered[a as usize]).count(),
+                });
+            }
+        }
+
+        CoverageSummary {
+            image: image.to_string(),
+            regions: analysis.regions.iter().map(|&(start, end)| Span { start, end }).collect(),
+            bytes,
+            executed_bytes,
+            percent: if bytes == 0 { 0.0 } else { executed_bytes as f64 * 100.0 / bytes as f64 },
+            instructions: addresses().filter(|&a| self.executed[a as usize]).count(),
+            not_executed,
+            routines,
+        }
+    }
+
+    fn analyse(&self, machine: &Machine) -> Analysis{
+        let read_only = |page: usize| machine.attributes((page << 8) as u16).contains(RegionAttributes::READ_ONLY);
+        let ran = |page: usize| self.executed[page << 8..(page + 1) << 8].iter().any(|&e| e);
+        let pages = match (0..256).any(read_only){
+            true => (0..256).filter(|&page| read_only(page)).collect::<Vec<usize>>(),
+            false => (0..256).filter(|&page| ran(page)).collect::<Vec<usize>>(),
+        };
+
+        let mut regions = Vec::<(u16, u16)>::new();
+        for page in pages{
+            let (first, last) = ((page << 8) as u16, ((page << 8) | 0xff) as u16);
+            match regions.last_mut(){
+                Some((_, end)) if end.wrapping_add(1) == first => *end = last,
+                _ => regions.push((first, last)),
+            }
+        }
+
+        let mut covered = vec![false; 0x10000];
+        for address in (0..0x10000).filter(|&a| self.executed[a]){
+            let length = disassembler::disassemble(address as u16, |a| machine.peek(a)).bytes.len().max(1);
+            for offset in 0..length{
+                covered[(address + offset) & 0xffff] = true;
+            }
+        }
+        Analysis { regions, covered }
+    }
+}
+impl Default for Coverage{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoverageSummary{
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()>{
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json + "\n")
+    }
+}
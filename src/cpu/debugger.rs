@@ -0,0 +1,191 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::bus::bus::Machine;
+use crate::cpu::disassembler;
+use crate::cpu::symbols::{parse_location, SymbolTable};
+use crate::cpu::w65c02s::{Register, W65C02S};
+use crate::memory::hexdump::hexdump;
+
+const HELP: &str = "\
+s, step [n]          run one instruction, or n
+c, continue          run to the next breakpoint
+b, break [addr]      set a breakpoint, or list them
+d, delete <addr>     clear a breakpoint
+r, regs              show the registers
+m, mem <addr> [n]    show n bytes of memory, 64 by default
+l, list [addr] [n]   disassemble n instructions, 10 by default, from the PC or addr
+q, quit              end the run
+an empty line repeats the last command; addresses can be labels";
+
+/// What the run loop does after `Debugger::check`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebugAction{
+    Run,
+    Quit,
+}
+
+/// A line-based debugger that stops the CPU before an instruction, either at a breakpoint or after stepping,
+/// and takes commands until told to go on. It starts stopped, before the first instruction.
+pub struct Debugger{
+    breakpoints: BTreeSet<u16>,
+    steps_left: Option<u64>,        // instructions to run before stopping, None to run to a breakpoint
+    symbols: SymbolTable,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    last_command: String,
+}
+impl Debugger{
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self{
+        Self {
+            breakpoints: BTreeSet::new(),
+            steps_left: Some(0),
+            symbols: SymbolTable::new(),
+            input: Box::new(input),
+            output: Box::new(output),
+            last_command: String::new(),
+        }
+    }
+    pub fn stdio() -> Self{
+        Self::new(io::stdin().lock(), io::stdout())
+    }
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self{
+        self.symbols = symbols;
+        self
+    }
+
+    /// Call before each step of the CPU. Takes commands when the CPU is to stop here, until one runs it on.
+    pub fn check(&mut self, cpu: &W65C02S, machine: &Machine) -> DebugAction{
+        let pc = cpu.register(Register::PC);
+        match self.steps_left{
+            Some(0) => (),
+            Some(n) if !self.breakpoints.contains(&pc) => {
+                self.steps_left = Some(n - 1);
+                return DebugAction::Run;
+            },
+            None if !self.breakpoints.contains(&pc) => return DebugAction::Run,
+            _ => self.say(&format!("breakpoint at {}", self.describe(pc))),
+        }
+        if let Some(arbitration) = cpu.last_arbitration(){
+            self.say(&format!("took {}", arbitration));
+        }
+
+        self.show_registers(cpu, machine);
+        loop{
+            let _ = write!(self.output, "> ");
+            let _ = self.output.flush();
+            let mut line = String::new();
+            match self.input.read_line(&mut line){
+                Ok(0) | Err(_) => return DebugAction::Quit,
+                Ok(_) => (),
+            }
+            let line = match line.trim(){
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            self.last_command = line.clone();
+            if let Some(action) = self.command(&line, cpu, machine){
+                return action;
+            }
+        }
+    }
+
+    /// Carries out a command, returning what the run loop does next when it is one that ends the stop.
+    fn command(&mut self, line: &str, cpu: &W65C02S, machine: &Machine) -> Option<DebugAction>{
+        let mut words = line.split_whitespace();
+        let command = words.next()?;
+        let arguments = words.collect::<Vec<&str>>();
+
+        match command{
+            "s" | "step" => match arguments.first().map(|n| n.parse::<u64>()){
+                None => self.steps_left = Some(0),
+                Some(Ok(n)) if n > 0 => self.steps_left = Some(n - 1),
+                Some(_) => {
+                    self.say("step takes a count of at least 1");
+                    return None;
+                },
+            },
+            "c" | "continue" => self.steps_left = None,
+            "b" | "break" => match arguments.first(){
+                Some(location) => if let Some(address) = self.location(location){
+                    self.breakpoints.insert(address);
+                    self.say(&format!("breakpoint at {}", self.describe(address)));
+                },
+                None if self.breakpoints.is_empty() => self.say("no breakpoints"),
+                None => for address in self.breakpoints.clone(){
+                    self.say(&self.describe(address));
+                },
+            },
+            "d" | "delete" => match arguments.first().and_then(|location| self.location(location)){
+                Some(address) if self.breakpoints.remove(&address) => self.say(&format!("cleared {}", self.describe(address))),
+                Some(address) => self.say(&format!("no breakpoint at {}", self.describe(address))),
+                None => (),
+            },
+            "r" | "regs" => self.show_registers(cpu, machine),
+            "m" | "mem" => {
+                let address = self.location(arguments.first()?)?;
+                let length = self.count(arguments.get(1), 64)?;
+                let last = address.saturating_add((length.max(1) - 1).min(u16::MAX as u64) as u16);
+                // device registers read as zero rather than being disturbed
+                let bytes = (address..=last).map(|a| machine.peek(a).unwrap_or(0)).collect::<Vec<u8>>();
+                let _ = write!(self.output, "{}", hexdump(&bytes, address));
+            },
+            "l" | "list" => {
+                let (mut address, count) = match arguments.first(){
+                    Some(location) => (self.location(location)?, arguments.get(1)),
+                    None => (cpu.register(Register::PC), None),
+                };
+                let count = self.count(count, 10)?;
+                for _ in 0..count{
+                    let instruction = disassembler::disassemble_with_symbols(address, |a| machine.peek(a), &self.symbols);
+                    self.say(&instruction.to_string());
+                    address = address.wrapping_add(instruction.bytes.len().max(1) as u16);
+                }
+            },
+            "q" | "quit" => return Some(DebugAction::Quit),
+            "h" | "help" | "?" => self.say(HELP),
+            _ => self.say(&format!("unknown command `{}`, `help` lists them", command)),
+        }
+        matches!(command, "s" | "step" | "c" | "continue").then_some(DebugAction::Run)
+    }
+
+    /// An address as the command line takes one, or the name of a symbol.
+    fn location(&mut self, text: &str) -> Option<u16>{
+        let address = parse_location(text, &self.symbols);
+        if address.is_none(){
+            self.say(&format!("`{}` is neither an address nor a label", text));
+        }
+        address
+    }
+    fn count(&mut self, text: Option<&&str>, default: u64) -> Option<u64>{
+        let Some(text) = text else { return Some(default) };
+        let count = text.parse().ok();
+        if count.is_none(){
+            self.say(&format!("`{}` is not a count", text));
+        }
+        count
+    }
+
+    /// An address, with its label when it has one.
+    fn describe(&self, address: u16) -> String{
+        match self.symbols.name_of(address){
+            Some(name) => format!("${:04X} ({})", address, name),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    fn show_registers(&mut self, cpu: &W65C02S, machine: &Machine){
+        let pc = cpu.register(Register::PC);
+        let instruction = disassembler::disassemble_with_symbols(pc, |address| machine.peek(address), &self.symbols);
+        let line = format!("{:<32}A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} CYC:{}",
+            instruction.to_string(),
+            cpu.register(Register::A), cpu.register(Register::X), cpu.register(Register::Y),
+            cpu.register(Register::SP), cpu.register(Register::P), cpu.cycles());
+        self.say(&line);
+    }
+
+    fn say(&mut self, text: &str){
+        // a console that cannot be written is not worth stopping the machine for
+        let _ = writeln!(self.output, "{}", text);
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::bus::access_log::{AccessKind, AccessLogger, BusTransaction};
+use crate::bus::bus::Machine;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+const MAGIC: &[u8; 8] = b"S6502FP\0";
+const VERSION: u8 = 1;
+
+/// Fingerprints every instruction of a run, to check that a second run of the same image does exactly the same.
+/// Recording writes them to a file; checking compares the run against the file, and reports the first
+/// instruction where the two part ways.
+///
+/// A fingerprint is the registers after the instruction, the cycles it took and a hash of what went over the
+/// bus while it ran, so a device that reads back something else the second time shows up at the read, before
+/// the program acts on it. Host input is only the same in both runs when it is replayed with `--replay-input`.
+///
+/// The file holds a magic number and a version byte, then 13 bytes an instruction: PC little-endian, A, X, Y,
+/// SP and P, the cycles little-endian in 16 bits, and the bus hash little-endian in 32.
+pub struct Fingerprints{
+    mode: Mode,
+    traffic: Rc<Cell<u32>>,     // hash of the transactions since the last instruction
+    cycles: u64,                // the count after the last instruction
+    pc: u16,                    // and where the next one starts
+    instruction: u64,           // instructions fingerprinted
+}
+
+enum Mode{
+    Record(BufWriter<File>),
+    Check(BufReader<File>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Fingerprint{
+    registers: [u16; 6],        // in the order of REGISTERS
+    cycles: u16,
+    traffic: u32,
+}
+
+const REGISTERS: [Register; 6] = [Register::PC, Register::A, Register::X, Register::Y, Register::SP, Register::P];
+
+/// Where a checked run parted ways with the recorded one.
+#[derive(Debug)]
+pub enum Divergence{
+    State{ instruction: u64, pc: u16, differences: Vec<String> },
+    RecordingEnded{ instruction: u64 },
+    RunEnded{ instruction: u64 },
+    Io(io::Error),              // the recording could not be read or written, so nothing is known
+}
+impl fmt::Display for Divergence{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Divergence::State { instruction, pc, differences } => write!(f, "diverged from the recording at instruction {}, at ${:04X}: {}",
+                instruction, pc, differences.join(", ")),
+            Divergence::RecordingEnded { instruction } => write!(f, "ran on past the end of the recording, after instruction {}", instruction),
+            Divergence::RunEnded { instruction } => write!(f, "ended after instruction {}, where the recording goes on", instruction),
+            Divergence::Io(e) => write!(f, "fingerprint recording failed: {}", e),
+        }
+    }
+}
+
+impl Fingerprints{
+    pub fn record(path: impl AsRef<Path>) -> io::Result<Self>{
+        let mut output = BufWriter::new(File::create(path)?);
+        output.write_all(MAGIC)?;
+        output.write_all(&[VERSION])?;
+        Ok(Self::new(Mode::Record(output)))
+    }
+    pub fn check(path: impl AsRef<Path>) -> io::Result<Self>{
+        let mut input = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 9];
+        input.read_exact(&mut header)?;
+        if header[..8] != MAGIC[..] || header[8] != VERSION{
+            return Err(io::Error::new(ErrorKind::InvalidData, "not a fingerprint recording"));
+        }
+        Ok(Self::new(Mode::Check(input)))
+    }
+    fn new(mode: Mode) -> Self{
+        Self { mode, traffic: Rc::new(Cell::new(FNV_OFFSET)), cycles: 0, pc: 0, instruction: 0 }
+    }
+
+    /// Hashes what goes over the bus of the machine the run is on. Call once, before the first instruction.
+    pub fn attach(&mut self, cpu: &W65C02S, machine: &mut Machine){
+        self.cycles = cpu.cycles();
+        self.pc = cpu.register(Register::PC);
+        let traffic = Rc::clone(&self.traffic);
+        machine.attach_logger(AccessLogger::new(move |transaction: &BusTransaction| {
+            let kind = match transaction.kind{
+                AccessKind::Read => 0,
+                AccessKind::Write => 1,
+            };
+            let [low, high] = transaction.address.to_le_bytes();
+            traffic.set([low, high, transaction.value, kind].into_iter().fold(traffic.get(), fnv1a));
+        }));
+    }
+
+    /// Call after each step of the CPU.
+    pub fn after(&mut self, cpu: &W65C02S) -> Result<(), Divergence>{
+        let fingerprint = Fingerprint {
+            registers: REGISTERS.map(|register| cpu.register(register)),
+            cycles: (cpu.cycles() - self.cycles).min(u16::MAX as u64) as u16,
+            traffic: self.traffic.replace(FNV_OFFSET),
+        };
+        let pc = std::mem::replace(&mut self.pc, cpu.register(Register::PC));
+        self.cycles = cpu.cycles();
+        self.instruction += 1;
+
+        match &mut self.mode{
+            Mode::Record(output) => {
+                // a recording that cannot be written is not worth stopping the machine for; the check will say so
+                let _ = output.write_all(&fingerprint.to_bytes());
+                Ok(())
+            },
+            Mode::Check(input) => match read_fingerprint(input){
+                Ok(Some(recorded)) if recorded == fingerprint => Ok(()),
+                Ok(Some(recorded)) => Err(Divergence::State { instruction: self.instruction, pc, differences: fingerprint.differences(&recorded) }),
+                Ok(None) => Err(Divergence::RecordingEnded { instruction: self.instruction - 1 }),
+                Err(e) => Err(Divergence::Io(e)),
+            },
+        }
+    }
+
+    /// Call when the run ends, to finish the recording or to check that the recorded run ended there too.
+    pub fn finish(&mut self) -> Result<(), Divergence>{
+        match &mut self.mode{
+            Mode::Record(output) => output.flush().map_err(Divergence::Io),
+            Mode::Check(input) => match read_fingerprint(input){
+                Ok(None) => Ok(()),
+                Ok(Some(_)) => Err(Divergence::RunEnded { instruction: self.instruction }),
+                Err(e) => Err(Divergence::Io(e)),
+            },
+        }
+    }
+}
+
+impl Fingerprint{
+    fn to_bytes(self) -> [u8; 13]{
+        let mut bytes = [0u8; 13];
+        bytes[..2].copy_from_slice(&self.registers[0].to_le_bytes());
+        for (byte, register) in bytes[2..7].iter_mut().zip(&self.registers[1..]){
+            *byte = *register as u8;
+        }
+        bytes[7..9].copy_from_slice(&self.cycles.to_le_bytes());
+        bytes[9..].copy_from_slice(&self.traffic.to_le_bytes());
+        bytes
+    }
+    fn from_bytes(bytes: [u8; 13]) -> Self{
+        let [a, x, y, sp, p] = [bytes[2], bytes[3], bytes[4], bytes[5], bytes[6]].map(u16::from);
+        Self {
+            registers: [u16::from_le_bytes([bytes[0], bytes[1]]), a, x, y, sp, p],
+            cycles: u16::from_le_bytes([bytes[7], bytes[8]]),
+            traffic: u32::from_le_bytes([bytes[9], bytes[10], bytes[11], bytes[12]]),
+        }
+    }
+
+    /// What differs, treating `self` as this run and `recorded` as the first.
+    fn differences(&self, recorded: &Fingerprint) -> Vec<String>{
+        let mut differences = REGISTERS.iter().zip(self.registers.iter().zip(recorded.registers))
+            .filter(|(_, (actual, recorded))| *actual != recorded)
+            .map(|(register, (actual, recorded))| match register{
+                Register::PC => format!("PC is ${:04X}, recorded ${:04X}", actual, recorded),
+                _ => format!("{:?} is ${:02X}, recorded ${:02X}", register, actual, recorded),
+            })
+            .collect::<Vec<String>>();
+        if self.cycles != recorded.cycles{
+            differences.push(format!("took {} cycles, recorded {}", self.cycles, recorded.cycles));
+        }
+        if self.traffic != recorded.traffic{
+            differences.push("other bus traffic".to_string());
+        }
+        differences
+    }
+}
+
+/// The next fingerprint, or None at the end of the file.
+fn read_fingerprint(input: &mut impl Read) -> io::Result<Option<Fingerprint>>{
+    let mut bytes = [0u8; 13];
+    match input.read_exact(&mut bytes){
+        Ok(()) => Ok(Some(Fingerprint::from_bytes(bytes))),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+fn fnv1a(hash: u32, byte: u8) -> u32{
+    (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::cpu::symbols::SymbolTable;
+use crate::cpu::w65c02s::{AddressingMode, Mnemonic, W65C02S};
+
+/// One instruction decoded from memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly{
+    pub address: u16,
+    pub bytes: Vec<u8>,     // the opcode and its operand, as far as they could be read
+    pub text: String,       // in the usual assembler syntax, such as `LDA ($12),Y`
+}
+impl fmt::Display for Disassembly{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+        write!(f, "{:04X}  {:<8}  {}", self.address, bytes, self.text)
+    }
+}
+
+/// Decodes the instruction at `address`, reading its bytes through `read`. Opcodes the CPU does not implement,
+/// and instructions whose bytes cannot all be read, come out as `???`.
+pub fn disassemble(address: u16, read: impl FnMut(u16) -> Option<u8>) -> Disassembly{
+    disassemble_with_symbols(address, read, &SymbolTable::new())
+}
+/// Same as `disassemble`, naming the addresses operands refer to where the symbols have a name for them, as in
+/// `JSR print_char`. Immediate operands stay numbers.
+pub fn disassemble_with_symbols(address: u16, mut read: impl FnMut(u16) -> Option<u8>, symbols: &SymbolTable) -> Disassembly{
+    let unknown = |bytes: Vec<u8>| Disassembly { address, bytes, text: "???".to_string() };
+
+    let Some(opcode) = read(address) else { return unknown(Vec::new()) };
+    let Some(operation) = W65C02S::operation(opcode) else { return unknown(vec![opcode]) };
+
+    let mode = operation.addressing_mode();
+    let mut bytes = vec![opcode];
+    for i in 1..=mode.num_operand_bytes() as u16{
+        match read(address.wrapping_add(i)){
+            Some(byte) => bytes.push(byte),
+            None => return unknown(bytes),
+        }
+    }
+
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+    let branch = |offset: u8| address.wrapping_add(bytes.len() as u16).wrapping_add(offset as i8 as u16);
+    let absolute = |target: u16| symbols.name_of(target).map_or_else(|| format!("${:04X}", target), str::to_string);
+    let zero_page = symbols.name_of(byte as u16).map_or_else(|| format!("${:02X}", byte), str::to_string);
+    let operand = match mode{
+        AddressingMode::Absolute => absolute(word),
+        AddressingMode::AbsoluteIndexedIndirect => format!("({},X)", absolute(word)),
+        AddressingMode::AbsoluteIndexedX => format!("{},X", absolute(word)),
+        AddressingMode::AbsoluteIndexedY => format!("{},Y", absolute(word)),
+        AddressingMode::AbsoluteIndirect => format!("({})", absolute(word)),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", byte),
+        AddressingMode::Implied | AddressingMode::Stack => String::new(),
+        AddressingMode::ProgramCounterRelative => absolute(branch(byte)),
+        AddressingMode::ZeroPage => zero_page,
+        AddressingMode::ZeroPageIndexedIndirect => format!("({},X)", zero_page),
+        AddressingMode::ZeroPageIndexedX => format!("{},X", zero_page),
+        AddressingMode::ZeroPageIndexedY => format!("{},Y", zero_page),
+        AddressingMode::ZeroPageIndirect => format!("({})", zero_page),
+        AddressingMode::ZeroPageIndirectIndexedY => format!("({}),Y", zero_page),
+        AddressingMode::ZeroPageRelative => format!("{},{}", zero_page, absolute(branch(bytes[2]))),
+    };
+
+    let text = if operand.is_empty() { operation.mnemonic().to_string() } else { format!("{} {}", operation.mnemonic(), operand) };
+    Disassembly { address, bytes, text }
+}
+
+/// Disassembles the part of `image` from `first` to `last`, both included, with the image loaded at `org`. Each
+/// address the symbols name gets a `name:` line of its own above its instruction. Bytes the image does not cover
+/// are left out, and an instruction running past `last` is shown whole.
+pub fn listing(image: &[u8], org: u16, (first, last): (u16, u16), symbols: &SymbolTable) -> String{
+    let end = org as usize + image.len();    // just past the last byte of the image
+    let read = |address: u16| (address as usize).checked_sub(org as usize).filter(|_| (address as usize) < end).map(|i| image[i]);
+
+    let mut listing = String::new();
+    let mut address = (first as usize).max(org as usize);
+    while address <= (last as usize).min(end.saturating_sub(1)){
+        if let Some(name) = symbols.name_of(address as u16){
+            listing.push_str(&format!("{}:\n", name));
+        }
+        let instruction = disassemble_with_symbols(address as u16, read, symbols);
+        listing.push_str(&format!("{}\n", instruction));
+        address += instruction.bytes.len().max(1);
+    }
+    listing
+}
+
+/// One line of generated source.
+enum Chunk{
+    Instruction(Disassembly),
+    Bytes(usize),           // how many
+    Fill(usize),            // how many of the same byte
+    Vectors,                // the NMI, reset and IRQ vectors, as words
+}
+
+const VECTORS: u16 = 0xfffa;
+// runs of one byte at least this long, such as unused ROM, are reserved with `.res` rather than listed
+const FILL_RUN: usize = 16;
+
+/// Disassembles all of `image`, loaded at `org`, into source that assembles back into the same bytes with the
+/// built-in assembler or with ca65. Instructions are taken from where `executed` says they start, such as the
+/// addresses of a trace, or without it from following the jumps, branches and calls from the vectors; every other
+/// byte is data. Each target of a jump, branch or call inside the image gets a label, `L8F3A` unless the symbols
+/// name it, and names for addresses that do not start a line are defined as constants at the top.
+pub fn source(image: &[u8], org: u16, executed: Option<&[u16]>, symbols: &SymbolTable) -> String{
+    let end = org as usize + image.len();    // just past the last byte of the image
+    let inside = |address: usize| (org as usize..end).contains(&address);
+    let read = |address: u16| (address as usize).checked_sub(org as usize).filter(|_| (address as usize) < end).map(|i| image[i]);
+    let word = |address: u16| u16::from_le_bytes([image[(address - org) as usize], image[(address + 1 - org) as usize]]);
+    let vectors = (inside(VECTORS as usize) && inside(0xffff)).then(|| [VECTORS, VECTORS + 2, VECTORS + 4].map(word));
+
+    let starts = match executed{
+        Some(executed) => executed.iter().copied().filter(|&address| inside(address as usize)).collect::<BTreeSet<u16>>(),
+        None => follow(read, vectors.iter().flatten().copied().filter(|&address| inside(address as usize))),
+    };
+
+    // an instruction that runs off the image or into the start of another is taken for data
+    let mut code = starts.iter()
+        .map(|&address| disassemble(address, read))
+        .filter(|instruction| instruction.text != "???" && instruction.bytes.len() == (1..instruction.bytes.len() as u16)
+            .take_while(|&i| !starts.contains(&instruction.address.wrapping_add(i))).count() + 1)
+        .map(|instruction| (instruction.address, instruction))
+        .collect::<BTreeMap<u16, Disassembly>>();
+
+    // every branch, jump and call target gets a label
+    let mut labels = symbols.clone();
+    let targets = code.keys().filter_map(|&address| target(address, read)).chain(vectors.iter().flatten().copied());
+    for address in targets.filter(|&address| inside(address as usize)).collect::<BTreeSet<u16>>(){
+        if labels.name_of(address).is_none(){
+            labels.insert(&format!("L{:04X}", address), address);
+        }
+    }
+
+    // lay the image out in lines, breaking runs of data at labels so that every label can start a line
+    let mut chunks = Vec::<(u16, Chunk)>::new();
+    let mut address = org as usize;
+    while address < end{
+        if let Some(instruction) = code.remove(&(address as u16)){
+            address += instruction.bytes.len();
+            chunks.push((instruction.address, Chunk::Instruction(instruction)));
+        }
+        else if vectors.is_some() && address == VECTORS as usize && code.is_empty(){
+            chunks.push((VECTORS, Chunk::Vectors));
+            address = end;
+        }
+        else{
+            let data = |a: usize| a == address || (!code.contains_key(&(a as u16)) && labels.name_of(a as u16).is_none() && !(vectors.is_some() && a == VECTORS as usize));
+            let fill = (address..end).take_while(|&a| data(a) && image[a - org as usize] == image[address - org as usize]).count();
+            let (run, chunk) = match fill >= FILL_RUN{
+                true => (fill, Chunk::Fill(fill)),
+                false => {
+                    let run = (address..end.min(address + 8)).take_while(|&a| data(a)).count();
+                    (run, Chunk::Bytes(run))
+                },
+            };
+            chunks.push((address as u16, chunk));
+            address += run;
+        }
+    }
+
+    let mut source = "        .setcpu \"65C02\"\n\n".to_string();
+    let line_starts = chunks.iter().map(|(address, _)| *address).collect::<BTreeSet<u16>>();
+    let constants = labels.names().into_iter().filter(|(address, _)| !line_starts.contains(address)).collect::<Vec<(u16, &str)>>();
+    for (address, name) in &constants{
+        source.push_str(&format!("{} = ${:04X}\n", name, address));
+    }
+    if !constants.is_empty(){
+        source.push('\n');
+    }
+    source.push_str(&format!("        .org ${:04X}\n", org));
+
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("${:02X}", b)).collect::<Vec<String>>().join(", ");
+    let listed = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+    for (address, chunk) in chunks{
+        if let Some(name) = labels.name_of(address){
+            source.push_str(&format!("{}:\n", name));
+        }
+        let offset = (address - org) as usize;
+        let line = match chunk{
+            Chunk::Instruction(instruction) => {
+                let named = disassemble_with_symbols(address, read, &labels);
+                // an absolute operand below $0100 would come back as zero page, so its bytes are given instead
+                match shrinks(&instruction){
+                    false => format!("        {:<31} ; {:04X}  {}", named.text, address, listed(&instruction.bytes)),
+                    true => format!("        {:<31} ; {:04X}  {}", format!(".byte {}", hex(&instruction.bytes)), address, instruction.text),
+                }
+            },
+            Chunk::Bytes(length) => format!("        {:<31} ; {:04X}", format!(".byte {}", hex(&image[offset..offset + length])), address),
+            Chunk::Fill(length) => format!("        {:<31} ; {:04X}", format!(".res {}, ${:02X}", length, image[offset]), address),
+            Chunk::Vectors => {
+                let words = vectors.unwrap_or_default().map(|vector| labels.name_of(vector).map_or_else(|| format!("${:04X}", vector), str::to_string));
+                format!("        {:<31} ; FFFA  NMI, reset, IRQ", format!(".word {}", words.join(", ")))
+            },
+        };
+        source.push_str(line.trim_end());
+        source.push('\n');
+    }
+    source
+}
+
+/// The addresses of the instructions reached from `entries` by following branches, jumps and calls, stopping at
+/// returns, indirect jumps and opcodes the CPU does not implement.
+fn follow(read: impl Fn(u16) -> Option<u8>, entries: impl Iterator<Item = u16>) -> BTreeSet<u16>{
+    let mut starts = BTreeSet::new();
+    let mut pending = entries.collect::<Vec<u16>>();
+    while let Some(mut address) = pending.pop(){
+        while starts.insert(address){
+            let instruction = disassemble(address, &read);
+            let Some(operation) = W65C02S::operation(instruction.bytes.first().copied().unwrap_or(0)).filter(|_| instruction.text != "???") else {
+                starts.remove(&address);
+                break;
+            };
+            pending.extend(target(address, &read));
+            if matches!(operation.mnemonic(), Mnemonic::JMP | Mnemonic::BRA | Mnemonic::RTS | Mnemonic::RTI | Mnemonic::BRK | Mnemonic::STP){
+                break;
+            }
+            address = address.wrapping_add(instruction.bytes.len() as u16);
+        }
+    }
+    starts
+}
+
+/// Where the instruction at `address` may go besides the next one: the target of a branch, an absolute jump or a
+/// call.
+fn target(address: u16, read: impl Fn(u16) -> Option<u8>) -> Option<u16>{
+    let instruction = disassemble(address, &read);
+    let operation = W65C02S::operation(*instruction.bytes.first()?)?;
+    let next = address.wrapping_add(instruction.bytes.len() as u16);
+    match (operation.addressing_mode(), instruction.bytes.as_slice()){
+        (AddressingMode::ProgramCounterRelative, [_, offset]) => Some(next.wrapping_add(*offset as i8 as u16)),
+        (AddressingMode::ZeroPageRelative, [_, _, offset]) => Some(next.wrapping_add(*offset as i8 as u16)),
+        (AddressingMode::Absolute, [_, low, high]) if matches!(operation.mnemonic(), Mnemonic::JMP | Mnemonic::JSR) => Some(u16::from_le_bytes([*low, *high])),
+        _ => None,
+    }
+}
+
+/// Whether an assembler would pick the zero page form of an instruction that has an absolute operand below $0100.
+fn shrinks(instruction: &Disassembly) -> bool{
+    let Some(operation) = W65C02S::operation(instruction.bytes[0]) else { return false };
+    let zero_page = match operation.addressing_mode(){
+        AddressingMode::Absolute => AddressingMode::ZeroPage,
+        AddressingMode::AbsoluteIndexedX => AddressingMode::ZeroPageIndexedX,
+        AddressingMode::AbsoluteIndexedY => AddressingMode::ZeroPageIndexedY,
+        AddressingMode::AbsoluteIndirect => AddressingMode::ZeroPageIndirect,
+        AddressingMode::AbsoluteIndexedIndirect => AddressingMode::ZeroPageIndexedIndirect,
+        _ => return false,
+    };
+    let mnemonic = operation.mnemonic().to_string();
+    instruction.bytes[2] == 0 && (0..=255).filter_map(W65C02S::operation)
+        .any(|other| other.addressing_mode() == zero_page && other.mnemonic().to_string() == mnemonic)
+}
+
+/// The addresses of the instructions in a trace, in either of the layouts `--trace-format` writes.
+pub fn traced_addresses(trace: &str) -> Vec<u16>{
+    trace.lines()
+        .filter_map(|line| line.strip_prefix(".C:").unwrap_or(line).split_whitespace().next())
+        .filter(|word| word.len() == 4)
+        .filter_map(|word| u16::from_str_radix(word, 16).ok())
+        .collect()
+}
@@ -0,0 +1,202 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use crate::bus::bus::{BusError, Machine};
+use crate::cpu::save_state::SaveState;
+use crate::cpu::w65c02s::{Arbitration, BlockEnd, CpuError, Register, W65C02S};
+
+/// What the emulation thread reports without being asked.
+#[derive(Debug)]
+pub enum Event{
+    /// The run paused, when told to or at the end of a `step`, with the PC it will go on from.
+    Paused{ pc: u16 },
+    /// The program executed a `BRK` at `pc`, which pauses the run as it ends a run on the command line.
+    Brk{ pc: u16 },
+    /// The instruction at `pc` could not be executed, and the run paused before it.
+    Failed{ pc: u16, error: CpuError },
+    /// The CPU took an interrupt, and which of those pending it chose; the run goes on.
+    Interrupt(Arbitration),
+}
+
+enum Request{
+    Run,
+    Pause,
+    Step(u64),
+    Peek{ address: u16, length: usize, reply: Sender<Vec<Option<u8>>> },
+    Poke{ address: u16, bytes: Vec<u8>, reply: Sender<Result<(), BusError>> },
+    Snapshot(Sender<SaveState>),
+    Quit,
+}
+
+/// A machine running on a thread of its own, driven through channels, for front ends and servers that must not
+/// block on the emulation. It starts paused.
+///
+/// Requests are taken between slices of `SLICE` instructions while running, and as they come while paused.
+/// Those that answer, such as `peek`, wait for the thread, and return None once it has gone.
+pub struct EmulatorHandle{
+    requests: Sender<Request>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EmulatorHandle{
+    /// Instructions run between looks for requests.
+    pub const SLICE: u64 = 1024;
+
+    /// Builds the CPU and machine with `build` on the new thread, where they stay, since devices need not be
+    /// `Send`.
+    pub fn spawn(build: impl FnOnce() -> (W65C02S, Machine) + Send + 'static) -> Self{
+        let (requests, requests_receiver) = mpsc::channel();
+        let (events_sender, events) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let (cpu, machine) = build();
+            Emulation { cpu, machine, running: false, steps_left: None, events: events_sender }.serve(requests_receiver);
+        });
+        Self { requests, events, thread: Some(thread) }
+    }
+
+    pub fn run(&self){
+        self.send(Request::Run);
+    }
+    pub fn pause(&self){
+        self.send(Request::Pause);
+    }
+    /// Runs `count` instructions and pauses.
+    pub fn step(&self, count: u64){
+        self.send(Request::Step(count));
+    }
+
+    /// `length` bytes from `address` on, None for a device register, which is not read so as not to disturb it.
+    pub fn peek(&self, address: u16, length: usize) -> Option<Vec<Option<u8>>>{
+        let (reply, answer) = mpsc::channel();
+        self.send(Request::Peek { address, length, reply });
+        answer.recv().ok()
+    }
+    /// Stores bytes from `address` on, in RAM or ROM, as a debugger would.
+    pub fn poke(&self, address: u16, bytes: &[u8]) -> Option<Result<(), BusError>>{
+        let (reply, answer) = mpsc::channel();
+        self.send(Request::Poke { address, bytes: bytes.to_vec(), reply });
+        answer.recv().ok()
+    }
+    /// The CPU and RAM as they are between two instructions.
+    pub fn snapshot(&self) -> Option<SaveState>{
+        let (reply, answer) = mpsc::channel();
+        self.send(Request::Snapshot(reply));
+        answer.recv().ok()
+    }
+
+    /// The events the thread has sent, to wait on or poll.
+    pub fn events(&self) -> &Receiver<Event>{
+        &self.events
+    }
+
+    /// Stops the thread and waits for it; dropping the handle does the same.
+    pub fn quit(self){ }
+
+    fn send(&self, request: Request){
+        // the thread only goes away on a quit or a panic, and the requests that answer then return None
+        let _ = self.requests.send(request);
+    }
+}
+impl Drop for EmulatorHandle{
+    fn drop(&mut self) {
+        self.send(Request::Quit);
+        if let Some(thread) = self.thread.take(){
+            // a panic on the thread has already been reported by the panic hook
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The thread's side of an `EmulatorHandle`.
+struct Emulation{
+    cpu: W65C02S,
+    machine: Machine,
+    running: bool,
+    steps_left: Option<u64>,    // instructions to run before pausing, None to run until told otherwise
+    events: Sender<Event>,
+}
+impl Emulation{
+    fn serve(mut self, requests: Receiver<Request>){
+        loop{
+            let request = if self.running{
+                match requests.try_recv(){
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            else{
+                let Ok(request) = requests.recv() else { return };
+                Some(request)
+            };
+
+            match request{
+                Some(Request::Quit) => return,
+                Some(request) => self.answer(request),
+                None => self.run_slice(),
+            }
+        }
+    }
+
+    fn answer(&mut self, request: Request){
+        match request{
+            Request::Run => {
+                self.running = true;
+                self.steps_left = None;
+            },
+            Request::Pause => self.pause(),
+            Request::Step(0) => self.pause(),
+            Request::Step(count) => {
+                self.running = true;
+                self.steps_left = Some(count);
+            },
+            // a handle that stopped waiting for an answer has gone, and will see that the thread has too
+            Request::Peek { address, length, reply } => {
+                let _ = reply.send((0..length).map(|offset| self.machine.peek(address.wrapping_add(offset as u16))).collect());
+            },
+            Request::Poke { address, bytes, reply } => {
+                let result = bytes.iter().enumerate().try_for_each(|(offset, &val)| self.machine.poke(address.wrapping_add(offset as u16), val));
+                let _ = reply.send(result);
+            },
+            Request::Snapshot(reply) => {
+                let _ = reply.send(SaveState::capture(&self.cpu, &self.machine));
+            },
+            Request::Quit => {},
+        }
+    }
+
+    fn run_slice(&mut self){
+        let count = self.steps_left.map_or(EmulatorHandle::SLICE, |left| left.min(EmulatorHandle::SLICE));
+        let before = self.cpu.instructions();
+        match self.cpu.run_block(&mut self.machine, count, u64::MAX){
+            BlockEnd::Ran => {},
+            // nobody listening for events is no reason to stop running
+            BlockEnd::Interrupted(arbitration) => {
+                let _ = self.events.send(Event::Interrupt(arbitration));
+            },
+            BlockEnd::Brk { pc } => return self.stop(Event::Brk { pc }),
+            BlockEnd::Failed { pc, error } => {
+                self.cpu.set_register(Register::PC, pc);
+                return self.stop(Event::Failed { pc, error });
+            },
+        }
+        if let Some(left) = &mut self.steps_left{
+            *left -= self.cpu.instructions() - before;
+            if *left == 0{
+                self.pause();
+            }
+        }
+    }
+
+    fn pause(&mut self){
+        let pc = self.cpu.register(Register::PC);
+        self.stop(Event::Paused { pc });
+    }
+    fn stop(&mut self, event: Event){
+        self.running = false;
+        self.steps_left = None;
+        // nobody listening for events is no reason to stop answering requests
+        let _ = self.events.send(event);
+    }
+}
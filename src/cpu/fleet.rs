@@ -0,0 +1,97 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::bus::bus::Machine;
+use crate::cpu::limits::{LimitExceeded, RunLimits};
+use crate::cpu::w65c02s::{BlockEnd, CpuError, W65C02S};
+
+/// How one machine's run ended.
+#[derive(Debug)]
+pub enum RunEnd{
+    /// The program executed a `BRK` at `pc`.
+    Brk{ pc: u16 },
+    Limit(LimitExceeded),
+    /// The instruction at `pc` could not be executed.
+    Failed{ pc: u16, error: CpuError },
+}
+
+/// Runs many independent machines to the end, spread over a pool of threads, for fuzzing firmware or searching
+/// over inputs, where each run is short and there are thousands of them.
+///
+/// Each machine is built on the thread that runs it, from the input it was given, since devices need not be
+/// `Send`, and only what `collect` takes from it comes back.
+pub struct Fleet{
+    threads: usize,
+    limits: RunLimits,
+}
+impl Fleet{
+    /// A thread per core the host has, and no limits.
+    pub fn new() -> Self{
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self { threads, limits: RunLimits::NONE }
+    }
+    pub fn with_threads(mut self, threads: usize) -> Self{
+        self.threads = threads.max(1);
+        self
+    }
+    /// Limits applied to each run on its own. A run without any only ends on a `BRK` or an error.
+    pub fn with_limits(mut self, limits: RunLimits) -> Self{
+        self.limits = limits;
+        self
+    }
+
+    /// Builds a machine from each input with `build`, runs it until it ends, and hands what it was left as to
+    /// `collect`. The results are in the order of the inputs.
+    pub fn run<I, R>(
+        &self,
+        inputs: impl IntoIterator<Item = I>,
+        build: impl Fn(I) -> (W65C02S, Machine) + Sync,
+        collect: impl Fn(RunEnd, &W65C02S, &Machine) -> R + Sync,
+    ) -> Vec<R>
+    where
+        I: Send,
+        R: Send,
+    {
+        let queue = Mutex::new(inputs.into_iter().enumerate().collect::<Vec<_>>().into_iter());
+        let results = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads{
+                scope.spawn(|| loop{
+                    // a worker that panicked takes the whole run down with it when the scope ends
+                    let Some((index, input)) = queue.lock().unwrap_or_else(|e| e.into_inner()).next() else { break };
+                    let (mut cpu, mut machine) = build(input);
+                    let end = self.run_one(&mut cpu, &mut machine);
+                    let result = collect(end, &cpu, &machine);
+                    results.lock().unwrap_or_else(|e| e.into_inner()).push((index, result));
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    fn run_one(&self, cpu: &mut W65C02S, machine: &mut Machine) -> RunEnd{
+        let started = Instant::now();
+        loop{
+            if let Some(exceeded) = self.limits.check(cpu, started){
+                return RunEnd::Limit(exceeded);
+            }
+            let (count, stop_cycles) = self.limits.batch(cpu);
+            match cpu.run_block(machine, count, stop_cycles){
+                BlockEnd::Ran | BlockEnd::Interrupted(_) => {},
+                BlockEnd::Brk { pc } => return RunEnd::Brk { pc },
+                BlockEnd::Failed { pc, error } => return RunEnd::Failed { pc, error },
+            }
+        }
+    }
+}
+impl Default for Fleet{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,969 @@
+use std::mem::{offset_of, ManuallyDrop};
+use std::time::Instant;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, MemFlags, SigRef, Signature, Type, Value};
+use cranelift_codegen::isa::{CallConv, OwnedTargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Module};
+
+use crate::bus::bus::{Bus, Machine, RegionAttributes};
+use crate::cpu::limits::{LimitExceeded, RunLimits};
+use crate::cpu::w65c02s::{AddressingMode, CpuError, DecodedInstruction, Mnemonic, Operation, Register, W65C02S};
+
+/// Times the interpreter has to start a block at an address before the block is compiled.
+const HOT: u16 = 1024;
+/// Most instructions compiled into one block.
+const MAX_BLOCK: usize = 64;
+/// What the read helper returns for an access that failed, out of the range of a byte.
+const FAILED: u32 = 0x100;
+
+/// Runs a `Machine` as the interpreter does, but compiles the blocks of code it keeps coming back to into native
+/// code with Cranelift, for long-running workloads such as BASIC interpreters and chess engines.
+///
+/// Only instructions the machine has decoded from ROM are compiled, so code a program can rewrite always runs in
+/// the interpreter, as does anything that reaches a device through its fetch. Compiled code reads and writes
+/// through the machine and, after every instruction, ticks it and takes any interrupt pending, so devices,
+/// faults and interrupts behave and happen when they would interpreted. Everything compiled is dropped when the
+/// machine drops what it decoded, on a change to its mappings, attributes, devices or loggers, or a poke into
+/// ROM.
+pub struct Jit{
+    isa: OwnedTargetIsa,
+    module: ManuallyDrop<JITModule>,    // holds the code of every block in `slots`
+    function_context: FunctionBuilderContext,
+    slots: Vec<Slot>,                   // by address
+    generation: Option<u64>,            // of the machine's decoded instructions the blocks were compiled from
+}
+impl Jit{
+    /// Fails on a host Cranelift does not generate code for.
+    pub fn new() -> Result<Self, String>{
+        let mut flags = settings::builder();
+        for (name, value) in [("opt_level", "none"), ("enable_verifier", "false"), ("regalloc_algorithm", "single_pass"), ("use_colocated_libcalls", "false"), ("is_pic", "false")]{
+            flags.set(name, value).map_err(|e| e.to_string())?;
+        }
+        let isa = cranelift_native::builder()?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            module: ManuallyDrop::new(new_module(&isa)),
+            isa,
+            function_context: FunctionBuilderContext::new(),
+            slots: vec![Slot::Cold(0); 0x10000],
+            generation: None,
+        })
+    }
+
+    /// Runs as `W65C02S::run` does, until a `BRK` has executed or until one of the limits is exceeded, and leaves
+    /// the CPU and machine as it would have. Blocks are left at every limit the interpreter would stop at.
+    pub fn run(&mut self, cpu: &mut W65C02S, machine: &mut Machine, limits: &RunLimits) -> Result<Option<LimitExceeded>, CpuError>{
+        let started = Instant::now();
+        // whether the PC is where a block would start, rather than in the middle of straight-line code, which
+        // only blocks cut short by a limit and instructions that do not jump leave it in
+        let mut at_start = true;
+        loop{
+            if let Some(exceeded) = limits.check(cpu, started){
+                return Ok(Some(exceeded));
+            }
+
+            let pc = cpu.register(Register::PC);
+            if let Some(block) = self.block_at(pc, at_start, machine){
+                at_start = enter(block, cpu, machine, limits)?;
+                continue;
+            }
+            let mnemonic = cpu.step(machine)?;
+            if let Mnemonic::BRK = mnemonic{
+                return Ok(None);
+            }
+            // an interrupt taken moves the PC further than any instruction is long
+            at_start = ends_block(mnemonic) || !(1..=3).contains(&cpu.register(Register::PC).wrapping_sub(pc));
+        }
+    }
+
+    /// Number of blocks compiled since the last time they were dropped.
+    pub fn blocks(&self) -> usize{
+        self.slots.iter().filter(|slot| matches!(slot, Slot::Compiled(_))).count()
+    }
+
+    /// The compiled block starting at `pc`, compiling it once a block has been started there often enough. Only
+    /// the times the address is reached `at_start` count.
+    fn block_at(&mut self, pc: u16, at_start: bool, machine: &mut Machine) -> Option<BlockFn>{
+        if self.generation != Some(machine.decode_generation()){
+            self.flush();
+            self.generation = Some(machine.decode_generation());
+        }
+
+        match &mut self.slots[pc as usize]{
+            Slot::Compiled(block) => Some(*block),
+            Slot::Interpreted => None,
+            Slot::Cold(_) if !at_start => None,
+            Slot::Cold(reached) if *reached + 1 < HOT => {
+                *reached += 1;
+                None
+            },
+            Slot::Cold(_) => {
+                let block = self.compile(pc, machine);
+                self.slots[pc as usize] = block.map_or(Slot::Interpreted, Slot::Compiled);
+                block
+            },
+        }
+    }
+
+    fn compile(&mut self, start: u16, machine: &mut Machine) -> Option<BlockFn>{
+        let mut instructions = Vec::new();
+        let mut pc = start;
+        while instructions.len() < MAX_BLOCK && !machine.attributes(pc).contains(RegionAttributes::NO_EXECUTE){
+            let Some(decoded) = machine.decoded_instruction(pc) else { break };
+            let mnemonic = decoded.operation().mnemonic();
+            if matches!(mnemonic, Mnemonic::BRK | Mnemonic::RTI | Mnemonic::STP | Mnemonic::WAI | Mnemonic::Invalid(_)){
+                break;
+            }
+
+            instructions.push((pc, decoded));
+            pc = pc.wrapping_add(decoded.length() as u16);
+            if ends_block(mnemonic){
+                break;
+            }
+        }
+        if instructions.is_empty(){
+            return None;
+        }
+
+        let pointer = self.module.target_config().pointer_type();
+        let call_conv = self.module.target_config().default_call_conv;
+        let mut context = self.module.make_context();
+        context.func.signature.params.extend([AbiParam::new(pointer), AbiParam::new(pointer)]);
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut self.function_context);
+        Translator::new(&mut builder, pointer, call_conv, machine.is_quiet()).translate(&instructions);
+        builder.finalize();
+
+        let compiled = self.define(&mut context);
+        self.module.clear_context(&mut context);
+        match compiled{
+            Ok(id) => {
+                log::debug!("compiled {} instructions at ${:04X}", instructions.len(), start);
+                // SAFETY: the function was declared with the signature of a block
+                Some(unsafe { std::mem::transmute::<*const u8, BlockFn>(self.module.get_finalized_function(id)) })
+            },
+            Err(e) => {
+                log::debug!("block at ${:04X} left to the interpreter: {}", start, e);
+                None
+            },
+        }
+    }
+
+    /// Turns a built function into code, ready to be called.
+    fn define(&mut self, context: &mut cranelift_codegen::Context) -> Result<FuncId, String>{
+        let id = self.module.declare_anonymous_function(&context.func.signature).map_err(|e| e.to_string())?;
+        self.module.define_function(id, context).map_err(|e| e.to_string())?;
+        self.module.finalize_definitions().map_err(|e| e.to_string())?;
+        Ok(id)
+    }
+
+    /// Drops every compiled block, with the memory holding their code.
+    fn flush(&mut self){
+        if self.slots.iter().any(|slot| matches!(slot, Slot::Compiled(_))){
+            log::debug!("dropping {} compiled blocks", self.blocks());
+        }
+        self.slots.fill(Slot::Cold(0));
+
+        let module = std::mem::replace(&mut *self.module, new_module(&self.isa));
+        // SAFETY: no compiled code is running, and the slots that pointed into it are cleared
+        unsafe { module.free_memory() };
+    }
+}
+impl Drop for Jit{
+    fn drop(&mut self) {
+        // SAFETY: the module is not used again, and none of its code can run without the Jit
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
+
+fn new_module(isa: &OwnedTargetIsa) -> JITModule{
+    JITModule::new(JITBuilder::with_isa(isa.clone(), default_libcall_names()))
+}
+
+/// Whether an instruction can leave the straight line, which ends the block it is compiled into.
+fn ends_block(mnemonic: Mnemonic) -> bool{
+    matches!(mnemonic,
+        Mnemonic::BBRN(_) | Mnemonic::BBSN(_) | Mnemonic::BCC | Mnemonic::BCS | Mnemonic::BEQ | Mnemonic::BMI |
+        Mnemonic::BNE | Mnemonic::BPL | Mnemonic::BRA | Mnemonic::BVC | Mnemonic::BVS |
+        Mnemonic::JMP | Mnemonic::JSR | Mnemonic::RTS)
+}
+
+/// Compiled code for a block, called with the context and the CPU whose registers it works on.
+type BlockFn = unsafe extern "C" fn(*mut Context, *mut W65C02S);
+
+#[derive(Copy, Clone)]
+enum Slot{
+    Cold(u16),          // times the interpreter reached the address
+    Compiled(BlockFn),
+    Interpreted,        // no block can start there
+}
+
+/// What compiled code and the helpers it calls share while a block runs.
+#[repr(C)]
+struct Context{
+    cpu: *mut W65C02S,
+    machine: *mut Machine,
+    stop_instructions: u64,     // leave the block once the CPU has executed this many instructions
+    stop_cycles: u64,           // or this many cycles
+    error: Option<CpuError>,
+}
+
+/// Runs a block from its first instruction until it ends, or until an error, an interrupt or a limit leaves it.
+/// Returns whether it was left where another block would start, as it is unless cut short by a limit.
+fn enter(block: BlockFn, cpu: &mut W65C02S, machine: &mut Machine, limits: &RunLimits) -> Result<bool, CpuError>{
+    let (count, stop_cycles) = limits.batch(cpu);
+    let mut context = Context {
+        cpu: &raw mut *cpu,
+        machine: &raw mut *machine,
+        stop_instructions: cpu.instructions().saturating_add(count),
+        stop_cycles,
+        error: None,
+    };
+    // SAFETY: the block was compiled for the machine's current generation, and reaches the CPU and machine only
+    // through the context, which outlives the call
+    unsafe { block(&mut context, context.cpu) };
+
+    match context.error{
+        Some(e) => Err(e),
+        None => Ok(cpu.instructions() < context.stop_instructions && cpu.cycles() < context.stop_cycles),
+    }
+}
+
+//#GROUP: helpers called by compiled code
+/// Reads through the machine for the instruction at `pc`, FAILED when the access failed, with the error kept in
+/// the context.
+extern "C" fn read(context: *mut Context, pc: u32, address: u32) -> u32{
+    // SAFETY: only called by a block, while `enter` holds the context and the machine it points to
+    let context = unsafe { &mut *context };
+    let machine = unsafe { &mut *context.machine };
+    machine.begin_instruction(pc as u16);
+    match machine.read(address as u16){
+        Ok(val) => val as u32,
+        Err(e) => {
+            context.error = Some(e.into());
+            FAILED
+        },
+    }
+}
+/// Writes through the machine for the instruction at `pc`, nonzero when the access failed, with the error kept
+/// in the context.
+extern "C" fn write(context: *mut Context, pc: u32, address: u32, val: u32) -> u32{
+    // SAFETY: as for `read`
+    let context = unsafe { &mut *context };
+    let machine = unsafe { &mut *context.machine };
+    machine.begin_instruction(pc as u16);
+    match machine.write(address as u16, val as u8){
+        Ok(()) => 0,
+        Err(e) => {
+            context.error = Some(e.into());
+            1
+        },
+    }
+}
+/// Finishes the instruction at `pc`, which left the PC at `next_pc` and took `cycles`, as the interpreter does,
+/// and returns nonzero when the block has to be left: on an error, an interrupt taken or a limit reached.
+extern "C" fn retire(context: *mut Context, pc: u32, next_pc: u32, cycles: u32) -> u32{
+    // SAFETY: as for `read`; the block has written back every register it changed
+    let context = unsafe { &mut *context };
+    let (cpu, machine) = unsafe { (&mut *context.cpu, &mut *context.machine) };
+
+    machine.begin_instruction(pc as u16);
+    cpu.set_register(Register::PC, next_pc as u16);
+    if let Err(e) = cpu.retire(machine, cycles){
+        context.error = Some(e.into());
+        return 1;
+    }
+
+    let leave = cpu.register(Register::PC) != next_pc as u16
+        || cpu.instructions() >= context.stop_instructions
+        || cpu.cycles() >= context.stop_cycles;
+    leave as u32
+}
+
+/// An operand once its addressing mode has been worked out.
+#[derive(Copy, Clone)]
+enum Operand{
+    Implied,
+    Accumulator,
+    Value(Value),
+    Address(Value),
+    Relative(u16),                  // the branch target
+    ZeroPageRelative(u8, u16),      // the zero page address tested, and the branch target
+}
+
+/// Emits the Cranelift IR for a block. Registers stay in the CPU, loaded and stored around each instruction, so
+/// that the helpers see them as the interpreter leaves them; 8-bit values are carried zero-extended in an i32.
+struct Translator<'a, 'b>{
+    builder: &'a mut FunctionBuilder<'b>,
+    pointer: Type,
+    quiet: bool,            // the machine has nothing to tick, so instructions are retired by the block itself
+    context: Value,
+    cpu: Value,
+    read: SigRef,
+    write: SigRef,
+    retire: SigRef,
+    exit: Block,            // returns from the block
+    fail: Block,            // stores its parameter as the PC, then returns
+    pc: u16,                // of the instruction being translated
+    fall_through: u16,      // the PC past it, where it is left on an error
+}
+impl<'a, 'b> Translator<'a, 'b>{
+    fn new(builder: &'a mut FunctionBuilder<'b>, pointer: Type, call_conv: CallConv, quiet: bool) -> Self{
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let (context, cpu) = (builder.block_params(entry)[0], builder.block_params(entry)[1]);
+
+        let mut signature = |params: usize| {
+            let mut signature = Signature::new(call_conv);
+            signature.params.push(AbiParam::new(pointer));
+            signature.params.extend((0..params).map(|_| AbiParam::new(types::I32)));
+            signature.returns.push(AbiParam::new(types::I32));
+            builder.import_signature(signature)
+        };
+        let (read, write, retire) = (signature(2), signature(3), signature(3));
+
+        let exit = builder.create_block();
+        let fail = builder.create_block();
+        builder.append_block_param(fail, types::I32);
+        Self { builder, pointer, quiet, context, cpu, read, write, retire, exit, fail, pc: 0, fall_through: 0 }
+    }
+
+    fn translate(mut self, instructions: &[(u16, DecodedInstruction)]){
+        for (i, (pc, decoded)) in instructions.iter().enumerate(){
+            let (next_pc, cycles) = self.instruction(*pc, decoded);
+            let last = i + 1 == instructions.len();
+            if self.quiet{
+                self.count(next_pc, cycles, last);
+            }
+            else{
+                let pc = self.constant(*pc as i64);
+                let leave = self.call(self.retire, retire as *const () as usize, &[pc, next_pc, cycles]);
+                if !last{
+                    self.leave_if(leave, false);
+                }
+            }
+        }
+        self.builder.ins().jump(self.exit, &[]);
+
+        self.builder.switch_to_block(self.fail);
+        let pc = self.builder.block_params(self.fail)[0];
+        let pc = self.builder.ins().ireduce(types::I16, pc);
+        self.builder.ins().store(MemFlags::trusted(), pc, self.cpu, W65C02S::register_offset(Register::PC) as i32);
+        self.builder.ins().jump(self.exit, &[]);
+        self.builder.switch_to_block(self.exit);
+        self.builder.ins().return_(&[]);
+        self.builder.seal_all_blocks();
+    }
+    /// Retires an instruction as `W65C02S::retire` does on a machine with nothing to tick or interrupt it, leaving
+    /// the block at a limit unless it is the last instruction, after which it is left anyway.
+    fn count(&mut self, next_pc: Value, cycles: Value, last: bool){
+        let flags = MemFlags::trusted();
+        let pc = self.builder.ins().ireduce(types::I16, next_pc);
+        self.builder.ins().store(flags, pc, self.cpu, W65C02S::register_offset(Register::PC) as i32);
+        let released = self.builder.ins().iconst(types::I8, 0);
+        self.builder.ins().store(flags, released, self.cpu, W65C02S::NMI_LINE_OFFSET as i32);
+        self.builder.ins().store(flags, released, self.cpu, W65C02S::RESET_LINE_OFFSET as i32);
+
+        let cycles = self.builder.ins().uextend(types::I64, cycles);
+        let total_cycles = self.builder.ins().load(types::I64, flags, self.cpu, W65C02S::CYCLES_OFFSET as i32);
+        let total_cycles = self.builder.ins().iadd(total_cycles, cycles);
+        self.builder.ins().store(flags, total_cycles, self.cpu, W65C02S::CYCLES_OFFSET as i32);
+        let instructions = self.builder.ins().load(types::I64, flags, self.cpu, W65C02S::INSTRUCTIONS_OFFSET as i32);
+        let instructions = self.builder.ins().iadd_imm(instructions, 1);
+        self.builder.ins().store(flags, instructions, self.cpu, W65C02S::INSTRUCTIONS_OFFSET as i32);
+        if last{
+            return;
+        }
+
+        let stop_instructions = self.builder.ins().load(types::I64, flags, self.context, offset_of!(Context, stop_instructions) as i32);
+        let stop_cycles = self.builder.ins().load(types::I64, flags, self.context, offset_of!(Context, stop_cycles) as i32);
+        let instructions_reached = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, instructions, stop_instructions);
+        let cycles_reached = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, total_cycles, stop_cycles);
+        let leave = self.builder.ins().bor(instructions_reached, cycles_reached);
+        self.leave_if(leave, false);
+    }
+
+    /// Emits one instruction, giving the PC it leaves and the cycles it takes.
+    fn instruction(&mut self, pc: u16, decoded: &DecodedInstruction) -> (Value, Value){
+        let operation = decoded.operation();
+        self.pc = pc;
+        self.fall_through = pc.wrapping_add(decoded.length() as u16);
+        let (operand, page_crossed) = self.resolve(operation.addressing_mode(), decoded.operand());
+
+        let cycles = operation.cycles() as i64;
+        let penalty = operation.extra_cycles(true, self.fall_through, self.fall_through) as i64;
+        let mut cycles = match page_crossed{
+            Some(crossed) if penalty > 0 => self.builder.ins().iadd_imm(crossed, cycles),
+            _ => self.constant(cycles),
+        };
+        let mut next_pc = None;
+
+        match operation.mnemonic(){
+            Mnemonic::LDA => self.load(operand, Register::A),
+            Mnemonic::LDX => self.load(operand, Register::X),
+            Mnemonic::LDY => self.load(operand, Register::Y),
+            Mnemonic::STA => self.store(operand, Register::A),
+            Mnemonic::STX => self.store(operand, Register::X),
+            Mnemonic::STY => self.store(operand, Register::Y),
+            Mnemonic::STZ => {
+                let zero = self.constant(0);
+                self.write_operand(operand, zero);
+            },
+
+            Mnemonic::TAX => self.transfer(Register::A, Register::X, true),
+            Mnemonic::TAY => self.transfer(Register::A, Register::Y, true),
+            Mnemonic::TSX => self.transfer(Register::SP, Register::X, true),
+            Mnemonic::TXA => self.transfer(Register::X, Register::A, true),
+            Mnemonic::TXS => self.transfer(Register::X, Register::SP, false),
+            Mnemonic::TYA => self.transfer(Register::Y, Register::A, true),
+
+            Mnemonic::INX => self.modify(Operand::Implied, Some(Register::X), |t, val| t.builder.ins().iadd_imm(val, 1)),
+            Mnemonic::INY => self.modify(Operand::Implied, Some(Register::Y), |t, val| t.builder.ins().iadd_imm(val, 1)),
+            Mnemonic::DEX => self.modify(Operand::Implied, Some(Register::X), |t, val| t.builder.ins().iadd_imm(val, 0xff)),
+            Mnemonic::DEY => self.modify(Operand::Implied, Some(Register::Y), |t, val| t.builder.ins().iadd_imm(val, 0xff)),
+            Mnemonic::INC => self.modify(operand, None, |t, val| t.builder.ins().iadd_imm(val, 1)),
+            Mnemonic::DEC => self.modify(operand, None, |t, val| t.builder.ins().iadd_imm(val, 0xff)),
+
+            Mnemonic::AND => self.logic(operand, |t, a, val| t.builder.ins().band(a, val)),
+            Mnemonic::ORA => self.logic(operand, |t, a, val| t.builder.ins().bor(a, val)),
+            Mnemonic::EOR => self.logic(operand, |t, a, val| t.builder.ins().bxor(a, val)),
+            Mnemonic::ADC => self.add(operand, false),
+            Mnemonic::SBC => self.add(operand, true),
+            Mnemonic::CMP => self.compare(operand, Register::A),
+            Mnemonic::CPX => self.compare(operand, Register::X),
+            Mnemonic::CPY => self.compare(operand, Register::Y),
+            Mnemonic::BIT => self.bit(operand),
+
+            Mnemonic::ASL => self.shift(operand, |t, val, _| {
+                let result = t.builder.ins().ishl_imm(val, 1);
+                (result, t.builder.ins().ushr_imm(val, 7))
+            }),
+            Mnemonic::LSR => self.shift(operand, |t, val, _| {
+                let result = t.builder.ins().ushr_imm(val, 1);
+                (result, t.builder.ins().band_imm(val, 1))
+            }),
+            Mnemonic::ROL => self.shift(operand, |t, val, carry| {
+                let shifted = t.builder.ins().ishl_imm(val, 1);
+                let result = t.builder.ins().bor(shifted, carry);
+                (result, t.builder.ins().ushr_imm(val, 7))
+            }),
+            Mnemonic::ROR => self.shift(operand, |t, val, carry| {
+                let shifted = t.builder.ins().ushr_imm(val, 1);
+                let carry = t.builder.ins().ishl_imm(carry, 7);
+                let result = t.builder.ins().bor(shifted, carry);
+                (result, t.builder.ins().band_imm(val, 1))
+            }),
+            Mnemonic::TRB => self.test_bits(operand, false),
+            Mnemonic::TSB => self.test_bits(operand, true),
+            Mnemonic::RMBN(n) => {
+                let val = self.read_operand(operand);
+                let result = self.builder.ins().band_imm(val, !(1i64 << n) & 0xff);
+                self.write_operand(operand, result);
+            },
+            Mnemonic::SMBN(n) => {
+                let val = self.read_operand(operand);
+                let result = self.builder.ins().bor_imm(val, 1i64 << n);
+                self.write_operand(operand, result);
+            },
+
+            Mnemonic::CLC => self.set_flag(0x01, false),
+            Mnemonic::CLD => self.set_flag(0x08, false),
+            Mnemonic::CLI => self.set_flag(0x04, false),
+            Mnemonic::CLV => self.set_flag(0x40, false),
+            Mnemonic::SEC => self.set_flag(0x01, true),
+            Mnemonic::SED => self.set_flag(0x08, true),
+            Mnemonic::SEI => self.set_flag(0x04, true),
+            Mnemonic::NOP => {},
+
+            Mnemonic::PHA => self.push_register(Register::A),
+            Mnemonic::PHX => self.push_register(Register::X),
+            Mnemonic::PHY => self.push_register(Register::Y),
+            Mnemonic::PHP => {
+                let p = self.register(Register::P);
+                let val = self.builder.ins().bor_imm(p, 0x30);
+                self.push(val);
+            },
+            Mnemonic::PLA => self.pull_register(Register::A),
+            Mnemonic::PLX => self.pull_register(Register::X),
+            Mnemonic::PLY => self.pull_register(Register::Y),
+            Mnemonic::PLP => {
+                let val = self.pull();
+                let val = self.builder.ins().bor_imm(val, 0x20);
+                let p = self.builder.ins().band_imm(val, !0x10 & 0xff);
+                self.set_register(Register::P, p);
+            },
+
+            Mnemonic::BCC => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x01, false),
+            Mnemonic::BCS => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x01, true),
+            Mnemonic::BNE => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x02, false),
+            Mnemonic::BEQ => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x02, true),
+            Mnemonic::BVC => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x40, false),
+            Mnemonic::BVS => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x40, true),
+            Mnemonic::BPL => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x80, false),
+            Mnemonic::BMI => (next_pc, cycles) = self.branch_on_flag(operation, operand, 0x80, true),
+            Mnemonic::BRA => {
+                let taken = self.builder.ins().iconst(types::I8, 1);
+                (next_pc, cycles) = self.branch(operation, operand, taken);
+            },
+            Mnemonic::BBRN(n) | Mnemonic::BBSN(n) => {
+                let Operand::ZeroPageRelative(address, _) = operand else { unreachable!() };
+                let address = self.constant(address as i64);
+                let val = self.read(address);
+                let bit = self.builder.ins().band_imm(val, 1i64 << n);
+                let set = matches!(operation.mnemonic(), Mnemonic::BBSN(_));
+                let taken = self.builder.ins().icmp_imm(if set { IntCC::NotEqual } else { IntCC::Equal }, bit, 0);
+                (next_pc, cycles) = self.branch(operation, operand, taken);
+            },
+            Mnemonic::JMP => {
+                let Operand::Address(target) = operand else { unreachable!() };
+                next_pc = Some(target);
+            },
+            Mnemonic::JSR => {
+                let Operand::Address(target) = operand else { unreachable!() };
+                let return_addr = self.fall_through.wrapping_sub(1);
+                let high = self.constant((return_addr >> 8) as i64);
+                self.push(high);
+                let low = self.constant((return_addr & 0xff) as i64);
+                self.push(low);
+                next_pc = Some(target);
+            },
+            Mnemonic::RTS => {
+                let low = self.pull();
+                let high = self.pull();
+                let high = self.builder.ins().ishl_imm(high, 8);
+                let addr = self.builder.ins().bor(high, low);
+                let addr = self.builder.ins().iadd_imm(addr, 1);
+                next_pc = Some(self.builder.ins().band_imm(addr, 0xffff));
+            },
+
+            Mnemonic::BRK | Mnemonic::RTI | Mnemonic::STP | Mnemonic::WAI | Mnemonic::Invalid(_) => unreachable!("never compiled"),
+        }
+
+        let next_pc = next_pc.unwrap_or_else(|| self.constant(self.fall_through as i64));
+        (next_pc, cycles)
+    }
+
+    /// Works out the operand as the interpreter's `resolve_operand` does, with the same reads, and whether
+    /// indexing crossed a page, as 0 or 1.
+    fn resolve(&mut self, mode: AddressingMode, bytes: [u8; 2]) -> (Operand, Option<Value>){
+        let absolute = u16::from_le_bytes(bytes) as i64;
+        let zero_page = bytes[0] as i64;
+        let relative = |fall_through: u16, offset: u8| fall_through.wrapping_add_signed(offset as i8 as i16);
+
+        match mode{
+            AddressingMode::Absolute => (Operand::Address(self.constant(absolute)), None),
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let address = self.indexed(absolute, Register::X, 0xffff);
+                (Operand::Address(self.pointer_at(address, 0xffff)), None)
+            },
+            AddressingMode::AbsoluteIndexedX | AddressingMode::AbsoluteIndexedY => {
+                let index = if mode == AddressingMode::AbsoluteIndexedX { Register::X } else { Register::Y };
+                let address = self.indexed(absolute, index, 0xffff);
+                let base = self.constant(absolute);
+                (Operand::Address(address), Some(self.crossed(base, address)))
+            },
+            AddressingMode::AbsoluteIndirect => {
+                let pointer = self.constant(absolute);
+                (Operand::Address(self.pointer_at(pointer, 0xffff)), None)
+            },
+            AddressingMode::Accumulator => (Operand::Accumulator, None),
+            AddressingMode::Immediate => (Operand::Value(self.constant(zero_page)), None),
+            AddressingMode::Implied | AddressingMode::Stack => (Operand::Implied, None),
+            AddressingMode::ProgramCounterRelative => (Operand::Relative(relative(self.fall_through, bytes[0])), None),
+            AddressingMode::ZeroPage => (Operand::Address(self.constant(zero_page)), None),
+            AddressingMode::ZeroPageIndexedIndirect => {
+                let pointer = self.indexed(zero_page, Register::X, 0xff);
+                (Operand::Address(self.pointer_at(pointer, 0xff)), None)
+            },
+            AddressingMode::ZeroPageIndexedX => (Operand::Address(self.indexed(zero_page, Register::X, 0xff)), None),
+            AddressingMode::ZeroPageIndexedY => (Operand::Address(self.indexed(zero_page, Register::Y, 0xff)), None),
+            AddressingMode::ZeroPageIndirect => {
+                let pointer = self.constant(zero_page);
+                (Operand::Address(self.pointer_at(pointer, 0xff)), None)
+            },
+            AddressingMode::ZeroPageIndirectIndexedY => {
+                let pointer = self.constant(zero_page);
+                let base = self.pointer_at(pointer, 0xff);
+                let y = self.register(Register::Y);
+                let address = self.builder.ins().iadd(base, y);
+                let address = self.builder.ins().band_imm(address, 0xffff);
+                (Operand::Address(address), Some(self.crossed(base, address)))
+            },
+            AddressingMode::ZeroPageRelative => (Operand::ZeroPageRelative(bytes[0], relative(self.fall_through, bytes[1])), None),
+        }
+    }
+    /// `base` plus an index register, wrapped by `mask`.
+    fn indexed(&mut self, base: i64, index: Register, mask: i64) -> Value{
+        let index = self.register(index);
+        let address = self.builder.ins().iadd_imm(index, base);
+        self.builder.ins().band_imm(address, mask)
+    }
+    /// Reads the little-endian pointer at `address`, the high byte wrapping by `mask`.
+    fn pointer_at(&mut self, address: Value, mask: i64) -> Value{
+        let low = self.read(address);
+        let next = self.builder.ins().iadd_imm(address, 1);
+        let next = self.builder.ins().band_imm(next, mask);
+        let high = self.read(next);
+        let high = self.builder.ins().ishl_imm(high, 8);
+        self.builder.ins().bor(high, low)
+    }
+    fn crossed(&mut self, a: Value, b: Value) -> Value{
+        let pages = self.builder.ins().bxor(a, b);
+        let pages = self.builder.ins().ushr_imm(pages, 8);
+        let crossed = self.builder.ins().icmp_imm(IntCC::NotEqual, pages, 0);
+        self.builder.ins().uextend(types::I32, crossed)
+    }
+
+    //#GROUP: operations
+    fn load(&mut self, operand: Operand, register: Register){
+        let val = self.read_operand(operand);
+        self.set_register(register, val);
+        self.set_zn(val);
+    }
+    fn store(&mut self, operand: Operand, register: Register){
+        let val = self.register(register);
+        self.write_operand(operand, val);
+    }
+    fn transfer(&mut self, from: Register, to: Register, zn: bool){
+        let val = self.register(from);
+        self.set_register(to, val);
+        if zn{
+            self.set_zn(val);
+        }
+    }
+    /// Read-modify-write of a register, or of the operand when there is none, setting Z and N.
+    fn modify(&mut self, operand: Operand, register: Option<Register>, op: impl FnOnce(&mut Self, Value) -> Value){
+        let val = match register{
+            Some(register) => self.register(register),
+            None => self.read_operand(operand),
+        };
+        let result = op(self, val);
+        let result = self.builder.ins().band_imm(result, 0xff);
+        self.set_zn(result);
+        match register{
+            Some(register) => self.set_register(register, result),
+            None => self.write_operand(operand, result),
+        }
+    }
+    fn logic(&mut self, operand: Operand, op: impl FnOnce(&mut Self, Value, Value) -> Value){
+        let val = self.read_operand(operand);
+        let a = self.register(Register::A);
+        let result = op(self, a, val);
+        self.set_zn(result);
+        self.set_register(Register::A, result);
+    }
+    /// ADC, or SBC as the addition of the operand's complement, in binary.
+    fn add(&mut self, operand: Operand, subtract: bool){
+        let val = self.read_operand(operand);
+        let a = self.register(Register::A);
+        let p = self.register(Register::P);
+        let carry = self.builder.ins().band_imm(p, 0x01);
+
+        let addend = if subtract { self.builder.ins().bxor_imm(val, 0xff) } else { val };
+        let sum = self.builder.ins().iadd(a, addend);
+        let sum = self.builder.ins().iadd(sum, carry);
+        let result = self.builder.ins().band_imm(sum, 0xff);
+        let carry = self.builder.ins().ushr_imm(sum, 8);
+
+        // overflow when the result's sign differs from that of both inputs to the addition
+        let a_result = self.builder.ins().bxor(a, result);
+        let addend_result = self.builder.ins().bxor(addend, result);
+        let overflow = self.builder.ins().band(a_result, addend_result);
+        let overflow = self.builder.ins().band_imm(overflow, 0x80);
+        let overflow = self.builder.ins().ushr_imm(overflow, 1);
+
+        let zn = self.zn_bits(result);
+        let flags = self.builder.ins().bor(zn, overflow);
+        let flags = self.builder.ins().bor(flags, carry);
+        self.set_flags(0xc3, flags);
+        self.set_register(Register::A, result);
+    }
+    fn compare(&mut self, operand: Operand, register: Register){
+        let val = self.read_operand(operand);
+        let reg = self.register(register);
+        let difference = self.builder.ins().isub(reg, val);
+        let difference = self.builder.ins().band_imm(difference, 0xff);
+        let carry = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, reg, val);
+        let carry = self.builder.ins().uextend(types::I32, carry);
+
+        let zn = self.zn_bits(difference);
+        let flags = self.builder.ins().bor(zn, carry);
+        self.set_flags(0x83, flags);
+    }
+    fn bit(&mut self, operand: Operand){
+        let val = self.read_operand(operand);
+        let a = self.register(Register::A);
+        let masked = self.builder.ins().band(a, val);
+        let zero = self.zero_bit(masked);
+        match operand{
+            Operand::Value(_) => self.set_flags(0x02, zero),
+            _ => {
+                let nv = self.builder.ins().band_imm(val, 0xc0);
+                let flags = self.builder.ins().bor(zero, nv);
+                self.set_flags(0xc2, flags);
+            },
+        }
+    }
+    /// A shift or rotate, given the operand and the carry, and giving the result and the carry out, both
+    /// unmasked.
+    fn shift(&mut self, operand: Operand, op: impl FnOnce(&mut Self, Value, Value) -> (Value, Value)){
+        let val = self.read_operand(operand);
+        let p = self.register(Register::P);
+        let carry = self.builder.ins().band_imm(p, 0x01);
+        let (result, carry) = op(self, val, carry);
+        let result = self.builder.ins().band_imm(result, 0xff);
+        let carry = self.builder.ins().band_imm(carry, 0x01);
+
+        let zn = self.zn_bits(result);
+        let flags = self.builder.ins().bor(zn, carry);
+        self.set_flags(0x83, flags);
+        self.write_operand(operand, result);
+    }
+    /// TSB, or TRB when not `set`.
+    fn test_bits(&mut self, operand: Operand, set: bool){
+        let val = self.read_operand(operand);
+        let a = self.register(Register::A);
+        let result = if set{
+            self.builder.ins().bor(val, a)
+        }
+        else{
+            let not_a = self.builder.ins().bxor_imm(a, 0xff);
+            self.builder.ins().band(val, not_a)
+        };
+
+        let masked = self.builder.ins().band(val, a);
+        let zero = self.zero_bit(masked);
+        self.set_flags(0x02, zero);
+        self.write_operand(operand, result);
+    }
+    /// A branch on a flag being `set`, giving the PC it leaves and the cycles it takes.
+    fn branch_on_flag(&mut self, operation: Operation, operand: Operand, mask: i64, set: bool) -> (Option<Value>, Value){
+        let p = self.register(Register::P);
+        let flag = self.builder.ins().band_imm(p, mask);
+        let taken = self.builder.ins().icmp_imm(if set { IntCC::NotEqual } else { IntCC::Equal }, flag, 0);
+        self.branch(operation, operand, taken)
+    }
+    fn branch(&mut self, operation: Operation, operand: Operand, taken: Value) -> (Option<Value>, Value){
+        let (Operand::Relative(target) | Operand::ZeroPageRelative(_, target)) = operand else { unreachable!() };
+        let cycles = operation.cycles() as i64;
+        let taken_cycles = self.constant(cycles + operation.extra_cycles(false, self.fall_through, target) as i64);
+        let not_taken_cycles = self.constant(cycles + operation.extra_cycles(false, self.fall_through, self.fall_through) as i64);
+        let target = self.constant(target as i64);
+        let fall_through = self.constant(self.fall_through as i64);
+
+        let next_pc = self.builder.ins().select(taken, target, fall_through);
+        (Some(next_pc), self.builder.ins().select(taken, taken_cycles, not_taken_cycles))
+    }
+    fn push_register(&mut self, register: Register){
+        let val = self.register(register);
+        self.push(val);
+    }
+    fn pull_register(&mut self, register: Register){
+        let val = self.pull();
+        self.set_register(register, val);
+        self.set_zn(val);
+    }
+    fn push(&mut self, val: Value){
+        let sp = self.register(Register::SP);
+        let address = self.builder.ins().bor_imm(sp, W65C02S::STACK_POINTER_BASE as i64);
+        self.write(address, val);
+        let sp = self.builder.ins().iadd_imm(sp, 0xff);
+        self.set_register(Register::SP, sp);
+    }
+    fn pull(&mut self) -> Value{
+        let sp = self.register(Register::SP);
+        let sp = self.builder.ins().iadd_imm(sp, 1);
+        let sp = self.builder.ins().band_imm(sp, 0xff);
+        self.set_register(Register::SP, sp);
+        let address = self.builder.ins().bor_imm(sp, W65C02S::STACK_POINTER_BASE as i64);
+        self.read(address)
+    }
+
+    //#GROUP: flags
+    fn set_flag(&mut self, mask: i64, set: bool){
+        let bits = self.constant(if set { mask } else { 0 });
+        self.set_flags(mask, bits);
+    }
+    /// Replaces the flags in `mask` with `bits`, which has none set outside it.
+    fn set_flags(&mut self, mask: i64, bits: Value){
+        let p = self.register(Register::P);
+        let p = self.builder.ins().band_imm(p, !mask & 0xff);
+        let p = self.builder.ins().bor(p, bits);
+        self.set_register(Register::P, p);
+    }
+    fn set_zn(&mut self, val: Value){
+        let bits = self.zn_bits(val);
+        self.set_flags(0x82, bits);
+    }
+    /// Z and N in place for a result.
+    fn zn_bits(&mut self, val: Value) -> Value{
+        let zero = self.zero_bit(val);
+        let negative = self.builder.ins().band_imm(val, 0x80);
+        self.builder.ins().bor(zero, negative)
+    }
+    /// Z in place, set when `val` is zero.
+    fn zero_bit(&mut self, val: Value) -> Value{
+        let zero = self.builder.ins().icmp_imm(IntCC::Equal, val, 0);
+        let zero = self.builder.ins().uextend(types::I32, zero);
+        self.builder.ins().ishl_imm(zero, 1)
+    }
+
+    //#GROUP: registers, memory and helpers
+    fn constant(&mut self, val: i64) -> Value{
+        self.builder.ins().iconst(types::I32, val)
+    }
+    fn register(&mut self, register: Register) -> Value{
+        let val = self.builder.ins().load(types::I8, MemFlags::trusted(), self.cpu, W65C02S::register_offset(register) as i32);
+        self.builder.ins().uextend(types::I32, val)
+    }
+    /// Stores the low byte of `val`.
+    fn set_register(&mut self, register: Register, val: Value){
+        let val = self.builder.ins().ireduce(types::I8, val);
+        self.builder.ins().store(MemFlags::trusted(), val, self.cpu, W65C02S::register_offset(register) as i32);
+    }
+
+    fn read_operand(&mut self, operand: Operand) -> Value{
+        match operand{
+            Operand::Value(val) => val,
+            Operand::Accumulator => self.register(Register::A),
+            Operand::Address(address) => self.read(address),
+            _ => unreachable!("the instruction has no operand to read"),
+        }
+    }
+    fn write_operand(&mut self, operand: Operand, val: Value){
+        match operand{
+            Operand::Accumulator => self.set_register(Register::A, val),
+            Operand::Address(address) => self.write(address, val),
+            _ => unreachable!("the instruction has no operand to write"),
+        }
+    }
+    fn read(&mut self, address: Value) -> Value{
+        let pc = self.constant(self.pc as i64);
+        let val = self.call(self.read, read as *const () as usize, &[pc, address]);
+        let failed = self.builder.ins().band_imm(val, FAILED as i64);
+        self.leave_if(failed, true);
+        val
+    }
+    fn write(&mut self, address: Value, val: Value){
+        let pc = self.constant(self.pc as i64);
+        let failed = self.call(self.write, write as *const () as usize, &[pc, address, val]);
+        self.leave_if(failed, true);
+    }
+
+    fn call(&mut self, signature: SigRef, helper: usize, args: &[Value]) -> Value{
+        let callee = self.builder.ins().iconst(self.pointer, helper as i64);
+        let args = [&[self.context][..], args].concat();
+        let call = self.builder.ins().call_indirect(signature, callee, &args);
+        self.builder.inst_results(call)[0]
+    }
+    /// Returns from the block when `condition` is nonzero, first leaving the PC past the instruction if `failed`,
+    /// where the interpreter leaves it on an error.
+    fn leave_if(&mut self, condition: Value, failed: bool){
+        let next = self.builder.create_block();
+        if failed{
+            let pc = self.constant(self.fall_through as i64);
+            self.builder.ins().brif(condition, self.fail, &[pc], next, &[]);
+        }
+        else{
+            self.builder.ins().brif(condition, self.exit, &[], next, &[]);
+        }
+        self.builder.switch_to_block(next);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::cpu::assembler;
+    use crate::cpu::bench;
+    use crate::devices::random::Random;
+
+    /// Runs a copy of the machine in the interpreter and another through the JIT, and checks that they stop for
+    /// the same reason with the same registers, counts and RAM. Returns the number of blocks compiled.
+    fn check_same(name: &str, machine: impl Fn() -> Machine, limits: RunLimits) -> usize{
+        let (mut interpreted, mut compiled) = (machine(), machine());
+        let (mut interpreter_cpu, mut jit_cpu) = (W65C02S::default(), W65C02S::default());
+        interpreter_cpu.reset(&mut interpreted).unwrap();
+        jit_cpu.reset(&mut compiled).unwrap();
+
+        let expected = interpreter_cpu.run(&mut interpreted, &limits);
+        let mut jit = Jit::new().unwrap();
+        let actual = jit.run(&mut jit_cpu, &mut compiled, &limits);
+
+        assert_eq!(format!("{:?}", actual), format!("{:?}", expected), "{}: stop", name);
+        for register in [Register::PC, Register::A, Register::X, Register::Y, Register::SP, Register::P]{
+            assert_eq!(jit_cpu.register(register), interpreter_cpu.register(register), "{}: {:?}", name, register);
+        }
+        assert_eq!(jit_cpu.cycles(), interpreter_cpu.cycles(), "{}: cycles", name);
+        assert_eq!(jit_cpu.instructions(), interpreter_cpu.instructions(), "{}: instructions", name);
+        assert!(compiled.ram_contents() == interpreted.ram_contents(), "{}: RAM", name);
+        jit.blocks()
+    }
+
+    /// A loop run often enough to be compiled, around a block of random instructions that neither leave it nor
+    /// reach outside RAM: no jumps, branches, returns or indirect modes, and absolute operands in page 2.
+    fn random_loop(seed: u64) -> Vec<u8>{
+        let mut random = Random::new(seed);
+        let mut rom = vec![0xa2, 0xff, 0x9a, 0xa9, 0x00, 0x8d, 0x00, 0x03, 0xa9, 0x05, 0x8d, 0x01, 0x03];
+        let body = 0x8000 + rom.len() as u16;
+        for _ in 0..1 + random.next_u64() % 40{
+            let (operation, opcode) = loop{
+                let opcode = random.next_u64() as u8;
+                let Some(operation) = W65C02S::operation(opcode) else { continue };
+                let allowed = !ends_block(operation.mnemonic())
+                    && !matches!(operation.mnemonic(), Mnemonic::BRK | Mnemonic::RTI | Mnemonic::STP | Mnemonic::WAI)
+                    && !matches!(operation.addressing_mode(), AddressingMode::ZeroPageIndirect
+                        | AddressingMode::ZeroPageIndexedIndirect | AddressingMode::ZeroPageIndirectIndexedY);
+                if allowed{
+                    break (operation, opcode);
+                }
+            };
+            rom.push(opcode);
+            match operation.addressing_mode().num_operand_bytes(){
+                1 => rom.push(random.next_u64() as u8),
+                2 => rom.extend([random.next_u64() as u8, 0x02]),
+                _ => {},
+            }
+        }
+        // DEC $0300, BNE body, DEC $0301, BNE body, BRK
+        rom.extend([0xce, 0x00, 0x03, 0xd0]);
+        rom.push((body as i32 - (0x8000 + rom.len() as i32 + 1)) as u8);
+        rom.extend([0xce, 0x01, 0x03, 0xd0]);
+        rom.push((body as i32 - (0x8000 + rom.len() as i32 + 1)) as u8);
+        rom.extend([0x00, 0x00]);
+
+        rom.resize(0x8000, 0x00);
+        rom[0x7ffc..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        rom
+    }
+
+    #[test]
+    fn runs_the_bench_workload_as_the_interpreter_does(){
+        let image = assembler::assemble(bench::WORKLOAD).unwrap().image;
+        let machine = || Machine::new_32k_ram_32k_rom(&image).unwrap();
+        for limits in [
+            RunLimits { max_instructions: Some(1), ..RunLimits::NONE },
+            RunLimits { max_instructions: Some(12_345), ..RunLimits::NONE },
+            RunLimits { max_instructions: Some(300_001), ..RunLimits::NONE },
+            RunLimits { max_cycles: Some(777_777), ..RunLimits::NONE },
+        ]{
+            check_same("bench", machine, limits);
+        }
+    }
+
+    #[test]
+    fn runs_random_blocks_as_the_interpreter_does(){
+        for seed in 0..64{
+            let rom = random_loop(seed);
+            let machine = || Machine::new_32k_ram_32k_rom(&rom).unwrap();
+            let blocks = check_same(&format!("seed {}", seed), machine, RunLimits { max_instructions: Some(200_000), ..RunLimits::NONE });
+            assert!(blocks > 0, "seed {}: nothing compiled", seed);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::cpu::w65c02s::W65C02S;
+
+/// Caps on how long a program may run, so one that never reaches its end cannot hang the host.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunLimits{
+    pub max_instructions: Option<u64>,
+    pub max_cycles: Option<u64>,
+    pub timeout: Option<Duration>,      // of wall-clock time, from when the run started
+}
+impl RunLimits{
+    /// No limits at all.
+    pub const NONE: Self = Self { max_instructions: None, max_cycles: None, timeout: None };
+    /// Instructions between looks at the clock.
+    pub(crate) const CHECK_EVERY: u64 = 4096;
+
+    /// The limit the CPU has reached, if any, in a run started at `started`. A run that checks before each
+    /// instruction executes exactly `max_instructions` of them, and stops at the first instruction boundary
+    /// at or past `max_cycles`. The clock is only looked at every few thousand instructions, so a timeout
+    /// stops the run a little after it expires.
+    pub fn check(&self, cpu: &W65C02S, started: Instant) -> Option<LimitExceeded>{
+        if let Some(max) = self.max_instructions && cpu.instructions() >= max{
+            return Some(LimitExceeded::Instructions(max));
+        }
+        if let Some(max) = self.max_cycles && cpu.cycles() >= max{
+            return Some(LimitExceeded::Cycles(max));
+        }
+        if let Some(timeout) = self.timeout && cpu.instructions().is_multiple_of(Self::CHECK_EVERY) && started.elapsed() >= timeout{
+            return Some(LimitExceeded::Time(timeout));
+        }
+        None
+    }
+    /// How many instructions a run that has just passed `check` may execute before it has to check again, and
+    /// the cycle count at which it has to stop sooner, for executing them together with `W65C02S::run_block`.
+    pub fn batch(&self, cpu: &W65C02S) -> (u64, u64){
+        let mut count = self.max_instructions.map_or(u64::MAX, |max| max - cpu.instructions());
+        if self.timeout.is_some(){
+            count = count.min(Self::CHECK_EVERY - cpu.instructions() % Self::CHECK_EVERY);
+        }
+        (count, self.max_cycles.unwrap_or(u64::MAX))
+    }
+}
+
+/// Which limit stopped a run, and its value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LimitExceeded{
+    Instructions(u64),
+    Cycles(u64),
+    Time(Duration),
+}
+impl fmt::Display for LimitExceeded{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            LimitExceeded::Instructions(max) => write!(f, "instruction limit of {} exceeded", max),
+            LimitExceeded::Cycles(max) => write!(f, "cycle limit of {} exceeded", max),
+            LimitExceeded::Time(timeout) => write!(f, "timeout of {:?} exceeded", timeout),
+        }
+    }
+}
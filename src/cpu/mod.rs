@@ -1 +1,25 @@
-pub mod w65c02s;
\ No newline at end of file
+pub mod w65c02s;
+pub mod limits;
+pub mod disassembler;
+pub mod trace;
+pub mod throttle;
+pub mod report;
+pub mod symbols;
+pub mod watch;
+pub mod assembler;
+pub mod profile;
+pub mod debugger;
+pub mod speed;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod save_state;
+pub mod test_suite;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod determinism;
+pub mod coverage;
+pub mod bench;
+pub mod emulator;
+pub mod fleet;
+#[cfg(feature = "jit")]
+pub mod jit;
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::bus::bus::Machine;
+use crate::cpu::disassembler;
+use crate::cpu::symbols::SymbolTable;
+use crate::cpu::w65c02s::{AddressingMode, Register, W65C02S};
+
+// entries listed in each table of the report
+const TOP: usize = 20;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Counts{
+    executions: u64,
+    cycles: u64,
+}
+
+/// Counts the instructions executed and the cycles spent at every address and on every opcode, and the calls
+/// to and cycles spent in every subroutine, for a report of where a program spends its time.
+///
+/// Subroutines are followed through `JSR` and `RTS`; a subroutine's cycles include those of the subroutines it
+/// calls. One that leaves by other means, such as by dropping its return address, stays open until the end of
+/// the run.
+pub struct Profiler{
+    addresses: HashMap<u16, Counts>,
+    opcodes: [Counts; 256],
+    subroutines: HashMap<u16, Counts>,      // executions count the calls
+    calls: Vec<(u16, u64)>,                 // the subroutines entered and not yet left, and the cycle count at entry
+    current: Option<(u16, u8, u64)>,        // the instruction about to run, its opcode, and the cycle count before it
+    symbols: SymbolTable,
+}
+impl Profiler{
+    pub fn new() -> Self{
+        Self {
+            addresses: HashMap::new(),
+            opcodes: [Counts::default(); 256],
+            subroutines: HashMap::new(),
+            calls: Vec::new(),
+            current: None,
+            symbols: SymbolTable::new(),
+        }
+    }
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self{
+        self.symbols = symbols;
+        self
+    }
+
+    /// Call before each step of the CPU.
+    pub fn before(&mut self, cpu: &W65C02S, machine: &Machine){
+        let pc = cpu.register(Register::PC);
+        self.current = machine.peek(pc).map(|opcode| (pc, opcode, cpu.cycles()));
+    }
+    /// Call after each step of the CPU.
+    pub fn after(&mut self, cpu: &W65C02S){
+        let Some((pc, opcode, started)) = self.current.take() else { return };
+        let cycles = cpu.cycles() - started;
+        for counts in [self.addresses.entry(pc).or_default(), &mut self.opcodes[opcode as usize]]{
+            counts.executions += 1;
+            counts.cycles += cycles;
+        }
+
+        match opcode{
+            JSR => {
+                let target = cpu.register(Register::PC);
+                self.subroutines.entry(target).or_default().executions += 1;
+                self.calls.push((target, started));
+            },
+            RTS => if let Some((target, entered)) = self.calls.pop(){
+                self.subroutines.entry(target).or_default().cycles += cpu.cycles() - entered;
+            },
+            _ => (),
+        }
+    }
+
+    /// The report, given the machine and CPU as the run left them. Subroutines still open are counted up to
+    /// the end of the run.
+    pub fn report(&self, cpu: &W65C02S, machine: &Machine) -> String{
+        let total = cpu.cycles().max(1);
+        let percent = |cycles: u64| 100.0 * cycles as f64 / total as f64;
+        let mut report = String::new();
+        let _ = writeln!(report, "{} cycles, {} instructions", cpu.cycles(), cpu.instructions());
+
+        let _ = writeln!(report, "\nHot spots\n{:<7}{:<24}{:<20}{:>12}{:>14}{:>8}", "", "Label", "Instruction", "Executions", "Cycles", "%");
+        for (address, counts) in top(self.addresses.iter().map(|(&address, &counts)| (address, counts))){
+            let instruction = disassembler::disassemble_with_symbols(address, |a| machine.peek(a), &self.symbols);
+            let _ = writeln!(report, "${:04X}  {:<24}{:<20}{:>12}{:>14}{:>7.1}%",
+                address, self.label(address), instruction.text, counts.executions, counts.cycles, percent(counts.cycles));
+        }
+
+        let mut subroutines = self.subroutines.clone();
+        for &(target, entered) in &self.calls{
+            subroutines.entry(target).or_default().cycles += cpu.cycles() - entered;
+        }
+        let _ = writeln!(report, "\nSubroutines\n{:<7}{:<24}{:>12}{:>14}{:>12}{:>8}", "", "Label", "Calls", "Cycles", "Per call", "%");
+        for (address, counts) in top(subroutines.into_iter()){
+            let _ = writeln!(report, "${:04X}  {:<24}{:>12}{:>14}{:>12}{:>7.1}%",
+                address, self.label(address), counts.executions, counts.cycles, counts.cycles / counts.executions.max(1), percent(counts.cycles));
+        }
+
+        let _ = writeln!(report, "\nOpcodes\n{:<7}{:<24}{:>12}{:>14}{:>8}", "", "Instruction", "Executions", "Cycles", "%");
+        for (opcode, counts) in top(self.opcodes.iter().enumerate().filter(|(_, c)| c.executions > 0).map(|(opcode, &counts)| (opcode as u8, counts))){
+            let _ = writeln!(report, "${:02X}    {:<24}{:>12}{:>14}{:>7.1}%",
+                opcode, opcode_name(opcode), counts.executions, counts.cycles, percent(counts.cycles));
+        }
+        report
+    }
+
+    /// The name of the symbol at or before `address`, with the distance past it where there is one.
+    fn label(&self, address: u16) -> String{
+        match self.symbols.nearest(address){
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{}", name, offset),
+            None => String::new(),
+        }
+    }
+}
+impl Default for Profiler{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The entries that took the most cycles, most first, ties going to the lower key.
+fn top<K: Ord + Copy>(entries: impl Iterator<Item = (K, Counts)>) -> Vec<(K, Counts)>{
+    let mut entries = entries.collect::<Vec<(K, Counts)>>();
+    entries.sort_by_key(|&(key, counts)| (std::cmp::Reverse(counts.cycles), key));
+    entries.truncate(TOP);
+    entries
+}
+
+/// The mnemonic and the addressing mode in the shorthand of data sheets, such as `LDA (zp),Y`.
+fn opcode_name(opcode: u8) -> String{
+    let Some(operation) = W65C02S::operation(opcode) else { return "???".to_string() };
+    let mode = match operation.addressing_mode(){
+        AddressingMode::Absolute => "abs",
+        AddressingMode::AbsoluteIndexedIndirect => "(abs,X)",
+        AddressingMode::AbsoluteIndexedX => "abs,X",
+        AddressingMode::AbsoluteIndexedY => "abs,Y",
+        AddressingMode::AbsoluteIndirect => "(abs)",
+        AddressingMode::Accumulator => "A",
+        AddressingMode::Immediate => "#",
+        AddressingMode::Implied | AddressingMode::Stack => "",
+        AddressingMode::ProgramCounterRelative => "rel",
+        AddressingMode::ZeroPage => "zp",
+        AddressingMode::ZeroPageIndexedIndirect => "(zp,X)",
+        AddressingMode::ZeroPageIndexedX => "zp,X",
+        AddressingMode::ZeroPageIndexedY => "zp,Y",
+        AddressingMode::ZeroPageIndirect => "(zp)",
+        AddressingMode::ZeroPageIndirectIndexedY => "(zp),Y",
+        AddressingMode::ZeroPageRelative => "zp,rel",
+    };
+    format!("{} {}", operation.mnemonic(), mode).trim_end().to_string()
+}
@@ -0,0 +1,376 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::bus::bus::Machine;
+use crate::cpu::debugger::DebugAction;
+use crate::cpu::disassembler;
+use crate::cpu::report::StopReason;
+use crate::cpu::symbols::{parse_location, SymbolTable};
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// Lets clients over the network watch and drive a run, with the same commands over plain HTTP and over a
+/// WebSocket. The run starts running; a client can pause it, step it, set breakpoints and read the machine.
+///
+/// HTTP answers one request per connection with JSON:
+///  - `GET /state`: the registers, the counts and whether the run is paused
+///  - `GET /memory?address=0200&length=64`: bytes of memory, `null` for a device register
+///  - `GET /disassemble?address=8000&count=10`: instructions, from the PC when no address is given
+///  - `GET /breakpoints`, `POST /breakpoints?address=8000`, `DELETE /breakpoints?address=8000`
+///  - `POST /pause`, `POST /run`, `POST /step?count=1`, `POST /quit`
+///  - `GET /snapshot`, with the `serde` feature: the whole CPU and `MachineState`
+///
+/// `GET /ws` upgrades to a WebSocket that takes the commands as JSON text, such as
+/// `{"command": "step", "count": 10}`, and answers each in turn. It is also told, unasked, when the run stops
+/// at a breakpoint, after a step or on a pause (`{"event": "stopped", ...}`), when the CPU takes an interrupt
+/// (`{"event": "interrupt", ...}`, with those it outranked) and when an image ends (`{"event": "ended", ...}`).
+/// Addresses are given as numbers, or as strings that are hex addresses or labels.
+pub struct Remote{
+    requests: Receiver<Request>,
+    subscribers: Vec<Sender<Value>>,    // the WebSockets, told of events
+    breakpoints: BTreeSet<u16>,
+    paused: bool,
+    steps_left: Option<u64>,            // instructions to run before pausing, None to run to a breakpoint
+    since_poll: u64,
+    symbols: SymbolTable,
+}
+
+enum Request{
+    Command(Command, Sender<Value>),
+    Subscribe(Sender<Value>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+enum Command{
+    State,
+    Memory{ address: Location, length: Option<u32> },
+    Disassemble{ address: Option<Location>, count: Option<u32> },
+    Breakpoints,
+    Break{ address: Location },
+    Delete{ address: Location },
+    Pause,
+    Run,
+    Step{ count: Option<u64> },
+    Quit,
+    #[cfg(feature = "serde")]
+    Snapshot,
+}
+
+/// An address, or text for `parse_location`, resolved on the run's side where the symbols are.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Location{
+    Address(u16),
+    Text(String),
+}
+
+impl Remote{
+    /// Instructions between looks for requests while running, which keeps the overhead down.
+    const POLL_EVERY: u64 = 1024;
+    /// How long a WebSocket waits for a command before passing on events.
+    const EVENT_LATENCY: Duration = Duration::from_millis(20);
+
+    /// Listens on `address`, answering clients from threads of its own.
+    pub fn serve(address: impl ToSocketAddrs) -> io::Result<Self>{
+        let listener = TcpListener::bind(address)?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten(){
+                let sender = sender.clone();
+                // a client that goes away mid-request is not worth stopping the machine for
+                thread::spawn(move || { let _ = connection(stream, sender); });
+            }
+        });
+        Ok(Self {
+            requests,
+            subscribers: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            paused: false,
+            steps_left: None,
+            since_poll: 0,
+            symbols: SymbolTable::new(),
+        })
+    }
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self{
+        self.symbols = symbols;
+        self
+    }
+
+    /// Call before each step of the CPU. Answers requests, and waits for them while paused.
+    pub fn check(&mut self, cpu: &W65C02S, machine: &Machine) -> DebugAction{
+        if let Some(arbitration) = cpu.last_arbitration(){
+            let outranked = arbitration.outranked().map(|interrupt| interrupt.to_string()).collect::<Vec<String>>();
+            self.broadcast(json!({ "event": "interrupt", "taken": arbitration.taken.to_string(), "pc": arbitration.pc, "outranked": outranked }));
+        }
+        let stop = match self.steps_left{
+            Some(0) => Some("step"),
+            Some(n) => {
+                self.steps_left = Some(n - 1);
+                None
+            },
+            None => None,
+        };
+        if let Some(reason) = stop.or(self.breakpoints.contains(&cpu.register(Register::PC)).then_some("breakpoint")){
+            self.pause(reason, cpu);
+        }
+
+        if !self.paused{
+            self.since_poll += 1;
+            if self.since_poll < Self::POLL_EVERY{
+                return DebugAction::Run;
+            }
+            self.since_poll = 0;
+            while let Ok(request) = self.requests.try_recv(){
+                if self.answer(request, cpu, machine) == Some(DebugAction::Quit){
+                    return DebugAction::Quit;
+                }
+            }
+        }
+        while self.paused{
+            // the listener holds a sender for as long as the process runs, so this only ends with a request
+            let Ok(request) = self.requests.recv() else { break };
+            if self.answer(request, cpu, machine) == Some(DebugAction::Quit){
+                return DebugAction::Quit;
+            }
+        }
+        DebugAction::Run
+    }
+
+    /// Call when an image's run ends, to tell the WebSockets why.
+    pub fn finish(&mut self, cpu: &W65C02S, stop: StopReason){
+        self.steps_left = None;
+        self.paused = false;
+        self.broadcast(json!({ "event": "ended", "stop": stop, "state": self.state(cpu) }));
+    }
+
+    fn pause(&mut self, reason: &str, cpu: &W65C02S){
+        self.paused = true;
+        self.steps_left = None;
+        self.broadcast(json!({ "event": "stopped", "reason": reason, "state": self.state(cpu) }));
+    }
+
+    fn broadcast(&mut self, event: Value){
+        self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Carries out a request, returning what the run loop does next when it ends the run.
+    fn answer(&mut self, request: Request, cpu: &W65C02S, machine: &Machine) -> Option<DebugAction>{
+        let (command, reply) = match request{
+            Request::Command(command, reply) => (command, reply),
+            Request::Subscribe(subscriber) => {
+                self.subscribers.push(subscriber);
+                return None;
+            },
+        };
+
+        let (answer, action) = match self.command(command, cpu, machine){
+            Ok(answer) => answer,
+            Err(error) => (json!({ "error": error }), None),
+        };
+        // a client that has gone away is not worth stopping the machine for
+        let _ = reply.send(answer);
+        action
+    }
+
+    fn command(&mut self, command: Command, cpu: &W65C02S, machine: &Machine) -> Result<(Value, Option<DebugAction>), String>{
+        let answer = match command{
+            Command::State => self.state(cpu),
+            #[cfg(feature = "serde")]
+            Command::Snapshot => json!({ "cpu": cpu, "machine": machine.state() }),
+            Command::Memory { address, length } => {
+                let address = self.location(&address)?;
+                let length = length.unwrap_or(64).min(0x10000 - address as u32);
+                // device registers are left alone rather than disturbed by a read
+                let bytes = (0..length).map(|offset| machine.peek(address.wrapping_add(offset as u16))).collect::<Vec<Option<u8>>>();
+                json!({ "address": address, "bytes": bytes })
+            },
+            Command::Disassemble { address, count } => {
+                let mut address = match address{
+                    Some(address) => self.location(&address)?,
+                    None => cpu.register(Register::PC),
+                };
+                let mut instructions = Vec::new();
+                for _ in 0..count.unwrap_or(10){
+                    let instruction = disassembler::disassemble_with_symbols(address, |a| machine.peek(a), &self.symbols);
+                    instructions.push(json!({ "address": address, "bytes": instruction.bytes, "text": instruction.text }));
+                    address = address.wrapping_add(instruction.bytes.len().max(1) as u16);
+                }
+                json!({ "instructions": instructions })
+            },
+            Command::Breakpoints => self.breakpoints(),
+            Command::Break { address } => {
+                let address = self.location(&address)?;
+                self.breakpoints.insert(address);
+                self.breakpoints()
+            },
+            Command::Delete { address } => {
+                let address = self.location(&address)?;
+                if !self.breakpoints.remove(&address){
+                    return Err(format!("no breakpoint at ${:04X}", address));
+                }
+                self.breakpoints()
+            },
+            Command::Pause => {
+                if !self.paused{
+                    self.pause("pause", cpu);
+                }
+                self.state(cpu)
+            },
+            Command::Run => {
+                self.paused = false;
+                self.steps_left = None;
+                self.state(cpu)
+            },
+            Command::Step { count } => {
+                let count = count.unwrap_or(1);
+                if count == 0{
+                    return Err("step takes a count of at least 1".to_string());
+                }
+                // the instruction at the PC runs as this returns, ahead of the next check
+                self.paused = false;
+                self.steps_left = Some(count - 1);
+                self.state(cpu)
+            },
+            Command::Quit => return Ok((json!({ "quit": true }), Some(DebugAction::Quit))),
+        };
+        Ok((answer, None))
+    }
+
+    fn location(&self, location: &Location) -> Result<u16, String>{
+        match location{
+            Location::Address(address) => Ok(*address),
+            Location::Text(text) => parse_location(text, &self.symbols).ok_or(format!("`{}` is neither an address nor a label", text)),
+        }
+    }
+
+    fn state(&self, cpu: &W65C02S) -> Value{
+        json!({
+            "paused": self.paused,
+            "registers": {
+                "pc": cpu.register(Register::PC),
+                "a": cpu.register(Register::A),
+                "x": cpu.register(Register::X),
+                "y": cpu.register(Register::Y),
+                "sp": cpu.register(Register::SP),
+                "p": cpu.register(Register::P),
+            },
+            "cycles": cpu.cycles(),
+            "instructions": cpu.instructions(),
+        })
+    }
+    fn breakpoints(&self) -> Value{
+        json!({ "breakpoints": self.breakpoints })
+    }
+}
+
+/// Sends a command to the run and waits for its answer.
+fn ask(sender: &Sender<Request>, command: Command) -> Value{
+    let (reply, answer) = mpsc::channel();
+    if sender.send(Request::Command(command, reply)).is_err(){
+        return json!({ "error": "the run has ended" });
+    }
+    answer.recv().unwrap_or(json!({ "error": "the run has ended" }))
+}
+
+fn connection(stream: TcpStream, sender: Sender<Request>) -> io::Result<()>{
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = HashMap::new();
+    loop{
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty(){
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':'){
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (method, target) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query.split('&').filter_map(|pair| pair.split_once('=')).collect::<HashMap<&str, &str>>();
+
+    if method == "GET" && path == "/ws"{
+        let key = headers.get("sec-websocket-key").ok_or(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket handshake"))?;
+        return websocket(stream, key, sender);
+    }
+    let (status, answer) = match route(method, path, &query){
+        Ok(command) => {
+            let answer = ask(&sender, command);
+            let status = if answer.get("error").is_some() { "400 Bad Request" } else { "200 OK" };
+            (status, answer)
+        },
+        Err(status) => (status, json!({ "error": status })),
+    };
+    let body = answer.to_string();
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)?;
+    stream.flush()
+}
+
+/// The command an HTTP request asks for, or the status that turns it down.
+fn route(method: &str, path: &str, query: &HashMap<&str, &str>) -> Result<Command, &'static str>{
+    let address = || query.get("address").map(|text| Location::Text(text.to_string()));
+    let number = |name: &str| query.get(name).map(|text| text.parse().map_err(|_| "400 Bad Request")).transpose();
+    let command = match (method, path){
+        ("GET", "/state") => Command::State,
+        ("GET", "/memory") => Command::Memory { address: address().ok_or("400 Bad Request")?, length: number("length")? },
+        ("GET", "/disassemble") => Command::Disassemble { address: address(), count: number("count")? },
+        ("GET", "/breakpoints") => Command::Breakpoints,
+        ("POST", "/breakpoints") => Command::Break { address: address().ok_or("400 Bad Request")? },
+        ("DELETE", "/breakpoints") => Command::Delete { address: address().ok_or("400 Bad Request")? },
+        ("POST", "/pause") => Command::Pause,
+        ("POST", "/run") => Command::Run,
+        ("POST", "/step") => Command::Step { count: number("count")?.map(u64::from) },
+        ("POST", "/quit") => Command::Quit,
+        #[cfg(feature = "serde")]
+        ("GET", "/snapshot") => Command::Snapshot,
+        (_, "/state" | "/memory" | "/disassemble" | "/breakpoints" | "/pause" | "/run" | "/step" | "/quit") => return Err("405 Method Not Allowed"),
+        _ => return Err("404 Not Found"),
+    };
+    Ok(command)
+}
+
+fn websocket(mut stream: TcpStream, key: &str, sender: Sender<Request>) -> io::Result<()>{
+    write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes()))?;
+    let (subscriber, events) = mpsc::channel();
+    if sender.send(Request::Subscribe(subscriber)).is_err(){
+        return Ok(());
+    }
+
+    // reads time out now and then to pass on the events that came in meanwhile
+    stream.set_read_timeout(Some(Remote::EVENT_LATENCY))?;
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    loop{
+        match socket.read(){
+            Ok(Message::Text(text)) => {
+                let answer = match serde_json::from_str::<Command>(&text){
+                    Ok(command) => ask(&sender, command),
+                    Err(e) => json!({ "error": e.to_string() }),
+                };
+                socket.send(Message::text(answer.to_string())).map_err(io::Error::other)?;
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => (),
+            Err(e) => return Err(io::Error::other(e)),
+        }
+        while let Ok(event) = events.try_recv(){
+            socket.send(Message::text(event.to_string())).map_err(io::Error::other)?;
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cpu::limits::LimitExceeded;
+use crate::cpu::w65c02s::{Arbitration, Interrupt, Register, Status, W65C02S};
+
+/// Why a run ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum StopReason{
+    Brk,
+    HaltAddress{ address: u16 },
+    InstructionLimit{ limit: u64 },
+    CycleLimit{ limit: u64 },
+    TimeLimit{ milliseconds: u64 },
+    Exit{ code: u8 },           // asked for through the host services
+    Result{ pass: bool },       // the program wrote its verdict
+    WindowClosed,
+    Quit,                       // told to by the debugger
+    Script,                     // told to by the script, without a verdict
+    Diverged,                   // from the run its fingerprints were checked against
+}
+impl fmt::Display for StopReason{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            StopReason::Brk => write!(f, "BRK"),
+            StopReason::HaltAddress { address } => write!(f, "halted at ${:04X}", address),
+            StopReason::InstructionLimit { .. } => write!(f, "instruction limit"),
+            StopReason::CycleLimit { .. } => write!(f, "cycle limit"),
+            StopReason::TimeLimit { .. } => write!(f, "timeout"),
+            StopReason::Exit { code } => write!(f, "exit {}", code),
+            StopReason::Result { pass: true } => write!(f, "result, pass"),
+            StopReason::Result { pass: false } => write!(f, "result, fail"),
+            StopReason::WindowClosed => write!(f, "window closed"),
+            StopReason::Quit => write!(f, "quit"),
+            StopReason::Script => write!(f, "script"),
+            StopReason::Diverged => write!(f, "diverged"),
+        }
+    }
+}
+impl From<LimitExceeded> for StopReason{
+    fn from(exceeded: LimitExceeded) -> Self {
+        match exceeded{
+            LimitExceeded::Instructions(limit) => StopReason::InstructionLimit { limit },
+            LimitExceeded::Cycles(limit) => StopReason::CycleLimit { limit },
+            LimitExceeded::Time(timeout) => StopReason::TimeLimit { milliseconds: timeout.as_millis() as u64 },
+        }
+    }
+}
+
+/// The state a run ended in, for harnesses that parse outcomes rather than read them.
+#[derive(Debug, Serialize)]
+pub struct Report{
+    image: String,
+    stop: StopReason,
+    registers: Registers,
+    flags: Flags,
+    cycles: u64,
+    instructions: u64,
+    memory: Vec<MemoryHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interrupts: Option<InterruptCounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,              // of everything random in the run
+}
+
+/// The interrupts the CPU took over a run, and those it passed over at the same boundary for one that
+/// outranked them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct InterruptCounts{
+    taken: PerInterrupt,
+    outranked: PerInterrupt,
+}
+impl InterruptCounts{
+    pub fn record(&mut self, arbitration: &Arbitration){
+        self.taken.count(arbitration.taken);
+        for interrupt in arbitration.outranked(){
+            self.outranked.count(interrupt);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+struct PerInterrupt{
+    reset: u64,
+    nmi: u64,
+    irq: u64,
+}
+impl PerInterrupt{
+    fn count(&mut self, interrupt: Interrupt){
+        match interrupt{
+            Interrupt::Reset => self.reset += 1,
+            Interrupt::Nmi => self.nmi += 1,
+            Interrupt::Irq => self.irq += 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Registers{
+    pc: u16,
+    a: u8,
+    x: u8,
+    y: u8,
+    sp: u8,
+    p: u8,
+}
+
+#[derive(Debug, Serialize)]
+struct Flags{
+    n: bool,
+    v: bool,
+    b: bool,
+    d: bool,
+    i: bool,
+    z: bool,
+    c: bool,
+}
+
+/// CRC-32 of a stretch of memory, the same checksum zlib and `crc32` compute.
+#[derive(Debug, Serialize)]
+struct MemoryHash{
+    name: String,
+    start: u16,
+    length: usize,
+    crc32: u32,
+}
+
+impl Report{
+    pub fn new(image: &str, stop: StopReason, cpu: &W65C02S) -> Self{
+        let byte = |register| cpu.register(register) as u8;
+        Self {
+            image: image.to_string(),
+            stop,
+            registers: Registers {
+                pc: cpu.register(Register::PC),
+                a: byte(Register::A), x: byte(Register::X), y: byte(Register::Y),
+                sp: byte(Register::SP), p: byte(Register::P),
+            },
+            flags: Flags {
+                n: cpu.status_check(Status::N), v: cpu.status_check(Status::V), b: cpu.status_check(Status::B),
+                d: cpu.status_check(Status::D), i: cpu.status_check(Status::I), z: cpu.status_check(Status::Z),
+                c: cpu.status_check(Status::C),
+            },
+            cycles: cpu.cycles(),
+            instructions: cpu.instructions(),
+            memory: Vec::new(),
+            interrupts: None,
+            seed: None,
+        }
+    }
+    /// Adds the hash of `bytes`, which start at `start`.
+    pub fn with_memory(mut self, name: &str, start: u16, bytes: &[u8]) -> Self{
+        self.memory.push(MemoryHash { name: name.to_string(), start, length: bytes.len(), crc32: crc32(bytes) });
+        self
+    }
+
+    pub fn with_interrupts(mut self, interrupts: InterruptCounts) -> Self{
+        self.interrupts = Some(interrupts);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self{
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()>{
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json + "\n")
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32{
+    let mut crc = !0u32;
+    for &byte in bytes{
+        crc ^= byte as u32;
+        for _ in 0..8{
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
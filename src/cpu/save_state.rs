@@ -0,0 +1,384 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bus::bus::{BusError, Machine, MemoryDifference};
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+const MAGIC: &[u8; 8] = b"S6502ST\0";
+const VERSION: u8 = 2;
+
+/// The CPU, RAM and devices of a running machine, enough to pick a run up again on a machine built the same
+/// way. The devices are saved with the `serde` feature, as `Machine::device_states` gives them; without it a
+/// state is only restored on a machine with no devices.
+///
+/// The file holds a magic number, a version byte, PC, A, X, Y, SP and P, the cycle and instruction counts,
+/// the RAM with its length, and the device states as JSON with their length, 0 when they were not saved.
+/// Multi-byte values are little-endian. Version 1 files, from before the device states, are still read.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SaveState{
+    registers: [u16; 6],    // in the order of REGISTERS
+    cycles: u64,
+    instructions: u64,
+    ram: Box<[u8]>,
+    #[cfg(feature = "serde")]
+    devices: Option<Vec<Option<serde_json::Value>>>,    // None when the file was written without them
+}
+
+/// Why a state could not be put back on a machine.
+#[derive(Debug)]
+pub enum RestoreError{
+    Bus(BusError),
+    DevicesNotSaved,                    // the machine has devices with state of their own that the file lacks
+    #[cfg(feature = "serde")]
+    DeviceState(serde_json::Error),     // the devices saved do not match those attached
+}
+impl fmt::Display for RestoreError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            RestoreError::Bus(e) => write!(f, "{}", e),
+            RestoreError::DevicesNotSaved => write!(f, "the save state does not hold the state of the machine's devices"),
+            #[cfg(feature = "serde")]
+            RestoreError::DeviceState(e) => write!(f, "the save state's devices do not match the machine's: {}", e),
+        }
+    }
+}
+impl Error for RestoreError{}
+
+const REGISTERS: [Register; 6] = [Register::PC, Register::A, Register::X, Register::Y, Register::SP, Register::P];
+
+impl SaveState{
+    pub fn capture(cpu: &W65C02S, machine: &Machine) -> Self{
+        Self {
+            registers: REGISTERS.map(|register| cpu.register(register)),
+            cycles: cpu.cycles(),
+            instructions: cpu.instructions(),
+            ram: machine.ram_contents(),
+            #[cfg(feature = "serde")]
+            devices: Some(machine.device_states()),
+        }
+    }
+    /// Puts the CPU, RAM and devices back as they were captured.
+    pub fn restore(&self, cpu: &mut W65C02S, machine: &mut Machine) -> Result<(), RestoreError>{
+        self.restore_devices(machine)?;
+        machine.load_ram(&self.ram).map_err(RestoreError::Bus)?;
+        for (register, value) in REGISTERS.into_iter().zip(self.registers){
+            cpu.set_register(register, value);
+        }
+        cpu.set_counts(self.cycles, self.instructions);
+        Ok(())
+    }
+    #[cfg(feature = "serde")]
+    fn restore_devices(&self, machine: &mut Machine) -> Result<(), RestoreError>{
+        match &self.devices{
+            Some(states) => machine.restore_device_states(states.clone()).map_err(RestoreError::DeviceState),
+            // devices that keep nothing of their own lose nothing by starting over
+            None if machine.device_states().iter().any(Option::is_some) => Err(RestoreError::DevicesNotSaved),
+            None => Ok(()),
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    fn restore_devices(&self, machine: &mut Machine) -> Result<(), RestoreError>{
+        match machine.has_devices(){
+            true => Err(RestoreError::DevicesNotSaved),
+            false => Ok(()),
+        }
+    }
+    /// Everything that differs from `expected`: the registers, the counts and each byte of RAM.
+    pub fn differences(&self, expected: &SaveState) -> Vec<StateDifference>{
+        let mut differences = REGISTERS.into_iter().zip(self.registers.into_iter().zip(expected.registers))
+            .filter(|(_, (actual, expected))| actual != expected)
+            .map(|(register, (actual, expected))| StateDifference::Register { register, actual, expected })
+            .collect::<Vec<StateDifference>>();
+        if self.cycles != expected.cycles{
+            differences.push(StateDifference::Cycles { actual: self.cycles, expected: expected.cycles });
+        }
+        if self.instructions != expected.instructions{
+            differences.push(StateDifference::Instructions { actual: self.instructions, expected: expected.instructions });
+        }
+        if self.ram.len() != expected.ram.len(){
+            differences.push(StateDifference::RamSize { actual: self.ram.len(), expected: expected.ram.len() });
+            return differences;
+        }
+        differences.extend(self.ram.iter().zip(&expected.ram).enumerate()
+            .filter(|(_, (actual, expected))| actual != expected)
+            .map(|(address, (&actual, &expected))| StateDifference::Memory(MemoryDifference { address: address as u16, expected: Some(expected), actual: Some(actual) })));
+        #[cfg(feature = "serde")]
+        if let (Some(actual), Some(expected)) = (&self.devices, &expected.devices){
+            differences.extend(device_differences(actual, expected));
+        }
+        differences
+    }
+
+    /// Writes the state to a file beside `path` and then renames it into place, so a crash part way through
+    /// leaves the last state whole.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()>{
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, self.to_bytes()?)?;
+        fs::rename(partial, path)
+    }
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self>{
+        let bytes = fs::read(path)?;
+        parse(&bytes).ok_or(io::Error::new(io::ErrorKind::InvalidData, "not a save state"))
+    }
+    fn to_bytes(&self) -> io::Result<Vec<u8>>{
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.registers[0].to_le_bytes());
+        bytes.extend(self.registers[1..].iter().map(|&r| r as u8));
+        bytes.extend_from_slice(&self.cycles.to_le_bytes());
+        bytes.extend_from_slice(&self.instructions.to_le_bytes());
+        bytes.extend_from_slice(&(self.ram.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.ram);
+        #[cfg(feature = "serde")]
+        let devices = match &self.devices{
+            Some(devices) => serde_json::to_vec(devices)?,
+            None => Vec::new(),
+        };
+        #[cfg(not(feature = "serde"))]
+        let devices = Vec::new();
+        bytes.extend_from_slice(&(devices.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&devices);
+        Ok(bytes)
+    }
+}
+
+/// The devices that differ, by each field of their state where both are objects and whole otherwise.
+#[cfg(feature = "serde")]
+fn device_differences(actual: &[Option<serde_json::Value>], expected: &[Option<serde_json::Value>]) -> Vec<StateDifference>{
+    use serde_json::Value;
+
+    if actual.len() != expected.len(){
+        return vec![StateDifference::DeviceCount { actual: actual.len(), expected: expected.len() }];
+    }
+    let mut differences = Vec::new();
+    for (device, (actual, expected)) in actual.iter().zip(expected).enumerate(){
+        match (actual, expected){
+            (Some(Value::Object(actual)), Some(Value::Object(expected))) => {
+                let fields = actual.keys().chain(expected.keys().filter(|key| !actual.contains_key(*key)));
+                differences.extend(fields.filter(|field| actual.get(*field) != expected.get(*field)).map(|field| StateDifference::Device {
+                    device,
+                    field: Some(field.clone()),
+                    actual: actual.get(field).cloned().unwrap_or_default(),
+                    expected: expected.get(field).cloned().unwrap_or_default(),
+                }));
+            },
+            _ if actual != expected => differences.push(StateDifference::Device {
+                device,
+                field: None,
+                actual: actual.clone().unwrap_or_default(),
+                expected: expected.clone().unwrap_or_default(),
+            }),
+            _ => {},
+        }
+    }
+    differences
+}
+
+/// Something a state has other than the golden state it is checked against.
+#[derive(Debug)]
+pub enum StateDifference{
+    Register{ register: Register, actual: u16, expected: u16 },
+    Cycles{ actual: u64, expected: u64 },
+    Instructions{ actual: u64, expected: u64 },
+    RamSize{ actual: usize, expected: usize },
+    Memory(MemoryDifference),
+    #[cfg(feature = "serde")]
+    DeviceCount{ actual: usize, expected: usize },
+    #[cfg(feature = "serde")]
+    Device{ device: usize, field: Option<String>, actual: serde_json::Value, expected: serde_json::Value },  // by the order attached
+}
+impl fmt::Display for StateDifference{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            StateDifference::Register { register: Register::PC, actual, expected } => write!(f, "PC is ${:04X}, expected ${:04X}", actual, expected),
+            StateDifference::Register { register, actual, expected } => write!(f, "{:?} is ${:02X}, expected ${:02X}", register, actual, expected),
+            StateDifference::Cycles { actual, expected } => write!(f, "ran {} cycles, expected {}", actual, expected),
+            StateDifference::Instructions { actual, expected } => write!(f, "ran {} instructions, expected {}", actual, expected),
+            StateDifference::RamSize { actual, expected } => write!(f, "RAM is {} bytes, expected {}", actual, expected),
+            StateDifference::Memory(difference) => write!(f, "{}", difference),
+            #[cfg(feature = "serde")]
+            StateDifference::DeviceCount { actual, expected } => write!(f, "{} devices, expected {}", actual, expected),
+            #[cfg(feature = "serde")]
+            StateDifference::Device { device, field: Some(field), actual, expected } => write!(f, "device {} {} is {}, expected {}", device, field, actual, expected),
+            #[cfg(feature = "serde")]
+            StateDifference::Device { device, field: None, actual, expected } => write!(f, "device {} is {}, expected {}", device, actual, expected),
+        }
+    }
+}
+
+fn parse(bytes: &[u8]) -> Option<SaveState>{
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (&version, rest) = rest.split_first()?;
+    if version != 1 && version != VERSION{
+        return None;
+    }
+    let (pc, rest) = rest.split_first_chunk::<2>()?;
+    let (registers, rest) = rest.split_first_chunk::<5>()?;
+    let (cycles, rest) = rest.split_first_chunk::<8>()?;
+    let (instructions, rest) = rest.split_first_chunk::<8>()?;
+    let (length, rest) = rest.split_first_chunk::<4>()?;
+    let (ram, rest) = rest.split_at_checked(u32::from_le_bytes(*length) as usize)?;
+    // version 1 ends with the RAM, later ones go on to the device states
+    let devices = match version{
+        1 => rest.is_empty().then_some(&[][..])?,
+        _ => {
+            let (length, devices) = rest.split_first_chunk::<4>()?;
+            (devices.len() == u32::from_le_bytes(*length) as usize).then_some(devices)?
+        },
+    };
+    #[cfg(feature = "serde")]
+    let devices = match devices.is_empty(){
+        true => None,
+        false => Some(serde_json::from_slice(devices).ok()?),
+    };
+    // without serde the device states cannot be read, and `restore` refuses a machine with devices
+    #[cfg(not(feature = "serde"))]
+    let _ = devices;
+
+    let [a, x, y, sp, p] = registers.map(u16::from);
+    Some(SaveState {
+        registers: [u16::from_le_bytes(*pc), a, x, y, sp, p],
+        cycles: u64::from_le_bytes(*cycles),
+        instructions: u64::from_le_bytes(*instructions),
+        ram: ram.into(),
+        #[cfg(feature = "serde")]
+        devices,
+    })
+}
+
+/// How often `Autosave` saves.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AutosaveInterval{
+    Wall(Duration),
+    Cycles(u64),
+}
+
+/// Saves the state every so often, rotating through a few files so that one being written when the host goes
+/// down still leaves the ones before it.
+pub struct Autosave{
+    interval: AutosaveInterval,
+    files: Vec<PathBuf>,
+    next_file: usize,
+    last: Instant,
+    next_cycles: u64,
+}
+impl Autosave{
+    /// Files kept before the oldest is overwritten.
+    pub const FILES: usize = 3;
+    /// Instructions between looks at the clock.
+    const CHECK_EVERY: u64 = 4096;
+
+    /// Saves to `<stem>_autosave_<n>.state` in `directory`.
+    pub fn new(interval: AutosaveInterval, directory: &Path, stem: &str, cycles: u64) -> Self{
+        let files = (0..Self::FILES).map(|n| directory.join(format!("{}_autosave_{}.state", stem, n))).collect::<Vec<PathBuf>>();
+        // carry on from the oldest, so a restarted run does not overwrite its latest save first
+        let next_file = (0..files.len())
+            .min_by_key(|&n| fs::metadata(&files[n]).and_then(|m| m.modified()).ok())
+            .unwrap_or(0);
+        let next_cycles = match interval{
+            AutosaveInterval::Cycles(every) => cycles + every,
+            AutosaveInterval::Wall(_) => 0,
+        };
+        Self { interval, files, next_file, last: Instant::now(), next_cycles }
+    }
+
+    /// Call after each step of the CPU.
+    pub fn tick(&mut self, cpu: &W65C02S, machine: &Machine) -> io::Result<()>{
+        let due = match self.interval{
+            AutosaveInterval::Cycles(every) => {
+                let due = cpu.cycles() >= self.next_cycles;
+                if due{
+                    self.next_cycles = cpu.cycles() + every;
+                }
+                due
+            },
+            AutosaveInterval::Wall(every) => {
+                let due = cpu.instructions().is_multiple_of(Self::CHECK_EVERY) && self.last.elapsed() >= every;
+                if due{
+                    self.last = Instant::now();
+                }
+                due
+            },
+        };
+        if !due{
+            return Ok(());
+        }
+
+        let file = self.next_file;
+        self.next_file = (file + 1) % self.files.len();
+        SaveState::capture(cpu, machine).write(&self.files[file])
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::bus::bus::Bus;
+    use crate::devices::cycle_counter::CycleCounter;
+    #[cfg(feature = "serde")]
+    use crate::devices::device::DeviceId;
+
+    fn counting_machine() -> Machine{
+        let mut machine = Machine::with_ram(0x80);
+        machine.attach_device(0xf100..=0xf107, CycleCounter::new());
+        machine
+    }
+
+    #[test]
+    fn resumes_the_cpu_and_ram(){
+        let (mut cpu, mut machine) = (W65C02S::default(), Machine::with_ram(0x80));
+        cpu.set_register(Register::A, 0x42);
+        machine.write(0x0200, 0x99).unwrap();
+        let bytes = SaveState::capture(&cpu, &machine).to_bytes().unwrap();
+
+        let (mut cpu, mut machine) = (W65C02S::default(), Machine::with_ram(0x80));
+        parse(&bytes).unwrap().restore(&mut cpu, &mut machine).unwrap();
+        assert_eq!(cpu.register(Register::A), 0x42);
+        assert_eq!(machine.peek(0x0200), Some(0x99));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn resumes_the_devices(){
+        let (cpu, mut machine) = (W65C02S::default(), counting_machine());
+        machine.tick(1000);
+        let bytes = SaveState::capture(&cpu, &machine).to_bytes().unwrap();
+
+        let (mut cpu, mut machine) = (W65C02S::default(), counting_machine());
+        parse(&bytes).unwrap().restore(&mut cpu, &mut machine).unwrap();
+        assert_eq!(machine.device::<CycleCounter>(DeviceId(0)).map(CycleCounter::cycles), Some(1000));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn golden_states_compare_the_devices(){
+        let (cpu, mut machine) = (W65C02S::default(), counting_machine());
+        let expected = SaveState::capture(&cpu, &machine);
+        machine.tick(5);
+
+        let differences = SaveState::capture(&cpu, &machine).differences(&expected).iter().map(ToString::to_string).collect::<Vec<String>>();
+        assert_eq!(differences, ["device 0 cycles is 5, expected 0"]);
+    }
+
+    #[test]
+    fn refuses_a_machine_whose_devices_were_not_saved(){
+        // a version 1 file, from before the device states
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(1);
+        bytes.extend_from_slice(&[0; 2 + 5 + 8 + 8]);
+        bytes.extend_from_slice(&0x8000u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 0x8000]);
+        let state = parse(&bytes).unwrap();
+
+        assert!(matches!(state.restore(&mut W65C02S::default(), &mut counting_machine()), Err(RestoreError::DevicesNotSaved)));
+        assert!(state.restore(&mut W65C02S::default(), &mut Machine::with_ram(0x80)).is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+
+use crate::bus::access_log::{AccessKind, AccessLogger, BusTransaction};
+use crate::bus::bus::Machine;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// How a script ended the run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScriptStop{
+    Stop,
+    Pass,
+    Fail,
+}
+
+/// A Rhai script driving a run. At its top level it says what it wants to hear about, with
+/// `breakpoint(addr)`, `watch_writes(first, last)` and `every(cycles)`; the run then calls the functions it
+/// defines for them, `on_breakpoint(pc)` before the instruction at a breakpoint, `on_write(address, value)`
+/// after an instruction wrote to a watched range, and `on_tick(cycles)` every so many cycles.
+///
+/// The callbacks can read and change the machine with `peek(addr)`, `poke(addr, value)`, `reg(name)`,
+/// `set_reg(name, value)`, `cycles()` and `instructions()`, and end the run with `stop()`, `pass()` or `fail()`.
+/// Registers are named `A`, `X`, `Y`, `SP`, `P` and `PC`. A byte that cannot be read without side effects, such
+/// as a device register, peeks as -1.
+pub struct Script{
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Rc<RefCell<ScriptState>>,
+    callbacks: Callbacks,
+}
+
+#[derive(Default)]
+struct ScriptState{
+    breakpoints: BTreeSet<u16>,
+    watched: Vec<RangeInclusive<u16>>,
+    writes: Vec<(u16, u8)>,             // to watched ranges, since the callbacks last heard of them
+    every: Option<u64>,                 // cycles between ticks
+    next_tick: u64,
+    stop: Option<ScriptStop>,
+    guest: Option<Guest>,
+}
+
+/// The CPU and machine, lent to the script for the length of one callback.
+#[derive(Copy, Clone)]
+struct Guest{
+    cpu: *mut W65C02S,
+    machine: *mut Machine,
+}
+
+#[derive(Copy, Clone)]
+struct Callbacks{
+    breakpoint: bool,
+    write: bool,
+    tick: bool,
+}
+
+/// A script that could not be loaded or failed in a callback.
+#[derive(Debug)]
+pub struct ScriptError(String);
+impl fmt::Display for ScriptError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl From<Box<EvalAltResult>> for ScriptError{
+    fn from(error: Box<EvalAltResult>) -> Self {
+        Self(error.to_string())
+    }
+}
+
+impl Script{
+    /// Compiles the script and runs its top level.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScriptError>{
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let engine = engine(&state);
+        let ast = engine.compile_file(path.as_ref().to_path_buf())?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+
+        let defines = |name: &str, params: usize| ast.iter_functions().any(|f| f.name == name && f.params.len() == params);
+        let callbacks = Callbacks {
+            breakpoint: defines("on_breakpoint", 1),
+            write: defines("on_write", 2),
+            tick: defines("on_tick", 1),
+        };
+        Ok(Self { engine, ast, scope, state, callbacks })
+    }
+
+    /// Lets the script see writes to the ranges it watches. Call once, on the machine it runs with.
+    pub fn attach(&self, machine: &mut Machine){
+        if !self.callbacks.write{
+            return;
+        }
+        let state = Rc::clone(&self.state);
+        machine.attach_logger(AccessLogger::new(move |transaction: &BusTransaction| {
+            let mut state = state.borrow_mut();
+            if transaction.kind == AccessKind::Write && state.watched.iter().any(|range| range.contains(&transaction.address)){
+                state.writes.push((transaction.address, transaction.value));
+            }
+        }));
+    }
+
+    /// Call before each step of the CPU. Some when the script ended the run.
+    pub fn before(&mut self, cpu: &mut W65C02S, machine: &mut Machine) -> Result<Option<ScriptStop>, ScriptError>{
+        let pc = cpu.register(Register::PC);
+        if self.callbacks.breakpoint && self.state.borrow().breakpoints.contains(&pc){
+            self.call(cpu, machine, "on_breakpoint", (pc as i64,))?;
+        }
+        Ok(self.state.borrow_mut().stop.take())
+    }
+
+    /// Call after each step of the CPU. Some when the script ended the run.
+    pub fn after(&mut self, cpu: &mut W65C02S, machine: &mut Machine) -> Result<Option<ScriptStop>, ScriptError>{
+        let writes = std::mem::take(&mut self.state.borrow_mut().writes);
+        for (address, value) in writes{
+            self.call(cpu, machine, "on_write", (address as i64, value as i64))?;
+        }
+
+        let due = {
+            let mut state = self.state.borrow_mut();
+            match state.every{
+                Some(every) if self.callbacks.tick && cpu.cycles() >= state.next_tick => {
+                    state.next_tick = cpu.cycles() + every;
+                    true
+                },
+                _ => false,
+            }
+        };
+        if due{
+            self.call(cpu, machine, "on_tick", (cpu.cycles() as i64,))?;
+        }
+        Ok(self.state.borrow_mut().stop.take())
+    }
+
+    fn call(&mut self, cpu: &mut W65C02S, machine: &mut Machine, name: &str, args: impl rhai::FuncArgs) -> Result<(), ScriptError>{
+        self.state.borrow_mut().guest = Some(Guest { cpu, machine });
+        // the top level ran once, at load; running it again would undo what the callbacks changed
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args);
+        self.state.borrow_mut().guest = None;
+        result.map(|_| ()).map_err(ScriptError::from)
+    }
+}
+
+fn register(name: &str) -> Result<Register, Box<EvalAltResult>>{
+    match name.to_ascii_uppercase().as_str(){
+        "A" => Ok(Register::A),
+        "X" => Ok(Register::X),
+        "Y" => Ok(Register::Y),
+        "SP" => Ok(Register::SP),
+        "P" => Ok(Register::P),
+        "PC" => Ok(Register::PC),
+        _ => Err(format!("no register named `{}`", name).into()),
+    }
+}
+
+fn address(value: i64) -> Result<u16, Box<EvalAltResult>>{
+    u16::try_from(value).map_err(|_| format!("{} is not an address", value).into())
+}
+
+/// Runs `f` on the CPU and machine lent to the current callback.
+fn with_guest<T>(state: &Rc<RefCell<ScriptState>>, f: impl FnOnce(&mut W65C02S, &mut Machine) -> T) -> Result<T, Box<EvalAltResult>>{
+    let guest = state.borrow().guest.ok_or("the machine can only be used from a callback")?;
+    // SAFETY: `Script::call` sets the guest from the borrows it holds and clears it before they end, and Rhai
+    // calls these functions only from within that call, one at a time.
+    Ok(unsafe { f(&mut *guest.cpu, &mut *guest.machine) })
+}
+
+fn engine(state: &Rc<RefCell<ScriptState>>) -> Engine{
+    let mut engine = Engine::new();
+
+    let s = Rc::clone(state);
+    engine.register_fn("breakpoint", move |address: i64| -> Result<(), Box<EvalAltResult>> {
+        s.borrow_mut().breakpoints.insert(self::address(address)?);
+        Ok(())
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("watch_writes", move |first: i64, last: i64| -> Result<(), Box<EvalAltResult>> {
+        s.borrow_mut().watched.push(address(first)?..=address(last)?);
+        Ok(())
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("every", move |cycles: i64| -> Result<(), Box<EvalAltResult>> {
+        let cycles = u64::try_from(cycles).ok().filter(|c| *c > 0).ok_or("`every` takes a positive number of cycles")?;
+        let mut state = s.borrow_mut();
+        state.every = Some(cycles);
+        state.next_tick = cycles;
+        Ok(())
+    });
+
+    let s = Rc::clone(state);
+    engine.register_fn("peek", move |address: i64| -> Result<i64, Box<EvalAltResult>> {
+        let address = self::address(address)?;
+        with_guest(&s, |_, machine| machine.peek(address).map_or(-1, i64::from))
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("poke", move |address: i64, value: i64| -> Result<(), Box<EvalAltResult>> {
+        let address = self::address(address)?;
+        let value = u8::try_from(value).map_err(|_| format!("{} is not a byte", value))?;
+        with_guest(&s, |_, machine| machine.poke(address, value))?.map_err(|e| format!("{:?}", e).into())
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("reg", move |name: &str| -> Result<i64, Box<EvalAltResult>> {
+        let register = register(name)?;
+        with_guest(&s, |cpu, _| cpu.register(register) as i64)
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("set_reg", move |name: &str, value: i64| -> Result<(), Box<EvalAltResult>> {
+        let register = register(name)?;
+        let value = u16::try_from(value).map_err(|_| format!("{} does not fit a register", value))?;
+        with_guest(&s, |cpu, _| cpu.set_register(register, value))
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("cycles", move || with_guest(&s, |cpu, _| cpu.cycles() as i64));
+    let s = Rc::clone(state);
+    engine.register_fn("instructions", move || with_guest(&s, |cpu, _| cpu.instructions() as i64));
+
+    for (name, stop) in [("stop", ScriptStop::Stop), ("pass", ScriptStop::Pass), ("fail", ScriptStop::Fail)]{
+        let s = Rc::clone(state);
+        engine.register_fn(name, move || s.borrow_mut().stop = Some(stop));
+    }
+    engine
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::cpu::w65c02s::W65C02S;
+
+/// Measures how fast the emulator runs a program against the wall clock, for a summary at the end of a run and,
+/// when live, a status line on stderr redrawn every second.
+pub struct SpeedMeter{
+    started: Sample,            // the counts when the meter was made, which a resumed run does not start from zero
+    live: Option<Sample>,       // the counts at the last redraw of the status line
+}
+
+#[derive(Copy, Clone)]
+struct Sample{
+    at: Instant,
+    instructions: u64,
+    cycles: u64,
+}
+
+impl SpeedMeter{
+    /// Instructions between looks at the clock, which keeps the overhead of a live line down.
+    const CHECK_EVERY: u64 = 4096;
+    const REDRAW: Duration = Duration::from_secs(1);
+
+    pub fn new(cpu: &W65C02S) -> Self{
+        Self { started: Sample::now(cpu), live: None }
+    }
+    pub fn live(mut self) -> Self{
+        self.live = Some(self.started);
+        self
+    }
+
+    /// Call after each step of the CPU.
+    pub fn tick(&mut self, cpu: &W65C02S){
+        let Some(last) = self.live else { return };
+        if !cpu.instructions().is_multiple_of(Self::CHECK_EVERY) || last.at.elapsed() < Self::REDRAW{
+            return;
+        }
+
+        let now = Sample::now(cpu);
+        let speed = Speed::between(last, now);
+        // a status line that cannot be written is not worth stopping the machine for
+        let _ = write!(io::stderr(), "\r\x1b[K{:.0}s: {:.0} instructions/s, {:.3} MHz",
+            self.started.at.elapsed().as_secs_f64(), speed.instructions_per_second(), speed.megahertz());
+        self.live = Some(now);
+    }
+
+    /// The speed over the whole run, ending the status line when there is one.
+    pub fn finish(&self, cpu: &W65C02S) -> Speed{
+        if self.live.is_some(){
+            let _ = write!(io::stderr(), "\r\x1b[K");
+        }
+        Speed::between(self.started, Sample::now(cpu))
+    }
+}
+
+impl Sample{
+    fn now(cpu: &W65C02S) -> Self{
+        Self { at: Instant::now(), instructions: cpu.instructions(), cycles: cpu.cycles() }
+    }
+}
+
+/// Instructions and cycles run over a stretch of wall time.
+#[derive(Copy, Clone, Debug)]
+pub struct Speed{
+    pub instructions: u64,
+    pub cycles: u64,
+    pub elapsed: Duration,
+}
+impl Speed{
+    fn between(first: Sample, last: Sample) -> Self{
+        Self {
+            instructions: last.instructions - first.instructions,
+            cycles: last.cycles - first.cycles,
+            elapsed: last.at - first.at,
+        }
+    }
+    pub fn instructions_per_second(&self) -> f64{
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+    /// The clock rate the CPU would need to run the cycles in the same time.
+    pub fn megahertz(&self) -> f64{
+        self.cycles as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE) / 1_000_000.0
+    }
+}
+impl fmt::Display for Speed{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instructions, {} cycles in {:.3}s: {:.0} instructions/s, {:.3} MHz effective",
+            self.instructions, self.cycles, self.elapsed.as_secs_f64(), self.instructions_per_second(), self.megahertz())
+    }
+}
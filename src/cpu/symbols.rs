@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Names for addresses, read from the label files assemblers and linkers write alongside a ROM.
+///
+/// Understands VICE label files, which `ld65 -Ln` also writes (`al C:8f3a .print_char`), and plain assignments
+/// (`print_char = $8f3a`, `print_char EQU $8F3A`). Other lines, such as comments, are skipped. Where several
+/// names share an address, the first one read names it.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable{
+    names: HashMap<u16, String>,
+    addresses: HashMap<String, u16>,
+}
+impl SymbolTable{
+    pub fn new() -> Self{
+        Self::default()
+    }
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self>{
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+    pub fn parse(text: &str) -> Self{
+        let mut table = Self::new();
+        for (name, address) in text.lines().filter_map(parse_line){
+            table.insert(name, address);
+        }
+        table
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16){
+        self.names.entry(address).or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+    pub fn name_of(&self, address: u16) -> Option<&str>{
+        self.names.get(&address).map(String::as_str)
+    }
+    /// The symbol at or closest below `address`, and how far past it `address` lies.
+    pub fn nearest(&self, address: u16) -> Option<(&str, u16)>{
+        self.names.iter()
+            .filter(|(named, _)| **named <= address)
+            .max_by_key(|(named, _)| **named)
+            .map(|(named, name)| (name.as_str(), address - named))
+    }
+    /// The name of each named address, in order of address.
+    pub fn names(&self) -> Vec<(u16, &str)>{
+        let mut names = self.names.iter().map(|(address, name)| (*address, name.as_str())).collect::<Vec<(u16, &str)>>();
+        names.sort();
+        names
+    }
+    pub fn address_of(&self, name: &str) -> Option<u16>{
+        self.addresses.get(name).copied()
+    }
+    pub fn is_empty(&self) -> bool{
+        self.addresses.is_empty()
+    }
+}
+
+/// Accepts `$f001`, `0xf001` and plain `f001`.
+pub fn parse_address(text: &str) -> Option<u16>{
+    let digits = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+/// An address as `parse_address` accepts it, or the name of a symbol.
+pub fn parse_location(text: &str, symbols: &SymbolTable) -> Option<u16>{
+    parse_address(text).or_else(|| symbols.address_of(text))
+}
+
+fn parse_line(line: &str) -> Option<(&str, u16)>{
+    let words = line.split_whitespace().collect::<Vec<&str>>();
+    match words.as_slice(){
+        ["al", address, name] => {
+            let address = address.rsplit(':').next()?;
+            Some((name.strip_prefix('.').unwrap_or(name), parse_value(&format!("${}", address))?))
+        },
+        [name, "=" | "EQU" | "equ", value] => Some((name, parse_value(value)?)),
+        _ => None,
+    }
+}
+
+/// `$8f3a`, `0x8f3a` or decimal. Wider values, such as the banked addresses some linkers write, keep their low
+/// 16 bits.
+fn parse_value(text: &str) -> Option<u16>{
+    let value = match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")){
+        Some(digits) => u32::from_str_radix(digits, 16).ok()?,
+        None => text.parse::<u32>().ok()?,
+    };
+    Some(value as u16)
+}
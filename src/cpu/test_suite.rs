@@ -0,0 +1,316 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use crate::bus::bus::Machine;
+use crate::cpu::limits::RunLimits;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// Runs a directory tree of the standard 65(C)02 test assets:
+///  - Tom Harte's single-step vectors, `*.json` files named after the opcode they test, each test run as one
+///    instruction on a flat 64KB of RAM and checked for its registers, the RAM it lists and its cycle count
+///  - Klaus Dormann's functional tests, 64KB `*.bin` images, run from `$0400` until they trap in a jump or
+///    branch to themselves; they pass when that is the success trap, which is taken from the `.lst` listing
+///    beside the image or given with `with_success`
+pub struct TestSuite{
+    entry: u16,
+    success: Vec<u16>,
+    limits: RunLimits,
+}
+
+/// What a tree of test assets came to, one entry per file, that shows as a pass/fail matrix per opcode.
+pub struct SuiteReport{
+    root: PathBuf,
+    files: Vec<FileResult>,
+}
+
+/// The tests of one file.
+struct FileResult{
+    path: PathBuf,                          // relative to the root of the tree
+    kind: SuiteKind,
+    passed: u64,
+    failed: u64,
+    first_failure: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SuiteKind{
+    Vectors,
+    Binary,
+}
+
+#[derive(Deserialize)]
+struct Vector{
+    name: String,
+    initial: VectorState,
+    #[serde(rename = "final")]
+    expected: VectorState,
+    cycles: Vec<IgnoredAny>,                // one entry per bus cycle
+}
+
+#[derive(Deserialize)]
+struct VectorState{
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+impl TestSuite{
+    /// Where the functional tests start unless told otherwise.
+    pub const DEFAULT_ENTRY: u16 = 0x0400;
+    /// Enough for the functional tests several times over, so that one looping forever still ends.
+    pub const DEFAULT_LIMITS: RunLimits = RunLimits { max_instructions: None, max_cycles: Some(1_000_000_000), timeout: None };
+    const IMAGE_SIZE: u64 = 0x10000;
+
+    pub fn new() -> Self{
+        Self { entry: Self::DEFAULT_ENTRY, success: Vec::new(), limits: Self::DEFAULT_LIMITS }
+    }
+    pub fn with_entry(mut self, entry: u16) -> Self{
+        self.entry = entry;
+        self
+    }
+    /// Traps that count as a pass for every binary, besides the one its listing names.
+    pub fn with_success(mut self, addresses: &[u16]) -> Self{
+        self.success.extend_from_slice(addresses);
+        self
+    }
+    pub fn with_limits(mut self, limits: RunLimits) -> Self{
+        self.limits = limits;
+        self
+    }
+
+    /// Runs every test file under `root`, in the order of their paths. Other files are passed over.
+    pub fn run(&self, root: &Path) -> io::Result<SuiteReport>{
+        let mut paths = Vec::new();
+        collect_files(root, &mut paths)?;
+        paths.sort();
+
+        let mut files = Vec::new();
+        for path in paths{
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            let result = match path.extension().and_then(|e| e.to_str()){
+                Some("json") => run_vectors(&path),
+                Some("bin") if fs::metadata(&path)?.len() == Self::IMAGE_SIZE => self.run_binary(&path),
+                _ => continue,
+            };
+            files.push(result.with_path(relative));
+        }
+        Ok(SuiteReport { root: root.to_path_buf(), files })
+    }
+
+    fn run_binary(&self, path: &Path) -> FileResult{
+        let outcome = fs::read(path).map_err(|e| e.to_string()).and_then(|image| {
+            let mut machine = Machine::new_64k_ram(&image).map_err(|e| format!("{:?}", e))?;
+            let mut success = self.success.clone();
+            success.extend(listed_success(&path.with_extension("lst")));
+
+            let mut cpu = W65C02S::default();
+            cpu.reset_to(self.entry);
+            let started = Instant::now();
+            loop{
+                if let Some(exceeded) = self.limits.check(&cpu, started){
+                    return Err(exceeded.to_string());
+                }
+                let pc = cpu.register(Register::PC);
+                cpu.step(&mut machine).map_err(|e| format!("{:?} at ${:04X}", e, pc))?;
+                if cpu.register(Register::PC) != pc{
+                    continue;
+                }
+                return match success.contains(&pc){
+                    true => Ok(()),
+                    false if success.is_empty() => Err(format!("trapped at ${:04X}, with no listing or --halt-addr naming the success trap", pc)),
+                    false => Err(format!("trapped at ${:04X}", pc)),
+                };
+            }
+        });
+        FileResult::single(SuiteKind::Binary, outcome)
+    }
+}
+impl Default for TestSuite{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileResult{
+    fn single(kind: SuiteKind, outcome: Result<(), String>) -> Self{
+        let failed = outcome.is_err() as u64;
+        Self { path: PathBuf::new(), kind, passed: 1 - failed, failed, first_failure: outcome.err() }
+    }
+    fn with_path(mut self, path: PathBuf) -> Self{
+        self.path = path;
+        self
+    }
+    /// The opcode a vector file is named after.
+    fn opcode(&self) -> Option<u8>{
+        let stem = self.path.file_stem()?.to_str()?;
+        (self.kind == SuiteKind::Vectors && stem.len() == 2).then(|| u8::from_str_radix(stem, 16).ok()).flatten()
+    }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()>{
+    for entry in fs::read_dir(directory)?{
+        let path = entry?.path();
+        if path.is_dir(){
+            collect_files(&path, files)?;
+        }
+        else{
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The address of the success trap in a listing from the functional tests' assembler, on the line that reads
+/// `test passed`.
+fn listed_success(listing: &Path) -> Option<u16>{
+    let text = fs::read_to_string(listing).ok()?;
+    let line = text.lines().find(|line| line.contains("test passed"))?;
+    u16::from_str_radix(line.split_whitespace().next()?, 16).ok()
+}
+
+fn run_vectors(path: &Path) -> FileResult{
+    let vectors = match fs::read(path).map_err(|e| e.to_string())
+        .and_then(|bytes| serde_json::from_slice::<Vec<Vector>>(&bytes).map_err(|e| format!("not a file of test vectors: {}", e))){
+        Ok(vectors) => vectors,
+        Err(e) => return FileResult::single(SuiteKind::Vectors, Err(e)),
+    };
+
+    // one machine for the whole file, cleared after each test of the bytes it touched
+    let mut machine = Machine::with_ram(256);
+    let mut cpu = W65C02S::default();
+    let mut result = FileResult { path: PathBuf::new(), kind: SuiteKind::Vectors, passed: 0, failed: 0, first_failure: None };
+    for vector in &vectors{
+        match run_vector(&mut cpu, &mut machine, vector){
+            Ok(()) => result.passed += 1,
+            Err(e) => {
+                result.failed += 1;
+                result.first_failure.get_or_insert_with(|| format!("\"{}\": {}", vector.name, e));
+            },
+        }
+        for &(address, _) in vector.initial.ram.iter().chain(&vector.expected.ram){
+            // all of the address space is RAM
+            let _ = machine.poke(address, 0);
+        }
+    }
+    result
+}
+
+fn run_vector(cpu: &mut W65C02S, machine: &mut Machine, vector: &Vector) -> Result<(), String>{
+    let initial = &vector.initial;
+    for &(address, value) in &initial.ram{
+        machine.poke(address, value).map_err(|e| format!("{:?}", e))?;
+    }
+    for (register, value) in registers(initial){
+        cpu.set_register(register, value);
+    }
+    cpu.set_counts(0, 0);
+    cpu.step(machine).map_err(|e| format!("{:?}", e))?;
+
+    let expected = &vector.expected;
+    let mut differences = Vec::new();
+    for (register, value) in registers(expected){
+        let actual = cpu.register(register);
+        if actual != value{
+            differences.push(match register{
+                Register::PC => format!("PC is ${:04X}, expected ${:04X}", actual, value),
+                _ => format!("{:?} is ${:02X}, expected ${:02X}", register, actual, value),
+            });
+        }
+    }
+    for &(address, value) in &expected.ram{
+        let actual = machine.peek(address).unwrap_or(0);
+        if actual != value{
+            differences.push(format!("${:04X} is ${:02X}, expected ${:02X}", address, actual, value));
+        }
+    }
+    if cpu.cycles() != vector.cycles.len() as u64{
+        differences.push(format!("took {} cycles, expected {}", cpu.cycles(), vector.cycles.len()));
+    }
+
+    match differences.is_empty(){
+        true => Ok(()),
+        false => Err(differences.join(", ")),
+    }
+}
+
+fn registers(state: &VectorState) -> [(Register, u16); 6]{
+    [
+        (Register::PC, state.pc),
+        (Register::A, state.a as u16),
+        (Register::X, state.x as u16),
+        (Register::Y, state.y as u16),
+        (Register::SP, state.s as u16),
+        (Register::P, state.p as u16),
+    ]
+}
+
+impl SuiteReport{
+    /// Files whose tests all passed.
+    pub fn passed(&self) -> u64{
+        self.files.iter().filter(|f| f.failed == 0).count() as u64
+    }
+    pub fn failed(&self) -> u64{
+        self.files.iter().filter(|f| f.failed > 0).count() as u64
+    }
+}
+
+/// A matrix of opcodes for each directory of vectors, `.` where every test passed, `X` where one failed and
+/// blank where there is no file, followed by the first failure of each opcode; then a line for each binary
+/// and each vector file not named after an opcode.
+impl fmt::Display for SuiteReport{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut directories = Vec::<&Path>::new();
+        for file in self.files.iter().filter(|file| file.opcode().is_some()){
+            let directory = file.path.parent().unwrap_or(Path::new(""));
+            if !directories.contains(&directory){
+                directories.push(directory);
+            }
+        }
+
+        for directory in directories{
+            let files = self.files.iter()
+                .filter(|file| file.path.parent() == Some(directory))
+                .filter_map(|file| Some((file.opcode()?, file)))
+                .collect::<Vec<(u8, &FileResult)>>();
+            let passing = files.iter().filter(|(_, file)| file.failed == 0).count();
+            writeln!(f, "{}: {} of {} opcodes pass", self.root.join(directory).display(), passing, files.len())?;
+
+            writeln!(f, "    0 1 2 3 4 5 6 7 8 9 A B C D E F")?;
+            for high in 0..16u8{
+                let cells = (0..16u8).map(|low| match files.iter().find(|(opcode, _)| *opcode == high << 4 | low){
+                    Some((_, file)) if file.failed == 0 => '.',
+                    Some(_) => 'X',
+                    None => ' ',
+                });
+                let row = format!("{:X}   {}", high, cells.map(String::from).collect::<Vec<String>>().join(" "));
+                writeln!(f, "{}", row.trim_end())?;
+            }
+            for (opcode, file) in files.iter().filter(|(_, file)| file.failed > 0){
+                writeln!(f, "  {:02x}: {} of {} failed, first {}", opcode, file.failed, file.passed + file.failed,
+                    file.first_failure.as_deref().unwrap_or(""))?;
+            }
+        }
+
+        for file in self.files.iter().filter(|file| file.opcode().is_none()){
+            let path = self.root.join(&file.path);
+            match (&file.first_failure, file.kind){
+                (None, SuiteKind::Binary) => writeln!(f, "{}: PASS", path.display())?,
+                (None, SuiteKind::Vectors) => writeln!(f, "{}: PASS, {} tests", path.display(), file.passed)?,
+                (Some(reason), SuiteKind::Binary) => writeln!(f, "{}: FAIL, {}", path.display(), reason)?,
+                (Some(reason), SuiteKind::Vectors) => writeln!(f, "{}: FAIL, {} of {} tests, first {}",
+                    path.display(), file.failed, file.passed + file.failed, reason)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Paces execution to a clock frequency, sleeping whenever the emulated CPU gets ahead of the wall clock, so
+/// interactive machines run at the speed their software expects.
+pub struct Throttle{
+    frequency: u64,         // in Hz
+    started: Instant,
+    start_cycles: u64,      // cycle count when `started` was taken
+    next_check: u64,        // cycle count at which to look at the clock again
+}
+impl Throttle{
+    /// Ahead of the wall clock by less than this is not worth sleeping for.
+    const SLICE: Duration = Duration::from_millis(2);
+    /// Further behind than this, the host stalled; catching up would run the CPU flat out in a burst.
+    const MAX_LAG: Duration = Duration::from_millis(100);
+
+    pub fn new(frequency: u64) -> Self{
+        Self { frequency: frequency.max(1), started: Instant::now(), start_cycles: 0, next_check: 0 }
+    }
+    pub fn frequency(&self) -> u64{
+        self.frequency
+    }
+
+    /// Call after each step with the CPU's cycle count.
+    pub fn pace(&mut self, cycles: u64){
+        if cycles < self.next_check{
+            return;
+        }
+        // looking at the clock about once a millisecond of emulated time keeps the overhead down
+        self.next_check = cycles + (self.frequency / 1000).max(1);
+
+        let emulated = Duration::from_nanos((cycles.saturating_sub(self.start_cycles) as u128 * 1_000_000_000 / self.frequency as u128) as u64);
+        let elapsed = self.started.elapsed();
+        if emulated > elapsed + Self::SLICE{
+            thread::sleep(emulated - elapsed);
+        } else if elapsed > emulated + Self::MAX_LAG{
+            self.started = Instant::now();
+            self.start_cycles = cycles;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::bus::bus::Machine;
+use crate::cpu::disassembler;
+use crate::cpu::symbols::SymbolTable;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// Writes a line for every instruction the CPU is about to execute: its address, bytes and disassembly, the
+/// registers before it runs, and the cycle count. An interrupt taken before it gets a line of its own, starting
+/// `;`, naming it and any it was taken over.
+///
+/// With a start address, tracing begins when the CPU reaches it; with a stop address, it ends before the
+/// instruction there. Reaching the start address again begins another stretch.
+pub struct Tracer{
+    output: Box<dyn Write>,
+    start: Option<u16>,
+    stop: Option<u16>,
+    active: bool,
+    symbols: SymbolTable,   // names operands by, when there are any
+    vice: bool,             // lay lines out as VICE's monitor does
+}
+impl Tracer{
+    pub fn new(output: impl Write + 'static) -> Self{
+        Self { output: Box::new(output), start: None, stop: None, active: true, symbols: SymbolTable::new(), vice: false }
+    }
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self>{
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+    pub fn with_start(mut self, address: u16) -> Self{
+        self.start = Some(address);
+        self.active = false;
+        self
+    }
+    pub fn with_stop(mut self, address: u16) -> Self{
+        self.stop = Some(address);
+        self
+    }
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self{
+        self.symbols = symbols;
+        self
+    }
+    /// Lays the lines out as the CPU history of VICE's monitor does, so that tools made for its traces can
+    /// read them:
+    ///
+    /// `.C:8000  A9 42       LDA #$42       - A:00 X:00 Y:00 SP:fd ..-..I..          7`
+    pub fn vice(mut self) -> Self{
+        self.vice = true;
+        self
+    }
+
+    /// Call before each step of the CPU.
+    pub fn trace(&mut self, cpu: &W65C02S, machine: &Machine){
+        let pc = cpu.register(Register::PC);
+        if Some(pc) == self.start{
+            self.active = true;
+        }
+        if Some(pc) == self.stop{
+            self.active = false;
+        }
+        if !self.active{
+            return;
+        }
+        // a trace that cannot be written is not worth stopping the machine for
+        if let Some(arbitration) = cpu.last_arbitration(){
+            let _ = writeln!(self.output, "; {}", arbitration);
+        }
+
+        let instruction = disassembler::disassemble_with_symbols(pc, |address| machine.peek(address), &self.symbols);
+        let line = match self.vice{
+            false => format!("{:<32}A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} CYC:{}\n",
+                instruction.to_string(),
+                cpu.register(Register::A), cpu.register(Register::X), cpu.register(Register::Y),
+                cpu.register(Register::SP), cpu.register(Register::P), cpu.cycles()),
+            true => format!(".C:{:04x}  {:<12}{:<15}- A:{:02X} X:{:02X} Y:{:02X} SP:{:02x} {} {:>10}\n",
+                pc,
+                instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" "),
+                instruction.text,
+                cpu.register(Register::A), cpu.register(Register::X), cpu.register(Register::Y),
+                cpu.register(Register::SP), vice_flags(cpu.register(Register::P) as u8), cpu.cycles()),
+        };
+        let _ = self.output.write_all(line.as_bytes());
+    }
+}
+
+/// The status register as VICE shows it, the letter of each flag that is set in `NV-BDIZC` and `.` for each
+/// that is clear.
+fn vice_flags(p: u8) -> String{
+    "NV-BDIZC".chars().enumerate()
+        .map(|(i, letter)| match letter{
+            '-' => '-',
+            _ if p & (0x80 >> i) != 0 => letter,
+            _ => '.',
+        })
+        .collect()
+}
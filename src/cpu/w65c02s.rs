@@ -1,12 +1,136 @@
-use crate::bus::bus::{Bus};
+use std::fmt;
+use std::time::Instant;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bus::bus::{Bus, BusError, RegionAttributes};
+use crate::cpu::limits::{LimitExceeded, RunLimits};
 
 #[derive(Debug)]
 pub enum CpuError{
     InvalidOpcode(u8),
     InvalidOperand(Operand),
+    ExecuteFromNoExecute(u16),
+    Unsupported(Mnemonic),      // an instruction that is decoded but not emulated
+    Bus(BusError),
+}
+impl From<BusError> for CpuError{
+    fn from(e: BusError) -> Self {
+        CpuError::Bus(e)
+    }
+}
+impl fmt::Display for CpuError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            CpuError::InvalidOpcode(opcode) => write!(f, "invalid opcode ${:02X}", opcode),
+            CpuError::InvalidOperand(operand) => write!(f, "invalid operand {:?}", operand),
+            CpuError::ExecuteFromNoExecute(address) => write!(f, "attempted to execute no-execute memory at address {:04X}", address),
+            CpuError::Unsupported(mnemonic) => write!(f, "{} is not emulated", mnemonic),
+            CpuError::Bus(e) => write!(f, "{}", e),
+        }
+    }
 }
 
-enum Status{
+/// How a `W65C02S::run_block` ended.
+#[derive(Debug)]
+pub enum BlockEnd{
+    /// Every instruction asked for executed, or the cycle count to stop at was reached.
+    Ran,
+    /// A `BRK` executed at `pc`.
+    Brk{ pc: u16 },
+    /// The instruction at `pc` could not be executed.
+    Failed{ pc: u16, error: CpuError },
+    /// The CPU took an interrupt after the last instruction, ending the block early so that it can be reported.
+    Interrupted(Arbitration),
+}
+
+/// What the CPU does on an opcode the W65C02S does not define.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InvalidOpcodePolicy{
+    /// Stops with `CpuError::InvalidOpcode`, for catching a program gone astray.
+    #[default]
+    Error,
+    /// Carries on past it as the chip does, as a NOP of the length and cycles the data sheet gives it.
+    Nop,
+}
+
+/// A source of interrupts, in the order the CPU ranks them when more than one is pending at an instruction
+/// boundary.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Interrupt{
+    Reset,
+    Nmi,
+    Irq,
+}
+impl fmt::Display for Interrupt{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Interrupt::Reset => write!(f, "RESET"),
+            Interrupt::Nmi => write!(f, "NMI"),
+            Interrupt::Irq => write!(f, "IRQ"),
+        }
+    }
+}
+
+/// What the CPU made of the interrupts pending at an instruction boundary where it took one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arbitration{
+    pub pc: u16,            // where the interrupted program would have gone on from
+    pub taken: Interrupt,
+    pub reset: bool,        // whether each was pending: RESET and NMI latched, IRQ asserted with the I flag clear
+    pub nmi: bool,
+    pub irq: bool,
+}
+impl Arbitration{
+    /// The interrupts that were pending too but lost to the one taken.
+    pub fn outranked(&self) -> impl Iterator<Item = Interrupt>{
+        [(Interrupt::Reset, self.reset), (Interrupt::Nmi, self.nmi), (Interrupt::Irq, self.irq)].into_iter()
+            .filter(|(interrupt, pending)| *pending && *interrupt > self.taken)
+            .map(|(interrupt, _)| interrupt)
+    }
+}
+impl fmt::Display for Arbitration{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at ${:04X}", self.taken, self.pc)?;
+        for interrupt in self.outranked(){
+            write!(f, ", ahead of {}", interrupt)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Register{
+    PC,
+    A,
+    X,
+    Y,
+    SP,
+    P,
+}
+
+/// A register that differs between two CPUs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterDifference{
+    pub register: Register,
+    pub expected: u16,
+    pub actual: u16,
+}
+impl fmt::Display for RegisterDifference{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.register{
+            Register::PC => write!(f, "register PC expected 0x{:04X} got 0x{:04X}", self.expected, self.actual),
+            _ => write!(f, "register {:?} expected 0x{:02X} got 0x{:02X}", self.register, self.expected, self.actual),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status{
     C,  // Carry
     Z,  // Zero
     I,  // Interrupt Disable
@@ -37,13 +161,24 @@ impl Status{
     Datasheet: https://www.westerndesigncenter.com/wdc/documentation/w65c02s.pdf
  */
 #[derive(Default)]
- pub struct W65C02S{
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct W65C02S{
     program_counter: u16,
     a_register: u8,
     y_register: u8,
     x_register: u8,
     stack_pointer: u8,
     processor_status_register: u8,
+
+    cycles: u64,    // elapsed since construction
+    instructions: u64,  // executed since construction
+    nmi_line: bool, // level of the NMI line after the last instruction, for detecting its falling edge
+    #[cfg_attr(feature = "serde", serde(default))]
+    reset_line: bool,   // and of the RESET line
+    #[cfg_attr(feature = "serde", serde(default))]
+    invalid_opcodes: InvalidOpcodePolicy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_arbitration: Option<Arbitration>,  // the interrupt taken after the last instruction, if one was
 }
 impl W65C02S{
     // high byte for all vectors immediately follow the low byte in address space
@@ -53,317 +188,552 @@ impl W65C02S{
 
     pub const STACK_POINTER_BASE: u16 = 0x0100; // When combined with the stack_pointer
 
-    // invalids = [3, 19, 35, 51, 67, 83, 99, 115, 131, 147, 163, 179, 195, 211, 227, 243, 2, 34, 66, 98, 130, 194, 226, 68, 84, 212, 244, 11, 27, 43, 59, 75, 91, 107, 123, 139, 155, 171, 187, 235, 251, 92, 220, 252]
-    pub const OPERATIONS: [Option<Operation>; 256] = [
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::BRK, exec: op_brk }),                          // 0x00 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::ORA, exec: op_ora }),        // 0x01 
-        Option::None,                                                                                                                       // 0x02 [Invalid]
-        Option::None,                                                                                                                       // 0x03 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::TSB, exec: op_tsb }),                       // 0x04 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::ORA, exec: op_ora }),                       // 0x05 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::ASL, exec: op_asl }),                       // 0x06 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(0), exec: op_alias_rmb0 }),            // 0x07 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PHP, exec: op_php }),                          // 0x08 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::ORA, exec: op_ora }),                      // 0x09 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::ASL, exec: op_asl }),                    // 0x0A 
-        Option::None,                                                                                                                       // 0x0B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::TSB, exec: op_tsb }),                       // 0x0C 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::ORA, exec: op_ora }),                       // 0x0D 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::ASL, exec: op_asl }),                       // 0x0E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(0), exec: op_alias_bbr0 }),    // 0x0F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BPL, exec: op_bpl }),         // 0x10 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::ORA, exec: op_ora }),       // 0x11 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::ORA, exec: op_ora }),               // 0x12 
-        Option::None,                                                                                                                       // 0x13 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::TRB, exec: op_trb }),                       // 0x14 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::ORA, exec: op_ora }),               // 0x15 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::ASL, exec: op_asl }),               // 0x16 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(1), exec: op_alias_rmb1 }),            // 0x17 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::CLC, exec: op_clc }),                        // 0x18 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::ORA, exec: op_ora }),               // 0x19 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::INC, exec: op_inc }),                    // 0x1A 
-        Option::None,                                                                                                                       // 0x1B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::TRB, exec: op_trb }),                       // 0x1C 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::ORA, exec: op_ora }),               // 0x1D 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::ASL, exec: op_asl }),               // 0x1E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(1), exec: op_alias_bbr1 }),    // 0x1F 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::JSR, exec: op_jsr }),                       // 0x20 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::AND, exec: op_and }),        // 0x21 
-        Option::None,                                                                                                                       // 0x22 [Invalid]
-        Option::None,                                                                                                                       // 0x23 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::BIT, exec: op_bit }),                       // 0x24 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::AND, exec: op_and }),                       // 0x25 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::ROL, exec: op_rol }),                       // 0x26 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(2), exec: op_alias_rmb2 }),            // 0x27 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PLP, exec: op_plp }),                          // 0x28 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::AND, exec: op_and }),                      // 0x29 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::ROL, exec: op_rol }),                    // 0x2A 
-        Option::None,                                                                                                                       // 0x2B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::BIT, exec: op_bit }),                       // 0x2C 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::AND, exec: op_and }),                       // 0x2D 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::ROL, exec: op_rol }),                       // 0x2E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(2), exec: op_alias_bbr2 }),    // 0x2F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BMI, exec: op_bmi }),         // 0x30 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::AND, exec: op_and }),       // 0x31 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::AND, exec: op_and }),               // 0x32 
-        Option::None,                                                                                                                       // 0x33 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::BIT, exec: op_bit }),               // 0x34 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::AND, exec: op_and }),               // 0x35 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::ROL, exec: op_rol }),               // 0x36 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(3), exec: op_alias_rmb3 }),            // 0x37 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::SEC, exec: op_sec }),                        // 0x38 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::AND, exec: op_and }),               // 0x39 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::DEC, exec: op_dec }),                    // 0x3A 
-        Option::None,                                                                                                                       // 0x3B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::BIT, exec: op_bit }),               // 0x3C 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::AND, exec: op_and }),               // 0x3D 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::ROL, exec: op_rol }),               // 0x3E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(3), exec: op_alias_bbr3 }),    // 0x3F 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::RTI, exec: op_rti }),                          // 0x40 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::EOR, exec: op_eor }),        // 0x41 
-        Option::None,                                                                                                                       // 0x42 [Invalid]
-        Option::None,                                                                                                                       // 0x43 [Invalid]
-        Option::None,                                                                                                                       // 0x44 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::EOR, exec: op_eor }),                       // 0x45 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::LSR, exec: op_lsr }),                       // 0x46 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(4), exec: op_alias_rmb4 }),            // 0x47 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PHA, exec: op_pha }),                          // 0x48 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::EOR, exec: op_eor }),                      // 0x49 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::LSR, exec: op_lsr }),                    // 0x4A 
-        Option::None,                                                                                                                       // 0x4B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::JMP, exec: op_jmp }),                       // 0x4C 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::EOR, exec: op_eor }),                       // 0x4D 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::LSR, exec: op_lsr }),                       // 0x4E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(4), exec: op_alias_bbr4 }),    // 0x4F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BVC, exec: op_bvc }),         // 0x50 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::EOR, exec: op_eor }),       // 0x51 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::EOR, exec: op_eor }),               // 0x52 
-        Option::None,                                                                                                                       // 0x53 [Invalid]
-        Option::None,                                                                                                                       // 0x54 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::EOR, exec: op_eor }),               // 0x55 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::LSR, exec: op_lsr }),               // 0x56 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(5), exec: op_alias_rmb5 }),            // 0x57 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::CLI, exec: op_cli }),                        // 0x58 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::EOR, exec: op_eor }),               // 0x59 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PHY, exec: op_phy }),                          // 0x5A 
-        Option::None,                                                                                                                       // 0x5B [Invalid]
-        Option::None,                                                                                                                       // 0x5C [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::EOR, exec: op_eor }),               // 0x5D 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::LSR, exec: op_lsr }),               // 0x5E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(5), exec: op_alias_bbr5 }),    // 0x5F 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::RTS, exec: op_rts }),                          // 0x60 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::ADC, exec: op_adc }),        // 0x61 
-        Option::None,                                                                                                                       // 0x62 [Invalid]
-        Option::None,                                                                                                                       // 0x63 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::STZ, exec: op_stz }),                       // 0x64 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::ADC, exec: op_adc }),                       // 0x65 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::ROR, exec: op_ror }),                       // 0x66 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(6), exec: op_alias_rmb6 }),            // 0x67 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PLA, exec: op_pla }),                          // 0x68 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::ADC, exec: op_adc }),                      // 0x69 
-        Option::Some(Operation { addressing_mode: AddressingMode::Accumulator, mnemomic: Mnemomic::ROR, exec: op_ror }),                    // 0x6A 
-        Option::None,                                                                                                                       // 0x6B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndirect, mnemomic: Mnemomic::JMP, exec: op_jmp }),               // 0x6C 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::ADC, exec: op_adc }),                       // 0x6D 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::ROR, exec: op_ror }),                       // 0x6E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(6), exec: op_alias_bbr6 }),    // 0x6F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BVS, exec: op_bvs }),         // 0x70 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::ADC, exec: op_adc }),       // 0x71 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::ADC, exec: op_adc }),               // 0x72 
-        Option::None,                                                                                                                       // 0x73 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::STZ, exec: op_stz }),               // 0x74 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::ADC, exec: op_adc }),               // 0x75 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::ROR, exec: op_ror }),               // 0x76 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::RMBN(7), exec: op_alias_rmb7 }),            // 0x77 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::SEI, exec: op_sei }),                        // 0x78 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::ADC, exec: op_adc }),               // 0x79 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PLY, exec: op_ply }),                          // 0x7A 
-        Option::None,                                                                                                                       // 0x7B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedIndirect, mnemomic: Mnemomic::JMP, exec: op_jmp }),        // 0x7C 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::ADC, exec: op_adc }),               // 0x7D 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::ROR, exec: op_ror }),               // 0x7E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBRN(7), exec: op_alias_bbr7 }),    // 0x7F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BRA, exec: op_bra }),         // 0x80 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::STA, exec: op_sta }),        // 0x81 
-        Option::None,                                                                                                                       // 0x82 [Invalid]
-        Option::None,                                                                                                                       // 0x83 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::STY, exec: op_sty }),                       // 0x84 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::STA, exec: op_sta }),                       // 0x85 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::STX, exec: op_stx }),                       // 0x86 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(0), exec: op_alias_smb0 }),            // 0x87 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::DEY, exec: op_dey }),                        // 0x88 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::BIT, exec: op_bit }),                      // 0x89 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TXA, exec: op_txa }),                        // 0x8A 
-        Option::None,                                                                                                                       // 0x8B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::STY, exec: op_sty }),                       // 0x8C 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::STA, exec: op_sta }),                       // 0x8D 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::STX, exec: op_stx }),                       // 0x8E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(0), exec: op_alias_bbs0 }),    // 0x8F 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BCC, exec: op_bcc }),         // 0x90 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::STA, exec: op_sta }),       // 0x91 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::STA, exec: op_sta }),               // 0x92 
-        Option::None,                                                                                                                       // 0x93 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::STY, exec: op_sty }),               // 0x94 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::STA, exec: op_sta }),               // 0x95 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedY, mnemomic: Mnemomic::STX, exec: op_stx }),               // 0x96 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(1), exec: op_alias_smb1 }),            // 0x97 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TYA, exec: op_tya }),                        // 0x98 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::STA, exec: op_sta }),               // 0x99 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TXS, exec: op_txs }),                        // 0x9A 
-        Option::None,                                                                                                                       // 0x9B [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::STZ, exec: op_stz }),               // 0x9C 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::STA, exec: op_sta }),               // 0x9D 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::STZ, exec: op_stz }),               // 0x9E 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(1), exec: op_alias_bbs1 }),    // 0x9F 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::LDY, exec: op_ldy }),                      // 0xA0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::LDA, exec: op_lda }),        // 0xA1 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::LDX, exec: op_ldx }),                      // 0xA2 
-        Option::None,                                                                                                                       // 0xA3 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::LDY, exec: op_ldy }),                       // 0xA4 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::LDA, exec: op_lda }),                       // 0xA5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::LDX, exec: op_ldx }),                       // 0xA6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(2), exec: op_alias_smb2 }),            // 0xA7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TAY, exec: op_tay }),                        // 0xA8 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::LDA, exec: op_lda }),                      // 0xA9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TAX, exec: op_tax }),                        // 0xAA 
-        Option::None,                                                                                                                       // 0xAB [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::LDY, exec: op_ldy }),                       // 0xAC 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::LDA, exec: op_lda }),                       // 0xAD 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::LDX, exec: op_ldx }),                       // 0xAE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(2), exec: op_alias_bbs2 }),    // 0xAF 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BCS, exec: op_bcs }),         // 0xB0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::LDA, exec: op_lda }),       // 0xB1 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::LDA, exec: op_lda }),               // 0xB2 
-        Option::None,                                                                                                                       // 0xB3 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::LDY, exec: op_ldy }),               // 0xB4 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::LDA, exec: op_lda }),               // 0xB5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedY, mnemomic: Mnemomic::LDX, exec: op_ldx }),               // 0xB6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(3), exec: op_alias_smb3 }),            // 0xB7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::CLV, exec: op_clv }),                        // 0xB8 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::LDA, exec: op_lda }),               // 0xB9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::TSX, exec: op_tsx }),                        // 0xBA 
-        Option::None,                                                                                                                       // 0xBB [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::LDY, exec: op_ldy }),               // 0xBC 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::LDA, exec: op_lda }),               // 0xBD 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::LDX, exec: op_ldx }),               // 0xBE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(3), exec: op_alias_bbs3 }),    // 0xBF 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::CPY, exec: op_cpy }),                      // 0xC0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::CMP, exec: op_cmp }),        // 0xC1 
-        Option::None,                                                                                                                       // 0xC2 [Invalid]
-        Option::None,                                                                                                                       // 0xC3 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::CPY, exec: op_cpy }),                       // 0xC4 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::CMP, exec: op_cmp }),                       // 0xC5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::DEC, exec: op_dec }),                       // 0xC6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(4), exec: op_alias_smb4 }),            // 0xC7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::INY, exec: op_iny }),                        // 0xC8 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::CMP, exec: op_cmp }),                      // 0xC9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::DEX, exec: op_dex }),                        // 0xCA 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::WAI, exec: op_wai }),                        // 0xCB 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::CPY, exec: op_cpy }),                       // 0xCC 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::CMP, exec: op_cmp }),                       // 0xCD 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::DEC, exec: op_dec }),                       // 0xCE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(4), exec: op_alias_bbs4 }),    // 0xCF 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BNE, exec: op_bne }),         // 0xD0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::CMP, exec: op_cmp }),       // 0xD1 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::CMP, exec: op_cmp }),               // 0xD2 
-        Option::None,                                                                                                                       // 0xD3 [Invalid]
-        Option::None,                                                                                                                       // 0xD4 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::CMP, exec: op_cmp }),               // 0xD5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::DEC, exec: op_dec }),               // 0xD6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(5), exec: op_alias_smb5 }),            // 0xD7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::CLD, exec: op_cld }),                        // 0xD8 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::CMP, exec: op_cmp }),               // 0xD9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::PHX, exec: op_phx }),                        // 0xDA 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::STP, exec: op_stp }),                        // 0xDB [Invalid]
-        Option::None,                                                                                                                       // 0xDC [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::CMP, exec: op_cmp }),               // 0xDD 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::DEC, exec: op_dec }),               // 0xDE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(5), exec: op_alias_bbs5 }),    // 0xDF 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::CPX, exec: op_cpx }),                      // 0xE0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemomic: Mnemomic::SBC, exec: op_sbc }),        // 0xE1 
-        Option::None,                                                                                                                       // 0xE2 [Invalid]
-        Option::None,                                                                                                                       // 0xE3 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::CPX, exec: op_cpx }),                       // 0xE4 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SBC, exec: op_sbc }),                       // 0xE5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::INC, exec: op_inc }),                       // 0xE6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(6), exec: op_alias_smb6 }),            // 0xE7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::INX, exec: op_inx }),                        // 0xE8 
-        Option::Some(Operation { addressing_mode: AddressingMode::Immediate, mnemomic: Mnemomic::SBC, exec: op_sbc }),                      // 0xE9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::NOP, exec: op_nop }),                        // 0xEA 
-        Option::None,                                                                                                                       // 0xEB [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::CPX, exec: op_cpx }),                       // 0xEC 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::SBC, exec: op_sbc }),                       // 0xED 
-        Option::Some(Operation { addressing_mode: AddressingMode::Absolute, mnemomic: Mnemomic::INC, exec: op_inc }),                       // 0xEE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(6), exec: op_alias_bbs6 }),    // 0xEF 
-        Option::Some(Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemomic: Mnemomic::BEQ, exec: op_beq }),         // 0xF0 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemomic: Mnemomic::SBC, exec: op_sbc }),       // 0xF1 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemomic: Mnemomic::SBC, exec: op_sbc }),               // 0xF2 
-        Option::None,                                                                                                                       // 0xF3 [Invalid]
-        Option::None,                                                                                                                       // 0xF4 [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::SBC, exec: op_sbc }),               // 0xF5 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemomic: Mnemomic::INC, exec: op_inc }),               // 0xF6 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPage, mnemomic: Mnemomic::SMBN(7), exec: op_alias_smb7 }),            // 0xF7 
-        Option::Some(Operation { addressing_mode: AddressingMode::Implied, mnemomic: Mnemomic::SED, exec: op_sed }),                        // 0xF8 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemomic: Mnemomic::SBC, exec: op_sbc }),               // 0xF9 
-        Option::Some(Operation { addressing_mode: AddressingMode::Stack, mnemomic: Mnemomic::PLX, exec: op_plx }),                          // 0xFA
-        Option::None,                                                                                                                       // 0xFB [Invalid] 
-        Option::None,                                                                                                                       // 0xFC [Invalid]
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::SBC, exec: op_sbc }),               // 0xFD 
-        Option::Some(Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemomic: Mnemomic::INC, exec: op_inc }),               // 0xFE 
-        Option::Some(Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemomic: Mnemomic::BBSN(7), exec: op_alias_bbs7 }),    // 0xFF 
+    // opcodes marked [Invalid] have the length and cycles the data sheet gives them as NOPs, and do what the CPU's
+    // `InvalidOpcodePolicy` says
+    pub const OPERATIONS: [Operation; 256] = [
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::BRK, cycles: 7 },                                                      // 0x00 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::ORA, cycles: 6 },                                    // 0x01 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0x02), cycles: 2 },                                        // 0x02 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x03), cycles: 1 },                                          // 0x03 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::TSB, cycles: 5 },                                                   // 0x04 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::ORA, cycles: 3 },                                                   // 0x05 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::ASL, cycles: 5 },                                                   // 0x06 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(0), cycles: 5 },                                               // 0x07 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PHP, cycles: 3 },                                                      // 0x08 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::ORA, cycles: 2 },                                                  // 0x09 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::ASL, cycles: 2 },                                                // 0x0A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x0B), cycles: 1 },                                          // 0x0B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::TSB, cycles: 6 },                                                   // 0x0C 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::ORA, cycles: 4 },                                                   // 0x0D 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::ASL, cycles: 6 },                                                   // 0x0E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(0), cycles: 5 },                                       // 0x0F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BPL, cycles: 2 },                                     // 0x10 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::ORA, cycles: 5 },                                   // 0x11 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::ORA, cycles: 5 },                                           // 0x12 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x13), cycles: 1 },                                          // 0x13 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::TRB, cycles: 5 },                                                   // 0x14 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::ORA, cycles: 4 },                                           // 0x15 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::ASL, cycles: 6 },                                           // 0x16 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(1), cycles: 5 },                                               // 0x17 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::CLC, cycles: 2 },                                                    // 0x18 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::ORA, cycles: 4 },                                           // 0x19 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::INC, cycles: 2 },                                                // 0x1A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x1B), cycles: 1 },                                          // 0x1B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::TRB, cycles: 6 },                                                   // 0x1C 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::ORA, cycles: 4 },                                           // 0x1D 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::ASL, cycles: 6 },                                           // 0x1E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(1), cycles: 5 },                                       // 0x1F 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::JSR, cycles: 6 },                                                   // 0x20 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::AND, cycles: 6 },                                    // 0x21 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0x22), cycles: 2 },                                        // 0x22 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x23), cycles: 1 },                                          // 0x23 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::BIT, cycles: 3 },                                                   // 0x24 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::AND, cycles: 3 },                                                   // 0x25 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::ROL, cycles: 5 },                                                   // 0x26 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(2), cycles: 5 },                                               // 0x27 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PLP, cycles: 4 },                                                      // 0x28 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::AND, cycles: 2 },                                                  // 0x29 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::ROL, cycles: 2 },                                                // 0x2A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x2B), cycles: 1 },                                          // 0x2B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::BIT, cycles: 4 },                                                   // 0x2C 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::AND, cycles: 4 },                                                   // 0x2D 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::ROL, cycles: 6 },                                                   // 0x2E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(2), cycles: 5 },                                       // 0x2F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BMI, cycles: 2 },                                     // 0x30 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::AND, cycles: 5 },                                   // 0x31 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::AND, cycles: 5 },                                           // 0x32 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x33), cycles: 1 },                                          // 0x33 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::BIT, cycles: 4 },                                           // 0x34 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::AND, cycles: 4 },                                           // 0x35 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::ROL, cycles: 6 },                                           // 0x36 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(3), cycles: 5 },                                               // 0x37 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::SEC, cycles: 2 },                                                    // 0x38 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::AND, cycles: 4 },                                           // 0x39 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::DEC, cycles: 2 },                                                // 0x3A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x3B), cycles: 1 },                                          // 0x3B [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::BIT, cycles: 4 },                                           // 0x3C 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::AND, cycles: 4 },                                           // 0x3D 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::ROL, cycles: 6 },                                           // 0x3E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(3), cycles: 5 },                                       // 0x3F 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::RTI, cycles: 6 },                                                      // 0x40 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::EOR, cycles: 6 },                                    // 0x41 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0x42), cycles: 2 },                                        // 0x42 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x43), cycles: 1 },                                          // 0x43 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::Invalid(0x44), cycles: 3 },                                         // 0x44 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::EOR, cycles: 3 },                                                   // 0x45 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::LSR, cycles: 5 },                                                   // 0x46 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(4), cycles: 5 },                                               // 0x47 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PHA, cycles: 3 },                                                      // 0x48 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::EOR, cycles: 2 },                                                  // 0x49 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::LSR, cycles: 2 },                                                // 0x4A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x4B), cycles: 1 },                                          // 0x4B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::JMP, cycles: 3 },                                                   // 0x4C 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::EOR, cycles: 4 },                                                   // 0x4D 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::LSR, cycles: 6 },                                                   // 0x4E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(4), cycles: 5 },                                       // 0x4F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BVC, cycles: 2 },                                     // 0x50 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::EOR, cycles: 5 },                                   // 0x51 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::EOR, cycles: 5 },                                           // 0x52 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x53), cycles: 1 },                                          // 0x53 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::Invalid(0x54), cycles: 4 },                                 // 0x54 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::EOR, cycles: 4 },                                           // 0x55 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::LSR, cycles: 6 },                                           // 0x56 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(5), cycles: 5 },                                               // 0x57 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::CLI, cycles: 2 },                                                    // 0x58 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::EOR, cycles: 4 },                                           // 0x59 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PHY, cycles: 3 },                                                      // 0x5A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x5B), cycles: 1 },                                          // 0x5B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::Invalid(0x5C), cycles: 8 },                                         // 0x5C [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::EOR, cycles: 4 },                                           // 0x5D 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::LSR, cycles: 6 },                                           // 0x5E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(5), cycles: 5 },                                       // 0x5F 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::RTS, cycles: 6 },                                                      // 0x60 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::ADC, cycles: 6 },                                    // 0x61 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0x62), cycles: 2 },                                        // 0x62 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x63), cycles: 1 },                                          // 0x63 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::STZ, cycles: 3 },                                                   // 0x64 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::ADC, cycles: 3 },                                                   // 0x65 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::ROR, cycles: 5 },                                                   // 0x66 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(6), cycles: 5 },                                               // 0x67 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PLA, cycles: 4 },                                                      // 0x68 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::ADC, cycles: 2 },                                                  // 0x69 
+        Operation { addressing_mode: AddressingMode::Accumulator, mnemonic: Mnemonic::ROR, cycles: 2 },                                                // 0x6A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x6B), cycles: 1 },                                          // 0x6B [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndirect, mnemonic: Mnemonic::JMP, cycles: 6 },                                           // 0x6C 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::ADC, cycles: 4 },                                                   // 0x6D 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::ROR, cycles: 6 },                                                   // 0x6E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(6), cycles: 5 },                                       // 0x6F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BVS, cycles: 2 },                                     // 0x70 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::ADC, cycles: 5 },                                   // 0x71 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::ADC, cycles: 5 },                                           // 0x72 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x73), cycles: 1 },                                          // 0x73 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::STZ, cycles: 4 },                                           // 0x74 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::ADC, cycles: 4 },                                           // 0x75 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::ROR, cycles: 6 },                                           // 0x76 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::RMBN(7), cycles: 5 },                                               // 0x77 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::SEI, cycles: 2 },                                                    // 0x78 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::ADC, cycles: 4 },                                           // 0x79 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PLY, cycles: 4 },                                                      // 0x7A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x7B), cycles: 1 },                                          // 0x7B [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedIndirect, mnemonic: Mnemonic::JMP, cycles: 6 },                                    // 0x7C 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::ADC, cycles: 4 },                                           // 0x7D 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::ROR, cycles: 6 },                                           // 0x7E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBRN(7), cycles: 5 },                                       // 0x7F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BRA, cycles: 3 },                                     // 0x80 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::STA, cycles: 6 },                                    // 0x81 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0x82), cycles: 2 },                                        // 0x82 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x83), cycles: 1 },                                          // 0x83 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::STY, cycles: 3 },                                                   // 0x84 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::STA, cycles: 3 },                                                   // 0x85 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::STX, cycles: 3 },                                                   // 0x86 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(0), cycles: 5 },                                               // 0x87 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::DEY, cycles: 2 },                                                    // 0x88 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::BIT, cycles: 2 },                                                  // 0x89 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TXA, cycles: 2 },                                                    // 0x8A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x8B), cycles: 1 },                                          // 0x8B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::STY, cycles: 4 },                                                   // 0x8C 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::STA, cycles: 4 },                                                   // 0x8D 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::STX, cycles: 4 },                                                   // 0x8E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(0), cycles: 5 },                                       // 0x8F 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BCC, cycles: 2 },                                     // 0x90 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::STA, cycles: 6 },                                   // 0x91 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::STA, cycles: 5 },                                           // 0x92 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x93), cycles: 1 },                                          // 0x93 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::STY, cycles: 4 },                                           // 0x94 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::STA, cycles: 4 },                                           // 0x95 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedY, mnemonic: Mnemonic::STX, cycles: 4 },                                           // 0x96 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(1), cycles: 5 },                                               // 0x97 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TYA, cycles: 2 },                                                    // 0x98 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::STA, cycles: 5 },                                           // 0x99 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TXS, cycles: 2 },                                                    // 0x9A 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0x9B), cycles: 1 },                                          // 0x9B [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::STZ, cycles: 4 },                                                   // 0x9C 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::STA, cycles: 5 },                                           // 0x9D 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::STZ, cycles: 5 },                                           // 0x9E 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(1), cycles: 5 },                                       // 0x9F 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::LDY, cycles: 2 },                                                  // 0xA0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::LDA, cycles: 6 },                                    // 0xA1 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::LDX, cycles: 2 },                                                  // 0xA2 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xA3), cycles: 1 },                                          // 0xA3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::LDY, cycles: 3 },                                                   // 0xA4 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::LDA, cycles: 3 },                                                   // 0xA5 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::LDX, cycles: 3 },                                                   // 0xA6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(2), cycles: 5 },                                               // 0xA7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TAY, cycles: 2 },                                                    // 0xA8 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::LDA, cycles: 2 },                                                  // 0xA9 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TAX, cycles: 2 },                                                    // 0xAA 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xAB), cycles: 1 },                                          // 0xAB [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::LDY, cycles: 4 },                                                   // 0xAC 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::LDA, cycles: 4 },                                                   // 0xAD 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::LDX, cycles: 4 },                                                   // 0xAE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(2), cycles: 5 },                                       // 0xAF 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BCS, cycles: 2 },                                     // 0xB0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::LDA, cycles: 5 },                                   // 0xB1 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::LDA, cycles: 5 },                                           // 0xB2 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xB3), cycles: 1 },                                          // 0xB3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::LDY, cycles: 4 },                                           // 0xB4 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::LDA, cycles: 4 },                                           // 0xB5 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedY, mnemonic: Mnemonic::LDX, cycles: 4 },                                           // 0xB6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(3), cycles: 5 },                                               // 0xB7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::CLV, cycles: 2 },                                                    // 0xB8 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::LDA, cycles: 4 },                                           // 0xB9 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::TSX, cycles: 2 },                                                    // 0xBA 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xBB), cycles: 1 },                                          // 0xBB [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::LDY, cycles: 4 },                                           // 0xBC 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::LDA, cycles: 4 },                                           // 0xBD 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::LDX, cycles: 4 },                                           // 0xBE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(3), cycles: 5 },                                       // 0xBF 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::CPY, cycles: 2 },                                                  // 0xC0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::CMP, cycles: 6 },                                    // 0xC1 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0xC2), cycles: 2 },                                        // 0xC2 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xC3), cycles: 1 },                                          // 0xC3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::CPY, cycles: 3 },                                                   // 0xC4 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::CMP, cycles: 3 },                                                   // 0xC5 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::DEC, cycles: 5 },                                                   // 0xC6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(4), cycles: 5 },                                               // 0xC7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::INY, cycles: 2 },                                                    // 0xC8 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::CMP, cycles: 2 },                                                  // 0xC9 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::DEX, cycles: 2 },                                                    // 0xCA 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::WAI, cycles: 3 },                                                    // 0xCB 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::CPY, cycles: 4 },                                                   // 0xCC 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::CMP, cycles: 4 },                                                   // 0xCD 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::DEC, cycles: 6 },                                                   // 0xCE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(4), cycles: 5 },                                       // 0xCF 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BNE, cycles: 2 },                                     // 0xD0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::CMP, cycles: 5 },                                   // 0xD1 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::CMP, cycles: 5 },                                           // 0xD2 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xD3), cycles: 1 },                                          // 0xD3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::Invalid(0xD4), cycles: 4 },                                 // 0xD4 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::CMP, cycles: 4 },                                           // 0xD5 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::DEC, cycles: 6 },                                           // 0xD6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(5), cycles: 5 },                                               // 0xD7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::CLD, cycles: 2 },                                                    // 0xD8 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::CMP, cycles: 4 },                                           // 0xD9 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::PHX, cycles: 3 },                                                    // 0xDA 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::STP, cycles: 3 },                                                    // 0xDB 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::Invalid(0xDC), cycles: 4 },                                         // 0xDC [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::CMP, cycles: 4 },                                           // 0xDD 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::DEC, cycles: 7 },                                           // 0xDE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(5), cycles: 5 },                                       // 0xDF 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::CPX, cycles: 2 },                                                  // 0xE0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedIndirect, mnemonic: Mnemonic::SBC, cycles: 6 },                                    // 0xE1 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::Invalid(0xE2), cycles: 2 },                                        // 0xE2 [Invalid]
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xE3), cycles: 1 },                                          // 0xE3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::CPX, cycles: 3 },                                                   // 0xE4 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SBC, cycles: 3 },                                                   // 0xE5 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::INC, cycles: 5 },                                                   // 0xE6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(6), cycles: 5 },                                               // 0xE7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::INX, cycles: 2 },                                                    // 0xE8 
+        Operation { addressing_mode: AddressingMode::Immediate, mnemonic: Mnemonic::SBC, cycles: 2 },                                                  // 0xE9 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::NOP, cycles: 2 },                                                    // 0xEA 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xEB), cycles: 1 },                                          // 0xEB [Invalid]
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::CPX, cycles: 4 },                                                   // 0xEC 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::SBC, cycles: 4 },                                                   // 0xED 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::INC, cycles: 6 },                                                   // 0xEE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(6), cycles: 5 },                                       // 0xEF 
+        Operation { addressing_mode: AddressingMode::ProgramCounterRelative, mnemonic: Mnemonic::BEQ, cycles: 2 },                                     // 0xF0 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirectIndexedY, mnemonic: Mnemonic::SBC, cycles: 5 },                                   // 0xF1 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndirect, mnemonic: Mnemonic::SBC, cycles: 5 },                                           // 0xF2 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xF3), cycles: 1 },                                          // 0xF3 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::Invalid(0xF4), cycles: 4 },                                 // 0xF4 [Invalid]
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::SBC, cycles: 4 },                                           // 0xF5 
+        Operation { addressing_mode: AddressingMode::ZeroPageIndexedX, mnemonic: Mnemonic::INC, cycles: 6 },                                           // 0xF6 
+        Operation { addressing_mode: AddressingMode::ZeroPage, mnemonic: Mnemonic::SMBN(7), cycles: 5 },                                               // 0xF7 
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::SED, cycles: 2 },                                                    // 0xF8 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedY, mnemonic: Mnemonic::SBC, cycles: 4 },                                           // 0xF9 
+        Operation { addressing_mode: AddressingMode::Stack, mnemonic: Mnemonic::PLX, cycles: 4 },                                                      // 0xFA
+        Operation { addressing_mode: AddressingMode::Implied, mnemonic: Mnemonic::Invalid(0xFB), cycles: 1 },                                          // 0xFB [Invalid] 
+        Operation { addressing_mode: AddressingMode::Absolute, mnemonic: Mnemonic::Invalid(0xFC), cycles: 4 },                                         // 0xFC [Invalid]
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::SBC, cycles: 4 },                                           // 0xFD 
+        Operation { addressing_mode: AddressingMode::AbsoluteIndexedX, mnemonic: Mnemonic::INC, cycles: 7 },                                           // 0xFE 
+        Operation { addressing_mode: AddressingMode::ZeroPageRelative, mnemonic: Mnemonic::BBSN(7), cycles: 5 },                                       // 0xFF 
     ];
 
     //#GROUP: artery functions
     #[inline]
-    fn fetch_u8(&mut self, bus: &mut dyn Bus) -> u8{
-        let val = bus.read(self.program_counter);
+    fn fetch_u8<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u8, BusError>{
+        let val = bus.read(self.program_counter)?;
         self.program_counter = self.program_counter.wrapping_add(1);
-        val
-    }
-    #[inline]
-    fn fetch_u16(&mut self, bus: &mut dyn Bus) -> u16{
-        let low = self.fetch_u8(bus) as u16;
-        let high = self.fetch_u8(bus) as u16;
-        (high << 8) | low
+        Ok(val)
     }
 
     #[inline]
-    fn stack_push_u8(&mut self, bus: &mut dyn Bus, val: u8){
-        bus.write(Self::STACK_POINTER_BASE | self.stack_pointer as u16, val);
+    fn stack_push_u8<B: Bus + ?Sized>(&mut self, bus: &mut B, val: u8) -> Result<(), BusError>{
+        bus.write(Self::STACK_POINTER_BASE | self.stack_pointer as u16, val)?;
         self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+        Ok(())
     }
     #[inline]
-    fn stack_pull_u8(&mut self, bus: &mut dyn Bus) -> u8{
+    fn stack_pull_u8<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u8, BusError>{
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
         bus.read(Self::STACK_POINTER_BASE | self.stack_pointer as u16)
     }
 
-    fn irq_run(&mut self, _bus: &mut dyn Bus){
-        todo!();
+    fn irq_run<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(), BusError>{
+        self.enter_interrupt(bus, Self::IRQB_LOW)
     }
-    fn nmi_run(&mut self, _bus: &mut dyn Bus){
-        todo!();
+    fn nmi_run<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(), BusError>{
+        self.enter_interrupt(bus, Self::NMIB_LOW)
     }
+    fn enter_interrupt<B: Bus + ?Sized>(&mut self, bus: &mut B, vector: u16) -> Result<(), BusError>{
+        self.stack_push_u8(bus, (self.program_counter >> 8) as u8)?;
+        self.stack_push_u8(bus, (self.program_counter & 0xff) as u8)?;
+        self.stack_push_u8(bus, (self.processor_status_register | 0x20) & !0x10)?;
+
+        self.status_set(Status::I, true);
+        self.status_set(Status::D, false);
+
+        self.program_counter = bus.read_u16(vector)?;
+        Ok(())
+    }
+
+    pub fn reset<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(), CpuError>{
+        let entry = bus.read_u16(Self::RESB_LOW)?;
+        self.reset_to(entry);
 
-    pub fn reset(&mut self, bus: &mut dyn Bus){
-        let entry = read_u16(bus, Self::RESB_LOW);
+        Ok(())
+    }
+    /// Resets as `reset` does, but starts executing at `entry` rather than where the reset vector points.
+    pub fn reset_to(&mut self, entry: u16){
         self.set_p_default();
         self.program_counter = entry;
     }
 
-    pub fn step(&mut self, bus: &mut dyn Bus) -> Result<Mnemomic, CpuError>{
-        let opcode = self.fetch_u8(bus);
-        let operation = Self::OPERATIONS[opcode as usize].as_ref().ok_or(CpuError::InvalidOpcode(opcode))?;
-        
-        let operand = resolve_operand(self, bus, &operation.addressing_mode);
-        (operation.exec)(self, bus, operand)?;
+    /// Executes one instruction, then takes an interrupt if one is pending. Compiled for the bus it is given, so
+    /// that stepping a `Machine` calls its accesses directly; a `&mut dyn Bus` works too, through its vtable.
+    pub fn step<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<Mnemonic, CpuError>{
+        let (mnemonic, cycles) = self.execute_next(bus)?;
+        self.retire(bus, cycles)?;
 
-        //check lines
-        //run appropriate interrupt if applicable
-        //nmi_run()
-        //irq_run()
+        Ok(mnemonic)
+    }
+    /// Executes one instruction without retiring it, returning it and the cycles it took.
+    #[inline]
+    fn execute_next<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(Mnemonic, u32), CpuError>{
+        if bus.attributes(self.program_counter).contains(RegionAttributes::NO_EXECUTE){
+            return Err(CpuError::ExecuteFromNoExecute(self.program_counter));
+        }
+        bus.begin_instruction(self.program_counter);
+
+        let (operation, operand) = match bus.decoded_instruction(self.program_counter){
+            Some(decoded) => {
+                self.program_counter = self.program_counter.wrapping_add(1);
+                let operand = resolve_operand(self, bus, &decoded.operation.addressing_mode, &mut FromDecoded(decoded.operand, 0))?;
+                (decoded.operation, operand)
+            },
+            None => {
+                let operation = Self::OPERATIONS[self.fetch_u8(bus)? as usize];
+                (operation, resolve_operand(self, bus, &operation.addressing_mode, &mut FromBus)?)
+            },
+        };
+        let page_crossed = operand.page_crossed;
+        let next_pc = self.program_counter;
+        execute(operation.mnemonic, self, bus, operand)?;
+
+        let cycles = operation.cycles + operation.extra_cycles(page_crossed, next_pc, self.program_counter);
+        Ok((operation.mnemonic, cycles as u32))
+    }
+    /// Executes up to `count` instructions, stopping early after a `BRK`, at an error, or once the cycle count
+    /// reaches `stop_cycles`. On a bus that says it is quiet, nothing can interrupt the CPU and ticking does
+    /// nothing, so neither is done between instructions; on any other, each is stepped as `step` does. Callers
+    /// with hooks or breakpoints to check between instructions step instead.
+    pub fn run_block<B: Bus + ?Sized>(&mut self, bus: &mut B, count: u64, stop_cycles: u64) -> BlockEnd{
+        if !bus.is_quiet(){
+            for _ in 0..count{
+                let pc = self.program_counter;
+                let retired = self.execute_next(bus).and_then(|(mnemonic, cycles)| Ok((mnemonic, self.retire(bus, cycles)?)));
+                match retired{
+                    Ok((Mnemonic::BRK, _)) => return BlockEnd::Brk { pc },
+                    Ok((_, Some(arbitration))) => return BlockEnd::Interrupted(arbitration),
+                    Ok((_, None)) => {},
+                    Err(error) => return BlockEnd::Failed { pc, error },
+                }
+                if self.cycles >= stop_cycles{
+                    break;
+                }
+            }
+            return BlockEnd::Ran;
+        }
 
-        Ok(operation.mnemomic)
+        // a quiet bus holds the NMI and RESET lines high, as the next look at them would have found
+        self.nmi_line = false;
+        self.reset_line = false;
+        self.last_arbitration = None;
+        for _ in 0..count{
+            let pc = self.program_counter;
+            match self.execute_next(bus){
+                Ok((Mnemonic::BRK, cycles)) => {
+                    self.cycles += cycles as u64;
+                    self.instructions += 1;
+                    return BlockEnd::Brk { pc };
+                },
+                Ok((_, cycles)) => {
+                    self.cycles += cycles as u64;
+                    self.instructions += 1;
+                },
+                Err(error) => return BlockEnd::Failed { pc, error },
+            }
+            if self.cycles >= stop_cycles{
+                break;
+            }
+        }
+        BlockEnd::Ran
+    }
+    /// Executes one instruction as `step` does, but as the CPU used to: fetched through the bus every time and
+    /// dispatched through a table of function pointers, for benchmarks to compare against.
+    #[cfg(feature = "dispatch-bench")]
+    pub fn step_by_table(&mut self, bus: &mut dyn Bus) -> Result<Mnemonic, CpuError>{
+        if bus.attributes(self.program_counter).contains(RegionAttributes::NO_EXECUTE){
+            return Err(CpuError::ExecuteFromNoExecute(self.program_counter));
+        }
+        bus.begin_instruction(self.program_counter);
+
+        let opcode = self.fetch_u8(bus)?;
+        let operation = Self::OPERATIONS[opcode as usize];
+        let operand = resolve_operand(self, bus, &operation.addressing_mode, &mut FromBus)?;
+        let page_crossed = operand.page_crossed;
+        let next_pc = self.program_counter;
+        let argument = match operation.mnemonic{
+            Mnemonic::BBRN(n) | Mnemonic::BBSN(n) | Mnemonic::RMBN(n) | Mnemonic::SMBN(n) => n,
+            _ => opcode,
+        };
+        HANDLERS[opcode as usize](self, bus, operand, argument)?;
+
+        let cycles = operation.cycles + operation.extra_cycles(page_crossed, next_pc, self.program_counter);
+        self.retire(bus, cycles as u32)?;
+
+        Ok(operation.mnemonic)
+    }
+    /// Counts an instruction of `cycles` cycles as executed, ticking the bus by them, then takes an interrupt if
+    /// one is pending, returning how it was chosen.
+    #[inline]
+    pub(crate) fn retire<B: Bus + ?Sized>(&mut self, bus: &mut B, cycles: u32) -> Result<Option<Arbitration>, BusError>{
+        self.advance(bus, cycles);
+        self.instructions += 1;
+        let arbitration = self.arbitrate(bus);
+        self.last_arbitration = arbitration.as_ref().ok().copied().flatten();
+        arbitration
+    }
+    /// The interrupt the CPU took after the last instruction it executed, and those it passed over for it; None
+    /// when it went straight on to the next instruction.
+    pub fn last_arbitration(&self) -> Option<Arbitration>{
+        self.last_arbitration
+    }
+    /// Takes the interrupt that wins of those pending at an instruction boundary, RESET over NMI over IRQ, as the
+    /// chip ranks them. RESET and NMI respond to their line becoming asserted, which latches them until the
+    /// boundary; a RESET drops an NMI latched with it. IRQ is a level, seen while the I flag is clear, so one that
+    /// loses is taken at a later boundary if it is still asserted and unmasked there.
+    ///
+    /// A RESET restarts the CPU from the reset vector as `reset` does, right away rather than once the line is
+    /// released.
+    fn arbitrate<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<Option<Arbitration>, BusError>{
+        let (reset, nmi) = (bus.reset(), bus.nmi());
+        let reset_edge = reset && !self.reset_line;
+        let nmi_edge = nmi && !self.nmi_line;
+        self.reset_line = reset;
+        self.nmi_line = nmi;
+        let irq = bus.irq() && !self.status_check(Status::I);
+
+        let taken = match (reset_edge, nmi_edge, irq){
+            (true, _, _) => Interrupt::Reset,
+            (false, true, _) => Interrupt::Nmi,
+            (false, false, true) => Interrupt::Irq,
+            (false, false, false) => return Ok(None),
+        };
+        let arbitration = Arbitration { pc: self.program_counter, taken, reset: reset_edge, nmi: nmi_edge, irq };
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("interrupt", kind = %taken, interrupted = arbitration.pc, cycles = self.cycles).entered();
+        match taken{
+            Interrupt::Reset => {
+                let entry = bus.read_u16(Self::RESB_LOW)?;
+                self.reset_to(entry);
+            },
+            Interrupt::Nmi => self.nmi_run(bus)?,
+            Interrupt::Irq => self.irq_run(bus)?,
+        }
+        self.advance(bus, 7);
+        log::debug!("{}, to the handler at ${:04X}", arbitration, self.program_counter);
+
+        Ok(Some(arbitration))
+    }
+
+    #[inline]
+    fn advance<B: Bus + ?Sized>(&mut self, bus: &mut B, cycles: u32){
+        self.cycles += cycles as u64;
+        bus.tick(cycles);
+    }
+    /// Cycles elapsed since the CPU was constructed, including interrupt entry.
+    pub fn cycles(&self) -> u64{
+        self.cycles
+    }
+    /// Instructions executed since the CPU was constructed.
+    pub fn instructions(&self) -> u64{
+        self.instructions
+    }
+
+    /// Sets the cycle and instruction counts, as when picking up a saved run.
+    pub fn set_counts(&mut self, cycles: u64, instructions: u64){
+        self.cycles = cycles;
+        self.instructions = instructions;
+    }
+
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy{
+        self.invalid_opcodes
+    }
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy){
+        self.invalid_opcodes = policy;
+    }
+
+    /// The operation of an opcode the W65C02S defines, None for one it does not.
+    pub fn operation(opcode: u8) -> Option<Operation>{
+        Some(Self::OPERATIONS[opcode as usize]).filter(|operation| !matches!(operation.mnemonic, Mnemonic::Invalid(_)))
+    }
+
+    /// Steps until a `BRK` has executed, or until one of the limits is exceeded, which is returned.
+    pub fn run<B: Bus + ?Sized>(&mut self, bus: &mut B, limits: &RunLimits) -> Result<Option<LimitExceeded>, CpuError>{
+        let started = Instant::now();
+        loop{
+            if let Some(exceeded) = limits.check(self, started){
+                return Ok(Some(exceeded));
+            }
+            let (count, stop_cycles) = limits.batch(self);
+            match self.run_block(bus, count, stop_cycles){
+                BlockEnd::Ran | BlockEnd::Interrupted(_) => {},
+                BlockEnd::Brk { .. } => return Ok(None),
+                BlockEnd::Failed { error, .. } => return Err(error),
+            }
+        }
+    }
+
+    pub fn register(&self, register: Register) -> u16{
+        match register{
+            Register::PC => self.program_counter,
+            Register::A => self.a_register as u16,
+            Register::X => self.x_register as u16,
+            Register::Y => self.y_register as u16,
+            Register::SP => self.stack_pointer as u16,
+            Register::P => self.processor_status_register as u16,
+        }
+    }
+
+    /// Sets a register, truncating the value for the 8-bit ones.
+    pub fn set_register(&mut self, register: Register, value: u16){
+        match register{
+            Register::PC => self.program_counter = value,
+            Register::A => self.a_register = value as u8,
+            Register::X => self.x_register = value as u8,
+            Register::Y => self.y_register = value as u8,
+            Register::SP => self.stack_pointer = value as u8,
+            Register::P => self.processor_status_register = value as u8,
+        }
+    }
+
+    /// Every register whose value differs, treating `self` as the actual state and `expected` as the golden one.
+    pub fn diff(&self, expected: &W65C02S) -> Vec<RegisterDifference>{
+        [Register::PC, Register::A, Register::X, Register::Y, Register::SP, Register::P].into_iter()
+            .filter(|r| self.register(*r) != expected.register(*r))
+            .map(|register| RegisterDifference { register, expected: expected.register(register), actual: self.register(register) })
+            .collect()
     }
 
     //#GROUP: processor status register helpers
@@ -373,7 +743,7 @@ impl W65C02S{
         self.processor_status_register = (self.processor_status_register & !mask) | (mask * val as u8);
     }
     #[inline]
-    fn status_check(&self, flag: Status) -> bool{
+    pub fn status_check(&self, flag: Status) -> bool{
         self.processor_status_register & flag.mask() > 0
     }
     #[inline]
@@ -385,12 +755,206 @@ impl W65C02S{
     fn set_p_default(&mut self){
         self.processor_status_register = 0x34; // 0b00110100
     }
+
+    /// Where the counts and the interrupt lines lie within the CPU, for compiled code that retires instructions itself.
+    #[cfg(feature = "jit")]
+    pub(crate) const CYCLES_OFFSET: usize = std::mem::offset_of!(W65C02S, cycles);
+    #[cfg(feature = "jit")]
+    pub(crate) const INSTRUCTIONS_OFFSET: usize = std::mem::offset_of!(W65C02S, instructions);
+    #[cfg(feature = "jit")]
+    pub(crate) const NMI_LINE_OFFSET: usize = std::mem::offset_of!(W65C02S, nmi_line);
+    #[cfg(feature = "jit")]
+    pub(crate) const RESET_LINE_OFFSET: usize = std::mem::offset_of!(W65C02S, reset_line);
+    /// Where a register lies within the CPU, for compiled code to reach it through a pointer.
+    #[cfg(feature = "jit")]
+    pub(crate) const fn register_offset(register: Register) -> usize{
+        match register{
+            Register::PC => std::mem::offset_of!(W65C02S, program_counter),
+            Register::A => std::mem::offset_of!(W65C02S, a_register),
+            Register::X => std::mem::offset_of!(W65C02S, x_register),
+            Register::Y => std::mem::offset_of!(W65C02S, y_register),
+            Register::SP => std::mem::offset_of!(W65C02S, stack_pointer),
+            Register::P => std::mem::offset_of!(W65C02S, processor_status_register),
+        }
+    }
 }
 
 type OpReturn = Result<(), CpuError>;
-type OpFn = fn(&mut W65C02S, &mut dyn Bus, ResolvedOperand) -> OpReturn;
+
+/// Carries out an instruction's operation. Dispatching on the mnemonic rather than through a table of function
+/// pointers lets the operations be compiled for each kind of bus, so that their memory accesses are direct calls.
+fn execute<B: Bus + ?Sized>(mnemonic: Mnemonic, cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
+    match mnemonic{
+        Mnemonic::ADC => op_adc(cpu, bus, r),
+        Mnemonic::AND => op_and(cpu, bus, r),
+        Mnemonic::ASL => op_asl(cpu, bus, r),
+        Mnemonic::BBRN(n) => op_bbrn(cpu, bus, r, n),
+        Mnemonic::BBSN(n) => op_bbsn(cpu, bus, r, n),
+        Mnemonic::BCC => op_bcc(cpu, bus, r),
+        Mnemonic::BCS => op_bcs(cpu, bus, r),
+        Mnemonic::BEQ => op_beq(cpu, bus, r),
+        Mnemonic::BIT => op_bit(cpu, bus, r),
+        Mnemonic::BMI => op_bmi(cpu, bus, r),
+        Mnemonic::BNE => op_bne(cpu, bus, r),
+        Mnemonic::BPL => op_bpl(cpu, bus, r),
+        Mnemonic::BRA => op_bra(cpu, bus, r),
+        Mnemonic::BRK => op_brk(cpu, bus, r),
+        Mnemonic::BVC => op_bvc(cpu, bus, r),
+        Mnemonic::BVS => op_bvs(cpu, bus, r),
+        Mnemonic::CLC => op_clc(cpu, bus, r),
+        Mnemonic::CLD => op_cld(cpu, bus, r),
+        Mnemonic::CLI => op_cli(cpu, bus, r),
+        Mnemonic::CLV => op_clv(cpu, bus, r),
+        Mnemonic::CMP => op_cmp(cpu, bus, r),
+        Mnemonic::CPX => op_cpx(cpu, bus, r),
+        Mnemonic::CPY => op_cpy(cpu, bus, r),
+        Mnemonic::DEC => op_dec(cpu, bus, r),
+        Mnemonic::DEX => op_dex(cpu, bus, r),
+        Mnemonic::DEY => op_dey(cpu, bus, r),
+        Mnemonic::EOR => op_eor(cpu, bus, r),
+        Mnemonic::INC => op_inc(cpu, bus, r),
+        Mnemonic::INX => op_inx(cpu, bus, r),
+        Mnemonic::INY => op_iny(cpu, bus, r),
+        Mnemonic::JMP => op_jmp(cpu, bus, r),
+        Mnemonic::JSR => op_jsr(cpu, bus, r),
+        Mnemonic::LDA => op_lda(cpu, bus, r),
+        Mnemonic::LDX => op_ldx(cpu, bus, r),
+        Mnemonic::LDY => op_ldy(cpu, bus, r),
+        Mnemonic::LSR => op_lsr(cpu, bus, r),
+        Mnemonic::NOP => op_nop(cpu, bus, r),
+        Mnemonic::ORA => op_ora(cpu, bus, r),
+        Mnemonic::PHA => op_pha(cpu, bus, r),
+        Mnemonic::PHP => op_php(cpu, bus, r),
+        Mnemonic::PHX => op_phx(cpu, bus, r),
+        Mnemonic::PHY => op_phy(cpu, bus, r),
+        Mnemonic::PLA => op_pla(cpu, bus, r),
+        Mnemonic::PLP => op_plp(cpu, bus, r),
+        Mnemonic::PLX => op_plx(cpu, bus, r),
+        Mnemonic::PLY => op_ply(cpu, bus, r),
+        Mnemonic::RMBN(n) => op_rmbn(cpu, bus, r, n),
+        Mnemonic::ROL => op_rol(cpu, bus, r),
+        Mnemonic::ROR => op_ror(cpu, bus, r),
+        Mnemonic::RTI => op_rti(cpu, bus, r),
+        Mnemonic::RTS => op_rts(cpu, bus, r),
+        Mnemonic::SBC => op_sbc(cpu, bus, r),
+        Mnemonic::SEC => op_sec(cpu, bus, r),
+        Mnemonic::SED => op_sed(cpu, bus, r),
+        Mnemonic::SEI => op_sei(cpu, bus, r),
+        Mnemonic::SMBN(n) => op_smbn(cpu, bus, r, n),
+        Mnemonic::STA => op_sta(cpu, bus, r),
+        Mnemonic::STP => op_stp(cpu, bus, r),
+        Mnemonic::STX => op_stx(cpu, bus, r),
+        Mnemonic::STY => op_sty(cpu, bus, r),
+        Mnemonic::STZ => op_stz(cpu, bus, r),
+        Mnemonic::TAX => op_tax(cpu, bus, r),
+        Mnemonic::TAY => op_tay(cpu, bus, r),
+        Mnemonic::TRB => op_trb(cpu, bus, r),
+        Mnemonic::TSB => op_tsb(cpu, bus, r),
+        Mnemonic::TSX => op_tsx(cpu, bus, r),
+        Mnemonic::TXA => op_txa(cpu, bus, r),
+        Mnemonic::TXS => op_txs(cpu, bus, r),
+        Mnemonic::TYA => op_tya(cpu, bus, r),
+        Mnemonic::WAI => op_wai(cpu, bus, r),
+        Mnemonic::Invalid(opcode) => op_invalid(cpu, bus, r, opcode),
+    }
+}
+
+/// Carries out an operation through a `&mut dyn Bus`, given the bit number of the bit instructions and the
+/// opcode of the others.
+#[cfg(feature = "dispatch-bench")]
+type Handler = fn(&mut W65C02S, &mut dyn Bus, ResolvedOperand, u8) -> OpReturn;
+
+/// The handler of every opcode, as the CPU dispatched before it matched on the mnemonic, kept for benchmarks to
+/// compare the two.
+#[cfg(feature = "dispatch-bench")]
+const HANDLERS: [Handler; 256] = {
+    let mut handlers = [handler(Mnemonic::NOP); 256];
+    let mut opcode = 0;
+    while opcode < 256{
+        handlers[opcode] = handler(W65C02S::OPERATIONS[opcode].mnemonic);
+        opcode += 1;
+    }
+    handlers
+};
+#[cfg(feature = "dispatch-bench")]
+const fn handler(mnemonic: Mnemonic) -> Handler{
+    match mnemonic{
+        Mnemonic::ADC => |cpu, bus, r, _| op_adc(cpu, bus, r),
+        Mnemonic::AND => |cpu, bus, r, _| op_and(cpu, bus, r),
+        Mnemonic::ASL => |cpu, bus, r, _| op_asl(cpu, bus, r),
+        Mnemonic::BBRN(_) => |cpu, bus, r, n| op_bbrn(cpu, bus, r, n),
+        Mnemonic::BBSN(_) => |cpu, bus, r, n| op_bbsn(cpu, bus, r, n),
+        Mnemonic::BCC => |cpu, bus, r, _| op_bcc(cpu, bus, r),
+        Mnemonic::BCS => |cpu, bus, r, _| op_bcs(cpu, bus, r),
+        Mnemonic::BEQ => |cpu, bus, r, _| op_beq(cpu, bus, r),
+        Mnemonic::BIT => |cpu, bus, r, _| op_bit(cpu, bus, r),
+        Mnemonic::BMI => |cpu, bus, r, _| op_bmi(cpu, bus, r),
+        Mnemonic::BNE => |cpu, bus, r, _| op_bne(cpu, bus, r),
+        Mnemonic::BPL => |cpu, bus, r, _| op_bpl(cpu, bus, r),
+        Mnemonic::BRA => |cpu, bus, r, _| op_bra(cpu, bus, r),
+        Mnemonic::BRK => |cpu, bus, r, _| op_brk(cpu, bus, r),
+        Mnemonic::BVC => |cpu, bus, r, _| op_bvc(cpu, bus, r),
+        Mnemonic::BVS => |cpu, bus, r, _| op_bvs(cpu, bus, r),
+        Mnemonic::CLC => |cpu, bus, r, _| op_clc(cpu, bus, r),
+        Mnemonic::CLD => |cpu, bus, r, _| op_cld(cpu, bus, r),
+        Mnemonic::CLI => |cpu, bus, r, _| op_cli(cpu, bus, r),
+        Mnemonic::CLV => |cpu, bus, r, _| op_clv(cpu, bus, r),
+        Mnemonic::CMP => |cpu, bus, r, _| op_cmp(cpu, bus, r),
+        Mnemonic::CPX => |cpu, bus, r, _| op_cpx(cpu, bus, r),
+        Mnemonic::CPY => |cpu, bus, r, _| op_cpy(cpu, bus, r),
+        Mnemonic::DEC => |cpu, bus, r, _| op_dec(cpu, bus, r),
+        Mnemonic::DEX => |cpu, bus, r, _| op_dex(cpu, bus, r),
+        Mnemonic::DEY => |cpu, bus, r, _| op_dey(cpu, bus, r),
+        Mnemonic::EOR => |cpu, bus, r, _| op_eor(cpu, bus, r),
+        Mnemonic::INC => |cpu, bus, r, _| op_inc(cpu, bus, r),
+        Mnemonic::INX => |cpu, bus, r, _| op_inx(cpu, bus, r),
+        Mnemonic::INY => |cpu, bus, r, _| op_iny(cpu, bus, r),
+        Mnemonic::JMP => |cpu, bus, r, _| op_jmp(cpu, bus, r),
+        Mnemonic::JSR => |cpu, bus, r, _| op_jsr(cpu, bus, r),
+        Mnemonic::LDA => |cpu, bus, r, _| op_lda(cpu, bus, r),
+        Mnemonic::LDX => |cpu, bus, r, _| op_ldx(cpu, bus, r),
+        Mnemonic::LDY => |cpu, bus, r, _| op_ldy(cpu, bus, r),
+        Mnemonic::LSR => |cpu, bus, r, _| op_lsr(cpu, bus, r),
+        Mnemonic::NOP => |cpu, bus, r, _| op_nop(cpu, bus, r),
+        Mnemonic::ORA => |cpu, bus, r, _| op_ora(cpu, bus, r),
+        Mnemonic::PHA => |cpu, bus, r, _| op_pha(cpu, bus, r),
+        Mnemonic::PHP => |cpu, bus, r, _| op_php(cpu, bus, r),
+        Mnemonic::PHX => |cpu, bus, r, _| op_phx(cpu, bus, r),
+        Mnemonic::PHY => |cpu, bus, r, _| op_phy(cpu, bus, r),
+        Mnemonic::PLA => |cpu, bus, r, _| op_pla(cpu, bus, r),
+        Mnemonic::PLP => |cpu, bus, r, _| op_plp(cpu, bus, r),
+        Mnemonic::PLX => |cpu, bus, r, _| op_plx(cpu, bus, r),
+        Mnemonic::PLY => |cpu, bus, r, _| op_ply(cpu, bus, r),
+        Mnemonic::RMBN(_) => |cpu, bus, r, n| op_rmbn(cpu, bus, r, n),
+        Mnemonic::ROL => |cpu, bus, r, _| op_rol(cpu, bus, r),
+        Mnemonic::ROR => |cpu, bus, r, _| op_ror(cpu, bus, r),
+        Mnemonic::RTI => |cpu, bus, r, _| op_rti(cpu, bus, r),
+        Mnemonic::RTS => |cpu, bus, r, _| op_rts(cpu, bus, r),
+        Mnemonic::SBC => |cpu, bus, r, _| op_sbc(cpu, bus, r),
+        Mnemonic::SEC => |cpu, bus, r, _| op_sec(cpu, bus, r),
+        Mnemonic::SED => |cpu, bus, r, _| op_sed(cpu, bus, r),
+        Mnemonic::SEI => |cpu, bus, r, _| op_sei(cpu, bus, r),
+        Mnemonic::SMBN(_) => |cpu, bus, r, n| op_smbn(cpu, bus, r, n),
+        Mnemonic::STA => |cpu, bus, r, _| op_sta(cpu, bus, r),
+        Mnemonic::STP => |cpu, bus, r, _| op_stp(cpu, bus, r),
+        Mnemonic::STX => |cpu, bus, r, _| op_stx(cpu, bus, r),
+        Mnemonic::STY => |cpu, bus, r, _| op_sty(cpu, bus, r),
+        Mnemonic::STZ => |cpu, bus, r, _| op_stz(cpu, bus, r),
+        Mnemonic::TAX => |cpu, bus, r, _| op_tax(cpu, bus, r),
+        Mnemonic::TAY => |cpu, bus, r, _| op_tay(cpu, bus, r),
+        Mnemonic::TRB => |cpu, bus, r, _| op_trb(cpu, bus, r),
+        Mnemonic::TSB => |cpu, bus, r, _| op_tsb(cpu, bus, r),
+        Mnemonic::TSX => |cpu, bus, r, _| op_tsx(cpu, bus, r),
+        Mnemonic::TXA => |cpu, bus, r, _| op_txa(cpu, bus, r),
+        Mnemonic::TXS => |cpu, bus, r, _| op_txs(cpu, bus, r),
+        Mnemonic::TYA => |cpu, bus, r, _| op_tya(cpu, bus, r),
+        Mnemonic::WAI => |cpu, bus, r, _| op_wai(cpu, bus, r),
+        Mnemonic::Invalid(_) => |cpu, bus, r, opcode| op_invalid(cpu, bus, r, opcode),
+    }
+}
+
 //#GROUP: op implementations
-fn op_adc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_adc<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let sum = cpu.a_register as u16 + val as u16 + cpu.status_check(Status::C) as u16;
     let result = sum as u8;
@@ -406,7 +970,7 @@ fn op_adc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_and(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_and<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.a_register & val;
 
@@ -416,7 +980,7 @@ fn op_and(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_asl(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_asl<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val << 1;
 
@@ -427,12 +991,12 @@ fn op_asl(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_bbrn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> OpReturn{
+fn op_bbrn<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand, n: u8) -> OpReturn{
     let mask = 1u8 << n;
 
     match r.operand{
         Operand::ZpAddrRelative(addr, offset) => {
-            let val = bus.read(addr as u16);
+            let val = bus.read(addr as u16)?;
 
             if (val & mask) == 0{
                 cpu.program_counter = cpu.program_counter.wrapping_add_signed(offset as i16);
@@ -443,12 +1007,12 @@ fn op_bbrn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> O
         _ => unreachable!(),
     }
 }
-fn op_bbsn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> OpReturn{
+fn op_bbsn<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand, n: u8) -> OpReturn{
     let mask = 1u8 << n;
 
     match r.operand{
         Operand::ZpAddrRelative(addr, offset) => {
-            let val = bus.read(addr as u16);
+            let val = bus.read(addr as u16)?;
 
             if (val & mask) > 0{
                 cpu.program_counter = cpu.program_counter.wrapping_add_signed(offset as i16);
@@ -459,7 +1023,7 @@ fn op_bbsn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> O
         _ => unreachable!(),
     }
 }
-fn op_bcc(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bcc<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if !cpu.status_check(Status::C){
@@ -471,7 +1035,7 @@ fn op_bcc(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bcs(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bcs<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if cpu.status_check(Status::C){
@@ -483,7 +1047,7 @@ fn op_bcs(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_beq(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_beq<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if cpu.status_check(Status::Z){
@@ -495,7 +1059,7 @@ fn op_beq(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bit(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bit<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     cpu.status_set(Status::Z, (cpu.a_register & val) == 0);
 
@@ -509,7 +1073,7 @@ fn op_bit(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_bmi(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bmi<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if cpu.status_check(Status::N){
@@ -521,7 +1085,7 @@ fn op_bmi(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bne(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bne<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if !cpu.status_check(Status::Z){
@@ -533,7 +1097,7 @@ fn op_bne(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bpl(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bpl<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if !cpu.status_check(Status::N){
@@ -545,7 +1109,7 @@ fn op_bpl(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bra(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bra<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => {
             cpu.program_counter = cpu.program_counter.wrapping_add_signed(offset as i16);
@@ -555,24 +1119,20 @@ fn op_bra(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_brk(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_brk<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     let return_addr = cpu.program_counter.wrapping_add(1);
 
-    cpu.stack_push_u8(bus, (return_addr >> 8) as u8);
-    cpu.stack_push_u8(bus, (return_addr & 0xff) as u8);
-    cpu.stack_push_u8(bus, cpu.processor_status_register | 0x10);
+    cpu.stack_push_u8(bus, (return_addr >> 8) as u8)?;
+    cpu.stack_push_u8(bus, (return_addr & 0xff) as u8)?;
+    cpu.stack_push_u8(bus, cpu.processor_status_register | 0x10)?;
 
     cpu.status_set(Status::I, true);
 
-    let low = bus.read(W65C02S::IRQB_LOW) as u16;
-    let high = bus.read(W65C02S::IRQB_LOW + 1) as u16;
-    let target = (high << 8) | low;
-
-    cpu.program_counter = target;
+    cpu.program_counter = bus.read_u16(W65C02S::IRQB_LOW)?;
 
     Ok(())
 }
-fn op_bvc(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bvc<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if !cpu.status_check(Status::V){
@@ -584,7 +1144,7 @@ fn op_bvc(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_bvs(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_bvs<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Relative(offset) => { 
             if cpu.status_check(Status::V){
@@ -596,27 +1156,27 @@ fn op_bvs(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn
         _ => unreachable!(),
     }
 }
-fn op_clc(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_clc<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::C, false);
 
     Ok(())
 }
-fn op_cld(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_cld<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::D, false);
 
     Ok(())
 }
-fn op_cli(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_cli<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::I, false);
 
     Ok(())
 }
-fn op_clv(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_clv<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::V, false);
 
     Ok(())
 }
-fn op_cmp(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_cmp<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.a_register.wrapping_sub(val);
 
@@ -625,7 +1185,7 @@ fn op_cmp(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_cpx(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_cpx<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.x_register.wrapping_sub(val);
 
@@ -634,7 +1194,7 @@ fn op_cpx(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_cpy(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_cpy<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.y_register.wrapping_sub(val);
 
@@ -643,7 +1203,7 @@ fn op_cpy(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_dec(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_dec<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val.wrapping_sub(1);
 
@@ -653,7 +1213,7 @@ fn op_dec(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_dex(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_dex<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     let result = cpu.x_register.wrapping_sub(1);
 
     cpu.status_update_zn(result);
@@ -662,7 +1222,7 @@ fn op_dex(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpRetur
 
     Ok(())
 }
-fn op_dey(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_dey<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     let result = cpu.y_register.wrapping_sub(1);
 
     cpu.status_update_zn(result);
@@ -671,7 +1231,7 @@ fn op_dey(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpRetur
 
     Ok(())
 }
-fn op_eor(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_eor<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.a_register ^ val;
 
@@ -681,7 +1241,7 @@ fn op_eor(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_inc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_inc<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val.wrapping_add(1);
 
@@ -691,7 +1251,7 @@ fn op_inc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_inx(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_inx<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     let result = cpu.x_register.wrapping_add(1);
     
     cpu.status_update_zn(result);
@@ -700,7 +1260,7 @@ fn op_inx(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpRetur
 
     Ok(())
 }
-fn op_iny(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_iny<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     let result = cpu.y_register.wrapping_add(1);
     
     cpu.status_update_zn(result);
@@ -709,21 +1269,21 @@ fn op_iny(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpRetur
 
     Ok(())
 }
-fn op_jmp(cpu: &mut W65C02S, _bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_jmp<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Address(addr) => { cpu.program_counter = addr; Ok(())},
         _ => unreachable!(),
     }
 }
-fn op_jsr(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_jsr<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     match r.operand{
         Operand::Address(addr) => {
             let return_addr = cpu.program_counter.wrapping_sub(1);
             let return_low = (return_addr & 0x00ff) as u8;
             let return_high = (return_addr >> 8) as u8;
 
-            cpu.stack_push_u8(bus, return_high);
-            cpu.stack_push_u8(bus, return_low);
+            cpu.stack_push_u8(bus, return_high)?;
+            cpu.stack_push_u8(bus, return_low)?;
 
             cpu.program_counter = addr;
 
@@ -732,28 +1292,28 @@ fn op_jsr(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
         _ => unreachable!()
     }
 }
-fn op_lda(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_lda<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     cpu.a_register = r.operand.read(cpu, bus)?;
 
     cpu.status_update_zn(cpu.a_register);
 
     Ok(())
 }
-fn op_ldx(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_ldx<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     cpu.x_register = r.operand.read(cpu, bus)?;
 
     cpu.status_update_zn(cpu.x_register);
 
     Ok(())
 }
-fn op_ldy(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_ldy<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     cpu.y_register = r.operand.read(cpu, bus)?;
 
     cpu.status_update_zn(cpu.y_register);
 
     Ok(())
 }
-fn op_lsr(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_lsr<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val >> 1;
 
@@ -764,10 +1324,10 @@ fn op_lsr(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_nop(_cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_nop<B: Bus + ?Sized>(_cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     Ok(())
 }
-fn op_ora(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_ora<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = cpu.a_register | val;
 
@@ -777,53 +1337,53 @@ fn op_ora(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_pha(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.stack_push_u8(bus, cpu.a_register);
+fn op_pha<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.stack_push_u8(bus, cpu.a_register)?;
 
     Ok(())
 }
-fn op_php(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.stack_push_u8(bus, cpu.processor_status_register | 0x30);
+fn op_php<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.stack_push_u8(bus, cpu.processor_status_register | 0x30)?;
 
     Ok(())
 }
-fn op_phx(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.stack_push_u8(bus, cpu.x_register);
+fn op_phx<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.stack_push_u8(bus, cpu.x_register)?;
 
     Ok(())
 }
-fn op_phy(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.stack_push_u8(bus, cpu.y_register);
+fn op_phy<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.stack_push_u8(bus, cpu.y_register)?;
 
     Ok(())
 }
-fn op_pla(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.a_register = cpu.stack_pull_u8(bus);
+fn op_pla<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.a_register = cpu.stack_pull_u8(bus)?;
 
     cpu.status_update_zn(cpu.a_register);
 
     Ok(())
 }
-fn op_plp(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.processor_status_register = (cpu.stack_pull_u8(bus) | 0x20) & (!0x10);
+fn op_plp<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.processor_status_register = (cpu.stack_pull_u8(bus)? | 0x20) & (!0x10);
 
     Ok(())
 }
-fn op_plx(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.x_register = cpu.stack_pull_u8(bus);
+fn op_plx<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.x_register = cpu.stack_pull_u8(bus)?;
 
     cpu.status_update_zn(cpu.x_register);
 
     Ok(())
 }
-fn op_ply(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    cpu.y_register = cpu.stack_pull_u8(bus);
+fn op_ply<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    cpu.y_register = cpu.stack_pull_u8(bus)?;
 
     cpu.status_update_zn(cpu.y_register);
 
     Ok(())
 }
-fn op_rmbn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> OpReturn{
+fn op_rmbn<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand, n: u8) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let mask = 1u8 << n;
 
@@ -831,7 +1391,7 @@ fn op_rmbn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> O
 
     Ok(())
 }
-fn op_rol(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_rol<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let c = (val >> 7) > 0;
     let result = (val << 1) | (cpu.status_check(Status::C) as u8);
@@ -843,7 +1403,7 @@ fn op_rol(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_ror(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_ror<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let c = (val & 1) > 0;
     let result = (val >> 1) | ((cpu.status_check(Status::C) as u8) << 7);
@@ -855,11 +1415,11 @@ fn op_ror(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_rti(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    let p = (cpu.stack_pull_u8(bus) | 0x20) & (!0x10);
+fn op_rti<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    let p = (cpu.stack_pull_u8(bus)? | 0x20) & (!0x10);
 
-    let low = cpu.stack_pull_u8(bus);
-    let high = cpu.stack_pull_u8(bus);
+    let low = cpu.stack_pull_u8(bus)?;
+    let high = cpu.stack_pull_u8(bus)?;
     let addr = ((high as u16) << 8) | (low as u16);
 
     cpu.processor_status_register = p;
@@ -867,16 +1427,16 @@ fn op_rti(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn
 
     Ok(())
 }
-fn op_rts(cpu: &mut W65C02S, bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
-    let low = cpu.stack_pull_u8(bus);
-    let high = cpu.stack_pull_u8(bus);
+fn op_rts<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    let low = cpu.stack_pull_u8(bus)?;
+    let high = cpu.stack_pull_u8(bus)?;
     let addr = ((high as u16) << 8) | (low as u16);
 
     cpu.program_counter = addr.wrapping_add(1);
 
     Ok(())
 }
-fn op_sbc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_sbc<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let diff = (cpu.a_register as u16).wrapping_add(!val as u16).wrapping_add(cpu.status_check(Status::C) as u16);
     let result = diff as u8;
@@ -889,22 +1449,22 @@ fn op_sbc(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_sec(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_sec<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::C, true);
 
     Ok(())
 }
-fn op_sed(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_sed<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::D, true);
 
     Ok(())
 }
-fn op_sei(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_sei<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.status_set(Status::I, true);
 
     Ok(())
 }
-fn op_smbn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> OpReturn{
+fn op_smbn<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand, n: u8) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let mask = 1u8 << n;
 
@@ -912,44 +1472,44 @@ fn op_smbn(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand, n: u8) -> O
 
     Ok(())
 }
-fn op_sta(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_sta<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     r.operand.write(cpu, bus, cpu.a_register)?;
 
     Ok(())
 }
-fn op_stp(_cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{ //
-    unimplemented!();
+fn op_stp<B: Bus + ?Sized>(_cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    Err(CpuError::Unsupported(Mnemonic::STP))
 }
-fn op_stx(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_stx<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     r.operand.write(cpu, bus, cpu.x_register)?;
 
     Ok(())
 }
-fn op_sty(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_sty<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     r.operand.write(cpu, bus, cpu.y_register)?;
 
     Ok(())
 }
-fn op_stz(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_stz<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     r.operand.write(cpu, bus, 0)?;
 
     Ok(())
 }
-fn op_tax(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_tax<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.x_register = cpu.a_register;
 
     cpu.status_update_zn(cpu.x_register);
 
     Ok(())
 }
-fn op_tay(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_tay<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.y_register = cpu.a_register;
 
     cpu.status_update_zn(cpu.y_register);
 
     Ok(())
 }
-fn op_trb(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_trb<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val & !cpu.a_register;
 
@@ -959,7 +1519,7 @@ fn op_trb(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_tsb(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
+fn op_tsb<B: Bus + ?Sized>(cpu: &mut W65C02S, bus: &mut B, r: ResolvedOperand) -> OpReturn{
     let val = r.operand.read(cpu, bus)?;
     let result = val | cpu.a_register;
 
@@ -969,109 +1529,106 @@ fn op_tsb(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn{
 
     Ok(())
 }
-fn op_tsx(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_tsx<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.x_register = cpu.stack_pointer;
 
     cpu.status_update_zn(cpu.x_register);
 
     Ok(())
 }
-fn op_txa(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_txa<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.a_register = cpu.x_register;
 
     cpu.status_update_zn(cpu.a_register);
 
     Ok(())
 }
-fn op_txs(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_txs<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.stack_pointer = cpu.x_register;
 
     Ok(())
 }
-fn op_tya(cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{
+fn op_tya<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
     cpu.a_register = cpu.y_register;
 
     cpu.status_update_zn(cpu.a_register);
 
     Ok(())
 }
-fn op_wai(_cpu: &mut W65C02S, _bus: &mut dyn Bus, _r: ResolvedOperand) -> OpReturn{ //
-    unimplemented!();
-}
-
-fn op_alias_bbr0(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 0) }
-fn op_alias_bbr1(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 1) }
-fn op_alias_bbr2(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 2) }
-fn op_alias_bbr3(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 3) }
-fn op_alias_bbr4(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 4) }
-fn op_alias_bbr5(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 5) }
-fn op_alias_bbr6(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 6) }
-fn op_alias_bbr7(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbrn(cpu, bus, r, 7) }
-fn op_alias_bbs0(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 0) }
-fn op_alias_bbs1(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 1) }
-fn op_alias_bbs2(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 2) }
-fn op_alias_bbs3(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 3) }
-fn op_alias_bbs4(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 4) }
-fn op_alias_bbs5(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 5) }
-fn op_alias_bbs6(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 6) }
-fn op_alias_bbs7(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_bbsn(cpu, bus, r, 7) }
-fn op_alias_rmb0(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 0) }
-fn op_alias_rmb1(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 1) }
-fn op_alias_rmb2(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 2) }
-fn op_alias_rmb3(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 3) }
-fn op_alias_rmb4(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 4) }
-fn op_alias_rmb5(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 5) }
-fn op_alias_rmb6(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 6) }
-fn op_alias_rmb7(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_rmbn(cpu, bus, r, 7) }
-fn op_alias_smb0(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 0) }
-fn op_alias_smb1(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 1) }
-fn op_alias_smb2(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 2) }
-fn op_alias_smb3(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 3) }
-fn op_alias_smb4(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 4) }
-fn op_alias_smb5(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 5) }
-fn op_alias_smb6(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 6) }
-fn op_alias_smb7(cpu: &mut W65C02S, bus: &mut dyn Bus, r: ResolvedOperand) -> OpReturn { op_smbn(cpu, bus, r, 7) }
+fn op_wai<B: Bus + ?Sized>(_cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand) -> OpReturn{
+    Err(CpuError::Unsupported(Mnemonic::WAI))
+}
+
+fn op_invalid<B: Bus + ?Sized>(cpu: &mut W65C02S, _bus: &mut B, _r: ResolvedOperand, opcode: u8) -> OpReturn{
+    match cpu.invalid_opcodes{
+        InvalidOpcodePolicy::Error => Err(CpuError::InvalidOpcode(opcode)),
+        InvalidOpcodePolicy::Nop => Ok(()),
+    }
+}
 
 #[inline]
 fn crosses_pages(a: u16, b: u16) -> bool{
     (a & 0xff00) != (b & 0xff00)
 }
-#[inline]
-fn read_u16(bus: &mut dyn Bus, address: u16) -> u16{
-    let low = bus.read(address) as u16;
-    let high = bus.read(address.wrapping_add(1)) as u16;
 
-    (high << 8) | low
+/// Where the bytes of an instruction's operand come from. Either way the PC steps over them.
+trait OperandSource{
+    fn next_u8<B: Bus + ?Sized>(&mut self, cpu: &mut W65C02S, bus: &mut B) -> Result<u8, BusError>;
+    #[inline]
+    fn next_u16<B: Bus + ?Sized>(&mut self, cpu: &mut W65C02S, bus: &mut B) -> Result<u16, BusError>{
+        let low = self.next_u8(cpu, bus)? as u16;
+        let high = self.next_u8(cpu, bus)? as u16;
+        Ok((high << 8) | low)
+    }
+}
+/// Fetched from the bus at the PC.
+struct FromBus;
+impl OperandSource for FromBus{
+    #[inline]
+    fn next_u8<B: Bus + ?Sized>(&mut self, cpu: &mut W65C02S, bus: &mut B) -> Result<u8, BusError>{
+        cpu.fetch_u8(bus)
+    }
+}
+/// Taken from a decoded instruction, with the index of the next byte.
+struct FromDecoded([u8; 2], usize);
+impl OperandSource for FromDecoded{
+    #[inline]
+    fn next_u8<B: Bus + ?Sized>(&mut self, cpu: &mut W65C02S, _bus: &mut B) -> Result<u8, BusError>{
+        let val = self.0[self.1];
+        self.1 += 1;
+        cpu.program_counter = cpu.program_counter.wrapping_add(1);
+        Ok(val)
+    }
 }
 
-fn resolve_operand(cpu: &mut W65C02S, bus: &mut dyn Bus, mode: &AddressingMode) -> ResolvedOperand{
-    match mode{
+fn resolve_operand<B: Bus + ?Sized, S: OperandSource>(cpu: &mut W65C02S, bus: &mut B, mode: &AddressingMode, source: &mut S) -> Result<ResolvedOperand, BusError>{
+    let resolved = match mode{
         AddressingMode::Absolute => {
-            let val = cpu.fetch_u16(bus);
+            let val = source.next_u16(cpu, bus)?;
             ResolvedOperand{ operand: Operand::Address(val), page_crossed: false}
         },
         AddressingMode::AbsoluteIndexedIndirect => {
-            let base = cpu.fetch_u16(bus);
+            let base = source.next_u16(cpu, bus)?;
             let addr = base.wrapping_add(cpu.x_register as u16);
 
-            let target = read_u16(bus, addr);
+            let target = bus.read_u16(addr)?;
             ResolvedOperand{ operand: Operand::Address(target), page_crossed: false}
         },
         AddressingMode::AbsoluteIndexedX => {
-            let base = cpu.fetch_u16(bus);
+            let base = source.next_u16(cpu, bus)?;
             let addr = base.wrapping_add(cpu.x_register as u16);
 
             ResolvedOperand { operand: Operand::Address(addr), page_crossed: crosses_pages(base, addr) }
         },
         AddressingMode::AbsoluteIndexedY => {
-            let base = cpu.fetch_u16(bus);
+            let base = source.next_u16(cpu, bus)?;
             let addr = base.wrapping_add(cpu.y_register as u16);
 
             ResolvedOperand { operand: Operand::Address(addr), page_crossed: crosses_pages(base, addr) }
         },
         AddressingMode::AbsoluteIndirect => {
-            let ptr = cpu.fetch_u16(bus);
-            let target = read_u16(bus, ptr);
+            let ptr = source.next_u16(cpu, bus)?;
+            let target = bus.read_u16(ptr)?;
 
             ResolvedOperand { operand: Operand::Address(target), page_crossed: false }
         },
@@ -1079,7 +1636,7 @@ fn resolve_operand(cpu: &mut W65C02S, bus: &mut dyn Bus, mode: &AddressingMode)
             ResolvedOperand { operand: Operand::Accumulator, page_crossed: false }
         },
         AddressingMode::Immediate => {
-            let val = cpu.fetch_u8(bus);
+            let val = source.next_u8(cpu, bus)?;
             
             ResolvedOperand { operand: Operand::Value(val), page_crossed: false }
         },
@@ -1087,7 +1644,7 @@ fn resolve_operand(cpu: &mut W65C02S, bus: &mut dyn Bus, mode: &AddressingMode)
             ResolvedOperand { operand: Operand::Implied, page_crossed: false }
         },
         AddressingMode::ProgramCounterRelative => {
-            let offset = cpu.fetch_u8(bus) as i8;
+            let offset = source.next_u8(cpu, bus)? as i8;
             
             ResolvedOperand { operand: Operand::Relative(offset), page_crossed: false }
         },
@@ -1095,56 +1652,53 @@ fn resolve_operand(cpu: &mut W65C02S, bus: &mut dyn Bus, mode: &AddressingMode)
             ResolvedOperand { operand: Operand::Implied, page_crossed: false }
         },
         AddressingMode::ZeroPage => {
-            let addr = cpu.fetch_u8(bus) as u16;
+            let addr = source.next_u8(cpu, bus)? as u16;
 
             ResolvedOperand { operand: Operand::Address(addr), page_crossed: false }
         },
         AddressingMode::ZeroPageIndexedIndirect => {
-            let zp_addr = cpu.fetch_u8(bus).wrapping_add(cpu.x_register);
-            let low = bus.read(zp_addr as u16) as u16;
-            let high = bus.read((zp_addr.wrapping_add(1)) as u16) as u16;
+            let zp_addr = source.next_u8(cpu, bus)?.wrapping_add(cpu.x_register);
 
-            let target = (high << 8) | low;
+            let target = bus.read_zp_pointer(zp_addr)?;
             ResolvedOperand { operand: Operand::Address(target), page_crossed: false }
         },
         AddressingMode::ZeroPageIndexedX => {
-            let zp_addr = cpu.fetch_u8(bus).wrapping_add(cpu.x_register);
+            let zp_addr = source.next_u8(cpu, bus)?.wrapping_add(cpu.x_register);
 
             ResolvedOperand { operand: Operand::Address(zp_addr as u16), page_crossed: false }
         },
         AddressingMode::ZeroPageIndexedY => {
-            let zp_addr = cpu.fetch_u8(bus).wrapping_add(cpu.y_register);
+            let zp_addr = source.next_u8(cpu, bus)?.wrapping_add(cpu.y_register);
 
             ResolvedOperand { operand: Operand::Address(zp_addr as u16), page_crossed: false }
         },
         AddressingMode::ZeroPageIndirect => {
-            let zp_addr = cpu.fetch_u8(bus);
-            let low = bus.read(zp_addr as u16) as u16;
-            let high = bus.read((zp_addr.wrapping_add(1)) as u16) as u16;
+            let zp_addr = source.next_u8(cpu, bus)?;
 
-            let target = (high << 8) | low;
+            let target = bus.read_zp_pointer(zp_addr)?;
             ResolvedOperand { operand: Operand::Address(target), page_crossed: false }
         },
         AddressingMode::ZeroPageIndirectIndexedY => {
-            let zp_addr = cpu.fetch_u8(bus);
-            let low = bus.read(zp_addr as u16) as u16;
-            let high = bus.read((zp_addr.wrapping_add(1)) as u16) as u16;
+            let zp_addr = source.next_u8(cpu, bus)?;
 
-            let base = (high << 8) | low;
+            let base = bus.read_zp_pointer(zp_addr)?;
             let target = base.wrapping_add(cpu.y_register as u16);
             ResolvedOperand { operand: Operand::Address(target), page_crossed: crosses_pages(base, target) }
         },
 
         AddressingMode::ZeroPageRelative => {
-            let zp_addr = cpu.fetch_u8(bus);
-            let rel = cpu.fetch_u8(bus) as i8;
+            let zp_addr = source.next_u8(cpu, bus)?;
+            let rel = source.next_u8(cpu, bus)? as i8;
 
             ResolvedOperand { operand: Operand::ZpAddrRelative(zp_addr, rel), page_crossed: false }
         }
-    }
+    };
+
+    Ok(resolved)
 }
 
-enum AddressingMode{
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AddressingMode{
     Absolute,                   // a
     AbsoluteIndexedIndirect,    // (a, x)
     AbsoluteIndexedX,           // a, x
@@ -1166,7 +1720,7 @@ enum AddressingMode{
 }
 impl AddressingMode{
     #[inline]
-    fn num_operand_bytes(&self) -> u8{
+    pub(crate) fn num_operand_bytes(&self) -> u8{
         match *self{
             AddressingMode::Absolute => 2,
             AddressingMode::AbsoluteIndexedIndirect => 2,
@@ -1190,14 +1744,78 @@ impl AddressingMode{
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct Operation{
     addressing_mode: AddressingMode,
-    mnemomic: Mnemomic,
-    exec: OpFn,
+    mnemonic: Mnemonic,
+    cycles: u8,     // before page crossing and branch penalties
+}
+impl Operation{
+    pub(crate) fn mnemonic(&self) -> Mnemonic{
+        self.mnemonic
+    }
+    pub(crate) fn addressing_mode(&self) -> AddressingMode{
+        self.addressing_mode
+    }
+    #[cfg(feature = "jit")]
+    pub(crate) fn cycles(&self) -> u8{
+        self.cycles
+    }
+
+    /// Cycles spent beyond the base count: one for an indexed read crossing a page, and for branches
+    /// one when taken plus one more when the target lies in another page.
+    pub(crate) fn extra_cycles(&self, page_crossed: bool, next_pc: u16, pc_after: u16) -> u8{
+        match self.mnemonic{
+            Mnemonic::BBRN(_) | Mnemonic::BBSN(_) | Mnemonic::BCC | Mnemonic::BCS | Mnemonic::BEQ | Mnemonic::BMI |
+            Mnemonic::BNE | Mnemonic::BPL | Mnemonic::BVC | Mnemonic::BVS => {
+                if next_pc == pc_after { 0 } else { 1 + crosses_pages(next_pc, pc_after) as u8 }
+            },
+            Mnemonic::BRA => crosses_pages(next_pc, pc_after) as u8,
+            Mnemonic::ADC | Mnemonic::AND | Mnemonic::BIT | Mnemonic::CMP | Mnemonic::EOR | Mnemonic::LDA |
+            Mnemonic::LDX | Mnemonic::LDY | Mnemonic::ORA | Mnemonic::SBC |
+            Mnemonic::ASL | Mnemonic::LSR | Mnemonic::ROL | Mnemonic::ROR => page_crossed as u8,
+            _ => 0,
+        }
+    }
+}
+
+/// An instruction decoded from memory that cannot change under it, such as ROM, for the CPU to execute without
+/// fetching its bytes again: its operation, which gives the handler, addressing mode and cycles, and the bytes
+/// of its operand.
+#[derive(Copy, Clone)]
+pub struct DecodedInstruction{
+    operation: Operation,
+    operand: [u8; 2],   // as many as the addressing mode takes, the rest zero
+}
+impl DecodedInstruction{
+    /// Decodes the instruction that starts with `bytes`; an invalid opcode decodes to what the CPU executes for it.
+    pub fn decode(bytes: [u8; 3]) -> Self{
+        let operation = W65C02S::OPERATIONS[bytes[0] as usize];
+        let mut operand = [bytes[1], bytes[2]];
+        operand[operation.addressing_mode.num_operand_bytes() as usize..].fill(0);
+        Self { operation, operand }
+    }
+    /// In bytes, the opcode included.
+    pub fn length(&self) -> u8{
+        1 + self.operation.addressing_mode.num_operand_bytes()
+    }
+
+    #[cfg(any(feature = "jit", test))]
+    pub(crate) fn operation(&self) -> Operation{
+        self.operation
+    }
+    #[cfg(any(feature = "jit", test))]
+    pub(crate) fn operand(&self) -> [u8; 2]{
+        self.operand
+    }
 }
 
+/// The name `Mnemonic` went by, misspelt, before the library had a prelude.
+#[deprecated(note = "renamed to `Mnemonic`")]
+pub type Mnemomic = Mnemonic;
+
 #[derive(Copy, Clone, Debug)]
-pub enum Mnemomic{
+pub enum Mnemonic{
     ADC,
     AND,
     ASL,
@@ -1268,115 +1886,131 @@ pub enum Mnemomic{
     TXS,
     TYA,
     WAI,
+    /// An opcode the W65C02S does not define.
+    Invalid(u8),
+}
+impl fmt::Display for Mnemonic{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Mnemonic::BBRN(bit) => write!(f, "BBR{}", bit),
+            Mnemonic::BBSN(bit) => write!(f, "BBS{}", bit),
+            Mnemonic::RMBN(bit) => write!(f, "RMB{}", bit),
+            Mnemonic::SMBN(bit) => write!(f, "SMB{}", bit),
+            Mnemonic::Invalid(_) => write!(f, "???"),
+            _ => write!(f, "{:?}", self),
+        }
+    }
 }
-impl Mnemomic{
-    pub fn from_str(mnem: &str) -> Option<Self>{
+impl Mnemonic{
+    pub fn from_name(mnem: &str) -> Option<Self>{
         match mnem.to_lowercase().as_str(){
-            "adc" => Some(Mnemomic::ADC),
-            "and" => Some(Mnemomic::AND),
-            "asl" => Some(Mnemomic::ASL),
-            "bbr0" => Some(Mnemomic::BBRN(0)),
-            "bbr1" => Some(Mnemomic::BBRN(1)),
-            "bbr2" => Some(Mnemomic::BBRN(2)),
-            "bbr3" => Some(Mnemomic::BBRN(3)),
-            "bbr4" => Some(Mnemomic::BBRN(4)),
-            "bbr5" => Some(Mnemomic::BBRN(5)),
-            "bbr6" => Some(Mnemomic::BBRN(6)),
-            "bbr7" => Some(Mnemomic::BBRN(7)),
-            "bbs0" => Some(Mnemomic::BBSN(0)),
-            "bbs1" => Some(Mnemomic::BBSN(1)),
-            "bbs2" => Some(Mnemomic::BBSN(2)),
-            "bbs3" => Some(Mnemomic::BBSN(3)),
-            "bbs4" => Some(Mnemomic::BBSN(4)),
-            "bbs5" => Some(Mnemomic::BBSN(5)),
-            "bbs6" => Some(Mnemomic::BBSN(6)),
-            "bbs7" => Some(Mnemomic::BBSN(7)),
-            "bcc" => Some(Mnemomic::BCC),
-            "bcs" => Some(Mnemomic::BCS),
-            "beq" => Some(Mnemomic::BEQ),
-            "bit" => Some(Mnemomic::BIT),
-            "bmi" => Some(Mnemomic::BMI),
-            "bne" => Some(Mnemomic::BNE),
-            "bpl" => Some(Mnemomic::BPL),
-            "bra" => Some(Mnemomic::BRA),
-            "brk" => Some(Mnemomic::BRK),
-            "bvc" => Some(Mnemomic::BVC),
-            "bvs" => Some(Mnemomic::BVS),
-            "clc" => Some(Mnemomic::CLC),
-            "cld" => Some(Mnemomic::CLD),
-            "cli" => Some(Mnemomic::CLI),
-            "clv" => Some(Mnemomic::CLV),
-            "cmp" => Some(Mnemomic::CMP),
-            "cpx" => Some(Mnemomic::CPX),
-            "cpy" => Some(Mnemomic::CPY),
-            "dec" => Some(Mnemomic::DEC),
-            "dex" => Some(Mnemomic::DEX),
-            "dey" => Some(Mnemomic::DEY),
-            "eor" => Some(Mnemomic::EOR),
-            "inc" => Some(Mnemomic::INC),
-            "inx" => Some(Mnemomic::INX),
-            "iny" => Some(Mnemomic::INY),
-            "jmp" => Some(Mnemomic::JMP),
-            "jsr" => Some(Mnemomic::JSR),
-            "lda" => Some(Mnemomic::LDA),
-            "ldx" => Some(Mnemomic::LDX),
-            "ldy" => Some(Mnemomic::LDY),
-            "lsr" => Some(Mnemomic::LSR),
-            "nop" => Some(Mnemomic::NOP),
-            "ora" => Some(Mnemomic::ORA),
-            "pha" => Some(Mnemomic::PHA),
-            "php" => Some(Mnemomic::PHP),
-            "phx" => Some(Mnemomic::PHX),
-            "phy" => Some(Mnemomic::PHY),
-            "pla" => Some(Mnemomic::PLA),
-            "plp" => Some(Mnemomic::PLP),
-            "plx" => Some(Mnemomic::PLX),
-            "ply" => Some(Mnemomic::PLY),
-            "rmb0" => Some(Mnemomic::RMBN(0)),
-            "rmb1" => Some(Mnemomic::RMBN(1)),
-            "rmb2" => Some(Mnemomic::RMBN(2)),
-            "rmb3" => Some(Mnemomic::RMBN(3)),
-            "rmb4" => Some(Mnemomic::RMBN(4)),
-            "rmb5" => Some(Mnemomic::RMBN(5)),
-            "rmb6" => Some(Mnemomic::RMBN(6)),
-            "rmb7" => Some(Mnemomic::RMBN(7)),
-            "rol" => Some(Mnemomic::ROL),
-            "ror" => Some(Mnemomic::ROR),
-            "rti" => Some(Mnemomic::RTI),
-            "rts" => Some(Mnemomic::RTS),
-            "sbc" => Some(Mnemomic::SBC),
-            "sec" => Some(Mnemomic::SEC),
-            "sed" => Some(Mnemomic::SED),
-            "sei" => Some(Mnemomic::SEI),
-            "smb0" => Some(Mnemomic::SMBN(0)),
-            "smb1" => Some(Mnemomic::SMBN(1)),
-            "smb2" => Some(Mnemomic::SMBN(2)),
-            "smb3" => Some(Mnemomic::SMBN(3)),
-            "smb4" => Some(Mnemomic::SMBN(4)),
-            "smb5" => Some(Mnemomic::SMBN(5)),
-            "smb6" => Some(Mnemomic::SMBN(6)),
-            "smb7" => Some(Mnemomic::SMBN(7)),
-            "sta" => Some(Mnemomic::STA),
-            "stp" => Some(Mnemomic::STP),
-            "stx" => Some(Mnemomic::STX),
-            "sty" => Some(Mnemomic::STY),
-            "stz" => Some(Mnemomic::STZ),
-            "tax" => Some(Mnemomic::TAX),
-            "tay" => Some(Mnemomic::TAY),
-            "trb" => Some(Mnemomic::TRB),
-            "tsb" => Some(Mnemomic::TSB),
-            "tsx" => Some(Mnemomic::TSX),
-            "txa" => Some(Mnemomic::TXA),
-            "txs" => Some(Mnemomic::TXS),
-            "tya" => Some(Mnemomic::TYA),
-            "wai" => Some(Mnemomic::WAI),
+            "adc" => Some(Mnemonic::ADC),
+            "and" => Some(Mnemonic::AND),
+            "asl" => Some(Mnemonic::ASL),
+            "bbr0" => Some(Mnemonic::BBRN(0)),
+            "bbr1" => Some(Mnemonic::BBRN(1)),
+            "bbr2" => Some(Mnemonic::BBRN(2)),
+            "bbr3" => Some(Mnemonic::BBRN(3)),
+            "bbr4" => Some(Mnemonic::BBRN(4)),
+            "bbr5" => Some(Mnemonic::BBRN(5)),
+            "bbr6" => Some(Mnemonic::BBRN(6)),
+            "bbr7" => Some(Mnemonic::BBRN(7)),
+            "bbs0" => Some(Mnemonic::BBSN(0)),
+            "bbs1" => Some(Mnemonic::BBSN(1)),
+            "bbs2" => Some(Mnemonic::BBSN(2)),
+            "bbs3" => Some(Mnemonic::BBSN(3)),
+            "bbs4" => Some(Mnemonic::BBSN(4)),
+            "bbs5" => Some(Mnemonic::BBSN(5)),
+            "bbs6" => Some(Mnemonic::BBSN(6)),
+            "bbs7" => Some(Mnemonic::BBSN(7)),
+            "bcc" => Some(Mnemonic::BCC),
+            "bcs" => Some(Mnemonic::BCS),
+            "beq" => Some(Mnemonic::BEQ),
+            "bit" => Some(Mnemonic::BIT),
+            "bmi" => Some(Mnemonic::BMI),
+            "bne" => Some(Mnemonic::BNE),
+            "bpl" => Some(Mnemonic::BPL),
+            "bra" => Some(Mnemonic::BRA),
+            "brk" => Some(Mnemonic::BRK),
+            "bvc" => Some(Mnemonic::BVC),
+            "bvs" => Some(Mnemonic::BVS),
+            "clc" => Some(Mnemonic::CLC),
+            "cld" => Some(Mnemonic::CLD),
+            "cli" => Some(Mnemonic::CLI),
+            "clv" => Some(Mnemonic::CLV),
+            "cmp" => Some(Mnemonic::CMP),
+            "cpx" => Some(Mnemonic::CPX),
+            "cpy" => Some(Mnemonic::CPY),
+            "dec" => Some(Mnemonic::DEC),
+            "dex" => Some(Mnemonic::DEX),
+            "dey" => Some(Mnemonic::DEY),
+            "eor" => Some(Mnemonic::EOR),
+            "inc" => Some(Mnemonic::INC),
+            "inx" => Some(Mnemonic::INX),
+            "iny" => Some(Mnemonic::INY),
+            "jmp" => Some(Mnemonic::JMP),
+            "jsr" => Some(Mnemonic::JSR),
+            "lda" => Some(Mnemonic::LDA),
+            "ldx" => Some(Mnemonic::LDX),
+            "ldy" => Some(Mnemonic::LDY),
+            "lsr" => Some(Mnemonic::LSR),
+            "nop" => Some(Mnemonic::NOP),
+            "ora" => Some(Mnemonic::ORA),
+            "pha" => Some(Mnemonic::PHA),
+            "php" => Some(Mnemonic::PHP),
+            "phx" => Some(Mnemonic::PHX),
+            "phy" => Some(Mnemonic::PHY),
+            "pla" => Some(Mnemonic::PLA),
+            "plp" => Some(Mnemonic::PLP),
+            "plx" => Some(Mnemonic::PLX),
+            "ply" => Some(Mnemonic::PLY),
+            "rmb0" => Some(Mnemonic::RMBN(0)),
+            "rmb1" => Some(Mnemonic::RMBN(1)),
+            "rmb2" => Some(Mnemonic::RMBN(2)),
+            "rmb3" => Some(Mnemonic::RMBN(3)),
+            "rmb4" => Some(Mnemonic::RMBN(4)),
+            "rmb5" => Some(Mnemonic::RMBN(5)),
+            "rmb6" => Some(Mnemonic::RMBN(6)),
+            "rmb7" => Some(Mnemonic::RMBN(7)),
+            "rol" => Some(Mnemonic::ROL),
+            "ror" => Some(Mnemonic::ROR),
+            "rti" => Some(Mnemonic::RTI),
+            "rts" => Some(Mnemonic::RTS),
+            "sbc" => Some(Mnemonic::SBC),
+            "sec" => Some(Mnemonic::SEC),
+            "sed" => Some(Mnemonic::SED),
+            "sei" => Some(Mnemonic::SEI),
+            "smb0" => Some(Mnemonic::SMBN(0)),
+            "smb1" => Some(Mnemonic::SMBN(1)),
+            "smb2" => Some(Mnemonic::SMBN(2)),
+            "smb3" => Some(Mnemonic::SMBN(3)),
+            "smb4" => Some(Mnemonic::SMBN(4)),
+            "smb5" => Some(Mnemonic::SMBN(5)),
+            "smb6" => Some(Mnemonic::SMBN(6)),
+            "smb7" => Some(Mnemonic::SMBN(7)),
+            "sta" => Some(Mnemonic::STA),
+            "stp" => Some(Mnemonic::STP),
+            "stx" => Some(Mnemonic::STX),
+            "sty" => Some(Mnemonic::STY),
+            "stz" => Some(Mnemonic::STZ),
+            "tax" => Some(Mnemonic::TAX),
+            "tay" => Some(Mnemonic::TAY),
+            "trb" => Some(Mnemonic::TRB),
+            "tsb" => Some(Mnemonic::TSB),
+            "tsx" => Some(Mnemonic::TSX),
+            "txa" => Some(Mnemonic::TXA),
+            "txs" => Some(Mnemonic::TXS),
+            "tya" => Some(Mnemonic::TYA),
+            "wai" => Some(Mnemonic::WAI),
             _ => None,
         }
     }
 }
 
+/// An operand once its addressing mode has been resolved; `CpuError::InvalidOperand` carries one the instruction
+/// cannot take.
 #[derive(Debug, Copy, Clone)]
-enum Operand{
+pub enum Operand{
     Implied,
     Accumulator,
     Value(u8),          // immediate
@@ -1385,18 +2019,18 @@ enum Operand{
     ZpAddrRelative(u8, i8)  // for BBRN and BBSn
 }
 impl Operand{
-    fn read(self, cpu: &W65C02S, bus: &mut dyn Bus) -> Result<u8, CpuError>{
+    fn read<B: Bus + ?Sized>(self, cpu: &W65C02S, bus: &mut B) -> Result<u8, CpuError>{
         match self{
             Operand::Value(v) => Ok(v),
-            Operand::Address(a) => Ok(bus.read(a)),
+            Operand::Address(a) => Ok(bus.read(a)?),
             Operand::Accumulator => Ok(cpu.a_register),
-            Operand::ZpAddrRelative(a, _) => Ok(bus.read(a as u16)),
+            Operand::ZpAddrRelative(a, _) => Ok(bus.read(a as u16)?),
             _ => Err(CpuError::InvalidOperand(self))
         }
     }
-    fn write(self, cpu: &mut W65C02S, bus: &mut dyn Bus, val: u8) -> Result<(), CpuError>{
+    fn write<B: Bus + ?Sized>(self, cpu: &mut W65C02S, bus: &mut B, val: u8) -> Result<(), CpuError>{
         match self{
-            Operand::Address(a) => { bus.write(a, val); Ok(()) },
+            Operand::Address(a) => { bus.write(a, val)?; Ok(()) },
             Operand::Accumulator => { cpu.a_register = val; Ok(())},
             _ => Err(CpuError::InvalidOperand(self))
         }
@@ -1405,4 +2039,191 @@ impl Operand{
 struct ResolvedOperand{
     operand: Operand,
     page_crossed: bool
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// 64KB of NOPs with the vectors pointing at handlers of their own, and interrupt lines the test drives.
+    struct StubBus{
+        memory: Vec<u8>,
+        reset: bool,
+        nmi: bool,
+        irq: bool,
+    }
+    impl StubBus{
+        const ENTRY: u16 = 0x8000;
+        const NMI_HANDLER: u16 = 0x9000;
+        const IRQ_HANDLER: u16 = 0xa000;
+
+        fn new() -> Self{
+            let mut memory = vec![0xea; 0x10000];
+            memory[0xfffa..].copy_from_slice(&[0x00, 0x90, 0x00, 0x80, 0x00, 0xa0]);
+            Self { memory, reset: false, nmi: false, irq: false }
+        }
+    }
+    impl Bus for StubBus{
+        fn read(&mut self, address: u16) -> Result<u8, BusError>{
+            Ok(self.memory[address as usize])
+        }
+        fn write(&mut self, address: u16, val: u8) -> Result<(), BusError>{
+            self.memory[address as usize] = val;
+            Ok(())
+        }
+        fn irq(&self) -> bool{
+            self.irq
+        }
+        fn nmi(&self) -> bool{
+            self.nmi
+        }
+        fn reset(&self) -> bool{
+            self.reset
+        }
+    }
+
+    /// A CPU reset onto the stub bus, with the I flag cleared so that an IRQ can be taken.
+    fn started(bus: &mut StubBus) -> W65C02S{
+        let mut cpu = W65C02S::default();
+        cpu.reset(bus).unwrap();
+        cpu.status_set(Status::I, false);
+        cpu
+    }
+    /// Runs one instruction and returns the interrupt taken after it, if any.
+    fn step(cpu: &mut W65C02S, bus: &mut StubBus) -> Option<Arbitration>{
+        match cpu.run_block(bus, 1, u64::MAX){
+            BlockEnd::Ran => None,
+            BlockEnd::Interrupted(arbitration) => Some(arbitration),
+            end => panic!("unexpected {:?}", end),
+        }
+    }
+
+    #[test]
+    fn reset_wins_over_nmi_and_drops_it(){
+        let mut bus = StubBus::new();
+        let mut cpu = started(&mut bus);
+        bus.reset = true;
+        bus.nmi = true;
+
+        let arbitration = step(&mut cpu, &mut bus);
+        assert_eq!(arbitration, Some(Arbitration { pc: 0x8001, taken: Interrupt::Reset, reset: true, nmi: true, irq: false }));
+        assert_eq!(arbitration.unwrap().outranked().collect::<Vec<_>>(), [Interrupt::Nmi]);
+        assert_eq!(cpu.register(Register::PC), StubBus::ENTRY);
+
+        // both edges were latched at that boundary, so holding the lines takes neither again
+        assert_eq!(step(&mut cpu, &mut bus), None);
+        assert_eq!(cpu.register(Register::PC), StubBus::ENTRY + 1);
+    }
+
+    #[test]
+    fn nmi_wins_over_irq(){
+        let mut bus = StubBus::new();
+        let mut cpu = started(&mut bus);
+        bus.nmi = true;
+        bus.irq = true;
+
+        let arbitration = step(&mut cpu, &mut bus);
+        assert_eq!(arbitration, Some(Arbitration { pc: 0x8001, taken: Interrupt::Nmi, reset: false, nmi: true, irq: true }));
+        assert_eq!(cpu.register(Register::PC), StubBus::NMI_HANDLER);
+        assert!(cpu.status_check(Status::I));
+
+        // the handler runs with the IRQ masked until it returns
+        assert_eq!(step(&mut cpu, &mut bus), None);
+        assert_eq!(cpu.register(Register::PC), StubBus::NMI_HANDLER + 1);
+    }
+
+    #[test]
+    fn step_keeps_the_arbitration_it_took(){
+        let mut bus = StubBus::new();
+        let mut cpu = started(&mut bus);
+        bus.nmi = true;
+        bus.irq = true;
+
+        cpu.step(&mut bus).unwrap();
+        assert_eq!(cpu.last_arbitration(), Some(Arbitration { pc: 0x8001, taken: Interrupt::Nmi, reset: false, nmi: true, irq: true }));
+        assert_eq!(cpu.last_arbitration().unwrap().outranked().collect::<Vec<_>>(), [Interrupt::Irq]);
+
+        cpu.step(&mut bus).unwrap();
+        assert_eq!(cpu.last_arbitration(), None);
+    }
+
+    #[test]
+    fn irq_waits_while_the_i_flag_is_set(){
+        let mut bus = StubBus::new();
+        let mut cpu = started(&mut bus);
+        cpu.status_set(Status::I, true);
+        bus.memory[0x8001] = 0x58;     // CLI
+        bus.irq = true;
+
+        assert_eq!(step(&mut cpu, &mut bus), None);
+        assert_eq!(cpu.register(Register::PC), 0x8001);
+
+        let arbitration = step(&mut cpu, &mut bus);
+        assert_eq!(arbitration, Some(Arbitration { pc: 0x8002, taken: Interrupt::Irq, reset: false, nmi: false, irq: true }));
+        assert_eq!(cpu.register(Register::PC), StubBus::IRQ_HANDLER);
+    }
+
+    #[test]
+    fn nmi_held_asserted_is_taken_once(){
+        let mut bus = StubBus::new();
+        let mut cpu = started(&mut bus);
+        bus.nmi = true;
+
+        let arbitration = step(&mut cpu, &mut bus);
+        assert_eq!(arbitration, Some(Arbitration { pc: 0x8001, taken: Interrupt::Nmi, reset: false, nmi: true, irq: false }));
+        assert_eq!(cpu.register(Register::PC), StubBus::NMI_HANDLER);
+        for i in 1..=8{
+            assert_eq!(step(&mut cpu, &mut bus), None);
+            assert_eq!(cpu.register(Register::PC), StubBus::NMI_HANDLER + i);
+        }
+
+        // released and asserted again, it is a new edge
+        bus.nmi = false;
+        assert_eq!(step(&mut cpu, &mut bus), None);
+        bus.nmi = true;
+        let arbitration = step(&mut cpu, &mut bus);
+        assert_eq!(arbitration, Some(Arbitration { pc: StubBus::NMI_HANDLER + 10, taken: Interrupt::Nmi, reset: false, nmi: true, irq: false }));
+        assert_eq!(cpu.register(Register::PC), StubBus::NMI_HANDLER);
+    }
+
+    /// The cycles the instruction `bytes` takes at `address`, run on the stub bus after `setup`.
+    fn cycles_of(address: u16, bytes: &[u8], setup: impl FnOnce(&mut W65C02S)) -> u64{
+        let mut bus = StubBus::new();
+        bus.memory[address as usize..address as usize + bytes.len()].copy_from_slice(bytes);
+        let mut cpu = W65C02S::default();
+        cpu.reset_to(address);
+        setup(&mut cpu);
+        let before = cpu.cycles();
+        cpu.step(&mut bus).unwrap();
+        cpu.cycles() - before
+    }
+
+    #[test]
+    fn indexed_reads_take_a_cycle_more_across_a_page(){
+        let lda = [0xbd, 0xff, 0x02];      // LDA $02FF,X
+        assert_eq!(cycles_of(0x8000, &lda, |cpu| cpu.set_register(Register::X, 0)), 4);
+        assert_eq!(cycles_of(0x8000, &lda, |cpu| cpu.set_register(Register::X, 1)), 5);
+
+        let lda = [0xb1, 0x10];            // LDA ($10),Y, the pointer being the stub's $EAEA
+        assert_eq!(cycles_of(0x8000, &lda, |cpu| cpu.set_register(Register::Y, 0x15)), 5);
+        assert_eq!(cycles_of(0x8000, &lda, |cpu| cpu.set_register(Register::Y, 0x16)), 6);
+
+        // a store takes its longest count either way
+        let sta = [0x9d, 0xff, 0x02];      // STA $02FF,X
+        assert_eq!(cycles_of(0x8000, &sta, |cpu| cpu.set_register(Register::X, 0)), 5);
+        assert_eq!(cycles_of(0x8000, &sta, |cpu| cpu.set_register(Register::X, 1)), 5);
+    }
+
+    #[test]
+    fn branches_take_a_cycle_more_when_taken_and_another_across_a_page(){
+        let beq = [0xf0, 0x10];            // BEQ *+$12
+        assert_eq!(cycles_of(0x8000, &beq, |cpu| cpu.status_set(Status::Z, false)), 2);
+        assert_eq!(cycles_of(0x8000, &beq, |cpu| cpu.status_set(Status::Z, true)), 3);
+        assert_eq!(cycles_of(0x80f0, &beq, |cpu| cpu.status_set(Status::Z, true)), 4);
+
+        // BRA is always taken, so only the page costs it more
+        let bra = [0x80, 0x10];
+        assert_eq!(cycles_of(0x8000, &bra, |_| ()), 3);
+        assert_eq!(cycles_of(0x80f0, &bra, |_| ()), 4);
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{self, Write};
+
+use crate::bus::bus::Machine;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// Something whose value a `Watcher` follows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WatchTarget{
+    Register(Register),
+    Byte(u16),
+    Word(u16),      // little-endian, as the CPU stores pointers
+}
+impl WatchTarget{
+    /// None when the memory cannot be read without side effects, such as a device register.
+    fn value(&self, cpu: &W65C02S, machine: &Machine) -> Option<u16>{
+        match *self{
+            WatchTarget::Register(register) => Some(cpu.register(register)),
+            WatchTarget::Byte(address) => machine.peek(address).map(u16::from),
+            WatchTarget::Word(address) => Some(u16::from_le_bytes([machine.peek(address)?, machine.peek(address.wrapping_add(1))?])),
+        }
+    }
+    fn is_word(&self) -> bool{
+        matches!(self, WatchTarget::Word(_) | WatchTarget::Register(Register::PC))
+    }
+}
+
+/// Prints a line with the value of every watch, either every so many instructions or whenever one of them
+/// changes; lighter than a full trace for following a long run.
+pub struct Watcher{
+    watches: Vec<(String, WatchTarget, Option<u16>)>,  // the name printed, what is watched, and its last value
+    every: Option<u64>,                                 // instructions between lines, rather than on change
+    output: Box<dyn Write>,
+    started: bool,
+}
+impl Watcher{
+    pub fn new(output: impl Write + 'static) -> Self{
+        Self { watches: Vec::new(), every: None, output: Box::new(output), started: false }
+    }
+    pub fn stderr() -> Self{
+        Self::new(io::stderr())
+    }
+    pub fn with_watch(mut self, name: &str, target: WatchTarget) -> Self{
+        self.watches.push((name.to_string(), target, None));
+        self
+    }
+    pub fn with_interval(mut self, instructions: u64) -> Self{
+        self.every = Some(instructions.max(1));
+        self
+    }
+
+    /// Call after each step of the CPU, and once before the first.
+    pub fn watch(&mut self, cpu: &W65C02S, machine: &Machine){
+        let mut changed = false;
+        for (_, target, last) in self.watches.iter_mut(){
+            let value = target.value(cpu, machine);
+            changed |= value != *last;
+            *last = value;
+        }
+
+        let due = match self.every{
+            Some(every) => cpu.instructions().is_multiple_of(every),
+            None => changed,
+        };
+        if !due && self.started{
+            return;
+        }
+        self.started = true;
+
+        let mut line = format!("[INS:{} CYC:{}]", cpu.instructions(), cpu.cycles());
+        for (name, target, value) in &self.watches{
+            let value = match (value, target.is_word()){
+                (Some(value), true) => format!("${:04X}", value),
+                (Some(value), false) => format!("${:02X}", value),
+                (None, _) => "??".to_string(),
+            };
+            line.push_str(&format!(" {}={}", name, value));
+        }
+        // watches that cannot be written are not worth stopping the machine for
+        let _ = writeln!(self.output, "{}", line);
+    }
+}
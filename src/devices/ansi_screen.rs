@@ -0,0 +1,162 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::devices::device::Device;
+use crate::devices::raster::RasterPosition;
+
+/**
+   Character-matrix screen drawn on the host terminal with ANSI escape codes, for machines run without a
+   window. The CPU reads and writes screen memory like RAM; the terminal is redrawn from it at the refresh
+   rate, measured in host time, whenever it has changed, or at every vertical blank of a raster timer it is
+   synced to.
+
+   Screen memory holds one byte per cell, row by row. Codes 0x20-0x7e are ASCII and show as themselves,
+   other codes as blanks, and bit 7 shows the character inverted. With colour, a second block of the same
+   size follows it with one attribute byte per cell: the foreground colour in bits 0-3, the background in
+   bits 4-7, from the 16 standard ANSI colours.
+ */
+pub struct AnsiScreen{
+    columns: usize,
+    rows: usize,
+    color: bool,
+    memory: Vec<u8>,
+
+    output: Box<dyn Write>,
+    dirty: bool,            // written to since the last redraw
+    started: bool,          // the terminal has been cleared
+    frame_interval: Duration,
+    last_frame: Instant,
+    unchecked_cycles: u32,  // cycles since the clock was last looked at
+    vsync: Option<(RasterPosition, u32)>,   // raster the screen is synced to, and the frame last drawn
+}
+impl AnsiScreen{
+    pub const DEFAULT_ADDRESS: u16 = 0x3000;
+    pub const DEFAULT_REFRESH_RATE: u32 = 30;
+    pub const DEFAULT_ATTRIBUTE: u8 = 0x07;    // light grey on black
+
+    const CLOCK_CHECK_INTERVAL: u32 = 1024;
+
+    pub fn new(columns: usize, rows: usize, color: bool, refresh_rate: u32, output: impl Write + 'static) -> Self{
+        let cells = columns * rows;
+        let mut memory = vec![b' '; cells];
+        if color{
+            memory.resize(cells * 2, Self::DEFAULT_ATTRIBUTE);
+        }
+
+        Self {
+            columns,
+            rows,
+            color,
+            memory,
+            output: Box::new(output),
+            dirty: true,
+            started: false,
+            frame_interval: Duration::from_secs(1) / refresh_rate.max(1),
+            last_frame: Instant::now(),
+            unchecked_cycles: 0,
+            vsync: None,
+        }
+    }
+    pub fn stdout(columns: usize, rows: usize, color: bool, refresh_rate: u32) -> Self{
+        Self::new(columns, rows, color, refresh_rate, io::stdout())
+    }
+
+    /// Bytes of memory the screen covers, including the colour attributes.
+    pub fn size(columns: usize, rows: usize, color: bool) -> usize{
+        columns * rows * if color { 2 } else { 1 }
+    }
+
+    /// Redraws at the start of every vertical blank of the raster instead of at the refresh rate.
+    pub fn sync_to(&mut self, position: RasterPosition){
+        let frame = position.frame();
+        self.vsync = Some((position, frame));
+    }
+
+    /// Redraws the terminal from screen memory.
+    pub fn present(&mut self){
+        let mut frame = Vec::with_capacity(self.memory.len() * 4);
+        if !self.started{
+            frame.extend_from_slice(b"\x1b[2J\x1b[?25l");   // clear, hide the cursor
+            self.started = true;
+        }
+
+        let cells = self.columns * self.rows;
+        for row in 0..self.rows{
+            frame.extend_from_slice(format!("\x1b[{};1H", row + 1).as_bytes());
+
+            let mut current = None;     // attributes last switched to, to avoid repeating them
+            for column in 0..self.columns{
+                let idx = row * self.columns + column;
+                let code = self.memory[idx];
+                let attribute = if self.color { self.memory[cells + idx] } else { Self::DEFAULT_ATTRIBUTE };
+                let inverted = (code & 0x80) > 0;
+
+                if current != Some((attribute, inverted)){
+                    frame.extend_from_slice(Self::sgr(attribute, inverted).as_bytes());
+                    current = Some((attribute, inverted));
+                }
+                frame.push(match code & 0x7f{
+                    c @ 0x20..=0x7e => c,
+                    _ => b' ',
+                });
+            }
+        }
+        frame.extend_from_slice(b"\x1b[0m");
+
+        // a terminal that cannot be written to only loses the picture
+        let _ = self.output.write_all(&frame).and_then(|_| self.output.flush());
+        self.dirty = false;
+        self.last_frame = Instant::now();
+    }
+
+    /// Select graphic rendition sequence for an attribute byte.
+    fn sgr(attribute: u8, inverted: bool) -> String{
+        let color = |index: u8, base: u8| if index < 8 { base + index } else { base + 60 + index - 8 };
+        let foreground = color(attribute & 0x0f, 30);
+        let background = color(attribute >> 4, 40);
+        format!("\x1b[0;{};{}{}m", foreground, background, if inverted { ";7" } else { "" })
+    }
+}
+impl Device for AnsiScreen{
+    fn read(&mut self, offset: u16) -> u8 {
+        self.memory.get(offset as usize).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if let Some(byte) = self.memory.get_mut(offset as usize) && *byte != val{
+            *byte = val;
+            self.dirty = true;
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.unchecked_cycles += cycles;
+        if self.unchecked_cycles < Self::CLOCK_CHECK_INTERVAL{
+            return;
+        }
+
+        self.unchecked_cycles = 0;
+        let due = match self.vsync.as_mut(){
+            Some((position, drawn)) => {
+                let frame = position.frame();
+                let due = frame != *drawn;
+                *drawn = frame;
+                due
+            },
+            None => self.last_frame.elapsed() >= self.frame_interval,
+        };
+        if self.dirty && due{
+            self.present();
+        }
+    }
+}
+impl Drop for AnsiScreen{
+    /// Shows the final picture and leaves the cursor below it.
+    fn drop(&mut self) {
+        if self.dirty || !self.started{
+            self.present();
+        }
+        let _ = write!(self.output, "\x1b[{};1H\x1b[0m\x1b[?25h\r\n", self.rows + 1);
+        let _ = self.output.flush();
+    }
+}
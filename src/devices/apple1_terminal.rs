@@ -0,0 +1,84 @@
+use std::io::{self, Write};
+use std::sync::mpsc::Receiver;
+
+use crate::devices::device::{self, Port, PortPeripheral};
+
+/**
+   The Apple 1's keyboard and video terminal, as wired to its PIA: the keyboard on port A strobing CA1,
+   the display on port B bits 0-6 latching on the CB2 handshake, and PB7 reading the display as ready.
+   Characters travel as upper-case ASCII with bit 7 set, and carriage returns end lines.
+ */
+pub struct Apple1Terminal{
+    keys: Receiver<u8>,
+    output: Box<dyn Write>,
+
+    key: u8,            // last key latched onto port A
+    key_taken: bool,    // the CPU has read the latched key, so the next one can be strobed in
+    display: u8,        // last levels seen on port B
+}
+impl Apple1Terminal{
+    pub fn new(keys: Receiver<u8>, output: impl Write + 'static) -> Self{
+        Self {
+            keys,
+            output: Box::new(output),
+            key: 0,
+            key_taken: true,
+            display: 0,
+        }
+    }
+
+    /// Keys come from the host's stdin, read on a background thread, and the display goes to stdout.
+    pub fn stdio() -> Self{
+        Self::new(device::stdin_bytes(), io::stdout())
+    }
+
+    /// Host bytes as the Apple 1 keyboard would produce them, or `None` for keys it does not have.
+    fn to_key(byte: u8) -> Option<u8>{
+        match byte{
+            b'\n' => Some(b'\r'),
+            b'\r' => None,     // the \n of a host \r\n ending is enough
+            0x08 | 0x7f => Some(b'_'),  // Wozmon's rubout
+            0x20..=0x7e => Some(byte.to_ascii_uppercase()),
+            0x1b => Some(byte),
+            _ => None,
+        }
+    }
+}
+impl PortPeripheral for Apple1Terminal{
+    fn outputs_changed(&mut self, _port_a: u8, port_b: u8) {
+        self.display = port_b;
+    }
+
+    fn inputs(&self) -> (u8, u8) {
+        (self.key | 0x80, 0x7f)
+    }
+
+    fn handshake(&mut self, port: Port) {
+        match port{
+            Port::A => self.key_taken = true,
+            Port::B => {
+                let c = match self.display & 0x7f{
+                    b'\r' => b'\n',
+                    c => c,
+                };
+                // a terminal that has gone away does not stop the machine
+                let _ = self.output.write_all(&[c]).and_then(|_| self.output.flush());
+            },
+        }
+    }
+
+    fn take_strobes(&mut self) -> (bool, bool) {
+        if !self.key_taken{
+            return (false, false);
+        }
+
+        match self.keys.try_iter().find_map(Self::to_key){
+            Some(key) => {
+                self.key = key;
+                self.key_taken = false;
+                (true, false)
+            },
+            None => (false, false),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+pub const MAX_SAMPLE_RATE: u32 = 192_000;
+
+const WAV_HEADER_SIZE: usize = 44;
+
+/// Somewhere sound-producing devices send their output, one mono sample at a time.
+pub trait AudioSink{
+    fn sample_rate(&self) -> u32;
+    fn push(&mut self, sample: i16);
+}
+
+/// Writes 16-bit mono PCM to a WAV file. The header's sizes are filled in when the writer is dropped.
+pub struct WavWriter<W: Write + Seek>{
+    output: W,
+    sample_rate: u32,
+    samples: u32,
+}
+impl WavWriter<BufWriter<File>>{
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self>{
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+impl<W: Write + Seek> WavWriter<W>{
+    pub fn new(mut output: W, sample_rate: u32) -> io::Result<Self>{
+        output.write_all(&Self::header(sample_rate, 0))?;
+        Ok(Self { output, sample_rate, samples: 0 })
+    }
+
+    /// Number of samples written so far.
+    pub fn samples(&self) -> u32{
+        self.samples
+    }
+
+    fn header(sample_rate: u32, samples: u32) -> [u8; WAV_HEADER_SIZE]{
+        let data_size = samples * 2;
+        let mut header = [0u8; WAV_HEADER_SIZE];
+
+        header[0..4].copy_from_slice(b"RIFF");
+        header[4..8].copy_from_slice(&(WAV_HEADER_SIZE as u32 - 8 + data_size).to_le_bytes());
+        header[8..12].copy_from_slice(b"WAVE");
+        header[12..16].copy_from_slice(b"fmt ");
+        header[16..20].copy_from_slice(&16u32.to_le_bytes());          // format chunk size
+        header[20..22].copy_from_slice(&1u16.to_le_bytes());           // PCM
+        header[22..24].copy_from_slice(&1u16.to_le_bytes());           // mono
+        header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+        header[28..32].copy_from_slice(&(sample_rate * 2).to_le_bytes()); // byte rate
+        header[32..34].copy_from_slice(&2u16.to_le_bytes());           // block align
+        header[34..36].copy_from_slice(&16u16.to_le_bytes());          // bits per sample
+        header[36..40].copy_from_slice(b"data");
+        header[40..44].copy_from_slice(&data_size.to_le_bytes());
+
+        header
+    }
+    fn finish(&mut self) -> io::Result<()>{
+        self.output.seek(SeekFrom::Start(0))?;
+        self.output.write_all(&Self::header(self.sample_rate, self.samples))?;
+        self.output.flush()
+    }
+}
+impl<W: Write + Seek> AudioSink for WavWriter<W>{
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, sample: i16) {
+        // a capture that fails to write is not worth stopping the machine for
+        if self.output.write_all(&sample.to_le_bytes()).is_ok(){
+            self.samples += 1;
+        }
+    }
+}
+impl<W: Write + Seek> Drop for WavWriter<W>{
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Mono samples and their sample rate from a PCM WAV file. 8 and 16 bit files are read; of several channels
+/// only the first is kept.
+pub fn read_wav(path: impl AsRef<Path>) -> io::Result<(u32, Vec<i16>)>{
+    let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+    let bytes = fs::read(path)?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE"{
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;  // channels, sample rate, bits per sample
+    let mut pos = 12;
+    while pos + 8 <= bytes.len(){
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes([bytes[pos + 4], bytes[pos + 5], bytes[pos + 6], bytes[pos + 7]]) as usize;
+        let body = &bytes[pos + 8..(pos + 8 + size).min(bytes.len())];
+
+        match id{
+            b"fmt " if body.len() >= 16 => {
+                if u16::from_le_bytes([body[0], body[1]]) != 1{
+                    return Err(invalid("WAV file is not PCM"));
+                }
+                let channels = u16::from_le_bytes([body[2], body[3]]).max(1) as usize;
+                let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                format = Some((channels, sample_rate, bits));
+            },
+            b"data" => {
+                let Some((channels, sample_rate, bits)) = format else { return Err(invalid("WAV data before its format")) };
+                let samples = match bits{
+                    8 => body.iter().step_by(channels).map(|b| ((*b as i16) - 128) << 8).collect(),
+                    16 => body.chunks_exact(2).step_by(channels).map(|b| i16::from_le_bytes([b[0], b[1]])).collect(),
+                    _ => return Err(invalid("WAV sample size is not 8 or 16 bits")),
+                };
+                return Ok((sample_rate, samples));
+            },
+            _ => {},
+        }
+        pos += 8 + size + (size & 1);   // chunks are padded to an even size
+    }
+
+    Err(invalid("WAV file has no data"))
+}
@@ -0,0 +1,241 @@
+use crate::devices::audio::AudioSink;
+use crate::devices::device::Device;
+
+// register numbers
+const TONE_PERIOD_A: usize = 0;    // fine, then coarse for each channel
+const NOISE_PERIOD: usize = 6;
+const MIXER: usize = 7;
+const AMPLITUDE_A: usize = 8;
+const ENVELOPE_PERIOD: usize = 11; // fine, then coarse
+const ENVELOPE_SHAPE: usize = 13;
+const PORT_A: usize = 14;
+
+// bits of each register that exist on the chip
+const REGISTER_MASKS: [u8; 16] = [0xff, 0x0f, 0xff, 0x0f, 0xff, 0x0f, 0x1f, 0xff, 0x1f, 0x1f, 0x1f, 0xff, 0xff, 0x0f, 0xff, 0xff];
+
+// relative output of each of the 16 amplitude levels, roughly 3dB apart
+const LEVELS: [f64; 16] = [
+    0.0, 0.0137, 0.0205, 0.0291, 0.0423, 0.0618, 0.0847, 0.1369,
+    0.1691, 0.2647, 0.3527, 0.4499, 0.5704, 0.6873, 0.8482, 1.0,
+];
+
+/**
+   Programmable sound generator with three square-wave tone channels, a noise generator and an envelope
+   generator, rendered to an audio sink at the CPU's pace. The chip is clocked at the CPU's rate, as it is
+   when fed from the CPU clock on most single board computers. The YM2149 is register compatible.
+
+   offset 0: writes select a register
+   offset 1: writes set the selected register
+   reads of either return the selected register
+
+   registers:
+   0-5     tone period of channels A, B and C, 12 bits each as fine then coarse; a channel's frequency is
+           clock / (16 * period)
+   6       noise period, 5 bits
+   7       mixer: bits 0-2 disable the tones of A-C, bits 3-5 their noise, bits 6-7 make the I/O ports outputs
+   8-10    amplitude of A-C, bits 0-3, or the envelope when bit 4 is set
+   11-12   envelope period, 16 bits as fine then coarse; one pass of the envelope takes 256 * period clocks
+   13      envelope shape: continue, attack, alternate, hold; writing restarts the envelope
+   14-15   I/O ports A and B; unconnected, so inputs read as 0xff
+
+    Datasheet: http://map.grauw.nl/resources/sound/generalinstrument_ay-3-8910.pdf
+ */
+pub struct AY38910{
+    sink: Box<dyn AudioSink>,
+    cycles_per_sample: f64,
+    pending_cycles: f64,    // elapsed cycles not yet turned into samples
+    prescaler: u32,         // elapsed cycles not yet turned into generator steps, which happen every 8
+    output_sum: f64,        // generator output summed since the last sample, for averaging
+    output_steps: u32,
+
+    selected: usize,
+    registers: [u8; 16],
+
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_counter: u16,
+    noise_half_step: bool,  // the noise generator steps every other time
+    noise_shift: u32,       // 17-bit LFSR
+    envelope_counter: u32,
+    envelope_step: u8,      // 0 to 15 within the current pass
+    envelope_attack: bool,  // rising during the current pass
+    envelope_holding: bool,
+}
+impl AY38910{
+    pub const DEFAULT_ADDRESS: u16 = 0xf090;
+    pub const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+
+    const AMPLITUDE: f64 = 10922.0;     // of each channel, so all three at full level fit in a sample
+
+    const ENVELOPE_HOLD: u8 = 0b0001;
+    const ENVELOPE_ALTERNATE: u8 = 0b0010;
+    const ENVELOPE_ATTACK: u8 = 0b0100;
+    const ENVELOPE_CONTINUE: u8 = 0b1000;
+
+    /// `clock_rate` is the CPU clock in Hz, which sets how many cycles make up each sample.
+    pub fn new(sink: impl AudioSink + 'static, clock_rate: u32) -> Self{
+        Self {
+            cycles_per_sample: clock_rate as f64 / sink.sample_rate() as f64,
+            sink: Box::new(sink),
+            pending_cycles: 0.0,
+            prescaler: 0,
+            output_sum: 0.0,
+            output_steps: 0,
+            selected: 0,
+            registers: [0; 16],
+            tone_counters: [0; 3],
+            tone_outputs: [false; 3],
+            noise_counter: 0,
+            noise_half_step: false,
+            noise_shift: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: false,
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16{
+        let fine = self.registers[TONE_PERIOD_A + channel * 2] as u16;
+        let coarse = self.registers[TONE_PERIOD_A + channel * 2 + 1] as u16;
+        ((coarse << 8) | fine).max(1)
+    }
+    fn envelope_period(&self) -> u32{
+        let fine = self.registers[ENVELOPE_PERIOD] as u32;
+        let coarse = self.registers[ENVELOPE_PERIOD + 1] as u32;
+        ((coarse << 8) | fine).max(1)
+    }
+    fn envelope_level(&self) -> u8{
+        if self.envelope_attack { self.envelope_step } else { 15 - self.envelope_step }
+    }
+
+    fn restart_envelope(&mut self){
+        self.envelope_counter = 0;
+        self.envelope_step = 0;
+        self.envelope_attack = (self.registers[ENVELOPE_SHAPE] & Self::ENVELOPE_ATTACK) != 0;
+        self.envelope_holding = false;
+    }
+    fn step_envelope(&mut self){
+        if self.envelope_holding{
+            return;
+        }
+        if self.envelope_step < 15{
+            self.envelope_step += 1;
+            return;
+        }
+
+        // end of a pass
+        let shape = self.registers[ENVELOPE_SHAPE];
+        if (shape & Self::ENVELOPE_CONTINUE) == 0{
+            self.envelope_attack = false;
+            self.envelope_holding = true;
+        }
+        else if (shape & Self::ENVELOPE_HOLD) != 0{
+            if (shape & Self::ENVELOPE_ALTERNATE) != 0{
+                self.envelope_attack = !self.envelope_attack;
+            }
+            self.envelope_holding = true;
+        }
+        else{
+            self.envelope_step = 0;
+            if (shape & Self::ENVELOPE_ALTERNATE) != 0{
+                self.envelope_attack = !self.envelope_attack;
+            }
+        }
+    }
+
+    /// Advances the generators by 8 clocks, so a tone output toggles after 8 * period and the envelope steps
+    /// after 16 * period.
+    fn step(&mut self){
+        for channel in 0..3{
+            self.tone_counters[channel] += 1;
+            if self.tone_counters[channel] >= self.tone_period(channel){
+                self.tone_counters[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+
+        self.noise_half_step = !self.noise_half_step;
+        if !self.noise_half_step{
+            self.noise_counter += 1;
+        }
+        if self.noise_counter >= (self.registers[NOISE_PERIOD] as u16).max(1){
+            self.noise_counter = 0;
+            let feedback = (self.noise_shift ^ (self.noise_shift >> 3)) & 1;
+            self.noise_shift = (self.noise_shift >> 1) | (feedback << 16);
+        }
+
+        self.envelope_counter += 1;
+        if self.envelope_counter >= self.envelope_period() * 2{
+            self.envelope_counter = 0;
+            self.step_envelope();
+        }
+    }
+
+    fn output(&self) -> f64{
+        let mixer = self.registers[MIXER];
+        let noise = (self.noise_shift & 1) != 0;
+
+        (0..3).map(|channel| {
+            let tone_off = (mixer & (1 << channel)) != 0;
+            let noise_off = (mixer & (1 << (channel + 3))) != 0;
+            if !((self.tone_outputs[channel] || tone_off) && (noise || noise_off)){
+                return 0.0;
+            }
+
+            let amplitude = self.registers[AMPLITUDE_A + channel];
+            let level = if (amplitude & 0x10) != 0 { self.envelope_level() } else { amplitude & 0x0f };
+            LEVELS[level as usize] * Self::AMPLITUDE
+        }).sum()
+    }
+
+    fn read_register(&self, register: usize) -> u8{
+        match register{
+            // ports set as inputs read the undriven pins
+            PORT_A | 15 if (self.registers[MIXER] & (1 << (register - PORT_A + 6))) == 0 => 0xff,
+            _ => self.registers[register],
+        }
+    }
+}
+impl Device for AY38910{
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.read_register(self.selected)
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if (offset & 0x1) == 0{
+            // addresses past the 16 registers deselect the chip on the real part; here they are ignored
+            if val < 16{
+                self.selected = val as usize;
+            }
+            return;
+        }
+
+        self.registers[self.selected] = val & REGISTER_MASKS[self.selected];
+        if self.selected == ENVELOPE_SHAPE{
+            self.restart_envelope();
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.prescaler += cycles;
+        while self.prescaler >= 8{
+            self.prescaler -= 8;
+            self.step();
+            self.output_sum += self.output();
+            self.output_steps += 1;
+        }
+
+        self.pending_cycles += cycles as f64;
+        while self.pending_cycles >= self.cycles_per_sample{
+            self.pending_cycles -= self.cycles_per_sample;
+            let sample = match self.output_steps{
+                0 => self.output(),
+                steps => self.output_sum / steps as f64,
+            };
+            self.output_sum = 0.0;
+            self.output_steps = 0;
+            self.sink.push(sample as i16);
+        }
+    }
+}
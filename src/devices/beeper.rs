@@ -0,0 +1,119 @@
+use crate::devices::audio::AudioSink;
+use crate::devices::device::Device;
+
+enum Register{
+    Speaker,        // any write toggles the speaker cone
+    FrequencyLow,   // tone frequency in Hz
+    FrequencyHigh,
+    Duration,       // writing starts a tone lasting this many hundredths of a second, 0 for until the frequency is set to 0
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x3{
+            0 => Register::Speaker,
+            1 => Register::FrequencyLow,
+            2 => Register::FrequencyHigh,
+            _ => Register::Duration,
+        }
+    }
+}
+
+/**
+   One-bit speaker with a square-wave tone generator, rendered to an audio sink at the CPU's pace.
+
+   offset 0: any write toggles the speaker, for programs that click it directly
+   offset 1: tone frequency in Hz, low byte
+   offset 2: tone frequency in Hz, high byte
+   offset 3: writing starts a tone lasting that many hundredths of a second (0 plays until the frequency is set
+             to 0), reading returns 1 while a tone is playing
+ */
+pub struct Beeper{
+    sink: Box<dyn AudioSink>,
+    cycles_per_sample: f64,
+    pending_cycles: f64,    // elapsed cycles not yet turned into samples
+
+    speaker: bool,
+    frequency: u16,
+    tone_playing: bool,
+    tone_samples_left: Option<u64>,
+    tone_phase: f64,        // position within the current period, 0 to 1
+}
+impl Beeper{
+    pub const DEFAULT_ADDRESS: u16 = 0xf020;
+    pub const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+
+    const AMPLITUDE: i16 = 8192;   // of each of the speaker and the tone, leaving headroom for both at once
+
+    /// `clock_rate` is the CPU clock in Hz, which sets how many cycles make up each sample.
+    pub fn new(sink: impl AudioSink + 'static, clock_rate: u32) -> Self{
+        Self {
+            cycles_per_sample: clock_rate as f64 / sink.sample_rate() as f64,
+            sink: Box::new(sink),
+            pending_cycles: 0.0,
+            speaker: false,
+            frequency: 0,
+            tone_playing: false,
+            tone_samples_left: None,
+            tone_phase: 0.0,
+        }
+    }
+
+    fn start_tone(&mut self, hundredths: u8){
+        self.tone_playing = self.frequency > 0;
+        self.tone_phase = 0.0;
+        self.tone_samples_left = match hundredths{
+            0 => None,
+            h => Some(self.sink.sample_rate() as u64 * h as u64 / 100),
+        };
+    }
+    fn set_frequency(&mut self, frequency: u16){
+        self.frequency = frequency;
+        if frequency == 0{
+            self.tone_playing = false;
+        }
+    }
+
+    fn next_sample(&mut self) -> i16{
+        let speaker = if self.speaker { Self::AMPLITUDE } else { -Self::AMPLITUDE };
+        if !self.tone_playing{
+            return speaker;
+        }
+
+        let tone = if self.tone_phase < 0.5 { Self::AMPLITUDE } else { -Self::AMPLITUDE };
+        self.tone_phase = (self.tone_phase + self.frequency as f64 / self.sink.sample_rate() as f64).fract();
+        if let Some(left) = self.tone_samples_left.as_mut(){
+            *left = left.saturating_sub(1);
+            self.tone_playing = *left > 0;
+        }
+
+        speaker + tone
+    }
+}
+impl Device for Beeper{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Speaker => 0,
+            Register::FrequencyLow => self.frequency.to_le_bytes()[0],
+            Register::FrequencyHigh => self.frequency.to_le_bytes()[1],
+            Register::Duration => self.tone_playing as u8,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Speaker => self.speaker = !self.speaker,
+            Register::FrequencyLow => self.set_frequency((self.frequency & 0xff00) | val as u16),
+            Register::FrequencyHigh => self.set_frequency((self.frequency & 0x00ff) | ((val as u16) << 8)),
+            Register::Duration => self.start_tone(val),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.pending_cycles += cycles as f64;
+        while self.pending_cycles >= self.cycles_per_sample{
+            self.pending_cycles -= self.cycles_per_sample;
+            let sample = self.next_sample();
+            self.sink.push(sample);
+        }
+    }
+}
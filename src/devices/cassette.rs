@@ -0,0 +1,266 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::devices::audio::{self, AudioSink};
+use crate::devices::device::{Device, Port, PortPeripheral};
+
+/// A cassette deck: the signal played back from tape, squared up as a comparator would, and the level
+/// written to tape recorded as a square wave. Both run at the CPU's pace.
+pub struct Tape{
+    cycles_per_playback_sample: f64,
+    playback: Vec<bool>,
+    position: f64,          // in playback samples
+    playing: bool,
+
+    recorder: Option<Box<dyn AudioSink>>,
+    cycles_per_recorded_sample: f64,
+    pending_cycles: f64,    // elapsed cycles not yet recorded
+    recording: bool,
+    output: bool,
+}
+impl Tape{
+    /// Sample rate of headerless tape images, which hold unsigned 8-bit samples.
+    pub const RAW_SAMPLE_RATE: u32 = audio::DEFAULT_SAMPLE_RATE;
+
+    const AMPLITUDE: i16 = 16384;
+    const THRESHOLD: i16 = 1024;    // hysteresis either side of zero, so noise does not make edges
+
+    /// `clock_rate` is the CPU clock in Hz. The deck starts empty, with nothing to play or record to.
+    pub fn new(clock_rate: u32) -> Self{
+        Self {
+            cycles_per_playback_sample: clock_rate as f64,
+            playback: Vec::new(),
+            position: 0.0,
+            playing: false,
+            recorder: None,
+            cycles_per_recorded_sample: clock_rate as f64,
+            pending_cycles: 0.0,
+            recording: false,
+            output: false,
+        }
+    }
+    /// Puts a recording in the deck and starts playing it.
+    pub fn with_playback(mut self, sample_rate: u32, samples: &[i16]) -> Self{
+        let mut level = false;
+        self.playback = samples.iter().map(|sample| {
+            if *sample > Self::THRESHOLD{
+                level = true;
+            }
+            else if *sample < -Self::THRESHOLD{
+                level = false;
+            }
+            level
+        }).collect();
+
+        self.cycles_per_playback_sample /= sample_rate.max(1) as f64;
+        self.position = 0.0;
+        self.playing = true;
+        self
+    }
+    /// Plays a WAV file, or a headerless file of unsigned 8-bit samples at `RAW_SAMPLE_RATE`.
+    pub fn load(self, path: impl AsRef<Path>) -> io::Result<Self>{
+        let bytes = fs::read(&path)?;
+        if bytes.starts_with(b"RIFF"){
+            let (sample_rate, samples) = audio::read_wav(&path)?;
+            Ok(self.with_playback(sample_rate, &samples))
+        }
+        else{
+            let samples = bytes.iter().map(|b| ((*b as i16) - 128) << 8).collect::<Vec<i16>>();
+            Ok(self.with_playback(Self::RAW_SAMPLE_RATE, &samples))
+        }
+    }
+    /// Records everything written to tape to the sink, starting now.
+    pub fn with_recorder(mut self, sink: impl AudioSink + 'static) -> Self{
+        self.cycles_per_recorded_sample /= sink.sample_rate().max(1) as f64;
+        self.recorder = Some(Box::new(sink));
+        self.recording = true;
+        self
+    }
+
+    /// Level of the signal coming off the tape. Low when nothing is playing.
+    pub fn input(&self) -> bool{
+        self.playing && self.playback.get(self.position as usize).copied().unwrap_or(false)
+    }
+    pub fn output(&self) -> bool{
+        self.output
+    }
+    pub fn set_output(&mut self, level: bool){
+        self.output = level;
+    }
+    pub fn toggle_output(&mut self){
+        self.output = !self.output;
+    }
+
+    pub fn playing(&self) -> bool{
+        self.playing
+    }
+    pub fn set_playing(&mut self, playing: bool){
+        self.playing = playing;
+    }
+    pub fn recording(&self) -> bool{
+        self.recording
+    }
+    pub fn set_recording(&mut self, recording: bool){
+        self.recording = recording && self.recorder.is_some();
+    }
+    /// Whether playback has run off the end of the recording.
+    pub fn at_end(&self) -> bool{
+        self.position as usize >= self.playback.len()
+    }
+    pub fn rewind(&mut self){
+        self.position = 0.0;
+    }
+
+    pub fn tick(&mut self, cycles: u32){
+        if self.playing && !self.at_end(){
+            self.position += cycles as f64 / self.cycles_per_playback_sample;
+        }
+
+        if !self.recording{
+            return;
+        }
+        let Some(recorder) = self.recorder.as_mut() else { return };
+        self.pending_cycles += cycles as f64;
+        while self.pending_cycles >= self.cycles_per_recorded_sample{
+            self.pending_cycles -= self.cycles_per_recorded_sample;
+            recorder.push(if self.output { Self::AMPLITUDE } else { -Self::AMPLITUDE });
+        }
+    }
+}
+
+enum Register{
+    Control,    // status on read
+    Output,
+    Toggle,     // any access toggles the output
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x3{
+            0 => Register::Control,
+            1 => Register::Output,
+            _ => Register::Toggle,
+        }
+    }
+}
+
+/**
+   Cassette interface with a control register and the tape signals in registers.
+
+   offset 0: reads bit 7 tape input level, bit 6 playing, bit 5 recording, bit 4 end of tape;
+             writes bit 6 to play or stop and bit 5 to record or stop recording
+   offset 1: bit 0 is the level written to tape
+   offset 2: any access toggles the level written to tape, as on the Apple 1 ACI; reads return the input
+             level in bit 7
+
+   A loaded tape starts playing, and recording starts when there is something to record to, as if the
+   buttons were pressed when the machine was switched on.
+ */
+pub struct Cassette{
+    tape: Tape,
+}
+impl Cassette{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0c0;
+    pub const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+
+    pub const STATUS_INPUT: u8 = 0b1000_0000;
+    pub const STATUS_PLAYING: u8 = 0b0100_0000;
+    pub const STATUS_RECORDING: u8 = 0b0010_0000;
+    pub const STATUS_END: u8 = 0b0001_0000;
+
+    pub fn new(tape: Tape) -> Self{
+        Self { tape }
+    }
+
+    pub fn tape(&self) -> &Tape{
+        &self.tape
+    }
+    pub fn tape_mut(&mut self) -> &mut Tape{
+        &mut self.tape
+    }
+
+    fn input(&self) -> u8{
+        if self.tape.input() { Self::STATUS_INPUT } else { 0 }
+    }
+}
+impl Device for Cassette{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Control => {
+                let mut status = self.input();
+                if self.tape.playing(){
+                    status |= Self::STATUS_PLAYING;
+                }
+                if self.tape.recording(){
+                    status |= Self::STATUS_RECORDING;
+                }
+                if self.tape.at_end(){
+                    status |= Self::STATUS_END;
+                }
+                status
+            },
+            Register::Output => self.tape.output() as u8,
+            Register::Toggle => {
+                self.tape.toggle_output();
+                self.input()
+            },
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Control => {
+                self.tape.set_playing((val & Self::STATUS_PLAYING) != 0);
+                self.tape.set_recording((val & Self::STATUS_RECORDING) != 0);
+            },
+            Register::Output => self.tape.set_output((val & 0x01) != 0),
+            Register::Toggle => self.tape.toggle_output(),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.tape.tick(cycles);
+    }
+}
+
+/// A cassette interface wired to port pins of a VIA or PIA instead: the tape input on one pin and the level
+/// written to tape taken from another, both on the same port.
+pub struct CassettePins{
+    tape: Tape,
+    port: Port,
+    input_pin: u8,
+    output_pin: u8,
+}
+impl CassettePins{
+    pub fn new(tape: Tape, port: Port, input_pin: u8, output_pin: u8) -> Self{
+        Self { tape, port, input_pin, output_pin }
+    }
+
+    pub fn tape(&self) -> &Tape{
+        &self.tape
+    }
+    pub fn tape_mut(&mut self) -> &mut Tape{
+        &mut self.tape
+    }
+}
+impl PortPeripheral for CassettePins{
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8) {
+        let pins = match self.port{
+            Port::A => port_a,
+            Port::B => port_b,
+        };
+        self.tape.set_output((pins & (1 << self.output_pin)) != 0);
+    }
+
+    fn inputs(&self) -> (u8, u8) {
+        let pins = if self.tape.input() { 0xff } else { !(1 << self.input_pin) };
+        match self.port{
+            Port::A => (pins, 0xff),
+            Port::B => (0xff, pins),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.tape.tick(cycles);
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::devices::device::{self, Device};
+
+/// Emits every byte written to it to the host, the putchar convention used by many 6502 test programs.
+/// Reads return 0.
+pub struct CharOutput{
+    output: Box<dyn Write>,
+}
+impl CharOutput{
+    pub const DEFAULT_ADDRESS: u16 = 0xf001;
+
+    pub fn new(output: impl Write + 'static) -> Self{
+        Self { output: Box::new(output) }
+    }
+    pub fn stdout() -> Self{
+        Self::new(io::stdout())
+    }
+}
+impl Device for CharOutput{
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, val: u8) {
+        // a host that has stopped listening does not stop the machine
+        let _ = self.output.write_all(&[val]).and_then(|_| self.output.flush());
+    }
+}
+
+/// The getchar half of the convention, as a pair of registers: a status byte that reads 0x80 while a
+/// character is waiting (so it can be polled with `BIT`/`BMI`), followed by the character itself, which
+/// reading consumes. The character reads 0 when nothing is waiting. Writes are ignored.
+pub struct CharInput{
+    input: Receiver<u8>,
+    pending: Option<u8>,
+}
+impl CharInput{
+    pub const DEFAULT_ADDRESS: u16 = 0xf004;
+
+    const STATUS: u16 = 0;
+    const READY: u8 = 0x80;
+
+    pub fn new(input: Receiver<u8>) -> Self{
+        Self { input, pending: None }
+    }
+    pub fn stdin() -> Self{
+        Self::new(device::stdin_bytes())
+    }
+    /// Input that is all known up front, such as a file of keystrokes for a scripted run. Once it has all been
+    /// read, nothing is waiting ever again.
+    pub fn from_bytes(bytes: &[u8]) -> Self{
+        let (sender, input) = mpsc::channel();
+        for &byte in bytes{
+            let _ = sender.send(byte);
+        }
+        Self::new(input)
+    }
+
+    fn poll(&mut self) -> Option<u8>{
+        if self.pending.is_none(){
+            self.pending = self.input.try_recv().ok();
+        }
+        self.pending
+    }
+}
+impl Device for CharInput{
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == Self::STATUS{
+            self.poll().map_or(0, |_| Self::READY)
+        }
+        else{
+            self.poll();
+            self.pending.take().unwrap_or(0)
+        }
+    }
+
+    fn write(&mut self, _offset: u16, _val: u8) { }
+}
+
+/// Both halves of the console on a TCP port, so a terminal can be attached with `telnet` or `nc`. One client is
+/// served at a time; when it disconnects the next connection takes over. Output while no client is connected
+/// is dropped.
+pub fn tcp_console(address: impl ToSocketAddrs) -> io::Result<(Receiver<u8>, TcpConsoleOutput)>{
+    let listener = TcpListener::bind(address)?;
+    let client = Arc::new(Mutex::new(None));
+    let (sender, input) = mpsc::channel();
+
+    let output = TcpConsoleOutput { client: client.clone() };
+    thread::spawn(move || {
+        for stream in listener.incoming(){
+            let Ok(stream) = stream else { continue };
+            let _ = stream.set_nodelay(true);
+            *client.lock().unwrap() = stream.try_clone().ok();
+
+            for byte in BufReader::new(stream).bytes(){
+                let Ok(byte) = byte else { break };
+                if sender.send(byte).is_err(){
+                    return;
+                }
+            }
+            *client.lock().unwrap() = None;
+        }
+    });
+
+    Ok((input, output))
+}
+
+/// The output half of `tcp_console`, for a `CharOutput`.
+pub struct TcpConsoleOutput{
+    client: Arc<Mutex<Option<TcpStream>>>,
+}
+impl Write for TcpConsoleOutput{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut client = self.client.lock().unwrap();
+        if let Some(stream) = client.as_mut() && stream.write_all(buf).is_err(){
+            *client = None;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::Device;
+
+/**
+   Counts the CPU cycles that have elapsed, so guest code can time its own routines.
+
+   offset 0-7: the count, little-endian. Reading offset 0 latches all 8 bytes, and the other offsets read the
+               latched count, so read the low byte first. Writing any offset resets the count to 0.
+
+   The count moves on as each instruction completes, so a read sees the cycles of every instruction before
+   the one reading it. A routine timed between a reset and a read is counted with the 4 cycles of the
+   absolute store that did the reset.
+ */
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CycleCounter{
+    cycles: u64,
+    latched: u64,
+}
+impl CycleCounter{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0b8;
+
+    pub fn new() -> Self{
+        Self { cycles: 0, latched: 0 }
+    }
+
+    pub fn cycles(&self) -> u64{
+        self.cycles
+    }
+}
+impl Default for CycleCounter{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Device for CycleCounter{
+    fn read(&mut self, offset: u16) -> u8 {
+        let offset = offset & 0x7;
+        if offset == 0{
+            self.latched = self.cycles;
+        }
+        (self.latched >> (offset * 8)) as u8
+    }
+
+    fn write(&mut self, _offset: u16, _val: u8) {
+        self.cycles = 0;
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        *self = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
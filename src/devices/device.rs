@@ -0,0 +1,104 @@
+use std::any::Any;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::bus::bus::GuestMemory;
+
+/// A memory-mapped peripheral. Accesses are routed to it ahead of any RAM or ROM mapping.
+pub trait Device: Any{
+    /// `offset` is relative to the first address the device is mapped at.
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, val: u8);
+
+    /// Advances the device by the given number of CPU cycles.
+    fn tick(&mut self, _cycles: u32){ }
+    /// Gives the device direct access to memory after every tick, for work on guest buffers.
+    fn dma(&mut self, _memory: &mut GuestMemory<'_>){ }
+    /// Whether the device is currently asserting the IRQ line.
+    fn irq(&self) -> bool{
+        false
+    }
+    /// Whether the device is currently asserting the NMI line.
+    fn nmi(&self) -> bool{
+        false
+    }
+    /// Whether the device is currently asserting the RESET line, as a watchdog or a reset button would.
+    fn reset(&self) -> bool{
+        false
+    }
+    /// What the device is called in the log, its type name unless it says otherwise.
+    fn name(&self) -> &'static str{
+        let name = std::any::type_name::<Self>();
+        name.split('<').next().unwrap_or(name).rsplit("::").next().unwrap_or(name)
+    }
+
+    /// The device's registers and internal state, for `Machine::state`. None for a device that starts over from
+    /// its power-on state when a machine is restored, such as one holding a host connection.
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        None
+    }
+    /// Puts back what `save_state` returned, keeping whatever is wired to the device.
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, _state: serde_json::Value) -> Result<(), serde_json::Error>{
+        Ok(())
+    }
+}
+
+/// Handle to a device attached to a `Machine`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId(pub(crate) usize);
+
+
+/// One of the two 8-bit ports of a VIA or PIA.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Port{
+    A,
+    B,
+}
+
+/// Something wired to the pins of a VIA's or PIA's ports.
+pub trait PortPeripheral: Any{
+    /// Called whenever the CPU writes a port's output or direction register. Pins configured as inputs are seen as high.
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8);
+    /// Levels the peripheral drives onto ports A and B. Undriven pins should be reported high,
+    /// since the levels of all peripherals are wired-AND together.
+    fn inputs(&self) -> (u8, u8){
+        (0xff, 0xff)
+    }
+
+    /// Called when the controller pulses a port's handshake output (CA2/CB2): after the CPU reads port A
+    /// or writes port B, when that line is in handshake or pulse mode.
+    fn handshake(&mut self, _port: Port){ }
+    /// Active transitions the peripheral made on the CA1 and CB1 inputs since it was last asked.
+    /// Polled every time the controller is ticked.
+    fn take_strobes(&mut self) -> (bool, bool){
+        (false, false)
+    }
+    /// Advances the peripheral by the given number of CPU cycles, every time the controller is ticked.
+    fn tick(&mut self, _cycles: u32){ }
+    /// The next bit the peripheral clocks into the controller's shift register, its level on CB2 sampled
+    /// on a CB1 clock. Polled every time the controller is ticked while its shift register is clocked
+    /// externally, with the cycles since the last poll.
+    fn shift_in(&mut self, _cycles: u32) -> Option<bool>{
+        None
+    }
+}
+
+
+/// Bytes read from the host's stdin on a background thread, for devices that take keyboard input.
+/// The channel disconnects once stdin is closed.
+pub fn stdin_bytes() -> Receiver<u8>{
+    let (sender, bytes) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes(){
+            let Ok(byte) = byte else { break };
+            if sender.send(byte).is_err(){
+                break;
+            }
+        }
+    });
+
+    bytes
+}
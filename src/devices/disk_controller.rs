@@ -0,0 +1,365 @@
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::bus::bus::GuestMemory;
+use crate::devices::device::Device;
+use crate::devices::sd_card::BlockImage;
+
+enum Register{
+    Command,    // status on read
+    Track,
+    Sector,
+    Data,
+    Drive,
+    Control,
+    DmaLow,     // DMA buffer address
+    DmaHigh,
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x7{
+            0 => Register::Command,
+            1 => Register::Track,
+            2 => Register::Sector,
+            3 => Register::Data,
+            4 => Register::Drive,
+            5 => Register::Control,
+            6 => Register::DmaLow,
+            _ => Register::DmaHigh,
+        }
+    }
+}
+
+/// Layout of a flat disk image: every sector of track 0, then of track 1, and so on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiskGeometry{
+    pub tracks: u16,
+    pub sectors: u16,       // per track
+    pub sector_size: usize,
+}
+impl DiskGeometry{
+    pub const DEFAULT: Self = Self { tracks: 80, sectors: 16, sector_size: 256 };
+
+    /// Bytes in a full image.
+    pub fn size(&self) -> u64{
+        self.tracks as u64 * self.sectors as u64 * self.sector_size as u64
+    }
+}
+
+struct Drive{
+    image: Box<dyn BlockImage>,
+    geometry: DiskGeometry,
+    write_protected: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Transfer{
+    Idle,
+    Reading,            // the CPU is taking the buffer through the data register
+    Writing,            // the CPU is filling the buffer through the data register
+    DmaRead,            // the buffer goes to memory at the next DMA slot
+    DmaWrite,           // the buffer comes from memory at the next DMA slot
+}
+
+/**
+   Disk controller for up to 4 drives of flat, sector-addressed image files.
+
+   offset 0:   writes a command, reads the status
+   offset 1:   track, from 0
+   offset 2:   sector, from 0
+   offset 3:   data; sector bytes are read or written here one at a time while DRQ is set
+   offset 4:   selected drive, 0-3
+   offset 5:   control, bit 0 enables the interrupt raised when a command completes
+   offset 6-7: DMA buffer address, little-endian
+
+   commands:
+   0x00  abort the transfer in progress
+   0x01  read the sector at track/sector into the data register's buffer
+   0x02  write the sector at track/sector from the bytes then written to the data register
+   setting bit 7 of a read or write moves the sector to or from memory at the DMA address instead, completing
+   before the next instruction runs
+
+   Reading the status acknowledges the interrupt. Parts of an image past the end of the file read as zeroes.
+ */
+pub struct DiskController{
+    drives: [Option<Drive>; 4],
+    selected: usize,
+    track: u8,
+    sector: u8,
+    control: u8,
+    dma_address: u16,
+
+    status: u8,
+    transfer: Transfer,
+    buffer: Vec<u8>,
+    position: usize,    // next byte of the buffer through the data register
+    irq: bool,
+}
+impl DiskController{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0d0;
+    pub const DRIVES: usize = 4;
+
+    // status register bits
+    pub const STATUS_NOT_READY: u8 = 0b1000_0000;          // no image in the selected drive
+    pub const STATUS_WRITE_PROTECTED: u8 = 0b0100_0000;
+    pub const STATUS_RECORD_NOT_FOUND: u8 = 0b0001_0000;   // track or sector past the disk's geometry
+    pub const STATUS_IO_ERROR: u8 = 0b0000_1000;           // the image or the DMA buffer could not be accessed
+    pub const STATUS_INVALID_COMMAND: u8 = 0b0000_0100;
+    pub const STATUS_DRQ: u8 = 0b0000_0010;                // the data register is ready for the next byte
+    pub const STATUS_BUSY: u8 = 0b0000_0001;
+
+    pub const CONTROL_IRQ_ENABLE: u8 = 0b0000_0001;
+
+    const COMMAND_ABORT: u8 = 0x00;
+    const COMMAND_READ: u8 = 0x01;
+    const COMMAND_WRITE: u8 = 0x02;
+    const COMMAND_DMA: u8 = 0x80;
+
+    pub fn new() -> Self{
+        Self {
+            drives: [None, None, None, None],
+            selected: 0,
+            track: 0,
+            sector: 0,
+            control: 0,
+            dma_address: 0,
+            status: 0,
+            transfer: Transfer::Idle,
+            buffer: Vec::new(),
+            position: 0,
+            irq: false,
+        }
+    }
+
+    /// Puts an image in a drive, replacing whatever was there.
+    ///
+    /// Panics if the geometry's sectors hold no bytes.
+    pub fn insert(&mut self, drive: usize, image: impl BlockImage + 'static, geometry: DiskGeometry, write_protected: bool){
+        assert!(geometry.sector_size > 0, "a disk sector holds at least one byte");
+        if let Some(slot) = self.drives.get_mut(drive){
+            *slot = Some(Drive { image: Box::new(image), geometry, write_protected });
+        }
+    }
+    /// Opens an image file for a drive. Files that cannot be written to are inserted write protected.
+    pub fn insert_file(&mut self, drive: usize, path: impl AsRef<Path>, geometry: DiskGeometry) -> io::Result<()>{
+        if geometry.sector_size == 0{
+            return Err(io::Error::new(ErrorKind::InvalidInput, "a disk sector holds at least one byte"));
+        }
+        match OpenOptions::new().read(true).write(true).open(&path){
+            Ok(file) => self.insert(drive, file, geometry, false),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                self.insert(drive, OpenOptions::new().read(true).open(&path)?, geometry, true);
+            },
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+    pub fn eject(&mut self, drive: usize){
+        if let Some(slot) = self.drives.get_mut(drive){
+            *slot = None;
+        }
+    }
+
+    fn complete(&mut self, status: u8){
+        log::debug!("complete, status ${:02X}", status);
+        self.status = status;
+        self.transfer = Transfer::Idle;
+        self.irq = true;
+    }
+    /// Byte offset of the addressed sector in the selected drive's image, checking everything a transfer needs.
+    fn locate(&self, writing: bool) -> Result<u64, u8>{
+        let Some(drive) = &self.drives[self.selected] else { return Err(Self::STATUS_NOT_READY) };
+        if writing && drive.write_protected{
+            return Err(Self::STATUS_WRITE_PROTECTED);
+        }
+
+        let geometry = drive.geometry;
+        if self.track as u16 >= geometry.tracks || self.sector as u16 >= geometry.sectors{
+            return Err(Self::STATUS_RECORD_NOT_FOUND);
+        }
+        Ok((self.track as u64 * geometry.sectors as u64 + self.sector as u64) * geometry.sector_size as u64)
+    }
+
+    fn command(&mut self, val: u8){
+        let dma = (val & Self::COMMAND_DMA) != 0;
+        log::debug!("command ${:02X}, drive {} track {} sector {}", val, self.selected, self.track, self.sector);
+        match val & !Self::COMMAND_DMA{
+            Self::COMMAND_ABORT => self.complete(0),
+            Self::COMMAND_READ => match self.read_sector(){
+                Ok(()) => {
+                    self.position = 0;
+                    self.transfer = if dma { Transfer::DmaRead } else { Transfer::Reading };
+                    self.status = Self::STATUS_BUSY | if dma { 0 } else { Self::STATUS_DRQ };
+                },
+                Err(status) => self.complete(status),
+            },
+            Self::COMMAND_WRITE => match self.locate(true){
+                Ok(_) => {
+                    let size = self.drives[self.selected].as_ref().map_or(0, |d| d.geometry.sector_size);
+                    self.buffer = vec![0; size];
+                    self.position = 0;
+                    self.transfer = if dma { Transfer::DmaWrite } else { Transfer::Writing };
+                    self.status = Self::STATUS_BUSY | if dma { 0 } else { Self::STATUS_DRQ };
+                },
+                Err(status) => self.complete(status),
+            },
+            _ => self.complete(Self::STATUS_INVALID_COMMAND),
+        }
+    }
+
+    fn read_sector(&mut self) -> Result<(), u8>{
+        let offset = self.locate(false)?;
+        let Some(drive) = self.drives[self.selected].as_mut() else { return Err(Self::STATUS_NOT_READY) };
+
+        let mut buffer = vec![0; drive.geometry.sector_size];
+        let read = drive.image.seek(SeekFrom::Start(offset)).and_then(|_| {
+            // a short read at the end of the image leaves the rest of the sector zeroed
+            let mut filled = 0;
+            while filled < buffer.len(){
+                match drive.image.read(&mut buffer[filled..])?{
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            Ok(())
+        });
+        self.buffer = buffer;
+        read.map_err(|_| Self::STATUS_IO_ERROR)
+    }
+    fn write_sector(&mut self) -> Result<(), u8>{
+        let offset = self.locate(true)?;
+        let Some(drive) = self.drives[self.selected].as_mut() else { return Err(Self::STATUS_NOT_READY) };
+
+        drive.image.seek(SeekFrom::Start(offset))
+            .and_then(|_| drive.image.write_all(&self.buffer))
+            .and_then(|_| drive.image.flush())
+            .map_err(|_| Self::STATUS_IO_ERROR)
+    }
+    fn finish_write(&mut self){
+        let status = match self.write_sector(){
+            Ok(()) => 0,
+            Err(status) => status,
+        };
+        self.complete(status);
+    }
+}
+impl Default for DiskController{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Device for DiskController{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Command => {
+                self.irq = false;
+                self.status
+            },
+            Register::Track => self.track,
+            Register::Sector => self.sector,
+            Register::Data => {
+                if self.transfer != Transfer::Reading{
+                    return 0;
+                }
+
+                let val = self.buffer[self.position];
+                self.position += 1;
+                if self.position == self.buffer.len(){
+                    self.complete(0);
+                }
+                val
+            },
+            Register::Drive => self.selected as u8,
+            Register::Control => self.control,
+            Register::DmaLow => self.dma_address.to_le_bytes()[0],
+            Register::DmaHigh => self.dma_address.to_le_bytes()[1],
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Command => self.command(val),
+            Register::Track => self.track = val,
+            Register::Sector => self.sector = val,
+            Register::Data => {
+                if self.transfer != Transfer::Writing{
+                    return;
+                }
+
+                self.buffer[self.position] = val;
+                self.position += 1;
+                if self.position == self.buffer.len(){
+                    self.finish_write();
+                }
+            },
+            Register::Drive => self.selected = (val as usize) % Self::DRIVES,
+            Register::Control => self.control = val,
+            Register::DmaLow => self.dma_address = (self.dma_address & 0xff00) | val as u16,
+            Register::DmaHigh => self.dma_address = (self.dma_address & 0x00ff) | ((val as u16) << 8),
+        }
+    }
+
+    fn dma(&mut self, memory: &mut GuestMemory<'_>) {
+        match self.transfer{
+            Transfer::DmaRead => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(address = self.dma_address, length = self.buffer.len(), "DMA to memory");
+                let written = self.buffer.iter().enumerate()
+                    .try_for_each(|(i, byte)| memory.write(self.dma_address.wrapping_add(i as u16), *byte));
+                self.complete(if written.is_ok() { 0 } else { Self::STATUS_IO_ERROR });
+            },
+            Transfer::DmaWrite => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(address = self.dma_address, length = self.buffer.len(), "DMA from memory");
+                let read = (0..self.buffer.len())
+                    .map(|i| memory.read(self.dma_address.wrapping_add(i as u16)))
+                    .collect::<Result<Vec<u8>, _>>();
+                match read{
+                    Ok(buffer) => {
+                        self.buffer = buffer;
+                        self.finish_write();
+                    },
+                    Err(_) => self.complete(Self::STATUS_IO_ERROR),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq && (self.control & Self::CONTROL_IRQ_ENABLE) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::io::Cursor;
+
+    use super::*;
+
+    const EMPTY_SECTORS: DiskGeometry = DiskGeometry { tracks: 1, sectors: 1, sector_size: 0 };
+
+    #[test]
+    fn reads_a_sector_through_the_data_register(){
+        let mut controller = DiskController::new();
+        controller.insert(0, Cursor::new(vec![0x11, 0x22, 0x33, 0x44]), DiskGeometry { tracks: 1, sectors: 2, sector_size: 2 }, true);
+        controller.write(2, 1);
+        controller.write(0, 0x01);
+
+        assert_eq!([controller.read(3), controller.read(3)], [0x33, 0x44]);
+        assert_eq!(controller.read(0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "a disk sector holds at least one byte")]
+    fn sectors_of_no_bytes_are_refused(){
+        DiskController::new().insert(0, Cursor::new(Vec::new()), EMPTY_SECTORS, false);
+    }
+
+    #[test]
+    fn image_files_with_sectors_of_no_bytes_are_refused(){
+        let result = DiskController::new().insert_file(0, "no such image", EMPTY_SECTORS);
+        assert_eq!(result.map_err(|e| e.kind()), Err(ErrorKind::InvalidInput));
+    }
+}
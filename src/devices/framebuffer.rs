@@ -0,0 +1,228 @@
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use font8x8::legacy::BASIC_LEGACY;
+use minifb::{InputCallback, Key, Scale, Window, WindowOptions};
+
+use crate::devices::device::Device;
+use crate::devices::joystick::{JoystickButtons, JoystickKeys};
+use crate::devices::raster::RasterPosition;
+
+/// How the bytes of a framebuffer are turned into pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FramebufferMode{
+    /// One bit per pixel, rows packed, the most significant bit leftmost.
+    Bitmap{ width: usize, height: usize },
+    /// One byte per 8x8 character cell. Codes 0x00-0x7f are ASCII, bit 7 shows the character inverted.
+    Text{ columns: usize, rows: usize },
+}
+impl FramebufferMode{
+    /// Bytes of memory the framebuffer covers.
+    pub fn size(&self) -> usize{
+        match *self{
+            FramebufferMode::Bitmap { width, height } => width.div_ceil(8) * height,
+            FramebufferMode::Text { columns, rows } => columns * rows,
+        }
+    }
+    /// Width and height in pixels.
+    pub fn resolution(&self) -> (usize, usize){
+        match *self{
+            FramebufferMode::Bitmap { width, height } => (width, height),
+            FramebufferMode::Text { columns, rows } => (columns * 8, rows * 8),
+        }
+    }
+}
+
+/**
+   Memory-mapped framebuffer shown in a host window. The CPU reads and writes the video memory like RAM;
+   the window is redrawn from it at the refresh rate, measured in host time, or at every vertical blank of a
+   raster timer it is synced to.
+ */
+pub struct Framebuffer{
+    mode: FramebufferMode,
+    vram: Vec<u8>,
+
+    window: Window,
+    pixels: Vec<u32>,
+    frame_interval: Duration,
+    last_frame: Instant,
+    unchecked_cycles: u32,  // cycles since the clock was last looked at
+    vsync: Option<(RasterPosition, u32)>,   // raster the window is synced to, and the frame last drawn
+    joystick: Option<(JoystickButtons, JoystickKeys)>,
+}
+impl Framebuffer{
+    pub const DEFAULT_ADDRESS: u16 = 0x4000;
+    pub const DEFAULT_REFRESH_RATE: u32 = 60;
+
+    const FOREGROUND: u32 = 0x00ff_ffff;
+    const BACKGROUND: u32 = 0x0000_0000;
+    const CLOCK_CHECK_INTERVAL: u32 = 1024;
+
+    pub fn new(mode: FramebufferMode, refresh_rate: u32) -> Result<Self, minifb::Error>{
+        let (width, height) = mode.resolution();
+        let scale = if width <= 320 { Scale::X2 } else { Scale::X1 };
+        let mut window = Window::new("Steel6502", width, height, WindowOptions { scale, ..WindowOptions::default() })?;
+        window.set_target_fps(0);   // frames are paced by the device
+
+        let mut framebuffer = Self {
+            mode,
+            vram: vec![0; mode.size()],
+            window,
+            pixels: vec![Self::BACKGROUND; width * height],
+            frame_interval: Duration::from_secs(1) / refresh_rate.max(1),
+            last_frame: Instant::now(),
+            unchecked_cycles: 0,
+            vsync: None,
+            joystick: None,
+        };
+        framebuffer.present();
+
+        Ok(framebuffer)
+    }
+
+    pub fn mode(&self) -> FramebufferMode{
+        self.mode
+    }
+    /// False once the user has closed the window.
+    pub fn is_open(&self) -> bool{
+        self.window.is_open()
+    }
+    /// Sends keys typed into the window as ASCII, for example to a `Keyboard` made with `Keyboard::channel`.
+    pub fn forward_keys(&mut self, keys: Sender<u8>){
+        self.window.set_input_callback(Box::new(KeyForwarder(keys)));
+    }
+
+    /// Holds the joystick's buttons while their keys, or the arrow keys, are held down in the window.
+    pub fn drive_joystick(&mut self, buttons: JoystickButtons, keys: JoystickKeys){
+        self.joystick = Some((buttons, keys));
+    }
+
+    /// Redraws at the start of every vertical blank of the raster instead of at the refresh rate.
+    pub fn sync_to(&mut self, position: RasterPosition){
+        let frame = position.frame();
+        self.vsync = Some((position, frame));
+    }
+
+    /// Redraws the window from video memory and handles its events.
+    pub fn present(&mut self){
+        self.render();
+
+        let (width, height) = self.mode.resolution();
+        // a window that failed to update is reported through `is_open`
+        let _ = self.window.update_with_buffer(&self.pixels, width, height);
+        self.last_frame = Instant::now();
+
+        if let Some((buttons, keys)) = &self.joystick{
+            let directions = [(Key::Up, JoystickButtons::UP), (Key::Down, JoystickButtons::DOWN), (Key::Left, JoystickButtons::LEFT), (Key::Right, JoystickButtons::RIGHT)];
+            let held = self.window.get_keys().into_iter()
+                .filter_map(|key| directions.iter().find(|(k, _)| *k == key).map(|(_, b)| *b).or_else(|| keys.button(key_char(key)?)))
+                .fold(0, |held, button| held | button);
+            if held != buttons.held(){
+                buttons.set(held);
+            }
+        }
+    }
+
+    fn render(&mut self){
+        match self.mode{
+            FramebufferMode::Bitmap { width, height } => {
+                let stride = width.div_ceil(8);
+                for y in 0..height{
+                    for x in 0..width{
+                        let set = (self.vram[y * stride + x / 8] & (0x80 >> (x % 8))) > 0;
+                        self.pixels[y * width + x] = if set { Self::FOREGROUND } else { Self::BACKGROUND };
+                    }
+                }
+            },
+            FramebufferMode::Text { columns, rows } => {
+                let width = columns * 8;
+                for row in 0..rows{
+                    for column in 0..columns{
+                        let code = self.vram[row * columns + column];
+                        let glyph = BASIC_LEGACY[(code & 0x7f) as usize];
+                        let inverted = (code & 0x80) > 0;
+
+                        for (line, bits) in glyph.iter().enumerate(){
+                            let start = (row * 8 + line) * width + column * 8;
+                            for (x, pixel) in self.pixels[start..start + 8].iter_mut().enumerate(){
+                                let set = ((bits >> x) & 1 > 0) != inverted;    // glyph rows are stored leftmost pixel first
+                                *pixel = if set { Self::FOREGROUND } else { Self::BACKGROUND };
+                            }
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+impl Device for Framebuffer{
+    fn read(&mut self, offset: u16) -> u8 {
+        self.vram.get(offset as usize).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if let Some(byte) = self.vram.get_mut(offset as usize){
+            *byte = val;
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.unchecked_cycles += cycles;
+        if self.unchecked_cycles < Self::CLOCK_CHECK_INTERVAL{
+            return;
+        }
+
+        self.unchecked_cycles = 0;
+        let due = match self.vsync.as_mut(){
+            Some((position, drawn)) => {
+                let frame = position.frame();
+                let due = frame != *drawn;
+                *drawn = frame;
+                due
+            },
+            None => self.last_frame.elapsed() >= self.frame_interval,
+        };
+        if due{
+            self.present();
+        }
+    }
+}
+
+/// The character on a letter, digit or space key.
+fn key_char(key: Key) -> Option<char>{
+    const LETTERS: [Key; 26] = [
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+        Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    ];
+    const DIGITS: [Key; 10] = [Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9];
+
+    if key == Key::Space{
+        return Some(' ');
+    }
+    LETTERS.iter().position(|k| *k == key).map(|idx| (b'a' + idx as u8) as char)
+        .or_else(|| DIGITS.iter().position(|k| *k == key).map(|idx| (b'0' + idx as u8) as char))
+}
+
+struct KeyForwarder(Sender<u8>);
+impl InputCallback for KeyForwarder{
+    fn add_char(&mut self, uni_char: u32) {
+        if let Ok(code) = u8::try_from(uni_char) && (0x20..0x7f).contains(&code){
+            let _ = self.0.send(code);
+        }
+    }
+
+    /// Control keys never reach `add_char`.
+    fn set_key_state(&mut self, key: Key, pressed: bool) {
+        let code = match key{
+            Key::Enter | Key::NumPadEnter => b'\r',
+            Key::Backspace => 0x08,
+            Key::Tab => b'\t',
+            Key::Escape => 0x1b,
+            Key::Delete => 0x7f,
+            _ => return,
+        };
+        if pressed{
+            let _ = self.0.send(code);
+        }
+    }
+}
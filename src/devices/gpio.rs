@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::devices::device::Device;
+
+enum Register{
+    Data,           // output latch on write, pin levels on read
+    Direction,      // 1 bits are outputs
+    IrqEnable,      // input pins whose changes raise an IRQ
+    IrqFlags,       // input pins that changed; writing 1 bits clears them
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x3{
+            0 => Register::Data,
+            1 => Register::Direction,
+            2 => Register::IrqEnable,
+            _ => Register::IrqFlags,
+        }
+    }
+}
+
+/// Levels the host drives onto a GPIO port's input pins. Clones share the same pins, so they can be handed
+/// to other threads.
+#[derive(Clone, Debug)]
+pub struct GpioInputs(Arc<AtomicU8>);
+impl GpioInputs{
+    pub fn set(&self, levels: u8){
+        self.0.store(levels, Ordering::Relaxed);
+    }
+    pub fn get(&self) -> u8{
+        self.0.load(Ordering::Relaxed)
+    }
+    /// Drives a single pin, leaving the others as they are.
+    pub fn set_pin(&self, pin: u8, high: bool){
+        let mask = 1 << pin;
+        if high{
+            self.0.fetch_or(mask, Ordering::Relaxed);
+        }
+        else{
+            self.0.fetch_and(!mask, Ordering::Relaxed);
+        }
+    }
+}
+
+/**
+   8-bit general purpose I/O port connecting the CPU to host code.
+
+   offset 0: writes set the output latch, reads return the pin levels
+   offset 1: data direction, 1 bits are outputs
+   offset 2: input pins whose changes raise an IRQ
+   offset 3: input pins that have changed since last cleared; writing 1 bits clears them
+
+   Changes to the levels the CPU drives are reported to a host callback. Input pins are undriven (high) until
+   the host sets them through `inputs`.
+ */
+pub struct Gpio{
+    output: u8,
+    direction: u8,
+    irq_enable: u8,
+    irq_flags: u8,
+
+    inputs: GpioInputs,
+    last_inputs: u8,        // input levels as of the last check, for detecting changes
+    on_change: Option<Box<dyn FnMut(u8, u8)>>,
+}
+impl Gpio{
+    pub const DEFAULT_ADDRESS: u16 = 0xf050;
+
+    pub fn new() -> Self{
+        Self {
+            output: 0,
+            direction: 0,
+            irq_enable: 0,
+            irq_flags: 0,
+            inputs: GpioInputs(Arc::new(AtomicU8::new(0xff))),
+            last_inputs: 0xff,
+            on_change: None,
+        }
+    }
+    /// Calls `on_change` with the pin levels and the mask of changed pins whenever the CPU changes what it drives.
+    pub fn with_callback(mut self, on_change: impl FnMut(u8, u8) + 'static) -> Self{
+        self.on_change = Some(Box::new(on_change));
+        self
+    }
+
+    /// Handle for driving the input pins from the host.
+    pub fn inputs(&self) -> GpioInputs{
+        self.inputs.clone()
+    }
+    /// Current level of every pin: the output latch where the CPU drives it, the host's level elsewhere.
+    pub fn pins(&self) -> u8{
+        (self.output & self.direction) | (self.inputs.get() & !self.direction)
+    }
+
+    fn driven(&self) -> u8{
+        (self.output & self.direction) | !self.direction
+    }
+    fn update_outputs(&mut self, before: u8){
+        let changed = before ^ self.driven();
+        if changed != 0{
+            let pins = self.pins();
+            if let Some(on_change) = self.on_change.as_mut(){
+                on_change(pins, changed);
+            }
+        }
+    }
+    fn check_inputs(&mut self){
+        let inputs = self.inputs.get();
+        self.irq_flags |= (inputs ^ self.last_inputs) & !self.direction;
+        self.last_inputs = inputs;
+    }
+}
+impl Default for Gpio{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Device for Gpio{
+    fn read(&mut self, offset: u16) -> u8 {
+        self.check_inputs();
+        match Register::decode(offset){
+            Register::Data => self.pins(),
+            Register::Direction => self.direction,
+            Register::IrqEnable => self.irq_enable,
+            Register::IrqFlags => self.irq_flags,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        let before = self.driven();
+        match Register::decode(offset){
+            Register::Data => self.output = val,
+            Register::Direction => self.direction = val,
+            Register::IrqEnable => self.irq_enable = val,
+            Register::IrqFlags => self.irq_flags &= !val,
+        }
+        self.update_outputs(before);
+    }
+
+    fn tick(&mut self, _cycles: u32) {
+        self.check_inputs();
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq_flags & self.irq_enable) != 0
+    }
+}
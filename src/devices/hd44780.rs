@@ -0,0 +1,223 @@
+use crate::devices::device::{Port, PortPeripheral};
+
+/// Which VIA pins the LCD is wired to. The data lines are on port B, either all 8 of them or 4 consecutive pins
+/// wired to D7-D4, for driving the controller in 4-bit mode.
+#[derive(Copy, Clone, Debug)]
+pub struct LcdWiring{
+    pub control: Port,  // port the control lines are on, which may share port B with a 4-bit data bus
+    pub e: u8,          // mask of the enable line
+    pub rw: u8,         // mask of the read/write line
+    pub rs: u8,         // mask of the register select line
+    pub data: u8,       // port B mask of the data lines: 0xff for D7-D0, or 4 consecutive pins for D7-D4
+}
+impl LcdWiring{
+    /// PA7 = E, PA6 = RW, PA5 = RS and PB7-PB0 = D7-D0, as on Ben Eater's breadboard computer.
+    pub const BEN_EATER: Self = Self { control: Port::A, e: 0b1000_0000, rw: 0b0100_0000, rs: 0b0010_0000, data: 0xff };
+    /// PB6 = E, PB5 = RW, PB4 = RS and PB3-PB0 = D7-D4, as on the later builds of Ben Eater's computer, which
+    /// free port A for a keyboard and drive the LCD in 4-bit mode.
+    pub const BEN_EATER_4BIT: Self = Self { control: Port::B, e: 0b0100_0000, rw: 0b0010_0000, rs: 0b0001_0000, data: 0b0000_1111 };
+
+    /// The levels of the control lines out of those of the ports.
+    fn control(&self, port_a: u8, port_b: u8) -> u8{
+        match self.control{
+            Port::A => port_a,
+            Port::B => port_b,
+        }
+    }
+    /// The byte on the data bus, the wired nibble in the high half when only D7-D4 are wired.
+    fn data_in(&self, port_b: u8) -> u8{
+        let bits = (port_b & self.data) >> self.data.trailing_zeros();
+        if self.data == 0xff { bits } else { bits << 4 }
+    }
+    /// The port B levels that drive `val` onto the data bus, its high nibble when only D7-D4 are wired.
+    fn data_out(&self, val: u8) -> u8{
+        let bits = if self.data == 0xff { val } else { val >> 4 };
+        (bits << self.data.trailing_zeros()) | !self.data
+    }
+}
+
+/**
+   Character LCD controller driven through a VIA's ports, in 8-bit mode or, once a function set clears DL, in
+   4-bit mode, where each byte goes over D7-D4 as two nibbles, the high one first.
+   The controller is never busy; the busy flag always reads clear.
+
+    Datasheet: https://www.sparkfun.com/datasheets/LCD/HD44780.pdf
+ */
+pub struct HD44780{
+    wiring: LcdWiring,
+    columns: usize,
+
+    ddram: [u8; 0x80],
+    address_counter: u8,
+    increment: bool,
+    two_line: bool,
+    display_on: bool,
+    cgram_selected: bool,   // data writes go to character generator RAM, which is not modelled
+    four_bit: bool,         // the interface is 4 bits wide, set by a function set with DL clear
+    high_nibble: Option<u8>,    // in 4-bit mode, the high nibble of a byte whose low one is still to come
+    reading_low: bool,      // in 4-bit mode, the next read transfers the low nibble
+
+    control: u8,    // last levels seen on the control lines
+}
+impl HD44780{
+    const LINE_2_START: u8 = 0x40;
+    const LINE_LENGTH: u8 = 0x28;
+
+    /// A 16x2 display.
+    pub fn new(wiring: LcdWiring) -> Self{
+        Self::with_columns(wiring, 16)
+    }
+    /// A display of up to 40 columns, the length of a line of display data RAM.
+    pub fn with_columns(wiring: LcdWiring, columns: usize) -> Self{
+        Self {
+            wiring,
+            columns: columns.min(Self::LINE_LENGTH as usize),
+            ddram: [b' '; 0x80],
+            address_counter: 0,
+            increment: true,
+            two_line: false,
+            display_on: false,
+            cgram_selected: false,
+            four_bit: false,
+            high_nibble: None,
+            reading_low: false,
+            control: 0,
+        }
+    }
+
+    pub fn display_on(&self) -> bool{
+        self.display_on
+    }
+    /// Visible text of a line, or `None` for a line the display is not configured to show.
+    pub fn line(&self, row: usize) -> Option<String>{
+        let start = match (row, self.two_line){
+            (0, _) => 0usize,
+            (1, true) => Self::LINE_2_START as usize,
+            _ => return None,
+        };
+
+        Some(self.ddram[start..start + self.columns].iter().map(|b| Self::glyph(*b)).collect())
+    }
+    pub fn lines(&self) -> Vec<String>{
+        (0..2).filter_map(|row| self.line(row)).collect()
+    }
+
+    /// Character ROM A00 matches ASCII for the printable range; anything else is shown as a block.
+    fn glyph(code: u8) -> char{
+        match code{
+            0x20..=0x7d => code as char,
+            _ => '\u{2588}',
+        }
+    }
+
+    fn step_address(&mut self){
+        let ac = if self.increment { self.address_counter.wrapping_add(1) } else { self.address_counter.wrapping_sub(1) };
+
+        self.address_counter = if self.two_line{
+            match ac{
+                a if a == Self::LINE_LENGTH => Self::LINE_2_START,
+                a if a == Self::LINE_2_START + Self::LINE_LENGTH => 0,
+                0xff => Self::LINE_2_START + Self::LINE_LENGTH - 1,
+                a if a == Self::LINE_2_START - 1 => Self::LINE_LENGTH - 1,
+                a => a,
+            }
+        }
+        else{
+            ac % (Self::LINE_LENGTH * 2)
+        };
+    }
+
+    fn execute(&mut self, instruction: u8){
+        match instruction.leading_zeros(){
+            0 => {  // set DDRAM address
+                self.address_counter = instruction & 0x7f;
+                self.cgram_selected = false;
+            },
+            1 => self.cgram_selected = true,   // set CGRAM address
+            2 => {  // function set
+                self.four_bit = (instruction & 0b0001_0000) == 0;
+                self.two_line = (instruction & 0b0000_1000) > 0;
+            },
+            3 if (instruction & 0b0000_1000) == 0 => {  // cursor shift; display shifts are not modelled
+                let increment = self.increment;
+                self.increment = (instruction & 0b0000_0100) > 0;
+                self.step_address();
+                self.increment = increment;
+            },
+            4 => self.display_on = (instruction & 0b0000_0100) > 0,    // display control
+            5 => self.increment = (instruction & 0b0000_0010) > 0,     // entry mode set
+            6 => {  // return home
+                self.address_counter = 0;
+                self.cgram_selected = false;
+            },
+            7 => {  // clear display
+                self.ddram = [b' '; 0x80];
+                self.address_counter = 0;
+                self.increment = true;
+                self.cgram_selected = false;
+            },
+            _ => {},
+        }
+    }
+    fn write_data(&mut self, val: u8){
+        if self.cgram_selected{
+            return;
+        }
+
+        self.ddram[self.address_counter as usize] = val;
+        self.step_address();
+    }
+}
+impl PortPeripheral for HD44780{
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8) {
+        let control = self.wiring.control(port_a, port_b);
+        let was_enabled = (self.control & self.wiring.e) > 0;
+        let enabled = (control & self.wiring.e) > 0;
+        self.control = control;
+
+        // the controller latches on the falling edge of E
+        if !was_enabled || enabled{
+            return;
+        }
+        if (control & self.wiring.rw) > 0{
+            // a read moves on to the other nibble
+            self.reading_low = self.four_bit && !self.reading_low;
+            return;
+        }
+
+        let mut val = self.wiring.data_in(port_b);
+        if self.four_bit{
+            match self.high_nibble.take(){
+                Some(high) => val = high | (val >> 4),
+                None => {
+                    self.high_nibble = Some(val);
+                    return;
+                },
+            }
+        }
+        if (control & self.wiring.rs) > 0{
+            self.write_data(val);
+        }
+        else{
+            self.execute(val);
+        }
+    }
+
+    /// While E and RW are high the controller drives the data bus: the busy flag (always clear) and
+    /// address counter, or the data RAM byte under the address counter.
+    fn inputs(&self) -> (u8, u8) {
+        let reading = (self.control & self.wiring.e) > 0 && (self.control & self.wiring.rw) > 0;
+        if !reading{
+            return (0xff, 0xff);
+        }
+
+        let val = if (self.control & self.wiring.rs) > 0{
+            self.ddram[self.address_counter as usize]
+        }
+        else{
+            self.address_counter & 0x7f
+        };
+        let val = if self.reading_low { val << 4 } else { val };
+        (0xff, self.wiring.data_out(val))
+    }
+}
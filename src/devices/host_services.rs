@@ -0,0 +1,300 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bus::bus::GuestMemory;
+use crate::devices::device::Device;
+
+/// Requests a guest can make by writing the command register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Command{
+    Print,          // NUL-terminated string at ARG0
+    Time,           // host wall time into the result registers
+    ReadBlock,      // block ARG1 of the host block file into the page at ARG0
+    WriteBlock,     // the page at ARG0 into block ARG1 of the host block file
+    LoadFile,       // the host file named at ARG0 into memory at ARG1
+    SaveFile,       // ARG2 bytes at ARG1 into the host file named at ARG0
+}
+impl Command{
+    fn decode(val: u8) -> Option<Self>{
+        match val{
+            0x01 => Some(Command::Print),
+            0x02 => Some(Command::Time),
+            0x03 => Some(Command::ReadBlock),
+            0x04 => Some(Command::WriteBlock),
+            0x05 => Some(Command::LoadFile),
+            0x06 => Some(Command::SaveFile),
+            _ => None,
+        }
+    }
+}
+
+/**
+   Paravirtual device through which guest programs ask the host for services, for firmware test suites and
+   other programs that need no real hardware.
+
+   offset 0:      write a command, read the status of the last one (0 is success)
+   offset 1:      write an exit code to stop the machine
+   offset 2-3:    ARG0, little-endian
+   offset 4-5:    ARG1, little-endian
+   offset 6-7:    ARG2, little-endian
+   offset 8-15:   result, little-endian
+
+   commands:
+   0x01  print the NUL-terminated string at ARG0
+   0x02  put the host wall time, in milliseconds since the Unix epoch, in the result
+   0x03  read block ARG1 of the host block file into the 256 bytes at ARG0; blocks past its end read as zeroes
+   0x04  write the 256 bytes at ARG0 to block ARG1 of the host block file
+   0x05  load the host file named by the NUL-terminated string at ARG0 into memory at ARG1, at most ARG2
+         bytes of it (0 for no limit) and never past $FFFF; the result is the number of bytes loaded
+   0x06  save the ARG2 bytes at ARG1 to the host file named at ARG0, replacing it
+
+   File names are relative to the host file directory, and may name subdirectories of it with '/' but not
+   leave it.
+
+   Commands finish before the next instruction runs.
+ */
+pub struct HostServices{
+    output: Box<dyn Write>,
+    block_file: Option<File>,
+    file_directory: Option<PathBuf>,
+
+    pending: Option<u8>,
+    status: u8,
+    args: [u8; 6],
+    result: [u8; 8],
+    exit_code: Option<u8>,
+}
+impl HostServices{
+    pub const DEFAULT_ADDRESS: u16 = 0xf070;
+    pub const BLOCK_SIZE: usize = 256;
+
+    // status codes
+    pub const OK: u8 = 0x00;
+    pub const UNKNOWN_COMMAND: u8 = 0x01;
+    pub const NO_BLOCK_FILE: u8 = 0x02;
+    pub const IO_ERROR: u8 = 0x03;
+    pub const MEMORY_ERROR: u8 = 0x04;  // the buffer is not readable or writable guest memory
+    pub const NO_FILE_DIRECTORY: u8 = 0x05;
+    pub const INVALID_FILE_NAME: u8 = 0x06;
+    pub const FILE_NOT_FOUND: u8 = 0x07;
+
+    const MAX_FILE_NAME: usize = 255;
+
+    pub fn new(output: impl Write + 'static) -> Self{
+        Self {
+            output: Box::new(output),
+            block_file: None,
+            file_directory: None,
+            pending: None,
+            status: Self::OK,
+            args: [0; 6],
+            result: [0; 8],
+            exit_code: None,
+        }
+    }
+    pub fn stdout() -> Self{
+        Self::new(io::stdout())
+    }
+    /// Backs the block commands with a host file, created if it does not exist.
+    pub fn with_block_file(mut self, path: impl AsRef<Path>) -> io::Result<Self>{
+        self.block_file = Some(OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?);
+        Ok(self)
+    }
+    /// Lets the file commands load and save files in a host directory, which must exist.
+    pub fn with_file_directory(mut self, path: impl AsRef<Path>) -> io::Result<Self>{
+        let directory = path.as_ref().canonicalize()?;
+        if !directory.is_dir(){
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, directory.display().to_string()));
+        }
+        self.file_directory = Some(directory);
+        Ok(self)
+    }
+
+    /// The code the guest asked to exit with, once it has.
+    pub fn exit_code(&self) -> Option<u8>{
+        self.exit_code
+    }
+
+    fn arg0(&self) -> u16{
+        u16::from_le_bytes([self.args[0], self.args[1]])
+    }
+    fn arg1(&self) -> u16{
+        u16::from_le_bytes([self.args[2], self.args[3]])
+    }
+    fn arg2(&self) -> u16{
+        u16::from_le_bytes([self.args[4], self.args[5]])
+    }
+
+    /// The host path for the file named by the string at `address`, or the status to fail with.
+    fn file_path(&self, address: u16, memory: &mut GuestMemory) -> Result<PathBuf, u8>{
+        let Some(directory) = self.file_directory.as_ref() else { return Err(Self::NO_FILE_DIRECTORY) };
+
+        let mut name = Vec::new();
+        loop{
+            match memory.read(address.wrapping_add(name.len() as u16)){
+                Ok(0) => break,
+                Ok(byte) if name.len() < Self::MAX_FILE_NAME => name.push(byte),
+                Ok(_) => return Err(Self::INVALID_FILE_NAME),
+                Err(_) => return Err(Self::MEMORY_ERROR),
+            }
+        }
+        let name = String::from_utf8(name).map_err(|_| Self::INVALID_FILE_NAME)?;
+        let name = Path::new(&name);
+
+        // only plain names, so the guest cannot reach outside the directory
+        if name.as_os_str().is_empty() || !name.components().all(|c| matches!(c, Component::Normal(_))){
+            return Err(Self::INVALID_FILE_NAME);
+        }
+        Ok(directory.join(name))
+    }
+
+    fn execute(&mut self, command: Command, memory: &mut GuestMemory) -> u8{
+        match command{
+            Command::Print => {
+                let mut text = Vec::new();
+                let mut address = self.arg0();
+                loop{
+                    match memory.read(address){
+                        Ok(0) => break,
+                        Ok(byte) => text.push(byte),
+                        Err(_) => return Self::MEMORY_ERROR,
+                    }
+                    address = address.wrapping_add(1);
+                    if address == self.arg0(){
+                        break;
+                    }
+                }
+
+                match self.output.write_all(&text).and_then(|_| self.output.flush()){
+                    Ok(_) => Self::OK,
+                    Err(_) => Self::IO_ERROR,
+                }
+            },
+            Command::Time => {
+                let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+                self.result = millis.to_le_bytes();
+                Self::OK
+            },
+            Command::ReadBlock => {
+                let offset = self.arg1() as u64 * Self::BLOCK_SIZE as u64;
+                let Some(file) = self.block_file.as_mut() else { return Self::NO_BLOCK_FILE };
+
+                let mut block = [0u8; Self::BLOCK_SIZE];
+                let read = file.seek(SeekFrom::Start(offset)).and_then(|_| {
+                    // a short read at the end of the file leaves the rest of the block zeroed
+                    let mut filled = 0;
+                    loop{
+                        match file.read(&mut block[filled..])?{
+                            0 => return Ok(()),
+                            n => filled += n,
+                        }
+                        if filled == block.len(){
+                            return Ok(());
+                        }
+                    }
+                });
+                if read.is_err(){
+                    return Self::IO_ERROR;
+                }
+
+                let start = self.arg0();
+                for (i, byte) in block.into_iter().enumerate(){
+                    if memory.write(start.wrapping_add(i as u16), byte).is_err(){
+                        return Self::MEMORY_ERROR;
+                    }
+                }
+                Self::OK
+            },
+            Command::WriteBlock => {
+                let offset = self.arg1() as u64 * Self::BLOCK_SIZE as u64;
+                let start = self.arg0();
+                let block = (0..Self::BLOCK_SIZE as u16)
+                    .map(|i| memory.read(start.wrapping_add(i)))
+                    .collect::<Result<Vec<u8>, _>>();
+                let Ok(block) = block else { return Self::MEMORY_ERROR };
+                let Some(file) = self.block_file.as_mut() else { return Self::NO_BLOCK_FILE };
+
+                match file.seek(SeekFrom::Start(offset)).and_then(|_| file.write_all(&block)).and_then(|_| file.flush()){
+                    Ok(_) => Self::OK,
+                    Err(_) => Self::IO_ERROR,
+                }
+            },
+            Command::LoadFile => {
+                let path = match self.file_path(self.arg0(), memory){
+                    Ok(path) => path,
+                    Err(status) => return status,
+                };
+                let contents = match fs::read(&path){
+                    Ok(contents) => contents,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::FILE_NOT_FOUND,
+                    Err(_) => return Self::IO_ERROR,
+                };
+
+                let start = self.arg1();
+                let room = 0x10000 - start as usize;
+                let limit = match self.arg2(){
+                    0 => room,
+                    max => (max as usize).min(room),
+                };
+                let loaded = contents.len().min(limit);
+                for (i, byte) in contents[..loaded].iter().enumerate(){
+                    if memory.write(start + i as u16, *byte).is_err(){
+                        return Self::MEMORY_ERROR;
+                    }
+                }
+                self.result = (loaded as u64).to_le_bytes();
+                Self::OK
+            },
+            Command::SaveFile => {
+                let path = match self.file_path(self.arg0(), memory){
+                    Ok(path) => path,
+                    Err(status) => return status,
+                };
+                let start = self.arg1();
+                let contents = (0..self.arg2())
+                    .map(|i| memory.read(start.wrapping_add(i)))
+                    .collect::<Result<Vec<u8>, _>>();
+                let Ok(contents) = contents else { return Self::MEMORY_ERROR };
+
+                match fs::write(&path, contents){
+                    Ok(_) => Self::OK,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Self::FILE_NOT_FOUND,
+                    Err(_) => Self::IO_ERROR,
+                }
+            },
+        }
+    }
+}
+impl Device for HostServices{
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0xf{
+            0 => self.status,
+            2..=7 => self.args[(offset & 0xf) as usize - 2],
+            8..=15 => self.result[(offset & 0xf) as usize - 8],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match offset & 0xf{
+            0 => self.pending = Some(val),
+            1 => self.exit_code = Some(val),
+            2..=7 => self.args[(offset & 0xf) as usize - 2] = val,
+            _ => {},
+        }
+    }
+
+    fn dma(&mut self, memory: &mut GuestMemory<'_>) {
+        if let Some(command) = self.pending.take(){
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("host service", command).entered();
+            self.status = match Command::decode(command){
+                Some(command) => self.execute(command, memory),
+                None => Self::UNKNOWN_COMMAND,
+            };
+            log::debug!("command ${:02X}, status ${:02X}", command, self.status);
+        }
+    }
+}
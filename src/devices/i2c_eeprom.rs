@@ -0,0 +1,256 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::devices::device::{Port, PortPeripheral};
+use crate::devices::sd_card::BlockImage;
+
+/// Which port pins a bit-banged I2C bus uses. Each mask should have a single bit set.
+///
+/// Both lines are open drain: the CPU pulls a line low by making its pin an output with a 0 in the output
+/// register, and releases it by making the pin an input again, where the pull-up holds it high.
+#[derive(Copy, Clone, Debug)]
+pub struct I2cWiring{
+    pub port: Port,
+    pub scl: u8,    // clock, driven by the CPU
+    pub sda: u8,    // data, driven by whichever side is sending
+}
+impl I2cWiring{
+    /// PB0 = SCL, PB1 = SDA.
+    pub const DEFAULT: Self = Self { port: Port::B, scl: 0b0000_0001, sda: 0b0000_0010 };
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State{
+    Idle,           // waiting for a start condition addressed to the chip
+    Control,        // receiving the control byte
+    AddressHigh,
+    AddressLow,
+    Writing,        // receiving data bytes into the page buffer
+    Reading,        // sending data bytes
+}
+
+/**
+   24LC256-style serial EEPROM: 32KiB in 64 byte pages, bit-banged through two port pins of a VIA or PIA
+   and backed by an image file.
+
+   Data is sampled on the rising edge of SCL and changed while SCL is low. SDA falling while SCL is high
+   is a start condition, SDA rising while SCL is high a stop condition. A transfer starts with the control
+   byte, the 7 bit chip address and the read/write bit, and the chip acknowledges every byte it receives by
+   pulling SDA low on the ninth clock.
+
+   A write sends the two address bytes, most significant first, then up to a page of data. The address wraps
+   within its page and the bytes are programmed at the stop condition, after which the chip ignores its
+   address for the write cycle time; poll by sending the control byte until it is acknowledged. A read sends
+   data from the current address, which a write of just the address bytes followed by a repeated start sets,
+   and carries on through the array for as long as the CPU acknowledges each byte.
+
+    Datasheet: https://ww1.microchip.com/downloads/en/DeviceDoc/24AA256-24LC256-24FC256-Data-Sheet-20001203W.pdf
+ */
+pub struct I2cEeprom{
+    wiring: I2cWiring,
+    chip_address: u8,
+    image: Box<dyn BlockImage>,
+    memory: Vec<u8>,
+
+    state: State,
+    scl: bool,          // levels last driven by the CPU
+    sda: bool,
+    clocks: u8,         // rising edges of SCL in the current byte, 9 with the acknowledge
+    shift: u8,
+    pull_sda_low: bool, // the chip's own output on SDA
+    sent: bool,         // the byte just clocked was sent by the chip rather than received
+    acknowledged: bool, // the CPU acknowledged the byte the chip sent
+
+    address: u16,       // current address
+    page: Vec<(u16, u8)>,   // bytes received for the write in progress
+    busy_cycles: u32,   // left of the write cycle
+}
+impl I2cEeprom{
+    pub const DEFAULT_VIA_ADDRESS: u16 = 0xf0e0;
+    pub const DEFAULT_CHIP_ADDRESS: u8 = 0x50;
+    pub const SIZE: usize = 0x8000;
+    pub const PAGE_SIZE: u16 = 64;
+    pub const WRITE_CYCLE_CYCLES: u32 = 5000;   // 5ms at 1MHz
+
+    /// Reads the contents from the image. An image shorter than the chip is extended with erased bytes, 0xFF.
+    pub fn new(wiring: I2cWiring, mut image: impl BlockImage + 'static) -> io::Result<Self>{
+        let mut memory = Vec::with_capacity(Self::SIZE);
+        image.seek(SeekFrom::Start(0))?;
+        Read::by_ref(&mut image).take(Self::SIZE as u64).read_to_end(&mut memory)?;
+        if memory.len() < Self::SIZE{
+            let stored = memory.len();
+            memory.resize(Self::SIZE, 0xff);
+            image.write_all(&memory[stored..])?;
+            image.flush()?;
+        }
+
+        Ok(Self {
+            wiring,
+            chip_address: Self::DEFAULT_CHIP_ADDRESS,
+            image: Box::new(image),
+            memory,
+            state: State::Idle,
+            scl: true,
+            sda: true,
+            clocks: 0,
+            shift: 0,
+            pull_sda_low: false,
+            sent: false,
+            acknowledged: false,
+            address: 0,
+            page: Vec::new(),
+            busy_cycles: 0,
+        })
+    }
+    /// Opens an image file for reading and writing, creating it if it does not exist.
+    pub fn open(wiring: I2cWiring, path: impl AsRef<Path>) -> io::Result<Self>{
+        Self::new(wiring, OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?)
+    }
+    /// Answers to another 7 bit chip address, as set by the A2-A0 pins.
+    pub fn with_chip_address(mut self, chip_address: u8) -> Self{
+        self.chip_address = chip_address & 0x7f;
+        self
+    }
+
+    pub fn contents(&self) -> &[u8]{
+        &self.memory
+    }
+
+    fn sending(&self) -> bool{
+        self.state == State::Reading
+    }
+
+    fn start(&mut self){
+        // a write not ended with a stop condition is never programmed
+        self.page.clear();
+        self.state = State::Control;
+        self.clocks = 0;
+        self.shift = 0;
+        self.pull_sda_low = false;
+    }
+    fn stop(&mut self){
+        if self.state == State::Writing && !self.page.is_empty(){
+            self.program();
+        }
+        self.state = State::Idle;
+        self.pull_sda_low = false;
+    }
+
+    /// Programs the received page and writes it through to the image.
+    fn program(&mut self){
+        for (address, val) in self.page.drain(..){
+            self.memory[address as usize] = val;
+        }
+        let start = (self.address & !(Self::PAGE_SIZE - 1)) as usize;
+        let page = &self.memory[start..start + Self::PAGE_SIZE as usize];
+        // the chip keeps the data even if the image cannot be updated
+        let _ = self.image.seek(SeekFrom::Start(start as u64))
+            .and_then(|_| self.image.write_all(page))
+            .and_then(|_| self.image.flush());
+        self.busy_cycles = Self::WRITE_CYCLE_CYCLES;
+    }
+
+    /// Takes a complete byte from the CPU and returns whether to acknowledge it.
+    fn receive(&mut self, byte: u8) -> bool{
+        match self.state{
+            State::Control => {
+                if (byte >> 1) != self.chip_address || self.busy_cycles > 0{
+                    self.state = State::Idle;
+                    return false;
+                }
+                self.state = if (byte & 0x01) != 0 { State::Reading } else { State::AddressHigh };
+            },
+            State::AddressHigh => {
+                self.address = ((byte as u16) << 8) & (Self::SIZE as u16 - 1);
+                self.state = State::AddressLow;
+            },
+            State::AddressLow => {
+                self.address |= byte as u16;
+                self.state = State::Writing;
+            },
+            State::Writing => {
+                self.page.push((self.address, byte));
+                let page = self.address & !(Self::PAGE_SIZE - 1);
+                self.address = page | (self.address.wrapping_add(1) & (Self::PAGE_SIZE - 1));
+            },
+            State::Idle | State::Reading => return false,
+        }
+        true
+    }
+    /// Loads the byte at the current address to be sent, and moves on to the next.
+    fn load(&mut self){
+        self.shift = self.memory[self.address as usize];
+        self.address = (self.address + 1) & (Self::SIZE as u16 - 1);
+    }
+
+    fn clock_rising(&mut self){
+        if self.clocks < 8{
+            if !self.sending(){
+                self.shift = (self.shift << 1) | self.sda as u8;
+            }
+        }
+        else{
+            self.acknowledged = !self.sda;
+        }
+        self.clocks += 1;
+    }
+    fn clock_falling(&mut self){
+        match self.clocks{
+            8 => {
+                // the ninth clock is the acknowledge, from whichever side did not send the byte
+                self.sent = self.sending();
+                self.pull_sda_low = !self.sent && self.receive(self.shift);
+            },
+            9 => {
+                self.clocks = 0;
+                self.pull_sda_low = false;
+                if self.sent && !self.acknowledged{
+                    // the CPU ends a read by not acknowledging, then sends a stop condition
+                    self.state = State::Idle;
+                }
+                else if self.sending(){
+                    self.load();
+                    self.pull_sda_low = (self.shift & 0x80) == 0;
+                }
+            },
+            clocks => {
+                if self.sending(){
+                    self.pull_sda_low = ((self.shift << clocks) & 0x80) == 0;
+                }
+            },
+        }
+    }
+}
+impl PortPeripheral for I2cEeprom{
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8) {
+        let levels = match self.wiring.port{
+            Port::A => port_a,
+            Port::B => port_b,
+        };
+        let scl = (levels & self.wiring.scl) != 0;
+        let sda = (levels & self.wiring.sda) != 0;
+
+        if scl && self.scl && sda != self.sda{
+            if sda { self.stop() } else { self.start() }
+        }
+        else if self.state != State::Idle && scl != self.scl{
+            self.sda = sda;
+            if scl { self.clock_rising() } else { self.clock_falling() }
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn inputs(&self) -> (u8, u8) {
+        let levels = if self.pull_sda_low { !self.wiring.sda } else { 0xff };
+        match self.wiring.port{
+            Port::A => (levels, 0xff),
+            Port::B => (0xff, levels),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.busy_cycles = self.busy_cycles.saturating_sub(cycles);
+    }
+}
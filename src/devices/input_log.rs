@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::devices::serial::SerialLink;
+
+/// Stands between the host and the devices that take input from it. Recording, it passes on every byte the host
+/// sends and writes it to a file; replaying, it ignores the host and feeds the devices the bytes of a recording
+/// instead, so a run that depended on when keys were pressed can be repeated exactly.
+///
+/// Bytes are handed to the devices only between instructions, and stamped with the number of instructions run
+/// before them. A replay hands each one over at the same point in the program as the recording did, and any
+/// interrupt a device raises in response follows at the same cycle. Each line of the file is the instruction,
+/// the cycle count then, the input and the byte, such as `1042 3310 keyboard 41`.
+pub struct InputLog{
+    mode: Mode,
+    taps: Vec<Tap>,
+    instructions: u64,      // times `deliver` has been called
+    diverged: bool,         // a replayed byte arrived at a cycle count other than the recorded one
+}
+
+enum Mode{
+    Record(File),
+    Replay(VecDeque<InputEvent>),
+}
+
+struct Tap{
+    name: String,
+    source: Box<dyn FnMut() -> Option<u8>>,     // the host's side, not read while replaying
+    sink: Sender<u8>,                           // the device's side
+}
+
+struct InputEvent{
+    instruction: u64,
+    cycles: u64,
+    input: String,
+    byte: u8,
+}
+
+impl InputLog{
+    pub fn record(path: impl AsRef<Path>) -> io::Result<Self>{
+        Ok(Self::new(Mode::Record(File::create(path)?)))
+    }
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Self>{
+        let events = fs::read_to_string(path)?.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_event)
+            .collect::<Option<VecDeque<InputEvent>>>()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidData, "not an input recording"))?;
+        Ok(Self::new(Mode::Replay(events)))
+    }
+    fn new(mode: Mode) -> Self{
+        Self { mode, taps: Vec::new(), instructions: 0, diverged: false }
+    }
+
+    /// Puts the log between `input`, where the host sends bytes, and the device, which is to read the returned
+    /// receiver instead. `name` ties the input to its bytes in the file.
+    pub fn tap(&mut self, name: &str, input: Receiver<u8>) -> Receiver<u8>{
+        self.tap_source(name, move || input.try_recv().ok())
+    }
+    /// Same as `tap`, for the receiving side of a serial line. What the machine sends still goes out on `link`.
+    pub fn tap_link<L: SerialLink + 'static>(&mut self, name: &str, link: L) -> TappedLink<L>{
+        let link = Rc::new(RefCell::new(link));
+        let host = Rc::clone(&link);
+        let received = self.tap_source(name, move || host.borrow_mut().receive());
+        TappedLink { link, received }
+    }
+    fn tap_source(&mut self, name: &str, source: impl FnMut() -> Option<u8> + 'static) -> Receiver<u8>{
+        let (sink, received) = mpsc::channel();
+        self.taps.push(Tap { name: name.to_string(), source: Box::new(source), sink });
+        received
+    }
+
+    /// Hands the devices what has arrived, or was recorded, since the last instruction. Call before every
+    /// instruction, with the cycles run so far.
+    pub fn deliver(&mut self, cycles: u64){
+        let instruction = self.instructions;
+        self.instructions += 1;
+
+        match &mut self.mode{
+            Mode::Record(file) => for tap in &mut self.taps{
+                while let Some(byte) = (tap.source)(){
+                    // a device dropped with the machine no longer reads, which is not worth stopping for
+                    let _ = tap.sink.send(byte);
+                    // nor is a recording that could not be written
+                    let _ = writeln!(file, "{} {} {} {:02X}", instruction, cycles, tap.name, byte);
+                }
+            },
+            Mode::Replay(events) => while events.front().is_some_and(|event| event.instruction <= instruction){
+                let Some(event) = events.pop_front() else { break };
+                if event.cycles != cycles && !self.diverged{
+                    self.diverged = true;
+                    log::warn!("input replay has diverged from the recording: cycle {} at instruction {}, recorded as {}", cycles, instruction, event.cycles);
+                }
+                if let Some(tap) = self.taps.iter().find(|tap| tap.name == event.input){
+                    let _ = tap.sink.send(event.byte);
+                }
+            },
+        }
+    }
+}
+
+fn parse_event(line: &str) -> Option<InputEvent>{
+    let mut fields = line.split_whitespace();
+    let event = InputEvent {
+        instruction: fields.next()?.parse().ok()?,
+        cycles: fields.next()?.parse().ok()?,
+        input: fields.next()?.to_string(),
+        byte: u8::from_str_radix(fields.next()?, 16).ok()?,
+    };
+    fields.next().is_none().then_some(event)
+}
+
+/// A serial line whose received bytes pass through an `InputLog`.
+pub struct TappedLink<L: SerialLink>{
+    link: Rc<RefCell<L>>,
+    received: Receiver<u8>,
+}
+impl<L: SerialLink> SerialLink for TappedLink<L>{
+    fn send(&mut self, byte: u8) {
+        self.link.borrow_mut().send(byte);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.received.try_recv().ok()
+    }
+}
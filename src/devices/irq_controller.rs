@@ -0,0 +1,73 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::Device;
+
+/**
+   Priority interrupt controller gathering up to 8 device IRQ lines into the CPU's one. Devices are routed to
+   its lines with `Machine::route_irq`; line 0 has the highest priority.
+
+   offset 0: lines that are asserted and enabled
+   offset 1: enable mask, 1 bits let a line through to the CPU
+   offset 2: number of the highest priority asserted and enabled line, 0xff when there is none
+   offset 3: asserted lines, enabled or not
+ */
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IrqController{
+    lines: u8,
+    enabled: u8,
+}
+impl IrqController{
+    pub const DEFAULT_ADDRESS: u16 = 0xf060;
+    pub const NO_LINE: u8 = 0xff;
+
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Called by the machine with the state of every line after devices have been ticked.
+    pub fn set_lines(&mut self, lines: u8){
+        self.lines = lines;
+    }
+    pub fn pending(&self) -> u8{
+        self.lines & self.enabled
+    }
+    /// The line the guest should service first.
+    pub fn highest_priority(&self) -> Option<u8>{
+        match self.pending(){
+            0 => None,
+            pending => Some(pending.trailing_zeros() as u8),
+        }
+    }
+}
+impl Device for IrqController{
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x3{
+            0 => self.pending(),
+            1 => self.enabled,
+            2 => self.highest_priority().unwrap_or(Self::NO_LINE),
+            _ => self.lines,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if (offset & 0x3) == 1{
+            self.enabled = val;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.pending() != 0
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        *self = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use crossterm::{execute, terminal};
+
+use crate::devices::device::Device;
+
+/// Buttons held on a joystick, for host input such as a gamepad to drive. Clones share the same buttons,
+/// so they can be handed to other threads.
+#[derive(Clone, Debug, Default)]
+pub struct JoystickButtons{
+    held: Arc<AtomicU8>,
+    pressed: Arc<AtomicU8>,     // pressed since the CPU last looked
+}
+impl JoystickButtons{
+    pub const UP: u8 = 0b0000_0001;
+    pub const DOWN: u8 = 0b0000_0010;
+    pub const LEFT: u8 = 0b0000_0100;
+    pub const RIGHT: u8 = 0b0000_1000;
+    pub const FIRE: u8 = 0b0001_0000;
+
+    pub fn held(&self) -> u8{
+        self.held.load(Ordering::Relaxed)
+    }
+    /// Holds exactly the given buttons, releasing the others.
+    pub fn set(&self, buttons: u8){
+        self.held.store(buttons, Ordering::Relaxed);
+        self.pressed.fetch_or(buttons, Ordering::Relaxed);
+    }
+    pub fn press(&self, buttons: u8){
+        self.held.fetch_or(buttons, Ordering::Relaxed);
+        self.pressed.fetch_or(buttons, Ordering::Relaxed);
+    }
+    pub fn release(&self, buttons: u8){
+        self.held.fetch_and(!buttons, Ordering::Relaxed);
+    }
+
+    fn take_pressed(&self) -> u8{
+        self.pressed.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Host keys standing in for the joystick's directions and fire button. The arrow keys always move it too.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct JoystickKeys{
+    pub up: char,
+    pub down: char,
+    pub left: char,
+    pub right: char,
+    pub fire: char,
+}
+impl JoystickKeys{
+    /// W, S, A and D, and space to fire.
+    pub const DEFAULT: Self = Self { up: 'w', down: 's', left: 'a', right: 'd', fire: ' ' };
+
+    /// Keys given as a string in the order up, down, left, right, fire.
+    pub fn parse(keys: &str) -> Option<Self>{
+        let mut chars = keys.chars().map(|c| c.to_ascii_lowercase());
+        let keys = Self { up: chars.next()?, down: chars.next()?, left: chars.next()?, right: chars.next()?, fire: chars.next()? };
+        chars.next().is_none().then_some(keys)
+    }
+
+    /// The button a key character stands for.
+    pub fn button(&self, key: char) -> Option<u8>{
+        let key = key.to_ascii_lowercase();
+        [(self.up, JoystickButtons::UP), (self.down, JoystickButtons::DOWN), (self.left, JoystickButtons::LEFT),
+            (self.right, JoystickButtons::RIGHT), (self.fire, JoystickButtons::FIRE)]
+            .into_iter().find(|(c, _)| *c == key).map(|(_, button)| button)
+    }
+}
+
+/// The host terminal in raw mode with key releases reported where it supports that, left again on drop.
+struct TerminalJoystick{
+    enhanced: bool,
+}
+impl Drop for TerminalJoystick{
+    fn drop(&mut self) {
+        if self.enhanced{
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/**
+   Digital joystick with its directions and fire button as bits in a register.
+
+   offset 0: buttons held. Bit 0 up, bit 1 down, bit 2 left, bit 3 right, bit 4 fire.
+   offset 1: buttons pressed since this register was last read, so short presses are not missed. Reading it
+             clears it.
+ */
+pub struct Joystick{
+    buttons: JoystickButtons,
+
+    _terminal: Option<TerminalJoystick>,
+}
+impl Joystick{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0f8;
+
+    /// Terminals that only report key presses hold a button this long after the first press of its key,
+    /// long enough for the key to start repeating, and `REPEAT_HOLD` after every repeat.
+    pub const FIRST_HOLD: Duration = Duration::from_millis(600);
+    pub const REPEAT_HOLD: Duration = Duration::from_millis(120);
+
+    const HELD: u16 = 0;
+
+    /// Buttons are driven through the handle returned by `buttons`.
+    pub fn new() -> Self{
+        Self { buttons: JoystickButtons::default(), _terminal: None }
+    }
+    /// Buttons are driven by keys on the host terminal, which is in raw mode for as long as the joystick
+    /// exists. Ctrl-C ends the process.
+    pub fn terminal(keys: JoystickKeys) -> io::Result<Self>{
+        terminal::enable_raw_mode()?;
+        let enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false)
+            && execute!(io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).is_ok();
+        let guard = TerminalJoystick { enhanced };
+
+        let buttons = JoystickButtons::default();
+        let handle = buttons.clone();
+        thread::spawn(move || Self::read_terminal(handle, keys, enhanced));
+
+        Ok(Self { buttons, _terminal: Some(guard) })
+    }
+
+    /// Handle for driving the buttons from the host.
+    pub fn buttons(&self) -> JoystickButtons{
+        self.buttons.clone()
+    }
+
+    fn read_terminal(buttons: JoystickButtons, keys: JoystickKeys, releases_reported: bool){
+        let mut release_at = HashMap::new();
+        loop{
+            let now = Instant::now();
+            release_at.retain(|button, at| {
+                let held = *at > now;
+                if !held{
+                    buttons.release(*button);
+                }
+                held
+            });
+
+            match event::poll(Duration::from_millis(10)){
+                Ok(true) => {},
+                Ok(false) => continue,
+                Err(_) => break,
+            }
+            let Ok(Event::Key(key)) = event::read() else { continue };
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c'){
+                let _ = terminal::disable_raw_mode();
+                std::process::exit(130);
+            }
+
+            let button = match key.code{
+                KeyCode::Up => JoystickButtons::UP,
+                KeyCode::Down => JoystickButtons::DOWN,
+                KeyCode::Left => JoystickButtons::LEFT,
+                KeyCode::Right => JoystickButtons::RIGHT,
+                KeyCode::Char(c) => match keys.button(c){
+                    Some(button) => button,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            match key.kind{
+                KeyEventKind::Release => buttons.release(button),
+                KeyEventKind::Press | KeyEventKind::Repeat if releases_reported => buttons.press(button),
+                KeyEventKind::Press | KeyEventKind::Repeat => {
+                    let hold = if release_at.contains_key(&button) { Self::REPEAT_HOLD } else { Self::FIRST_HOLD };
+                    release_at.insert(button, Instant::now() + hold);
+                    buttons.press(button);
+                },
+            }
+        }
+    }
+}
+impl Default for Joystick{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Device for Joystick{
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == Self::HELD{
+            self.buttons.held()
+        }
+        else{
+            self.buttons.take_pressed()
+        }
+    }
+
+    fn write(&mut self, _offset: u16, _val: u8) { }
+}
@@ -0,0 +1,143 @@
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+
+use crate::devices::device::Device;
+
+/**
+   Keyboard with a status and a data register, fed with ASCII key codes from the host.
+
+   offset 0, status:  bit 7 set while a key is waiting, bit 6 the IRQ enable. Writes set the IRQ enable.
+   offset 1, data:    the waiting key, 0 if there is none. Reading it takes the key and drops the IRQ.
+
+   Keys pressed while one is waiting are queued rather than lost.
+ */
+pub struct Keyboard{
+    keys: Receiver<u8>,
+    waiting: Option<u8>,
+    irq_enabled: bool,
+
+    _terminal: Option<RawTerminal>,   // keeps the host terminal in raw mode for as long as the keyboard exists
+}
+impl Keyboard{
+    pub const DEFAULT_ADDRESS: u16 = 0xf010;
+
+    pub const STATUS_KEY_WAITING: u8 = 0b1000_0000;
+    pub const STATUS_IRQ_ENABLE: u8 = 0b0100_0000;
+
+    const STATUS: u16 = 0;
+
+    /// Keys come from whatever holds the sending half, such as a window's event loop.
+    pub fn new(keys: Receiver<u8>) -> Self{
+        Self { keys, waiting: None, irq_enabled: false, _terminal: None }
+    }
+    /// A channel pair for feeding keys to a keyboard from the host.
+    pub fn channel() -> (Sender<u8>, Self){
+        let (sender, keys) = mpsc::channel();
+        (sender, Self::new(keys))
+    }
+
+    /// Puts the host terminal in raw mode so that every key press reaches the machine as it happens, without
+    /// echo or line editing. Raw mode is left when the keyboard is dropped. Ctrl-C cannot reach the machine
+    /// and ends the process instead.
+    pub fn terminal() -> std::io::Result<Self>{
+        Self::terminal_via(|keys| keys)
+    }
+    /// Same as `terminal`, with the keys passing through `route` on their way, such as into an `InputLog`.
+    pub fn terminal_via(route: impl FnOnce(Receiver<u8>) -> Receiver<u8>) -> std::io::Result<Self>{
+        let (keys, raw) = terminal_keys()?;
+        Ok(Self { _terminal: Some(raw), ..Self::new(route(keys)) })
+    }
+
+    fn poll(&mut self){
+        if self.waiting.is_none(){
+            self.waiting = self.keys.try_recv().ok();
+        }
+    }
+}
+impl Device for Keyboard{
+    fn read(&mut self, offset: u16) -> u8 {
+        self.poll();
+        if offset == Self::STATUS{
+            let waiting = if self.waiting.is_some() { Self::STATUS_KEY_WAITING } else { 0 };
+            let irq_enable = if self.irq_enabled { Self::STATUS_IRQ_ENABLE } else { 0 };
+            waiting | irq_enable
+        }
+        else{
+            self.waiting.take().unwrap_or(0)
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if offset == Self::STATUS{
+            self.irq_enabled = (val & Self::STATUS_IRQ_ENABLE) > 0;
+        }
+    }
+
+    fn tick(&mut self, _cycles: u32) {
+        if self.irq_enabled{
+            self.poll();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_enabled && self.waiting.is_some()
+    }
+}
+
+/// ASCII codes of the keys pressed on the host terminal, which is put in raw mode until the returned guard
+/// is dropped. Ctrl-C ends the process.
+pub fn terminal_keys() -> std::io::Result<(Receiver<u8>, RawTerminal)>{
+    let raw = RawTerminal::enable()?;
+    let (sender, keys) = mpsc::channel();
+
+    thread::spawn(move || {
+        while let Ok(event) = event::read(){
+            let Event::Key(key) = event else { continue };
+            if key.kind != KeyEventKind::Press{
+                continue;
+            }
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c'){
+                let _ = terminal::disable_raw_mode();
+                process::exit(130);
+            }
+
+            if let Some(code) = ascii(&key) && sender.send(code).is_err(){
+                break;
+            }
+        }
+    });
+
+    Ok((keys, raw))
+}
+
+/// The ASCII code a key press produces, if any.
+fn ascii(key: &KeyEvent) -> Option<u8>{
+    match key.code{
+        KeyCode::Char(c) if c.is_ascii() && key.modifiers.contains(KeyModifiers::CONTROL) => Some((c as u8) & 0x1f),
+        KeyCode::Char(c) if c.is_ascii() => Some(c as u8),
+        KeyCode::Enter => Some(b'\r'),
+        KeyCode::Tab => Some(b'\t'),
+        KeyCode::Backspace => Some(0x08),
+        KeyCode::Esc => Some(0x1b),
+        KeyCode::Delete => Some(0x7f),
+        _ => None,
+    }
+}
+
+/// Raw mode on the host terminal, left again on drop.
+pub struct RawTerminal;
+impl RawTerminal{
+    fn enable() -> std::io::Result<Self>{
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+impl Drop for RawTerminal{
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,130 @@
+use std::any::Any;
+
+use crate::bus::bus::GuestMemory;
+use crate::devices::cycle_counter::CycleCounter;
+use crate::devices::device::Device;
+use crate::devices::irq_controller::IrqController;
+use crate::devices::mc6850::MC6850;
+use crate::devices::random::RandomDevice;
+use crate::devices::w65c21::W65C21;
+use crate::devices::w65c22::W65C22;
+use crate::devices::w65c51::W65C51;
+
+/// A device as a `Machine` holds it: the chips built in are stored inline and reached through a `match` the
+/// compiler can inline, and anything else, such as a user's own peripheral, behind a trait object.
+pub enum KnownDevice{
+    W65C22(W65C22),
+    W65C21(W65C21),
+    W65C51(W65C51),
+    MC6850(MC6850),
+    IrqController(IrqController),
+    CycleCounter(CycleCounter),
+    RandomDevice(RandomDevice),
+    Other(Box<dyn Device>),
+}
+
+/// Takes the device out of `device` if it is a `K`.
+fn take_as<T: Device, K: Device>(device: &mut Option<T>) -> Option<K>{
+    (device as &mut dyn Any).downcast_mut::<Option<K>>().and_then(Option::take)
+}
+
+/// Calls a `Device` method on whichever device the variant holds, with a call of its own for a boxed one when
+/// the box itself would get in the way.
+macro_rules! dispatch{
+    ($self:expr, $device:ident => $call:expr) => {
+        dispatch!($self, $device => $call, $device => $call)
+    };
+    ($self:expr, $device:ident => $call:expr, $other:ident => $other_call:expr) => {
+        match $self{
+            KnownDevice::W65C22($device) => $call,
+            KnownDevice::W65C21($device) => $call,
+            KnownDevice::W65C51($device) => $call,
+            KnownDevice::MC6850($device) => $call,
+            KnownDevice::IrqController($device) => $call,
+            KnownDevice::CycleCounter($device) => $call,
+            KnownDevice::RandomDevice($device) => $call,
+            KnownDevice::Other($other) => $other_call,
+        }
+    };
+}
+
+impl KnownDevice{
+    /// Stores a device inline if it is one of the built-in chips, and boxed otherwise.
+    pub fn new(device: impl Device) -> Self{
+        let mut device = Some(device);
+        if let Some(via) = take_as(&mut device){
+            return KnownDevice::W65C22(via);
+        }
+        if let Some(pia) = take_as(&mut device){
+            return KnownDevice::W65C21(pia);
+        }
+        if let Some(acia) = take_as(&mut device){
+            return KnownDevice::W65C51(acia);
+        }
+        if let Some(acia) = take_as(&mut device){
+            return KnownDevice::MC6850(acia);
+        }
+        if let Some(controller) = take_as(&mut device){
+            return KnownDevice::IrqController(controller);
+        }
+        if let Some(counter) = take_as(&mut device){
+            return KnownDevice::CycleCounter(counter);
+        }
+        if let Some(random) = take_as(&mut device){
+            return KnownDevice::RandomDevice(random);
+        }
+        match device{
+            Some(device) => KnownDevice::Other(Box::new(device)),
+            None => unreachable!("a device is only taken when it is returned"),
+        }
+    }
+
+    /// The device itself, to downcast to its type.
+    pub fn as_any(&self) -> &dyn Any{
+        dispatch!(self, device => device as &dyn Any, device => device.as_ref() as &dyn Any)
+    }
+    pub fn as_any_mut(&mut self) -> &mut dyn Any{
+        dispatch!(self, device => device as &mut dyn Any, device => device.as_mut() as &mut dyn Any)
+    }
+
+    #[inline]
+    pub fn read(&mut self, offset: u16) -> u8{
+        dispatch!(self, device => device.read(offset))
+    }
+    #[inline]
+    pub fn write(&mut self, offset: u16, val: u8){
+        dispatch!(self, device => device.write(offset, val))
+    }
+    #[inline]
+    pub fn tick(&mut self, cycles: u32){
+        dispatch!(self, device => device.tick(cycles))
+    }
+    #[inline]
+    pub fn dma(&mut self, memory: &mut GuestMemory<'_>){
+        dispatch!(self, device => device.dma(memory))
+    }
+    #[inline]
+    pub fn irq(&self) -> bool{
+        dispatch!(self, device => device.irq())
+    }
+    #[inline]
+    pub fn nmi(&self) -> bool{
+        dispatch!(self, device => device.nmi())
+    }
+    #[inline]
+    pub fn reset(&self) -> bool{
+        dispatch!(self, device => device.reset())
+    }
+    pub fn name(&self) -> &'static str{
+        dispatch!(self, device => device.name())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save_state(&self) -> Option<serde_json::Value>{
+        dispatch!(self, device => device.save_state())
+    }
+    #[cfg(feature = "serde")]
+    pub fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        dispatch!(self, device => device.load_state(state))
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::devices::device::{Device, Port, PortPeripheral};
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Digit{
+    pattern: u8,    // segments last lit on the digit
+    lit_at: u64,    // cycle the digit was last lit
+}
+
+/**
+   A bank of 8 LEDs and a row of multiplexed 7-segment digits, as on trainer boards.
+
+   The digits share one set of segment lines and each has a select line; a digit shows the segments while it
+   is selected. Multiplexing code lights the digits one after another, and a digit keeps showing what it was
+   last lit with for `PERSISTENCE_CYCLES`, so a display refreshed fast enough reads as steady.
+
+   Segment bits are a (bit 0, top), b, c, d, e, f, g (bit 6, middle) and the decimal point in bit 7; select
+   bit n selects digit n from the left. All are active high.
+ */
+pub struct LedBank{
+    leds: u8,
+    segments: u8,
+    select: u8,
+    digits: Vec<Digit>,
+    cycles: u64,
+
+    output: Option<Box<dyn Write>>,
+    frame_interval: Duration,
+    last_frame: Instant,
+    unchecked_cycles: u32,  // cycles since the clock was last looked at
+    drawn: Option<(u8, Vec<u8>)>,   // what the terminal shows
+}
+impl LedBank{
+    pub const MAX_DIGITS: usize = 8;
+    pub const PERSISTENCE_CYCLES: u64 = 50_000;     // 50ms at 1MHz
+    pub const DEFAULT_REFRESH_RATE: u32 = 30;
+
+    const CLOCK_CHECK_INTERVAL: u32 = 1024;
+
+    /// Up to `MAX_DIGITS` digits, which may be none for just the LEDs.
+    pub fn new(digits: usize) -> Self{
+        Self {
+            leds: 0,
+            segments: 0,
+            select: 0,
+            digits: vec![Digit::default(); digits.min(Self::MAX_DIGITS)],
+            cycles: 0,
+            output: None,
+            frame_interval: Duration::from_secs(1) / Self::DEFAULT_REFRESH_RATE,
+            last_frame: Instant::now(),
+            unchecked_cycles: 0,
+            drawn: None,
+        }
+    }
+    /// Draws the bank on a terminal whenever it changes, at most `refresh_rate` times a second.
+    pub fn with_output(mut self, output: impl Write + 'static, refresh_rate: u32) -> Self{
+        self.output = Some(Box::new(output));
+        self.frame_interval = Duration::from_secs(1) / refresh_rate.max(1);
+        self
+    }
+    pub fn stderr(digits: usize, refresh_rate: u32) -> Self{
+        Self::new(digits).with_output(io::stderr(), refresh_rate)
+    }
+
+    pub fn leds(&self) -> u8{
+        self.leds
+    }
+    pub fn set_leds(&mut self, leds: u8){
+        self.leds = leds;
+    }
+    pub fn set_segments(&mut self, segments: u8){
+        self.latch();
+        self.segments = segments;
+    }
+    pub fn set_select(&mut self, select: u8){
+        self.latch();
+        self.select = select;
+    }
+    /// Changes the segments and the selection together.
+    pub fn set_digits(&mut self, segments: u8, select: u8){
+        self.latch();
+        self.segments = segments;
+        self.select = select;
+    }
+
+    /// Segments showing on each digit, from the left.
+    pub fn digits(&self) -> Vec<u8>{
+        (0..self.digits.len()).map(|idx| self.digit(idx)).collect()
+    }
+    pub fn digit(&self, idx: usize) -> u8{
+        let Some(digit) = self.digits.get(idx) else { return 0 };
+        if (self.select & (1 << idx)) != 0{
+            self.segments
+        }
+        else if self.cycles - digit.lit_at <= Self::PERSISTENCE_CYCLES{
+            digit.pattern
+        }
+        else{
+            0
+        }
+    }
+    /// The digits read as characters: hex digits, '-' and blanks, with '.' after a digit for its decimal
+    /// point, and '?' for patterns that are none of those.
+    pub fn text(&self) -> String{
+        let mut text = String::new();
+        for pattern in self.digits(){
+            text.push(Self::character(pattern & 0x7f));
+            if (pattern & 0x80) != 0{
+                text.push('.');
+            }
+        }
+        text
+    }
+
+    fn character(pattern: u8) -> char{
+        const HEX: [u8; 16] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71];
+        match pattern{
+            0x00 => ' ',
+            0x40 => '-',
+            0x27 => '7',    // with segment f, as some decoders draw it
+            _ => HEX.iter().position(|p| *p == pattern)
+                .and_then(|d| char::from_digit(d as u32, 16))
+                .map_or('?', |c| c.to_ascii_uppercase()),
+        }
+    }
+
+    /// Records what the selected digits show, before the segments or the selection change.
+    fn latch(&mut self){
+        for (idx, digit) in self.digits.iter_mut().enumerate(){
+            // a blanked digit is the usual pause between digits, and shows nothing new
+            if (self.select & (1 << idx)) != 0 && self.segments != 0{
+                *digit = Digit { pattern: self.segments, lit_at: self.cycles };
+            }
+        }
+    }
+
+    /// Redraws the terminal if what the bank shows has changed since it was last drawn.
+    pub fn present(&mut self){
+        let shown = (self.leds, self.digits());
+        if self.drawn.as_ref() == Some(&shown){
+            return;
+        }
+        let Some(output) = self.output.as_mut() else { return };
+
+        let mut lines = [String::new(), String::new(), String::new(), String::new()];
+        lines[0] = (0..8).rev().map(|bit| if (shown.0 & (1 << bit)) != 0 { '\u{25cf}' } else { '\u{25cb}' }).collect();
+        for pattern in shown.1.iter(){
+            let segment = |bit: u8, c: char| if (pattern & (1 << bit)) != 0 { c } else { ' ' };
+            lines[1].extend([' ', segment(0, '_'), ' ', ' ']);
+            lines[2].extend([segment(5, '|'), segment(6, '_'), segment(1, '|'), ' ']);
+            lines[3].extend([segment(4, '|'), segment(3, '_'), segment(2, '|'), segment(7, '.')]);
+        }
+
+        let lines = if shown.1.is_empty() { &lines[..1] } else { &lines[..] };
+        let mut frame = String::new();
+        if self.drawn.is_some(){
+            frame.push_str(&format!("\x1b[{}A", lines.len()));  // back to the top of the previous drawing
+        }
+        for line in lines.iter(){
+            frame.push_str("\r\x1b[2K");
+            frame.push_str(line);
+            frame.push_str("\r\n");
+        }
+        // a terminal that cannot be written to only loses the picture
+        let _ = output.write_all(frame.as_bytes()).and_then(|_| output.flush());
+        self.drawn = Some(shown);
+        self.last_frame = Instant::now();
+    }
+
+    pub fn tick(&mut self, cycles: u32){
+        self.cycles += cycles as u64;
+        if self.output.is_none(){
+            return;
+        }
+
+        self.unchecked_cycles += cycles;
+        if self.unchecked_cycles < Self::CLOCK_CHECK_INTERVAL{
+            return;
+        }
+        self.unchecked_cycles = 0;
+        if self.last_frame.elapsed() >= self.frame_interval{
+            self.present();
+        }
+    }
+}
+impl Drop for LedBank{
+    /// Leaves the final state on the terminal.
+    fn drop(&mut self) {
+        self.present();
+    }
+}
+
+enum Register{
+    Leds,
+    Segments,
+    Select,
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x3{
+            0 => Register::Leds,
+            1 => Register::Segments,
+            _ => Register::Select,
+        }
+    }
+}
+
+/**
+   LED bank and 7-segment digits driven by output registers.
+
+   offset 0: the 8 LEDs, bit 7 leftmost
+   offset 1: the segments of the selected digits
+   offset 2: digit select, bit 0 the leftmost digit
+
+   Every register reads back what was last written to it.
+ */
+pub struct LedDisplay{
+    bank: LedBank,
+}
+impl LedDisplay{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0f0;
+    pub const DEFAULT_DIGITS: usize = 6;
+
+    pub fn new(bank: LedBank) -> Self{
+        Self { bank }
+    }
+
+    pub fn bank(&self) -> &LedBank{
+        &self.bank
+    }
+    pub fn bank_mut(&mut self) -> &mut LedBank{
+        &mut self.bank
+    }
+}
+impl Device for LedDisplay{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Leds => self.bank.leds,
+            Register::Segments => self.bank.segments,
+            Register::Select => self.bank.select,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Leds => self.bank.set_leds(val),
+            Register::Segments => self.bank.set_segments(val),
+            Register::Select => self.bank.set_select(val),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.bank.tick(cycles);
+    }
+}
+
+/// Which port of a VIA or PIA drives which part of the bank.
+#[derive(Copy, Clone, Debug)]
+pub enum LedWiring{
+    /// One LED on each pin of the port.
+    Leds(Port),
+    /// Segment lines on one port and digit select lines on the other.
+    Digits{ segments: Port },
+}
+
+/// An LED bank or 7-segment digits wired to the port pins of a VIA or PIA instead.
+pub struct LedPins{
+    bank: LedBank,
+    wiring: LedWiring,
+}
+impl LedPins{
+    pub fn new(bank: LedBank, wiring: LedWiring) -> Self{
+        Self { bank, wiring }
+    }
+
+    pub fn bank(&self) -> &LedBank{
+        &self.bank
+    }
+    pub fn bank_mut(&mut self) -> &mut LedBank{
+        &mut self.bank
+    }
+}
+impl PortPeripheral for LedPins{
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8) {
+        match self.wiring{
+            LedWiring::Leds(Port::A) => self.bank.set_leds(port_a),
+            LedWiring::Leds(Port::B) => self.bank.set_leds(port_b),
+            LedWiring::Digits { segments: Port::A } => self.bank.set_digits(port_a, port_b),
+            LedWiring::Digits { segments: Port::B } => self.bank.set_digits(port_b, port_a),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.bank.tick(cycles);
+    }
+}
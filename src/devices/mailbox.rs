@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::Device;
+
+enum Register{
+    Status,         // reads the status, writes the control
+    Data,
+    LengthLow,      // bytes left in the incoming message
+    LengthHigh,
+    Command,
+}
+impl Register{
+    fn decode(offset: u16) -> Option<Self>{
+        match offset & 0x7{
+            0 => Some(Register::Status),
+            1 => Some(Register::Data),
+            2 => Some(Register::LengthLow),
+            3 => Some(Register::LengthHigh),
+            4 => Some(Register::Command),
+            _ => None,
+        }
+    }
+}
+
+/**
+   Mailbox for exchanging whole messages between guest firmware and the application embedding the machine.
+   The host side goes through `send` and `recv`, on the device found with `Machine::device_mut`.
+
+   offset 0:   reads the status. Bit 0 is set while a message is waiting for the guest, bit 1 while the guest
+               can write another byte, bit 7 while the interrupt is asserted. Writes set the control; bit 0
+               asserts the interrupt while a message is waiting.
+   offset 1:   reads the next byte of the waiting message, which is taken once its last byte has been read.
+               Writes add a byte to the message being sent to the host.
+   offset 2-3: bytes left in the waiting message, little-endian
+   offset 4:   write 0x01 to send the message written so far, 0x02 to drop the rest of the waiting message
+
+   Messages queue in both directions. The guest can have up to `CAPACITY` bytes on their way to the host,
+   counting the message it is writing; bytes written while there is no room are lost.
+ */
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mailbox{
+    to_guest: VecDeque<VecDeque<u8>>,
+    to_host: VecDeque<Vec<u8>>,
+    writing: Vec<u8>,       // the message the guest is writing
+    control: u8,
+}
+impl Mailbox{
+    pub const DEFAULT_ADDRESS: u16 = 0xf048;
+    pub const CAPACITY: usize = 4096;
+
+    pub const STATUS_MESSAGE_WAITING: u8 = 0b0000_0001;
+    pub const STATUS_ROOM: u8 = 0b0000_0010;
+    pub const STATUS_IRQ: u8 = 0b1000_0000;
+    pub const CONTROL_IRQ_ENABLE: u8 = 0b0000_0001;
+
+    pub const COMMAND_SEND: u8 = 0x01;
+    pub const COMMAND_SKIP: u8 = 0x02;
+
+    pub fn new() -> Self{
+        Self { to_guest: VecDeque::new(), to_host: VecDeque::new(), writing: Vec::new(), control: 0 }
+    }
+
+    /// Queues a message for the guest.
+    pub fn send(&mut self, message: &[u8]){
+        self.to_guest.push_back(message.iter().copied().collect());
+    }
+    /// The next message the guest has sent, if any.
+    pub fn recv(&mut self) -> Option<Vec<u8>>{
+        self.to_host.pop_front()
+    }
+    /// Messages sent to the guest that it has not finished reading.
+    pub fn pending(&self) -> usize{
+        self.to_guest.len()
+    }
+
+    fn queued_bytes(&self) -> usize{
+        self.to_host.iter().map(|message| message.len()).sum::<usize>() + self.writing.len()
+    }
+    fn remaining(&self) -> u16{
+        self.to_guest.front().map_or(0, |message| message.len().min(u16::MAX as usize) as u16)
+    }
+
+    fn read_byte(&mut self) -> u8{
+        let Some(message) = self.to_guest.front_mut() else { return 0 };
+        let byte = message.pop_front().unwrap_or(0);
+        if message.is_empty(){
+            self.to_guest.pop_front();
+        }
+        byte
+    }
+    fn status(&self) -> u8{
+        let waiting = if self.to_guest.is_empty() { 0 } else { Self::STATUS_MESSAGE_WAITING };
+        let room = if self.queued_bytes() < Self::CAPACITY { Self::STATUS_ROOM } else { 0 };
+        let irq = if self.irq() { Self::STATUS_IRQ } else { 0 };
+        waiting | room | irq
+    }
+}
+impl Default for Mailbox{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Device for Mailbox{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Some(Register::Status) => self.status(),
+            Some(Register::Data) => self.read_byte(),
+            Some(Register::LengthLow) => self.remaining() as u8,
+            Some(Register::LengthHigh) => (self.remaining() >> 8) as u8,
+            Some(Register::Command) | None => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Some(Register::Status) => self.control = val,
+            Some(Register::Data) => {
+                if self.queued_bytes() < Self::CAPACITY{
+                    self.writing.push(val);
+                }
+            },
+            Some(Register::Command) => match val{
+                Self::COMMAND_SEND => self.to_host.push_back(std::mem::take(&mut self.writing)),
+                Self::COMMAND_SKIP => { self.to_guest.pop_front(); },
+                _ => {},
+            },
+            Some(Register::LengthLow) | Some(Register::LengthHigh) | None => {},
+        }
+    }
+
+    fn irq(&self) -> bool {
+        (self.control & Self::CONTROL_IRQ_ENABLE) != 0 && !self.to_guest.is_empty()
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        *self = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
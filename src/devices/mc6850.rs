@@ -0,0 +1,129 @@
+use crate::devices::device::Device;
+use crate::devices::serial::SerialLink;
+
+enum Register{
+    StatusControl,  // status on read, control on write
+    Data,           // receive on read, transmit on write
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x1{
+            0 => Register::StatusControl,
+            _ => Register::Data,
+        }
+    }
+}
+
+/**
+   Asynchronous communications interface adapter, connected to a host serial line.
+   Like the W65C51 it transfers bytes as soon as they are written or arrive, so the clock divide and word
+   select bits have no effect and overruns never occur. The modem lines are always active, so DCD and CTS
+   read as low. The chip stays in master reset, ignoring the line, until a control word that releases it is
+   written.
+ */
+pub struct MC6850{
+    link: Box<dyn SerialLink>,
+
+    received: Option<u8>,
+    control: u8,
+    in_reset: bool,
+
+    unpolled_cycles: u32,   // cycles since the line was last checked for input
+}
+impl MC6850{
+    pub const DEFAULT_ADDRESS: u16 = 0xf080;
+
+    // status register bits
+    pub const STATUS_RECEIVER_FULL: u8 = 0b0000_0001;
+    pub const STATUS_TRANSMITTER_EMPTY: u8 = 0b0000_0010;
+    pub const STATUS_IRQ: u8 = 0b1000_0000;
+
+    const CONTROL_DIVIDE_MASK: u8 = 0b0000_0011;
+    const CONTROL_MASTER_RESET: u8 = 0b0000_0011;           // in the divide bits
+    const CONTROL_TRANSMIT_MASK: u8 = 0b0110_0000;
+    const CONTROL_TRANSMIT_IRQ: u8 = 0b0010_0000;           // in the transmit bits
+    const CONTROL_RECEIVE_IRQ: u8 = 0b1000_0000;
+
+    const POLL_INTERVAL: u32 = 1000;
+
+    pub fn new(link: impl SerialLink + 'static) -> Self{
+        Self {
+            link: Box::new(link),
+            received: None,
+            control: 0,
+            in_reset: true,
+            unpolled_cycles: 0,
+        }
+    }
+
+    fn poll(&mut self){
+        self.unpolled_cycles = 0;
+        if self.received.is_none() && !self.in_reset{
+            self.received = self.link.receive();
+        }
+    }
+    fn status(&self) -> u8{
+        if self.in_reset{
+            return 0;
+        }
+
+        let mut status = Self::STATUS_TRANSMITTER_EMPTY;
+        if self.received.is_some(){
+            status |= Self::STATUS_RECEIVER_FULL;
+        }
+        if self.irq(){
+            status |= Self::STATUS_IRQ;
+        }
+        status
+    }
+}
+impl Device for MC6850{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::StatusControl => {
+                self.poll();
+                self.status()
+            },
+            Register::Data => {
+                let val = self.received.take().unwrap_or(0);
+                self.poll();
+                val
+            },
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::StatusControl => {
+                self.in_reset = (val & Self::CONTROL_DIVIDE_MASK) == Self::CONTROL_MASTER_RESET;
+                if self.in_reset{
+                    self.received = None;
+                }
+                self.control = val;
+            },
+            Register::Data => {
+                if !self.in_reset{
+                    self.link.send(val);
+                }
+            },
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.unpolled_cycles += cycles;
+        if self.unpolled_cycles >= Self::POLL_INTERVAL{
+            self.poll();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        if self.in_reset{
+            return false;
+        }
+
+        let receive = self.received.is_some() && (self.control & Self::CONTROL_RECEIVE_IRQ) != 0;
+        // the transmitter is always empty, so an enabled transmit interrupt is always asserted
+        let transmit = (self.control & Self::CONTROL_TRANSMIT_MASK) == Self::CONTROL_TRANSMIT_IRQ;
+        receive || transmit
+    }
+}
@@ -0,0 +1,288 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::devices::device::Device;
+
+const MIDI_FILE_HEADER_SIZE: usize = 22;   // the header chunk and the track chunk's header
+
+/// Somewhere complete MIDI messages go, each with the time it finished arriving.
+pub trait MidiSink{
+    /// `seconds` is the machine's time since it started.
+    fn send(&mut self, seconds: f64, message: &[u8]);
+}
+
+/// Writes messages as raw bytes, as soon as they are sent, for example to a host MIDI port's device file.
+pub struct RawMidi<W: Write>{
+    output: W,
+}
+impl RawMidi<File>{
+    /// Opens a port device, such as `/dev/snd/midiC1D0`, or creates a file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self>{
+        Ok(Self::new(OpenOptions::new().write(true).create(true).truncate(true).open(path)?))
+    }
+}
+impl<W: Write> RawMidi<W>{
+    pub fn new(output: W) -> Self{
+        Self { output }
+    }
+}
+impl<W: Write> MidiSink for RawMidi<W>{
+    fn send(&mut self, _seconds: f64, message: &[u8]) {
+        // a port that goes away is not worth stopping the machine for
+        let _ = self.output.write_all(message).and_then(|_| self.output.flush());
+    }
+}
+
+/// Writes a standard MIDI file with a single track, at the default tempo of 120 beats a minute. The track's
+/// length is filled in when the writer is dropped. System common and real-time messages have no place in a
+/// file and are left out.
+pub struct MidiFileWriter<W: Write + Seek>{
+    output: W,
+    track_size: u32,
+    last_tick: u64,
+}
+impl MidiFileWriter<BufWriter<File>>{
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self>{
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+impl<W: Write + Seek> MidiFileWriter<W>{
+    pub const TICKS_PER_QUARTER_NOTE: u16 = 480;
+    const TICKS_PER_SECOND: f64 = Self::TICKS_PER_QUARTER_NOTE as f64 * 2.0;    // a quarter note lasts half a second
+
+    pub fn new(mut output: W) -> io::Result<Self>{
+        output.write_all(&Self::header(0))?;
+        Ok(Self { output, track_size: 0, last_tick: 0 })
+    }
+
+    fn header(track_size: u32) -> [u8; MIDI_FILE_HEADER_SIZE]{
+        let mut header = [0u8; MIDI_FILE_HEADER_SIZE];
+        header[0..4].copy_from_slice(b"MThd");
+        header[4..8].copy_from_slice(&6u32.to_be_bytes());
+        header[8..10].copy_from_slice(&0u16.to_be_bytes());        // format 0
+        header[10..12].copy_from_slice(&1u16.to_be_bytes());       // one track
+        header[12..14].copy_from_slice(&Self::TICKS_PER_QUARTER_NOTE.to_be_bytes());
+        header[14..18].copy_from_slice(b"MTrk");
+        header[18..22].copy_from_slice(&track_size.to_be_bytes());
+        header
+    }
+    /// A variable-length quantity: 7 bits a byte, most significant first, with bit 7 set on all but the last.
+    fn variable_length(mut value: u32) -> Vec<u8>{
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value > 0{
+            bytes.insert(0, 0x80 | (value & 0x7f) as u8);
+            value >>= 7;
+        }
+        bytes
+    }
+
+    fn write_event(&mut self, tick: u64, event: &[u8]) -> io::Result<()>{
+        let delta = tick.saturating_sub(self.last_tick).min(0x0fff_ffff) as u32;
+        let mut bytes = Self::variable_length(delta);
+        bytes.extend_from_slice(event);
+
+        self.output.write_all(&bytes)?;
+        self.track_size += bytes.len() as u32;
+        self.last_tick = tick.max(self.last_tick);
+        Ok(())
+    }
+    fn finish(&mut self) -> io::Result<()>{
+        self.write_event(self.last_tick, &[0xff, 0x2f, 0x00])?;     // end of track
+        self.output.seek(SeekFrom::Start(0))?;
+        self.output.write_all(&Self::header(self.track_size))?;
+        self.output.flush()
+    }
+}
+impl<W: Write + Seek> MidiSink for MidiFileWriter<W>{
+    fn send(&mut self, seconds: f64, message: &[u8]) {
+        let tick = (seconds * Self::TICKS_PER_SECOND) as u64;
+        let event = match message.first(){
+            Some(0x80..=0xef) => message.to_vec(),
+            Some(0xf0) => {
+                // stored without its F0, after the length of the rest
+                let mut event = vec![0xf0];
+                event.extend(Self::variable_length(message.len() as u32 - 1));
+                event.extend_from_slice(&message[1..]);
+                event
+            },
+            _ => return,
+        };
+        // a capture that fails to write is not worth stopping the machine for
+        let _ = self.write_event(tick, &event);
+    }
+}
+impl<W: Write + Seek> Drop for MidiFileWriter<W>{
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Gathers the bytes sent over the wire into complete messages, expanding running status.
+#[derive(Default)]
+struct MessageParser{
+    running_status: Option<u8>,
+    message: Vec<u8>,
+    sysex: bool,
+}
+impl MessageParser{
+    /// Data bytes following a status byte.
+    fn data_bytes(status: u8) -> usize{
+        match status{
+            0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+            0x80..=0xef | 0xf2 => 2,
+            _ => 0,
+        }
+    }
+
+    /// Takes the next byte, and returns the messages it completes.
+    fn push(&mut self, byte: u8) -> Vec<Vec<u8>>{
+        let mut complete = Vec::new();
+        match byte{
+            // real-time messages may come between the bytes of any other
+            0xf8..=0xff => complete.push(vec![byte]),
+            0x80..=0xf7 => {
+                if self.sysex{
+                    self.sysex = false;
+                    let mut sysex = std::mem::take(&mut self.message);
+                    sysex.push(0xf7);
+                    complete.push(sysex);
+                    if byte == 0xf7{
+                        return complete;
+                    }
+                }
+
+                self.message = vec![byte];
+                self.running_status = (byte < 0xf0).then_some(byte);
+                self.sysex = byte == 0xf0;
+                if !self.sysex && Self::data_bytes(byte) == 0{
+                    complete.push(std::mem::take(&mut self.message));
+                }
+            },
+            _ if self.sysex => self.message.push(byte),
+            _ => {
+                if self.message.is_empty(){
+                    let Some(status) = self.running_status else { return complete };
+                    self.message.push(status);
+                }
+                self.message.push(byte);
+                if self.message.len() == 1 + Self::data_bytes(self.message[0]){
+                    complete.push(std::mem::take(&mut self.message));
+                }
+            },
+        }
+        complete
+    }
+}
+
+/**
+   MIDI output port: a transmit-only UART fixed at MIDI's 31250 baud, whose messages go to host sinks.
+
+   offset 0: reads the status. Bit 1 is set while the data register can take another byte, bit 7 while the
+             interrupt is asserted. Writes set the control; bit 0 enables the interrupt while bit 1 of the
+             status is set.
+   offset 1: writes the next byte to send.
+
+   Each byte takes 10 bit times on the wire, 320 cycles at 1MHz. A byte written while the data register is
+   still full replaces the one waiting there.
+ */
+pub struct MidiOut{
+    sinks: Vec<Box<dyn MidiSink>>,
+    parser: MessageParser,
+    clock_rate: u32,
+    cycles: u64,
+
+    control: u8,
+    holding: Option<u8>,            // written and waiting for the shift register
+    shifting: Option<(u8, f64)>,    // on the wire, and the cycles until it has been sent
+}
+impl MidiOut{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0d8;
+    pub const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+    pub const BAUD_RATE: u32 = 31250;
+
+    pub const STATUS_TDRE: u8 = 0b0000_0010;
+    pub const STATUS_IRQ: u8 = 0b1000_0000;
+    pub const CONTROL_IRQ_ENABLE: u8 = 0b0000_0001;
+
+    const STATUS: u16 = 0;
+
+    /// `clock_rate` is the CPU clock in Hz. Sends to nowhere until given sinks.
+    pub fn new(clock_rate: u32) -> Self{
+        Self {
+            sinks: Vec::new(),
+            parser: MessageParser::default(),
+            clock_rate: clock_rate.max(1),
+            cycles: 0,
+            control: 0,
+            holding: None,
+            shifting: None,
+        }
+    }
+    pub fn with_sink(mut self, sink: impl MidiSink + 'static) -> Self{
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Cycles it takes to send one byte: a start bit, 8 data bits and a stop bit.
+    fn byte_cycles(&self) -> f64{
+        self.clock_rate as f64 * 10.0 / Self::BAUD_RATE as f64
+    }
+
+    fn sent(&mut self, byte: u8){
+        let seconds = self.cycles as f64 / self.clock_rate as f64;
+        for message in self.parser.push(byte){
+            for sink in self.sinks.iter_mut(){
+                sink.send(seconds, &message);
+            }
+        }
+    }
+}
+impl Device for MidiOut{
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == Self::STATUS{
+            let tdre = if self.holding.is_none() { Self::STATUS_TDRE } else { 0 };
+            let irq = if self.irq() { Self::STATUS_IRQ } else { 0 };
+            tdre | irq
+        }
+        else{
+            0
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if offset == Self::STATUS{
+            self.control = val;
+        }
+        else{
+            self.holding = Some(val);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+
+        let mut cycles = cycles as f64;
+        loop{
+            match self.shifting{
+                Some((byte, left)) if left <= cycles => {
+                    cycles -= left;
+                    self.shifting = None;
+                    self.sent(byte);
+                },
+                Some((byte, left)) => {
+                    self.shifting = Some((byte, left - cycles));
+                    return;
+                },
+                None => {},
+            }
+            let Some(byte) = self.holding.take() else { return };
+            self.shifting = Some((byte, self.byte_cycles()));
+        }
+    }
+
+    fn irq(&self) -> bool {
+        (self.control & Self::CONTROL_IRQ_ENABLE) != 0 && self.holding.is_none()
+    }
+}
@@ -0,0 +1,34 @@
+pub mod device;
+pub mod w65c22;
+pub mod hd44780;
+pub mod w65c21;
+pub mod apple1_terminal;
+pub mod char_io;
+pub mod keyboard;
+#[cfg(feature = "video")]
+pub mod framebuffer;
+pub mod audio;
+pub mod beeper;
+pub mod spi;
+pub mod sd_card;
+pub mod serial;
+pub mod w65c51;
+pub mod gpio;
+pub mod irq_controller;
+pub mod host_services;
+pub mod mc6850;
+pub mod ay38910;
+pub mod ps2_keyboard;
+pub mod ansi_screen;
+pub mod raster;
+pub mod cassette;
+pub mod disk_controller;
+pub mod i2c_eeprom;
+pub mod leds;
+pub mod joystick;
+pub mod midi;
+pub mod cycle_counter;
+pub mod mailbox;
+pub mod input_log;
+pub mod random;
+pub mod known;
\ No newline at end of file
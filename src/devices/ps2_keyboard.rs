@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::devices::device::PortPeripheral;
+use crate::devices::keyboard::{self, RawTerminal};
+
+/**
+   PS/2 keyboard with its clock wired to a VIA's CB1 and its data to CB2, to be read through the VIA's shift
+   register shifting in under external control (ACR bits 4-2 = 011). Host keys are typed as set 2 scancodes:
+   the make code, then the break code (0xF0 and the make code), with shift or ctrl around them where the
+   character needs it.
+
+   Each scancode is an 11 bit frame: a 0 start bit, the 8 data bits least significant first, odd parity and a
+   1 stop bit. The VIA shifts towards the most significant bit and flags an interrupt every 8 clocks, so after
+   the first 8 its register holds the start bit and data bits 0-6 in reverse order, and the remaining 3 bits
+   follow. Frames are separated by a pause long enough for the driver to reset the VIA's bit count by reading
+   the shift register.
+ */
+pub struct Ps2Keyboard{
+    keys: Receiver<u8>,
+    scancodes: VecDeque<u8>,
+    frame: VecDeque<bool>,      // bits of the frame being sent
+    wait_cycles: u32,           // until the next bit may be clocked out
+
+    _terminal: Option<RawTerminal>,   // keeps the host terminal in raw mode for as long as the keyboard exists
+}
+impl Ps2Keyboard{
+    pub const DEFAULT_VIA_ADDRESS: u16 = 0xf0a0;
+    pub const BIT_CYCLES: u32 = 80;            // a 12.5kHz clock at 1MHz
+    pub const FRAME_GAP_CYCLES: u32 = 2000;
+
+    const SHIFT: u8 = 0x12;
+    const CTRL: u8 = 0x14;
+    const BREAK: u8 = 0xf0;
+
+    /// Keys are ASCII codes from whatever holds the sending half.
+    pub fn new(keys: Receiver<u8>) -> Self{
+        Self { keys, scancodes: VecDeque::new(), frame: VecDeque::new(), wait_cycles: 0, _terminal: None }
+    }
+    pub fn channel() -> (Sender<u8>, Self){
+        let (sender, keys) = mpsc::channel();
+        (sender, Self::new(keys))
+    }
+    /// Keys come from the host terminal, in raw mode for as long as the keyboard exists.
+    pub fn terminal() -> io::Result<Self>{
+        Self::terminal_via(|keys| keys)
+    }
+    /// Same as `terminal`, with the keys passing through `route` on their way, such as into an `InputLog`.
+    pub fn terminal_via(route: impl FnOnce(Receiver<u8>) -> Receiver<u8>) -> io::Result<Self>{
+        let (keys, raw) = keyboard::terminal_keys()?;
+        Ok(Self { _terminal: Some(raw), ..Self::new(route(keys)) })
+    }
+
+    /// The set 2 make code of the key producing `byte`, and whether shift is needed for it.
+    fn make_code(byte: u8) -> Option<(u8, bool)>{
+        const UNSHIFTED: &[u8; 47] = b"abcdefghijklmnopqrstuvwxyz1234567890`-=[]\\;',./";
+        const SHIFTED: &[u8; 47] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ!@#$%^&*()~_+{}|:\"<>?";
+        const CODES: [u8; 47] = [
+            0x1c, 0x32, 0x21, 0x23, 0x24, 0x2b, 0x34, 0x33, 0x43, 0x3b, 0x42, 0x4b, 0x3a, 0x31, 0x44, 0x4d,
+            0x15, 0x2d, 0x1b, 0x2c, 0x3c, 0x2a, 0x1d, 0x22, 0x35, 0x1a,
+            0x16, 0x1e, 0x26, 0x25, 0x2e, 0x36, 0x3d, 0x3e, 0x46, 0x45,
+            0x0e, 0x4e, 0x55, 0x54, 0x5b, 0x5d, 0x4c, 0x52, 0x41, 0x49, 0x4a,
+        ];
+
+        match byte{
+            b' ' => Some((0x29, false)),
+            b'\r' | b'\n' => Some((0x5a, false)),
+            b'\t' => Some((0x0d, false)),
+            0x08 | 0x7f => Some((0x66, false)),
+            0x1b => Some((0x76, false)),
+            _ => {
+                if let Some(idx) = UNSHIFTED.iter().position(|c| *c == byte){
+                    Some((CODES[idx], false))
+                }
+                else{
+                    SHIFTED.iter().position(|c| *c == byte).map(|idx| (CODES[idx], true))
+                }
+            },
+        }
+    }
+
+    /// The scancodes for pressing and releasing the key or key combination producing `byte`.
+    fn scancodes(byte: u8) -> Vec<u8>{
+        let (code, modifier) = match Self::make_code(byte){
+            Some((code, shifted)) => (code, shifted.then_some(Self::SHIFT)),
+            // control characters are ctrl and a letter
+            None if byte < 0x20 => match Self::make_code(byte | 0x60){
+                Some((code, _)) => (code, Some(Self::CTRL)),
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+
+        match modifier{
+            Some(modifier) => vec![modifier, code, Self::BREAK, code, Self::BREAK, modifier],
+            None => vec![code, Self::BREAK, code],
+        }
+    }
+
+    fn start_frame(&mut self){
+        while self.scancodes.is_empty(){
+            let Ok(byte) = self.keys.try_recv() else { return };
+            self.scancodes.extend(Self::scancodes(byte));
+        }
+
+        let Some(code) = self.scancodes.pop_front() else { return };
+        let parity = code.count_ones() % 2 == 0;
+        self.frame.push_back(false);
+        self.frame.extend((0..8).map(|bit| (code >> bit) & 1 != 0));
+        self.frame.push_back(parity);
+        self.frame.push_back(true);
+    }
+}
+impl PortPeripheral for Ps2Keyboard{
+    fn outputs_changed(&mut self, _port_a: u8, _port_b: u8) { }
+
+    fn shift_in(&mut self, cycles: u32) -> Option<bool> {
+        self.wait_cycles = self.wait_cycles.saturating_sub(cycles);
+        if self.wait_cycles > 0{
+            return None;
+        }
+
+        if self.frame.is_empty(){
+            self.start_frame();
+        }
+        let bit = self.frame.pop_front()?;
+        self.wait_cycles = if self.frame.is_empty() { Self::FRAME_GAP_CYCLES } else { Self::BIT_CYCLES };
+        Some(bit)
+    }
+}
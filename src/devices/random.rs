@@ -0,0 +1,69 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::Device;
+
+/// Pseudo-random numbers from a seed, SplitMix64, so that everything random in a run comes back the same when
+/// the run is repeated with the same `--seed`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Random{
+    state: u64,
+}
+impl Random{
+    pub fn new(seed: u64) -> Self{
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    pub fn fill(&mut self, bytes: &mut [u8]){
+        for chunk in bytes.chunks_mut(8){
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+    }
+    /// A generator of its own for another part of the machine, so that what one part draws does not shift the
+    /// numbers of another.
+    pub fn fork(&mut self) -> Self{
+        Self::new(self.next_u64())
+    }
+}
+
+/**
+   Random numbers for guest code, drawn from the seed of the run so that a run using them can be repeated.
+
+   offset 0: each read returns the next random byte. Writes are ignored.
+ */
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RandomDevice{
+    random: Random,
+}
+impl RandomDevice{
+    pub const DEFAULT_ADDRESS: u16 = 0xf028;
+
+    pub fn new(random: Random) -> Self{
+        Self { random }
+    }
+}
+impl Device for RandomDevice{
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.random.next_u64() as u8
+    }
+
+    fn write(&mut self, _offset: u16, _val: u8) {}
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        *self = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
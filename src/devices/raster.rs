@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use crate::devices::device::Device;
+
+enum Register{
+    LineLow,        // current scanline
+    LineHigh,
+    Status,         // flags; writing 1 bits clears them
+    Control,        // interrupt enables
+    CompareLow,     // scanline that raises the line match flag
+    CompareHigh,
+    FrameLow,       // frames since start, wrapping
+    FrameHigh,
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x7{
+            0 => Register::LineLow,
+            1 => Register::LineHigh,
+            2 => Register::Status,
+            3 => Register::Control,
+            4 => Register::CompareLow,
+            5 => Register::CompareHigh,
+            6 => Register::FrameLow,
+            _ => Register::FrameHigh,
+        }
+    }
+}
+
+/// Where the beam of a `RasterTimer` is, for host-side devices that draw in step with it. Clones share the
+/// same position, so they can be handed to other devices.
+#[derive(Clone, Debug, Default)]
+pub struct RasterPosition{
+    line: Arc<AtomicU16>,
+    frame: Arc<AtomicU32>,
+}
+impl RasterPosition{
+    pub fn scanline(&self) -> u16{
+        self.line.load(Ordering::Relaxed)
+    }
+    /// Frames started since the timer was created. Changes once per frame, at the start of vertical blank.
+    pub fn frame(&self) -> u32{
+        self.frame.load(Ordering::Relaxed)
+    }
+}
+
+/**
+   Raster timing source. Counts scanlines at the pace a display with the given frame rate and line count
+   would scan them at the CPU's clock rate, and signals the start of vertical blank, so guest code can wait
+   for it or be interrupted by it.
+
+   offset 0-1: current scanline, little-endian; lines at and past the visible count are vertical blank
+   offset 2:   status. Bit 7 VSYNC, set when vertical blank starts. Bit 6 set while in vertical blank.
+               Bit 5 line match, set when the beam reaches the compare line. Writing 1 bits clears the flags.
+   offset 3:   control. Bit 0 sends VSYNC to IRQ, bit 1 sends VSYNC to NMI, bit 2 sends line match to IRQ.
+   offset 4-5: compare line, little-endian
+   offset 6-7: frames since start, little-endian and wrapping
+
+   Both interrupts are held until the flag behind them is cleared.
+ */
+pub struct RasterTimer{
+    cycles_per_frame: f64,
+    lines: u16,
+    visible_lines: u16,
+    frame_cycles: f64,      // position within the current frame
+    line: u16,
+
+    status: u8,
+    control: u8,
+    compare: u16,
+    position: RasterPosition,
+}
+impl RasterTimer{
+    pub const DEFAULT_ADDRESS: u16 = 0xf0b0;
+    pub const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+    pub const DEFAULT_FRAME_RATE: u32 = 60;
+    pub const DEFAULT_LINES: u16 = 262;
+    pub const DEFAULT_VISIBLE_LINES: u16 = 240;
+
+    // status register bits
+    pub const STATUS_VSYNC: u8 = 0b1000_0000;
+    pub const STATUS_VBLANK: u8 = 0b0100_0000;
+    pub const STATUS_LINE_MATCH: u8 = 0b0010_0000;
+
+    // control register bits
+    pub const CONTROL_VSYNC_IRQ: u8 = 0b0000_0001;
+    pub const CONTROL_VSYNC_NMI: u8 = 0b0000_0010;
+    pub const CONTROL_LINE_IRQ: u8 = 0b0000_0100;
+
+    /// `clock_rate` is the CPU clock in Hz. `lines` is the scanlines in a frame, the first `visible_lines` of
+    /// them drawn and the rest vertical blank.
+    pub fn new(clock_rate: u32, frame_rate: u32, lines: u16, visible_lines: u16) -> Self{
+        let lines = lines.max(1);
+        Self {
+            cycles_per_frame: clock_rate as f64 / frame_rate.max(1) as f64,
+            lines,
+            visible_lines: visible_lines.min(lines - 1),
+            frame_cycles: 0.0,
+            line: 0,
+            status: 0,
+            control: 0,
+            compare: 0,
+            position: RasterPosition::default(),
+        }
+    }
+
+    /// Handle for following the beam from the host.
+    pub fn position(&self) -> RasterPosition{
+        self.position.clone()
+    }
+    pub fn scanline(&self) -> u16{
+        self.line
+    }
+    pub fn in_vblank(&self) -> bool{
+        self.line >= self.visible_lines
+    }
+
+    fn enter_line(&mut self, line: u16){
+        self.line = line;
+        self.position.line.store(line, Ordering::Relaxed);
+
+        if line == self.visible_lines{
+            self.status |= Self::STATUS_VSYNC;
+            self.position.frame.fetch_add(1, Ordering::Relaxed);
+        }
+        if line == self.compare{
+            self.status |= Self::STATUS_LINE_MATCH;
+        }
+    }
+    fn status(&self) -> u8{
+        let vblank = if self.in_vblank() { Self::STATUS_VBLANK } else { 0 };
+        (self.status & !Self::STATUS_VBLANK) | vblank
+    }
+}
+impl Default for RasterTimer{
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CLOCK_RATE, Self::DEFAULT_FRAME_RATE, Self::DEFAULT_LINES, Self::DEFAULT_VISIBLE_LINES)
+    }
+}
+impl Device for RasterTimer{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::LineLow => self.line.to_le_bytes()[0],
+            Register::LineHigh => self.line.to_le_bytes()[1],
+            Register::Status => self.status(),
+            Register::Control => self.control,
+            Register::CompareLow => self.compare.to_le_bytes()[0],
+            Register::CompareHigh => self.compare.to_le_bytes()[1],
+            Register::FrameLow => self.position.frame().to_le_bytes()[0],
+            Register::FrameHigh => self.position.frame().to_le_bytes()[1],
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Status => self.status &= !val,
+            Register::Control => self.control = val,
+            Register::CompareLow => self.compare = (self.compare & 0xff00) | val as u16,
+            Register::CompareHigh => self.compare = (self.compare & 0x00ff) | ((val as u16) << 8),
+            _ => {},
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.frame_cycles += cycles as f64;
+        if self.frame_cycles >= self.cycles_per_frame{
+            self.frame_cycles -= self.cycles_per_frame;
+        }
+
+        // every line passed is entered, so none of their events are missed
+        let line = (self.frame_cycles / self.cycles_per_frame * self.lines as f64) as u16;
+        while self.line != line{
+            self.enter_line((self.line + 1) % self.lines);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        ((self.control & Self::CONTROL_VSYNC_IRQ) != 0 && (self.status & Self::STATUS_VSYNC) != 0)
+            || ((self.control & Self::CONTROL_LINE_IRQ) != 0 && (self.status & Self::STATUS_LINE_MATCH) != 0)
+    }
+
+    fn nmi(&self) -> bool {
+        (self.control & Self::CONTROL_VSYNC_NMI) != 0 && (self.status & Self::STATUS_VSYNC) != 0
+    }
+}
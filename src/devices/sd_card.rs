@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::devices::spi::SpiDevice;
+
+/// Backing storage for a card's blocks.
+pub trait BlockImage: Read + Write + Seek{ }
+impl<T: Read + Write + Seek> BlockImage for T{ }
+
+enum State{
+    Command{ frame: [u8; 6], len: usize },
+    AwaitingWrite{ block: u32 },                      // waiting for the data token of a single block write
+    ReceivingWrite{ block: u32, data: Vec<u8> },      // 512 bytes followed by 2 CRC bytes
+}
+
+/**
+   SD card (SDHC, block addressed) speaking the SPI-mode protocol, backed by an image of 512 byte blocks.
+
+   Supports the commands needed to initialise a card and move single blocks: CMD0, CMD1, CMD8, CMD9, CMD16,
+   CMD17, CMD24, CMD55, CMD58 and ACMD41. CRCs are neither checked nor generated.
+
+    Spec: https://www.sdcard.org/downloads/pls/
+ */
+pub struct SdCard{
+    image: Box<dyn BlockImage>,
+    blocks: u32,
+
+    selected: bool,
+    idle: bool,             // in the idle state until initialised with ACMD41 or CMD1
+    app_command: bool,      // the previous command was CMD55
+    state: State,
+    out: VecDeque<u8>,
+}
+impl SdCard{
+    pub const BLOCK_SIZE: usize = 512;
+
+    // R1 response bits
+    const R1_IDLE: u8 = 0x01;
+    const R1_ILLEGAL_COMMAND: u8 = 0x04;
+    const R1_ADDRESS_ERROR: u8 = 0x20;
+    const R1_PARAMETER_ERROR: u8 = 0x40;
+
+    const DATA_TOKEN: u8 = 0xfe;
+    const DATA_ACCEPTED: u8 = 0x05;
+    const DATA_WRITE_ERROR: u8 = 0x0d;
+
+    pub fn new(mut image: impl BlockImage + 'static) -> io::Result<Self>{
+        let size = image.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            image: Box::new(image),
+            blocks: (size / Self::BLOCK_SIZE as u64).min(u32::MAX as u64) as u32,
+            selected: false,
+            idle: true,
+            app_command: false,
+            state: State::Command { frame: [0; 6], len: 0 },
+            out: VecDeque::new(),
+        })
+    }
+    /// Opens an image file for reading and writing.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self>{
+        Self::new(OpenOptions::new().read(true).write(true).open(path)?)
+    }
+
+    /// Number of 512 byte blocks on the card.
+    pub fn blocks(&self) -> u32{
+        self.blocks
+    }
+
+    fn r1(&self, errors: u8) -> u8{
+        errors | if self.idle { Self::R1_IDLE } else { 0 }
+    }
+    fn respond(&mut self, bytes: &[u8]){
+        self.out.push_back(0xff);   // one byte of command response time
+        self.out.extend(bytes);
+    }
+    fn respond_with_data(&mut self, r1: u8, data: &[u8]){
+        self.respond(&[r1, 0xff, Self::DATA_TOKEN]);
+        self.out.extend(data);
+        self.out.extend([0xff, 0xff]);  // CRC
+    }
+
+    fn read_block(&mut self, block: u32) -> io::Result<Vec<u8>>{
+        let mut data = vec![0; Self::BLOCK_SIZE];
+        self.image.seek(SeekFrom::Start(block as u64 * Self::BLOCK_SIZE as u64))?;
+        self.image.read_exact(&mut data)?;
+        Ok(data)
+    }
+    fn write_block(&mut self, block: u32, data: &[u8]) -> io::Result<()>{
+        self.image.seek(SeekFrom::Start(block as u64 * Self::BLOCK_SIZE as u64))?;
+        self.image.write_all(data)?;
+        self.image.flush()
+    }
+
+    /// Card-specific data, version 2.0 (SDHC) layout.
+    fn csd(&self) -> [u8; 16]{
+        let c_size = (self.blocks / 1024).saturating_sub(1);    // capacity is (C_SIZE + 1) * 512KB
+        [
+            0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00,
+            ((c_size >> 16) & 0x3f) as u8, (c_size >> 8) as u8, c_size as u8,
+            0x7f, 0x80, 0x0a, 0x40, 0x00, 0x01,
+        ]
+    }
+
+    fn execute(&mut self, frame: [u8; 6]){
+        let command = frame[0] & 0x3f;
+        let argument = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]);
+        let app_command = std::mem::take(&mut self.app_command);
+        log::debug!("{}{} argument ${:08X}", if app_command { "ACMD" } else { "CMD" }, command, argument);
+
+        match (app_command, command){
+            (_, 0) => {
+                self.idle = true;
+                self.respond(&[Self::R1_IDLE]);
+            },
+            (false, 1) | (true, 41) => {
+                self.idle = false;
+                self.respond(&[0x00]);
+            },
+            (_, 8) => {
+                let r1 = self.r1(0);
+                self.respond(&[r1, 0x00, 0x00, ((argument >> 8) & 0x0f) as u8, argument as u8]);
+            },
+            (_, 9) => {
+                let csd = self.csd();
+                self.respond_with_data(self.r1(0), &csd);
+            },
+            (_, 16) => {
+                let r1 = self.r1(if argument as usize == Self::BLOCK_SIZE { 0 } else { Self::R1_PARAMETER_ERROR });
+                self.respond(&[r1]);
+            },
+            (_, 17) if argument >= self.blocks => self.respond(&[self.r1(Self::R1_ADDRESS_ERROR)]),
+            (_, 17) => match self.read_block(argument){
+                Ok(data) => self.respond_with_data(self.r1(0), &data),
+                Err(_) => self.respond(&[self.r1(0), 0xff, 0x01]),  // data error token: error
+            },
+            (_, 24) if argument >= self.blocks => self.respond(&[self.r1(Self::R1_ADDRESS_ERROR)]),
+            (_, 24) => {
+                self.respond(&[self.r1(0)]);
+                self.state = State::AwaitingWrite { block: argument };
+            },
+            (_, 55) => {
+                self.app_command = true;
+                self.respond(&[self.r1(0)]);
+            },
+            (_, 58) => self.respond(&[self.r1(0), 0xc0, 0xff, 0x80, 0x00]),     // powered up, high capacity
+            _ => self.respond(&[self.r1(Self::R1_ILLEGAL_COMMAND)]),
+        }
+    }
+}
+impl SpiDevice for SdCard{
+    fn select(&mut self, selected: bool) {
+        self.selected = selected;
+        if !selected{
+            self.out.clear();
+            self.state = State::Command { frame: [0; 6], len: 0 };
+        }
+    }
+
+    fn next_out(&mut self) -> u8 {
+        if self.selected { self.out.pop_front().unwrap_or(0xff) } else { 0xff }
+    }
+
+    fn receive(&mut self, mosi: u8) {
+        if !self.selected{
+            return;
+        }
+
+        match &mut self.state{
+            State::Command { frame, len } => {
+                // commands start with a 0 then a 1 bit; anything else is the master clocking out a response
+                if *len == 0 && (mosi & 0xc0) != 0x40{
+                    return;
+                }
+                frame[*len] = mosi;
+                *len += 1;
+                if *len == frame.len(){
+                    let frame = *frame;
+                    self.state = State::Command { frame: [0; 6], len: 0 };
+                    self.execute(frame);
+                }
+            },
+            State::AwaitingWrite { block } => {
+                if mosi == Self::DATA_TOKEN{
+                    self.state = State::ReceivingWrite { block: *block, data: Vec::with_capacity(Self::BLOCK_SIZE + 2) };
+                }
+            },
+            State::ReceivingWrite { block, data } => {
+                data.push(mosi);
+                if data.len() == Self::BLOCK_SIZE + 2{
+                    let (block, data) = (*block, std::mem::take(data));
+                    self.state = State::Command { frame: [0; 6], len: 0 };
+
+                    let response = match self.write_block(block, &data[..Self::BLOCK_SIZE]){
+                        Ok(_) => Self::DATA_ACCEPTED,
+                        Err(_) => Self::DATA_WRITE_ERROR,
+                    };
+                    self.out.extend([response, 0x00, 0x00]);    // followed by busy while programming
+                }
+            },
+        }
+    }
+}
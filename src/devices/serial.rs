@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+#[cfg(unix)]
+use std::ffi::CStr;
+#[cfg(unix)]
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// The host end of a UART's serial line.
+pub trait SerialLink{
+    /// Sends a byte down the line. Bytes sent while nothing is listening are lost, as on a real line.
+    fn send(&mut self, byte: u8);
+    /// The next byte that has arrived, if any. Must not block.
+    fn receive(&mut self) -> Option<u8>;
+}
+
+/// A serial line bridged to a TCP port, so a terminal can be attached with `telnet` or `nc`. One client is
+/// served at a time; when it disconnects the next connection takes over.
+pub struct TcpSerial{
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    received: VecDeque<u8>,
+}
+impl TcpSerial{
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self>{
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener, client: None, received: VecDeque::new() })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr>{
+        self.listener.local_addr()
+    }
+    pub fn is_connected(&self) -> bool{
+        self.client.is_some()
+    }
+
+    fn accept(&mut self){
+        if self.client.is_some(){
+            return;
+        }
+
+        if let Ok((stream, peer)) = self.listener.accept() && stream.set_nonblocking(true).is_ok(){
+            log::info!("Serial client connected from {}", peer);
+            let _ = stream.set_nodelay(true);
+            self.client = Some(stream);
+        }
+    }
+    fn disconnect(&mut self){
+        log::info!("Serial client disconnected");
+        self.client = None;
+    }
+}
+impl SerialLink for TcpSerial{
+    fn send(&mut self, byte: u8) {
+        self.accept();
+        if let Some(client) = self.client.as_mut() && client.write_all(&[byte]).is_err(){
+            self.disconnect();
+        }
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        if self.received.is_empty(){
+            self.accept();
+            if let Some(client) = self.client.as_mut(){
+                let mut buffer = [0u8; 256];
+                match client.read(&mut buffer){
+                    Ok(0) => self.disconnect(),     // closed by the client
+                    Ok(len) => self.received.extend(&buffer[..len]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {},
+                    Err(_) => self.disconnect(),
+                }
+            }
+        }
+
+        self.received.pop_front()
+    }
+}
+
+/// A serial line on a newly allocated pseudo-terminal, so terminal programs such as `minicom` or `picocom`
+/// can open its path as if it were a serial port. The line is raw: bytes pass through unchanged, with no
+/// echo or line editing, as on a real port.
+#[cfg(unix)]
+pub struct PtySerial{
+    master: File,
+    path: PathBuf,
+}
+#[cfg(unix)]
+impl PtySerial{
+    pub fn open() -> io::Result<Self>{
+        let check = |result: libc::c_int| if result < 0 { Err(io::Error::last_os_error()) } else { Ok(result) };
+
+        // SAFETY: the descriptor is owned by `master` from here on, and `ptsname` is only called before any
+        // other thread could use it
+        unsafe{
+            let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+            let master = File::from(OwnedFd::from_raw_fd(fd));
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+            let name = libc::ptsname(fd);
+            if name.is_null(){
+                return Err(io::Error::last_os_error());
+            }
+            let path = PathBuf::from(CStr::from_ptr(name).to_string_lossy().into_owned());
+
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            check(libc::tcgetattr(fd, &mut termios))?;
+            libc::cfmakeraw(&mut termios);
+            check(libc::tcsetattr(fd, libc::TCSANOW, &termios))?;
+
+            let flags = check(libc::fcntl(fd, libc::F_GETFL))?;
+            check(libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+
+            Ok(Self { master, path })
+        }
+    }
+
+    /// The path for a terminal program to open.
+    pub fn path(&self) -> &Path{
+        &self.path
+    }
+}
+#[cfg(unix)]
+impl SerialLink for PtySerial{
+    fn send(&mut self, byte: u8) {
+        // a full buffer, while no terminal is reading, loses the byte as a real line would
+        let _ = self.master.write(&[byte]);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        // reads fail while no terminal has the port open
+        let mut byte = [0u8];
+        match self.master.read(&mut byte){
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
+/// One end of a null-modem cable: what is sent at one end is received at the other.
+pub struct NullModem{
+    transmit: Sender<u8>,
+    receive: Receiver<u8>,
+}
+impl SerialLink for NullModem{
+    fn send(&mut self, byte: u8) {
+        // the other end may have been dropped, which leaves nothing listening
+        let _ = self.transmit.send(byte);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.receive.try_recv().ok()
+    }
+}
+
+/// The two ends of a null-modem cable, for connecting the UARTs of two machines. Bytes wait at the receiving
+/// end until they are taken.
+pub fn null_modem() -> (NullModem, NullModem){
+    let (to_second, from_first) = mpsc::channel();
+    let (to_first, from_second) = mpsc::channel();
+    (NullModem { transmit: to_second, receive: from_second }, NullModem { transmit: to_first, receive: from_first })
+}
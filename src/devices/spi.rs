@@ -0,0 +1,183 @@
+use std::any::Any;
+
+use crate::devices::device::{Device, Port, PortPeripheral};
+
+/// Something on an SPI bus. Transfers are a byte at a time, and what the device shifts out for a byte must
+/// not depend on the byte shifted in alongside it, so that bit-banged masters can sample it as they go.
+pub trait SpiDevice: Any{
+    /// Chip select, already converted from the usual active-low level.
+    fn select(&mut self, selected: bool);
+    /// The byte the device shifts out next, MSB first.
+    fn next_out(&mut self) -> u8;
+    /// A byte the master has finished shifting in.
+    fn receive(&mut self, mosi: u8);
+
+    fn exchange(&mut self, mosi: u8) -> u8{
+        let miso = self.next_out();
+        self.receive(mosi);
+        miso
+    }
+}
+
+/**
+   A dedicated SPI master with up to 8 devices on it.
+
+   offset 0, data:    writing exchanges a byte with the selected devices; reading returns the last byte received
+   offset 1, select:  bit n selects device n
+ */
+#[derive(Default)]
+pub struct SpiController{
+    devices: Vec<Box<dyn SpiDevice>>,
+    selected: u8,
+    received: u8,
+}
+impl SpiController{
+    pub const DEFAULT_ADDRESS: u16 = 0xf030;
+
+    const DATA: u16 = 0;
+
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Adds a device to the bus, returning its select bit number, or `None` when all 8 are taken.
+    pub fn attach(&mut self, device: impl SpiDevice) -> Option<usize>{
+        if self.devices.len() == 8{
+            return None;
+        }
+
+        self.devices.push(Box::new(device));
+        Some(self.devices.len() - 1)
+    }
+    pub fn device<T: SpiDevice>(&self, idx: usize) -> Option<&T>{
+        let device: &dyn Any = self.devices.get(idx)?.as_ref();
+        device.downcast_ref::<T>()
+    }
+    pub fn device_mut<T: SpiDevice>(&mut self, idx: usize) -> Option<&mut T>{
+        let device: &mut dyn Any = self.devices.get_mut(idx)?.as_mut();
+        device.downcast_mut::<T>()
+    }
+}
+impl Device for SpiController{
+    fn read(&mut self, offset: u16) -> u8 {
+        if (offset & 1) == Self::DATA { self.received } else { self.selected }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        if (offset & 1) == Self::DATA{
+            // MISO is pulled up and wired-AND between the selected devices
+            self.received = self.devices.iter_mut().enumerate()
+                .filter(|(idx, _)| (self.selected & (1 << idx)) > 0)
+                .fold(0xff, |miso, (_, device)| miso & device.exchange(val));
+        }
+        else{
+            for (idx, device) in self.devices.iter_mut().enumerate(){
+                let was = (self.selected & (1 << idx)) > 0;
+                let is = (val & (1 << idx)) > 0;
+                if was != is{
+                    device.select(is);
+                }
+            }
+            self.selected = val;
+        }
+    }
+}
+
+/// Which port pins a bit-banged SPI bus uses. Each mask should have a single bit set.
+#[derive(Copy, Clone, Debug)]
+pub struct SpiWiring{
+    pub port: Port,
+    pub cs: u8,     // active low chip select, driven by the CPU
+    pub sck: u8,    // clock, driven by the CPU
+    pub mosi: u8,   // data from the CPU
+    pub miso: u8,   // data to the CPU
+}
+
+/// A single SPI device bit-banged through a VIA's or PIA's port pins, in SPI mode 0: data is sampled on the
+/// rising edge of SCK and MISO moves to the next bit on the falling edge.
+pub struct SpiPins{
+    wiring: SpiWiring,
+    device: Box<dyn SpiDevice>,
+
+    selected: bool,
+    clock: bool,
+    bits: u8,       // bits sampled from MOSI in the current byte
+    shifted: u8,    // bits of `out` already shifted out on MISO
+    mosi: u8,
+    out: u8,
+}
+impl SpiPins{
+    pub fn new(wiring: SpiWiring, device: impl SpiDevice) -> Self{
+        Self {
+            wiring,
+            device: Box::new(device),
+            selected: false,
+            clock: false,
+            bits: 0,
+            shifted: 0,
+            mosi: 0,
+            out: 0xff,
+        }
+    }
+
+    pub fn device<T: SpiDevice>(&self) -> Option<&T>{
+        let device: &dyn Any = self.device.as_ref();
+        device.downcast_ref::<T>()
+    }
+    pub fn device_mut<T: SpiDevice>(&mut self) -> Option<&mut T>{
+        let device: &mut dyn Any = self.device.as_mut();
+        device.downcast_mut::<T>()
+    }
+
+    fn start_byte(&mut self){
+        self.bits = 0;
+        self.shifted = 0;
+        self.mosi = 0;
+        self.out = self.device.next_out();
+    }
+}
+impl PortPeripheral for SpiPins{
+    fn outputs_changed(&mut self, port_a: u8, port_b: u8) {
+        let levels = match self.wiring.port{
+            Port::A => port_a,
+            Port::B => port_b,
+        };
+
+        let selected = (levels & self.wiring.cs) == 0;
+        if selected != self.selected{
+            self.selected = selected;
+            self.device.select(selected);
+            if selected{
+                self.start_byte();
+            }
+        }
+
+        let clock = (levels & self.wiring.sck) > 0;
+        if self.selected && clock != self.clock{
+            if clock{
+                self.mosi = (self.mosi << 1) | ((levels & self.wiring.mosi) > 0) as u8;
+                self.bits += 1;
+                if self.bits == 8{
+                    self.device.receive(self.mosi);
+                }
+            }
+            else if self.bits == 8{
+                self.start_byte();
+            }
+            else{
+                self.shifted = self.bits;
+            }
+        }
+        self.clock = clock;
+    }
+
+    fn inputs(&self) -> (u8, u8) {
+        let high = !self.selected || ((self.out << self.shifted) & 0x80) > 0;
+        let levels = if high { 0xff } else { !self.wiring.miso };
+
+        match self.wiring.port{
+            Port::A => (levels, 0xff),
+            Port::B => (0xff, levels),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::any::Any;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::{Device, Port, PortPeripheral};
+
+/**
+   Peripheral interface adapter: two 8-bit ports, each with a control register and two control lines.
+   Register-compatible with the MC6821 and MOS 6520. Registers repeat every 4 bytes.
+
+    Datasheet: https://www.westerndesigncenter.com/wdc/documentation/w65c21.pdf
+ */
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct W65C21{
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    cra: u8,
+    crb: u8,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    peripherals: Vec<Box<dyn PortPeripheral>>,
+}
+impl W65C21{
+    // control register bits
+    pub const CR_IRQ1_ENABLE: u8 = 0b0000_0001;
+    pub const CR_OUTPUT_SELECT: u8 = 0b0000_0100;  // the data register is addressed instead of the direction register
+    pub const CR_IRQ2_FLAG: u8 = 0b0100_0000;
+    pub const CR_IRQ1_FLAG: u8 = 0b1000_0000;
+
+    const CR_C2_OUTPUT_MASK: u8 = 0b0011_0000;
+    const CR_C2_HANDSHAKE: u8 = 0b0010_0000;       // handshake or pulse output
+
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Wires a peripheral to the ports and returns its index among the attached peripherals.
+    pub fn attach(&mut self, peripheral: impl PortPeripheral) -> usize{
+        self.peripherals.push(Box::new(peripheral));
+        self.notify_outputs();
+        self.peripherals.len() - 1
+    }
+    pub fn peripheral<T: PortPeripheral>(&self, idx: usize) -> Option<&T>{
+        let peripheral: &dyn Any = self.peripherals.get(idx)?.as_ref();
+        peripheral.downcast_ref::<T>()
+    }
+    pub fn peripheral_mut<T: PortPeripheral>(&mut self, idx: usize) -> Option<&mut T>{
+        let peripheral: &mut dyn Any = self.peripherals.get_mut(idx)?.as_mut();
+        peripheral.downcast_mut::<T>()
+    }
+
+    #[inline]
+    fn pins(&self) -> (u8, u8){
+        ((self.ora & self.ddra) | !self.ddra, (self.orb & self.ddrb) | !self.ddrb)
+    }
+    fn inputs(&self) -> (u8, u8){
+        self.peripherals.iter().fold((0xff, 0xff), |(a, b), p| {
+            let (pa, pb) = p.inputs();
+            (a & pa, b & pb)
+        })
+    }
+    fn handshake(&mut self, port: Port){
+        let control = match port{
+            Port::A => self.cra,
+            Port::B => self.crb,
+        };
+        if (control & Self::CR_C2_OUTPUT_MASK) == Self::CR_C2_HANDSHAKE{
+            for peripheral in self.peripherals.iter_mut(){
+                peripheral.handshake(port);
+            }
+        }
+    }
+    fn notify_outputs(&mut self){
+        let (a, b) = self.pins();
+        for peripheral in self.peripherals.iter_mut(){
+            peripheral.outputs_changed(a, b);
+        }
+    }
+}
+impl Device for W65C21{
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x3{
+            0 if (self.cra & Self::CR_OUTPUT_SELECT) > 0 => {
+                self.cra &= !(Self::CR_IRQ1_FLAG | Self::CR_IRQ2_FLAG);
+                let val = (self.ora & self.ddra) | (self.inputs().0 & !self.ddra);
+                self.handshake(Port::A);
+                val
+            },
+            0 => self.ddra,
+            1 => self.cra,
+            2 if (self.crb & Self::CR_OUTPUT_SELECT) > 0 => {
+                self.crb &= !(Self::CR_IRQ1_FLAG | Self::CR_IRQ2_FLAG);
+                (self.orb & self.ddrb) | (self.inputs().1 & !self.ddrb)
+            },
+            2 => self.ddrb,
+            _ => self.crb,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match offset & 0x3{
+            0 if (self.cra & Self::CR_OUTPUT_SELECT) > 0 => {
+                self.ora = val;
+                self.notify_outputs();
+            },
+            0 => {
+                self.ddra = val;
+                self.notify_outputs();
+            },
+            1 => self.cra = (self.cra & 0xc0) | (val & 0x3f),  // the interrupt flags are read-only
+            2 if (self.crb & Self::CR_OUTPUT_SELECT) > 0 => {
+                self.orb = val;
+                self.notify_outputs();
+                self.handshake(Port::B);
+            },
+            2 => {
+                self.ddrb = val;
+                self.notify_outputs();
+            },
+            _ => self.crb = (self.crb & 0xc0) | (val & 0x3f),
+        }
+    }
+
+    /// CA1/CB1 transitions are taken as active regardless of the edge selected in the control registers.
+    fn tick(&mut self, cycles: u32) {
+        for peripheral in self.peripherals.iter_mut(){
+            peripheral.tick(cycles);
+            let (ca1, cb1) = peripheral.take_strobes();
+            if ca1{
+                self.cra |= Self::CR_IRQ1_FLAG;
+            }
+            if cb1{
+                self.crb |= Self::CR_IRQ1_FLAG;
+            }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        let asserting = |cr: u8| (cr & (Self::CR_IRQ1_FLAG | Self::CR_IRQ1_ENABLE)) == (Self::CR_IRQ1_FLAG | Self::CR_IRQ1_ENABLE);
+        asserting(self.cra) || asserting(self.crb)
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        let mut loaded: Self = serde_json::from_value(state)?;
+        loaded.peripherals = std::mem::take(&mut self.peripherals);
+        *self = loaded;
+        Ok(())
+    }
+}
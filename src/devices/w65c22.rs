@@ -0,0 +1,323 @@
+use std::any::Any;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::devices::device::{Device, Port, PortPeripheral};
+
+enum Register{
+    Orb,    // output/input register B
+    Ora,    // output/input register A
+    Ddrb,   // data direction register B
+    Ddra,   // data direction register A
+    T1cl,   // timer 1 counter low
+    T1ch,   // timer 1 counter high
+    T1ll,   // timer 1 latch low
+    T1lh,   // timer 1 latch high
+    T2cl,   // timer 2 counter low
+    T2ch,   // timer 2 counter high
+    Sr,     // shift register
+    Acr,    // auxiliary control register
+    Pcr,    // peripheral control register
+    Ifr,    // interrupt flag register
+    Ier,    // interrupt enable register
+    OraNh,  // output/input register A, no handshake
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x0f{
+            0x0 => Register::Orb,
+            0x1 => Register::Ora,
+            0x2 => Register::Ddrb,
+            0x3 => Register::Ddra,
+            0x4 => Register::T1cl,
+            0x5 => Register::T1ch,
+            0x6 => Register::T1ll,
+            0x7 => Register::T1lh,
+            0x8 => Register::T2cl,
+            0x9 => Register::T2ch,
+            0xa => Register::Sr,
+            0xb => Register::Acr,
+            0xc => Register::Pcr,
+            0xd => Register::Ifr,
+            0xe => Register::Ier,
+            _ => Register::OraNh,
+        }
+    }
+}
+
+/**
+   Versatile interface adapter: two 8-bit ports, two timers and a shift register.
+   Registers repeat every 16 bytes, so it can be mapped over a larger decoded window.
+
+    Datasheet: https://www.westerndesigncenter.com/wdc/documentation/w65c22.pdf
+ */
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct W65C22{
+    orb: u8,
+    ora: u8,
+    ddrb: u8,
+    ddra: u8,
+
+    t1_counter: u16,
+    t1_latch: u16,
+    t1_armed: bool,     // one-shot mode only flags the first time-out after each load
+    t2_counter: u16,
+    t2_latch_low: u8,
+    t2_armed: bool,
+
+    sr: u8,
+    sr_count: u8,       // bits shifted since the shift register was last accessed
+    acr: u8,
+    pcr: u8,
+    ifr: u8,
+    ier: u8,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    peripherals: Vec<Box<dyn PortPeripheral>>,
+}
+impl W65C22{
+    // interrupt flag/enable bits
+    pub const IRQ_CA2: u8 = 0b0000_0001;
+    pub const IRQ_CA1: u8 = 0b0000_0010;
+    pub const IRQ_SR: u8 = 0b0000_0100;
+    pub const IRQ_CB2: u8 = 0b0000_1000;
+    pub const IRQ_CB1: u8 = 0b0001_0000;
+    pub const IRQ_T2: u8 = 0b0010_0000;
+    pub const IRQ_T1: u8 = 0b0100_0000;
+
+    const ACR_T1_FREE_RUN: u8 = 0b0100_0000;
+    const ACR_SR_MODE_MASK: u8 = 0b0001_1100;
+    const ACR_SR_IN_EXTERNAL: u8 = 0b0000_1100;     // shift in under control of CB1
+    const PCR_CA2_OUTPUT_MASK: u8 = 0b0000_1100;
+    const PCR_CB2_OUTPUT_MASK: u8 = 0b1100_0000;
+    const PCR_CA2_HANDSHAKE: u8 = 0b0000_1000;     // handshake or pulse output
+    const PCR_CB2_HANDSHAKE: u8 = 0b1000_0000;
+
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Wires a peripheral to the ports and returns its index among the attached peripherals.
+    pub fn attach(&mut self, peripheral: impl PortPeripheral) -> usize{
+        self.peripherals.push(Box::new(peripheral));
+        self.notify_outputs();
+        self.peripherals.len() - 1
+    }
+    pub fn peripheral<T: PortPeripheral>(&self, idx: usize) -> Option<&T>{
+        let peripheral: &dyn Any = self.peripherals.get(idx)?.as_ref();
+        peripheral.downcast_ref::<T>()
+    }
+    pub fn peripheral_mut<T: PortPeripheral>(&mut self, idx: usize) -> Option<&mut T>{
+        let peripheral: &mut dyn Any = self.peripherals.get_mut(idx)?.as_mut();
+        peripheral.downcast_mut::<T>()
+    }
+
+    /// Raises interrupt flags on behalf of external signals such as the CA1/CB1 control lines.
+    pub fn raise(&mut self, flags: u8){
+        self.ifr |= flags & 0x7f;
+    }
+
+    #[inline]
+    fn pins(&self) -> (u8, u8){
+        ((self.ora & self.ddra) | !self.ddra, (self.orb & self.ddrb) | !self.ddrb)
+    }
+    fn inputs(&self) -> (u8, u8){
+        self.peripherals.iter().fold((0xff, 0xff), |(a, b), p| {
+            let (pa, pb) = p.inputs();
+            (a & pa, b & pb)
+        })
+    }
+    fn handshake(&mut self, port: Port){
+        let handshaking = match port{
+            Port::A => (self.pcr & Self::PCR_CA2_OUTPUT_MASK) == Self::PCR_CA2_HANDSHAKE,
+            Port::B => (self.pcr & Self::PCR_CB2_OUTPUT_MASK) == Self::PCR_CB2_HANDSHAKE,
+        };
+        if handshaking{
+            for peripheral in self.peripherals.iter_mut(){
+                peripheral.handshake(port);
+            }
+        }
+    }
+    /// In this mode the counter keeps shifting after 8 bits, flagging an interrupt after every 8.
+    fn shift_in_external(&mut self, cycles: u32){
+        let bits = self.peripherals.iter_mut().filter_map(|p| p.shift_in(cycles)).collect::<Vec<bool>>();
+        for bit in bits{
+            self.sr = (self.sr << 1) | bit as u8;
+            self.sr_count += 1;
+            if self.sr_count == 8{
+                self.sr_count = 0;
+                self.ifr |= Self::IRQ_SR;
+            }
+        }
+    }
+    fn notify_outputs(&mut self){
+        let (a, b) = self.pins();
+        for peripheral in self.peripherals.iter_mut(){
+            peripheral.outputs_changed(a, b);
+        }
+    }
+}
+impl Device for W65C22{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Orb => {
+                self.ifr &= !(Self::IRQ_CB1 | Self::IRQ_CB2);
+                (self.orb & self.ddrb) | (self.inputs().1 & !self.ddrb)
+            },
+            Register::Ora => {
+                self.ifr &= !(Self::IRQ_CA1 | Self::IRQ_CA2);
+                let val = (self.ora & self.ddra) | (self.inputs().0 & !self.ddra);
+                self.handshake(Port::A);
+                val
+            },
+            Register::OraNh => (self.ora & self.ddra) | (self.inputs().0 & !self.ddra),
+            Register::Ddrb => self.ddrb,
+            Register::Ddra => self.ddra,
+            Register::T1cl => {
+                self.ifr &= !Self::IRQ_T1;
+                (self.t1_counter & 0xff) as u8
+            },
+            Register::T1ch => (self.t1_counter >> 8) as u8,
+            Register::T1ll => (self.t1_latch & 0xff) as u8,
+            Register::T1lh => (self.t1_latch >> 8) as u8,
+            Register::T2cl => {
+                self.ifr &= !Self::IRQ_T2;
+                (self.t2_counter & 0xff) as u8
+            },
+            Register::T2ch => (self.t2_counter >> 8) as u8,
+            Register::Sr => {
+                self.ifr &= !Self::IRQ_SR;
+                self.sr_count = 0;
+                self.sr
+            },
+            Register::Acr => self.acr,
+            Register::Pcr => self.pcr,
+            Register::Ifr => {
+                let any = (self.ifr & self.ier & 0x7f) != 0;
+                self.ifr | ((any as u8) << 7)
+            },
+            Register::Ier => self.ier | 0x80,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Orb => {
+                self.ifr &= !(Self::IRQ_CB1 | Self::IRQ_CB2);
+                self.orb = val;
+                self.notify_outputs();
+                self.handshake(Port::B);
+            },
+            Register::Ora => {
+                self.ifr &= !(Self::IRQ_CA1 | Self::IRQ_CA2);
+                self.ora = val;
+                self.notify_outputs();
+                self.handshake(Port::A);
+            },
+            Register::OraNh => {
+                self.ora = val;
+                self.notify_outputs();
+            },
+            Register::Ddrb => {
+                self.ddrb = val;
+                self.notify_outputs();
+            },
+            Register::Ddra => {
+                self.ddra = val;
+                self.notify_outputs();
+            },
+            Register::T1cl | Register::T1ll => self.t1_latch = (self.t1_latch & 0xff00) | val as u16,
+            Register::T1ch => {
+                self.t1_latch = (self.t1_latch & 0x00ff) | ((val as u16) << 8);
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.ifr &= !Self::IRQ_T1;
+            },
+            Register::T1lh => {
+                self.t1_latch = (self.t1_latch & 0x00ff) | ((val as u16) << 8);
+                self.ifr &= !Self::IRQ_T1;
+            },
+            Register::T2cl => self.t2_latch_low = val,
+            Register::T2ch => {
+                self.t2_counter = ((val as u16) << 8) | self.t2_latch_low as u16;
+                self.t2_armed = true;
+                self.ifr &= !Self::IRQ_T2;
+            },
+            Register::Sr => {
+                self.ifr &= !Self::IRQ_SR;
+                self.sr_count = 0;
+                self.sr = val;
+            },
+            Register::Acr => self.acr = val,
+            Register::Pcr => self.pcr = val,
+            Register::Ifr => self.ifr &= !(val & 0x7f),
+            Register::Ier => {
+                if (val & 0x80) > 0{
+                    self.ier |= val & 0x7f;
+                }
+                else{
+                    self.ier &= !(val & 0x7f);
+                }
+            },
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        for peripheral in self.peripherals.iter_mut(){
+            peripheral.tick(cycles);
+            let (ca1, cb1) = peripheral.take_strobes();
+            self.ifr |= if ca1 { Self::IRQ_CA1 } else { 0 } | if cb1 { Self::IRQ_CB1 } else { 0 };
+        }
+        if (self.acr & Self::ACR_SR_MODE_MASK) == Self::ACR_SR_IN_EXTERNAL{
+            self.shift_in_external(cycles);
+        }
+
+        // timer 1 counts down through zero and times out one cycle past it, before reloading in free-run mode
+        let mut remaining = cycles;
+        while remaining > 0{
+            let until_timeout = self.t1_counter as u32 + 1;
+            if remaining < until_timeout{
+                self.t1_counter -= remaining as u16;
+                break;
+            }
+
+            remaining -= until_timeout;
+            if self.t1_armed{
+                self.ifr |= Self::IRQ_T1;
+            }
+            if (self.acr & Self::ACR_T1_FREE_RUN) > 0{
+                self.t1_counter = self.t1_latch;
+            }
+            else{
+                self.t1_armed = false;
+                self.t1_counter = 0xffffu16.wrapping_sub(remaining as u16);
+                break;
+            }
+        }
+
+        let (counter, underflowed) = self.t2_counter.overflowing_sub(cycles.min(0xffff) as u16);
+        if (underflowed || cycles > 0xffff) && self.t2_armed{
+            self.ifr |= Self::IRQ_T2;
+            self.t2_armed = false;
+        }
+        self.t2_counter = counter;
+    }
+
+    fn irq(&self) -> bool {
+        (self.ifr & self.ier & 0x7f) != 0
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> Option<serde_json::Value>{
+        serde_json::to_value(self).ok()
+    }
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), serde_json::Error>{
+        let mut loaded: Self = serde_json::from_value(state)?;
+        loaded.peripherals = std::mem::take(&mut self.peripherals);
+        *self = loaded;
+        Ok(())
+    }
+}
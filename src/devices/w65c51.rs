@@ -0,0 +1,120 @@
+use crate::devices::device::Device;
+use crate::devices::serial::SerialLink;
+
+enum Register{
+    Data,       // transmit on write, receive on read
+    Status,     // a write is a programmed reset
+    Command,
+    Control,
+}
+impl Register{
+    fn decode(offset: u16) -> Self{
+        match offset & 0x3{
+            0 => Register::Data,
+            1 => Register::Status,
+            2 => Register::Command,
+            _ => Register::Control,
+        }
+    }
+}
+
+/**
+   Asynchronous communications interface adapter, connected to a host serial line.
+   Bytes are transferred as soon as they are written or arrive, so the baud rate in the control register has
+   no effect, and input waits on the host side until the receiver is empty, so overruns never occur.
+   As on the W65C51N, the transmitter data register empty flag always reads as set.
+
+    Datasheet: https://www.westerndesigncenter.com/wdc/documentation/w65c51n.pdf
+ */
+pub struct W65C51{
+    link: Box<dyn SerialLink>,
+
+    received: Option<u8>,
+    command: u8,
+    control: u8,
+
+    unpolled_cycles: u32,   // cycles since the line was last checked for input
+}
+impl W65C51{
+    pub const DEFAULT_ADDRESS: u16 = 0xf040;
+
+    // status register bits
+    pub const STATUS_RECEIVER_FULL: u8 = 0b0000_1000;
+    pub const STATUS_TRANSMITTER_EMPTY: u8 = 0b0001_0000;
+    pub const STATUS_IRQ: u8 = 0b1000_0000;
+
+    const COMMAND_DTR: u8 = 0b0000_0001;                     // enables the receiver and its interrupt
+    const COMMAND_RECEIVER_IRQ_DISABLE: u8 = 0b0000_0010;
+
+    const POLL_INTERVAL: u32 = 1000;
+
+    pub fn new(link: impl SerialLink + 'static) -> Self{
+        Self {
+            link: Box::new(link),
+            received: None,
+            command: 0,
+            control: 0,
+            unpolled_cycles: 0,
+        }
+    }
+
+    fn receiver_enabled(&self) -> bool{
+        (self.command & Self::COMMAND_DTR) > 0
+    }
+    fn poll(&mut self){
+        self.unpolled_cycles = 0;
+        if self.received.is_none() && self.receiver_enabled(){
+            self.received = self.link.receive();
+        }
+    }
+    fn status(&self) -> u8{
+        let mut status = Self::STATUS_TRANSMITTER_EMPTY;
+        if self.received.is_some(){
+            status |= Self::STATUS_RECEIVER_FULL;
+        }
+        if self.irq(){
+            status |= Self::STATUS_IRQ;
+        }
+        status
+    }
+}
+impl Device for W65C51{
+    fn read(&mut self, offset: u16) -> u8 {
+        match Register::decode(offset){
+            Register::Data => {
+                let val = self.received.take().unwrap_or(0);
+                self.poll();
+                val
+            },
+            Register::Status => {
+                self.poll();
+                self.status()
+            },
+            Register::Command => self.command,
+            Register::Control => self.control,
+        }
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        match Register::decode(offset){
+            Register::Data => self.link.send(val),
+            Register::Status => {
+                // programmed reset clears the command register's low 5 bits
+                self.command &= 0b1110_0000;
+            },
+            Register::Command => self.command = val,
+            Register::Control => self.control = val,
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.unpolled_cycles += cycles;
+        if self.unpolled_cycles >= Self::POLL_INTERVAL{
+            self.poll();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.received.is_some() && self.receiver_enabled() && (self.command & Self::COMMAND_RECEIVER_IRQ_DISABLE) == 0
+    }
+}
@@ -0,0 +1,185 @@
+//! The C interface, declared in `include/steel6502.h`. A `Steel6502` is a 65C02 on a machine of 32KB of RAM and
+//! 32KB of ROM, which the host can extend with ranges of addresses handled by its own callbacks.
+
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::ptr;
+use std::slice;
+
+use crate::bus::bus::Machine;
+use crate::cpu::w65c02s::{Register, W65C02S};
+
+/// Reads a byte at an address of a range mapped with `steel6502_map`.
+pub type ReadHook = Option<unsafe extern "C" fn(user: *mut c_void, address: u16) -> u8>;
+/// Takes a byte written to an address of a range mapped with `steel6502_map`.
+pub type WriteHook = Option<unsafe extern "C" fn(user: *mut c_void, address: u16, value: u8)>;
+
+/// A CPU and the machine it runs on, handed to C as an opaque pointer.
+pub struct Steel6502{
+    cpu: W65C02S,
+    machine: Machine,
+    error: Option<CString>,     // what the last call that failed went wrong with
+}
+impl Steel6502{
+    fn fail(&mut self, error: impl ToString) -> c_int{
+        self.error = CString::new(error.to_string()).ok();
+        -1
+    }
+}
+
+fn register(register: c_int) -> Option<Register>{
+    match register{
+        0 => Some(Register::PC),
+        1 => Some(Register::A),
+        2 => Some(Register::X),
+        3 => Some(Register::Y),
+        4 => Some(Register::SP),
+        5 => Some(Register::P),
+        _ => None,
+    }
+}
+
+/// Builds a machine with `rom` in its upper 32KB and resets the CPU through the reset vector. Returns null
+/// when the ROM does not fit.
+///
+/// # Safety
+/// `rom` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_create(rom: *const u8, length: usize) -> *mut Steel6502{
+    if rom.is_null(){
+        return ptr::null_mut();
+    }
+    let rom = unsafe { slice::from_raw_parts(rom, length) };
+    let Ok(mut machine) = Machine::new_32k_ram_32k_rom(rom) else { return ptr::null_mut() };
+    let mut cpu = W65C02S::default();
+    if cpu.reset(&mut machine).is_err(){
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(Steel6502 { cpu, machine, error: None }))
+}
+
+/// Frees a machine made by `steel6502_create`, and its hooks with it.
+///
+/// # Safety
+/// `emulator` must be null or come from `steel6502_create`, and not be used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_destroy(emulator: *mut Steel6502){
+    if !emulator.is_null(){
+        drop(unsafe { Box::from_raw(emulator) });
+    }
+}
+
+/// Starts the CPU again from the reset vector, leaving memory as it is. Returns 0, or -1 on a bus error.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_reset(emulator: *mut Steel6502) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_mut() }) else { return -1 };
+    match emulator.cpu.reset(&mut emulator.machine){
+        Ok(()) => 0,
+        Err(error) => emulator.fail(error),
+    }
+}
+
+/// Executes one instruction, and any interrupt it lets in. Returns the cycles it took, or -1 when the CPU
+/// stopped, such as on an invalid opcode.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_step(emulator: *mut Steel6502) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_mut() }) else { return -1 };
+    let before = emulator.cpu.cycles();
+    match emulator.cpu.step(&mut emulator.machine){
+        Ok(_) => (emulator.cpu.cycles() - before) as c_int,
+        Err(error) => emulator.fail(error),
+    }
+}
+
+/// The byte at `address`, read without side effects and without calling hooks, or -1 when nothing is
+/// mapped there.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_read(emulator: *const Steel6502, address: u16) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_ref() }) else { return -1 };
+    emulator.machine.peek(address).map_or(-1, c_int::from)
+}
+
+/// Stores a byte in RAM or ROM, as a debugger would. Returns 0, or -1 when nothing is mapped there.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_write(emulator: *mut Steel6502, address: u16, value: u8) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_mut() }) else { return -1 };
+    match emulator.machine.poke(address, value){
+        Ok(()) => 0,
+        Err(error) => emulator.fail(error),
+    }
+}
+
+/// A register, numbered as `STEEL6502_PC` to `STEEL6502_P` are, or 0 for any other number.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_register(emulator: *const Steel6502, number: c_int) -> u16{
+    let Some(emulator) = (unsafe { emulator.as_ref() }) else { return 0 };
+    register(number).map_or(0, |register| emulator.cpu.register(register))
+}
+
+/// Sets a register, the 8-bit ones to the low byte of `value`. Returns 0, or -1 for an unknown register.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_set_register(emulator: *mut Steel6502, number: c_int, value: u16) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_mut() }) else { return -1 };
+    let Some(register) = register(number) else { return emulator.fail(format!("no register numbered {}", number)) };
+    emulator.cpu.set_register(register, value);
+    0
+}
+
+/// Cycles elapsed since the machine was created.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_cycles(emulator: *const Steel6502) -> u64{
+    unsafe { emulator.as_ref() }.map_or(0, |emulator| emulator.cpu.cycles())
+}
+
+/// Hands CPU accesses from `first` to `last` to the host, ahead of RAM, ROM and ranges mapped before. The hooks
+/// get `user` and the address accessed; without a read hook the range reads as $FF, and without a write hook
+/// writes to it are dropped. Returns 0, or -1 when `first` is past `last`.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`, and the hooks must be safe to call with `user` for as long as
+/// the machine lives.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_map(emulator: *mut Steel6502, first: u16, last: u16, on_read: ReadHook, on_write: WriteHook, user: *mut c_void) -> c_int{
+    let Some(emulator) = (unsafe { emulator.as_mut() }) else { return -1 };
+    if first > last{
+        return emulator.fail(format!("${:04X} is past ${:04X}", first, last));
+    }
+    emulator.machine.map_registers(
+        first..=last,
+        move |address| on_read.map_or(0xff, |hook| unsafe { hook(user, address) }),
+        move |address, value| if let Some(hook) = on_write{
+            unsafe { hook(user, address, value) }
+        },
+    );
+    0
+}
+
+/// What the last call that returned -1 went wrong with, or null when none has. The text lives until the
+/// next failing call or the machine is destroyed.
+///
+/// # Safety
+/// `emulator` must come from `steel6502_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steel6502_last_error(emulator: *const Steel6502) -> *const c_char{
+    unsafe { emulator.as_ref() }.and_then(|emulator| emulator.error.as_ref()).map_or(ptr::null(), |error| error.as_ptr())
+}
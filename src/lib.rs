@@ -0,0 +1,28 @@
+//! The emulator without its command line: the 65C02 in `cpu`, the machine it runs on in `bus`, the memory
+//! behind it in `memory` and the devices mapped into it in `devices`, for building into other emulators,
+//! front ends and test harnesses. `runner` holds what the command line runs images with, from its options to
+//! the errors it reports.
+//!
+//! A machine is built, loaded with an image and reset, then stepped an instruction at a time:
+//!
+//! ```no_run
+//! use steel6502::prelude::*;
+//!
+//! let image = std::fs::read("rom.bin").unwrap();
+//! let mut machine = Machine::new_32k_ram_32k_rom(&image).unwrap();
+//! let mut cpu = W65C02S::default();
+//! cpu.reset(&mut machine).unwrap();
+//! for _ in 0..1000{
+//!     cpu.step(&mut machine).unwrap();
+//! }
+//! ```
+//!
+//! `prelude` gathers the types most programs need; the rest are reached through their modules.
+
+pub mod memory;
+pub mod cpu;
+pub mod bus;
+pub mod devices;
+pub mod ffi;
+pub mod prelude;
+pub mod runner;
@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes log records to stderr: what the run is doing as plain lines, warnings and errors marked as such, and
+/// the detail of `-v` and `-vv` with the module it comes from, such as `w65c22` or `bus`.
+struct StderrLogger;
+
+impl Log for StderrLogger{
+    // the libraries the emulator is built on only have their warnings and errors shown, as their chatter is
+    // about them and not the run
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let ours = metadata.target().get(..9).is_some_and(|krate| krate.eq_ignore_ascii_case("steel6502"));
+        metadata.level() <= log::max_level() && (ours || metadata.level() <= Level::Warn)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()){
+            return;
+        }
+
+        let module = record.target().rsplit("::").next().unwrap_or_default();
+        // a log line that cannot be written is not worth stopping the machine for
+        let _ = match record.level(){
+            Level::Error => writeln!(io::stderr().lock(), "error: {}", record.args()),
+            Level::Warn => writeln!(io::stderr().lock(), "warning: {}", record.args()),
+            Level::Info => writeln!(io::stderr().lock(), "{}", record.args()),
+            Level::Debug | Level::Trace => writeln!(io::stderr().lock(), "[{}] {}", module, record.args()),
+        };
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+/// Sends log records to stderr from here on: warnings and errors with `quiet`, and otherwise what the run is
+/// doing, plus device activity, interrupts and bus faults at a `verbose` of 1, and every device access at 2.
+pub fn init(verbose: u8, quiet: bool){
+    let level = match (quiet, verbose){
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    // only fails when a logger is already set, and then that one logs
+    let _ = log::set_logger(&StderrLogger);
+    log::set_max_level(level);
+}
@@ -1,130 +1,428 @@
-mod memory;
-mod cpu;
-mod bus;
+mod cli;
+mod logging;
 
 use std::fs;
 use std::env;
-use std::path::{Path, PathBuf};
-
-use crate::bus::bus::{Machine};
-use crate::cpu::w65c02s::{CpuError, Mnemomic, W65C02S};
-
-macro_rules! match_sequence {
-    ($coll:expr, [$($pattern:pat),+ $(,)?] => $($output:expr),+) => {{
-        let __pattern_len: usize = <[()]>::len(&[ $( { let _ = stringify!($pattern); ()} ),+ ]);
-        let mut __pos: usize = 0;
-        
-        loop {
-            if $coll.len() < __pattern_len || __pos >= ($coll.len() - __pattern_len){
-                break None;
-            }
-            if let Some(__slice) = $coll.get(__pos..__pos + __pattern_len){
-                if let [$($pattern),+] = __slice{
-                    break Some((__pos, $($output),+));
-                }
-                else{
-                    __pos += 1;
-                }
-            }
-            else{
-                break None;
-            }
-        }
-    }};
-    ($coll:expr, [$($pattern:pat),+ $(,)?]) => {{
-        let __pattern_len: usize = <[()]>::len(&[ $( { let _ = stringify!($pattern); ()} ),+ ]);
-        let mut __pos: usize = 0;
-        
-        loop {
-            if $coll.len() < __pattern_len || __pos >= ($coll.len() - __pattern_len){
-                break None;
-            }
-            if let Some(__slice) = $coll.get(__pos..__pos + __pattern_len){
-                if let [$($pattern),+] = __slice{
-                    break Some(__pos);
-                }
-                else{
-                    __pos += 1;
-                }
-            }
-            else{
-                break None;
-            }
-        }
-    }};
+use std::time::SystemTime;
+
+use clap::Parser;
+
+use steel6502::bus::board::{is_prg, load_image, AnsiScreenOptions, ConsoleEnd, ConsoleOptions, DeviceOptions, DiskOptions, HostServicesOptions, MidiOptions, SerialChip, TapeOptions};
+#[cfg(feature = "video")]
+use steel6502::bus::board::VideoOptions;
+use steel6502::bus::bus::{Machine, RomPlacement};
+use steel6502::bus::result_watch::ResultWatch;
+use crate::cli::{AsmArgs, BenchArgs, Cli, Command, DisasmArgs, ErrorFormat, RunArgs, VerifyArgs};
+use steel6502::cpu::assembler;
+use steel6502::cpu::bench;
+use steel6502::cpu::disassembler;
+#[cfg(feature = "jit")]
+use steel6502::cpu::jit::Jit;
+use steel6502::cpu::limits::RunLimits;
+use steel6502::cpu::symbols::{parse_location, SymbolTable};
+use steel6502::cpu::w65c02s::{InvalidOpcodePolicy, Register, W65C02S};
+use steel6502::cpu::watch::WatchTarget;
+use steel6502::devices::ansi_screen::AnsiScreen;
+use steel6502::devices::ay38910::AY38910;
+use steel6502::devices::beeper::Beeper;
+use steel6502::devices::cassette::Cassette;
+use steel6502::devices::char_io::{CharInput, CharOutput};
+use steel6502::devices::cycle_counter::CycleCounter;
+use steel6502::devices::random::RandomDevice;
+use steel6502::devices::disk_controller::{DiskController, DiskGeometry};
+#[cfg(feature = "video")]
+use steel6502::devices::framebuffer::Framebuffer;
+use steel6502::devices::host_services::HostServices;
+use steel6502::devices::i2c_eeprom::I2cEeprom;
+use steel6502::devices::irq_controller::IrqController;
+use steel6502::devices::joystick::{Joystick, JoystickKeys};
+use steel6502::devices::keyboard::Keyboard;
+use steel6502::devices::leds::LedDisplay;
+use steel6502::devices::midi::MidiOut;
+use steel6502::devices::ps2_keyboard::Ps2Keyboard;
+use steel6502::devices::raster::RasterTimer;
+use steel6502::devices::spi::SpiController;
+use steel6502::runner::error::{ErrorReport, ProgramError};
+#[cfg(feature = "video")]
+use steel6502::runner::options::parse_video_mode;
+use steel6502::runner::options::{parse_compare, parse_ram_image, parse_template, parse_watch_target, utc_timestamp, DumpOptions, FingerprintFile, Golden, InputLogFile, Mode, Options, ResultOptions, TraceOptions, WatchOptions};
+use steel6502::runner::session;
+
+
+/// `stdio` or `tcp:<host:port>` for both halves, overridden by a file for either.
+fn parse_console(args: &RunArgs) -> Result<ConsoleOptions, ProgramError>{
+    let (mut input, mut output) = match args.console.as_deref(){
+        Some("stdio") => (Some(ConsoleEnd::Stdio), Some(ConsoleEnd::Stdio)),
+        Some(console) => match console.strip_prefix("tcp:"){
+            Some(listen) => (Some(ConsoleEnd::Tcp(listen.to_string())), Some(ConsoleEnd::Tcp(listen.to_string()))),
+            None => return Err(ProgramError::InvalidConsole(console.to_string())),
+        },
+        None => (None, None),
+    };
+    if let Some(path) = &args.console_in{
+        input = Some(ConsoleEnd::File(path.clone()));
+    }
+    if let Some(path) = &args.console_out{
+        output = Some(ConsoleEnd::File(path.clone()));
+    }
+    Ok(ConsoleOptions { input, output })
+}
+
+/// A device mapped by a switch at its default address, or by its address flag alone.
+fn switched(on: bool, address: Option<u16>, default: u16) -> Option<u16>{
+    address.or(on.then_some(default))
 }
 
-#[derive(Debug)]
-enum ProgramError{
-    OutputPathIsNotDirectory(String),
-    CouldNotLocateFile(String),
-    CouldNotReadFile(String),
-    CouldNotWriteFile(String),
-    CpuError(CpuError),
-    NoRomFile,
-    MalformedRomFile,
+fn parse_location_flag(text: &Option<String>, symbols: &SymbolTable) -> Result<Option<u16>, ProgramError>{
+    match text{
+        Some(text) => Ok(Some(parse_location(text, symbols).ok_or(ProgramError::InvalidAddress(text.clone()))?)),
+        None => Ok(None),
+    }
 }
 
-fn parse_output_flag(args: &[&str]) -> Result<PathBuf, String>{
-    if let Some((_, desired)) = match_sequence!(args, ["-o", o] => o){
-        let pot = env::current_dir().unwrap().join(Path::new(desired));
 
-        if !pot.is_dir(){ Err(pot.to_str().unwrap().to_owned()) } else { Ok(pot) }
-    } else { Ok(env::current_dir().unwrap()) }
+fn parse_watches(args: &RunArgs, symbols: &SymbolTable) -> Result<Option<WatchOptions>, ProgramError>{
+    if args.watch.is_empty(){
+        return Ok(None);
+    }
+    let watches = args.watch.iter()
+        .map(|text| Ok((text.clone(), parse_watch_target(text, symbols).ok_or(ProgramError::InvalidWatch(text.clone()))?)))
+        .collect::<Result<Vec<(String, WatchTarget)>, ProgramError>>()?;
+    Ok(Some(WatchOptions { watches, every: args.watch_every }))
 }
 
-fn parse_flags(args: &[String]) -> Result<PathBuf, ProgramError>{
-    let sendable: Box<[&str]> = args.iter().map(String::as_str).collect();
 
-    Ok(parse_output_flag(&sendable).map_err(|f| ProgramError::OutputPathIsNotDirectory(f))?)
+/// The ACIA a serial connection goes through, and its address.
+fn serial_chip(args: &RunArgs) -> (SerialChip, u16){
+    let chip = args.serial_chip.unwrap_or(SerialChip::W65C51);
+    (chip, args.serial_addr.unwrap_or(chip.default_address()))
 }
 
-fn main() -> Result<(), ProgramError>{
-    let args = env::args().skip(1).collect::<Vec<String>>();
-    let output_dir = parse_flags(&args)?;
 
-    let mut skipped = false;
-    for arg in args{
-        if arg.starts_with('-') || skipped{
-            skipped = !skipped;
-            continue;
-        }
+fn parse_ansi_screen(args: &RunArgs) -> Result<Option<AnsiScreenOptions>, ProgramError>{
+    let Some((columns, rows)) = args.ansi_screen else { return Ok(None) };
+    let color = args.ansi_color;
+    let address = args.ansi_addr.unwrap_or(AnsiScreen::DEFAULT_ADDRESS);
+    let refresh_rate = args.refresh.unwrap_or(AnsiScreen::DEFAULT_REFRESH_RATE);
 
-        let rom_path = PathBuf::from(&arg);
-        if !rom_path.exists(){
-            return Err(ProgramError::CouldNotLocateFile(arg.to_string()));
-        }
-        let file_name = rom_path.file_stem().expect("Could not extract file name").to_str().expect("Failed to convert").to_owned();
-        let rom = fs::read(rom_path).map_err(|_| ProgramError::CouldNotReadFile(arg.to_string()))?;
-        
-        let mut cpu = W65C02S::default();
-        let mut machine_bus = Machine::new_32k_ram_32k_rom(&rom[0x8000..]);
-        let rom_size = 32768usize;
+    if address as usize + AnsiScreen::size(columns, rows, color) > 0x10000{
+        return Err(ProgramError::InvalidAddress(format!("{:#06X}", address)));
+    }
 
+    Ok(Some(AnsiScreenOptions { columns, rows, color, address, refresh_rate }))
+}
 
-        if rom.len() < rom_size{
-            return Err(ProgramError::MalformedRomFile);
-        }
 
-        println!("Emulating {}", file_name);
-        cpu.reset(&mut machine_bus);
+#[cfg(feature = "video")]
+fn parse_video(args: &RunArgs) -> Result<Option<VideoOptions>, ProgramError>{
+    let Some(mode) = &args.video else { return Ok(None) };
+    let mode = parse_video_mode(mode).ok_or(ProgramError::InvalidVideoMode(mode.clone()))?;
+    let address = args.video_addr.unwrap_or(Framebuffer::DEFAULT_ADDRESS);
+    let refresh_rate = args.refresh.unwrap_or(Framebuffer::DEFAULT_REFRESH_RATE);
+
+    if address as usize + mode.size() > 0x10000{
+        return Err(ProgramError::InvalidAddress(format!("{:#06X}", address)));
+    }
 
-        loop{
-            let op = cpu.step(&mut machine_bus).map_err(|e| ProgramError::CpuError(e))?;
-            match op{
-                Mnemomic::BRK => {break;},
-                _ => {}
+    Ok(Some(VideoOptions { mode, address, refresh_rate }))
+}
+
+/// Turns the flags into options. Clap has checked each value on its own; what is left is what depends on the
+/// symbols or on other flags.
+fn parse_flags(args: &RunArgs) -> Result<Options, ProgramError>{
+    #[cfg(not(feature = "video"))]
+    if args.video.is_some(){
+        return Err(ProgramError::FeatureNotEnabled("video"));
+    }
+    #[cfg(not(feature = "scripting"))]
+    if args.script.is_some(){
+        return Err(ProgramError::FeatureNotEnabled("scripting"));
+    }
+    #[cfg(not(feature = "remote"))]
+    if args.remote.is_some(){
+        return Err(ProgramError::FeatureNotEnabled("remote"));
+    }
+    #[cfg(not(unix))]
+    if args.serial_pty{
+        return Err(ProgramError::UnsupportedOnPlatform("--serial-pty"));
+    }
+
+    let output_dir = match &args.output_dir{
+        Some(dir) => {
+            let dir = env::current_dir().unwrap().join(dir);
+            if !dir.is_dir(){
+                return Err(ProgramError::OutputPathIsNotDirectory(dir.display().to_string()));
             }
+            dir
+        },
+        None => env::current_dir().unwrap(),
+    };
+
+    // addresses given to the flags below can be symbol names
+    let symbols = match &args.symbols{
+        Some(path) => SymbolTable::load(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?,
+        None => SymbolTable::new(),
+    };
+    let halt_addresses = args.halt_addr.iter()
+        .map(|address| parse_location(address, &symbols).ok_or(ProgramError::InvalidAddress(address.clone())))
+        .collect::<Result<Vec<u16>, ProgramError>>()?;
+    let trace = match &args.trace{
+        Some(file) => Some(TraceOptions {
+            file: file.clone(),
+            start: parse_location_flag(&args.trace_start, &symbols)?,
+            stop: parse_location_flag(&args.trace_stop, &symbols)?,
+            format: args.trace_format,
+        }),
+        None => None,
+    };
+    let tape = (args.tape_in.is_some() || args.tape_out.is_some()).then(|| TapeOptions {
+        playback: args.tape_in.clone(),
+        recording: args.tape_out.clone(),
+        address: args.tape_addr.unwrap_or(Cassette::DEFAULT_ADDRESS),
+    });
+    let midi = (args.midi_file.is_some() || args.midi_out.is_some()).then(|| MidiOptions {
+        file: args.midi_file.clone(),
+        port: args.midi_out.clone(),
+        address: args.midi_addr.unwrap_or(MidiOut::DEFAULT_ADDRESS),
+    });
+    let disks = (args.disk.is_some() || args.disk2.is_some()).then(|| DiskOptions {
+        images: [args.disk.clone(), args.disk2.clone()],
+        geometry: args.disk_geometry.unwrap_or(DiskGeometry::DEFAULT),
+        address: args.disk_addr.unwrap_or(DiskController::DEFAULT_ADDRESS),
+    });
+    let host_services = switched(args.host_services, args.host_services_addr, HostServices::DEFAULT_ADDRESS).map(|address| HostServicesOptions {
+        address,
+        block_file: args.host_block_file.clone(),
+        file_directory: args.host_dir.clone(),
+    });
+    let result = args.result_addr.map(|address| ResultOptions {
+        address,
+        pass: args.result_pass.unwrap_or(ResultWatch::DEFAULT_PASS),
+        fail: args.result_fail.unwrap_or(ResultWatch::DEFAULT_FAIL),
+        error_address: args.result_error_addr,
+    });
+    let input_log = match (&args.record_input, &args.replay_input){
+        (Some(path), _) => Some(InputLogFile::Record(path.clone())),
+        (None, Some(path)) => Some(InputLogFile::Replay(path.clone())),
+        (None, None) => None,
+    };
+    let fingerprints = match (&args.record_fingerprints, &args.check_fingerprints){
+        (Some(path), _) => Some(FingerprintFile::Record(path.clone())),
+        (None, Some(path)) => Some(FingerprintFile::Check(path.clone())),
+        (None, None) => None,
+    };
+
+    let (chip, serial_address) = serial_chip(args);
+
+    Ok(Options {
+        output_dir,
+        machine: args.machine.clone(),
+        placement: args.place_top.then_some(RomPlacement::AlignToVectors),
+        entry: parse_location_flag(&args.entry, &symbols)?,
+        devices: DeviceOptions {
+            char_out: switched(args.char_out, args.char_out_addr, CharOutput::DEFAULT_ADDRESS),
+            char_in: switched(args.char_in, args.char_in_addr, CharInput::DEFAULT_ADDRESS),
+            console: parse_console(args)?,
+            keyboard: switched(args.keyboard, args.keyboard_addr, Keyboard::DEFAULT_ADDRESS),
+            ps2_keyboard: switched(args.ps2_keyboard, args.ps2_addr, Ps2Keyboard::DEFAULT_VIA_ADDRESS),
+            beeper: args.beeper.clone().map(|path| (path, args.beeper_addr.unwrap_or(Beeper::DEFAULT_ADDRESS))),
+            psg: args.psg.clone().map(|path| (path, args.psg_addr.unwrap_or(AY38910::DEFAULT_ADDRESS))),
+            tape,
+            sample_rate: args.sample_rate,
+            midi,
+            sd_card: args.sd.clone().map(|path| (path, args.sd_addr.unwrap_or(SpiController::DEFAULT_ADDRESS))),
+            disks,
+            eeprom: args.eeprom.clone().map(|path| (path, args.eeprom_addr.unwrap_or(I2cEeprom::DEFAULT_VIA_ADDRESS))),
+            leds: switched(args.leds, args.leds_addr, LedDisplay::DEFAULT_ADDRESS).map(|address| (address, args.digits.unwrap_or(LedDisplay::DEFAULT_DIGITS))),
+            joystick: switched(args.joystick, args.joystick_addr, Joystick::DEFAULT_ADDRESS).map(|address| (address, args.joystick_keys.unwrap_or(JoystickKeys::DEFAULT))),
+            serial_tcp: args.serial_tcp.clone().map(|listen| (listen, chip, serial_address)),
+            link: args.link.is_some().then_some((chip, serial_address)),
+            serial_pty: args.serial_pty.then_some((chip, serial_address)),
+            irq_controller: switched(args.irq_controller, args.irq_controller_addr, IrqController::DEFAULT_ADDRESS),
+            ansi_screen: parse_ansi_screen(args)?,
+            vsync: switched(args.vsync, args.vsync_addr, RasterTimer::DEFAULT_ADDRESS).map(|address| (address, args.frame_rate.unwrap_or(RasterTimer::DEFAULT_FRAME_RATE))),
+            host_services,
+            cycle_counter: switched(args.cycle_counter, args.cycle_counter_addr, CycleCounter::DEFAULT_ADDRESS),
+            rng: switched(args.rng, args.rng_addr, RandomDevice::DEFAULT_ADDRESS),
+            #[cfg(feature = "video")]
+            video: parse_video(args)?,
+        },
+        link: args.link.clone(),
+        result,
+        limits: RunLimits { max_instructions: args.max_instructions, max_cycles: args.max_cycles, timeout: args.timeout },
+        clock: args.clock,
+        unlimited: args.unlimited,
+        resume: args.resume.clone(),
+        seed: args.seed,
+        random_ram: args.random_ram,
+        ram_image: args.ram_image.as_deref().map(parse_ram_image),
+        invalid_opcodes: if args.invalid_nop { InvalidOpcodePolicy::Nop } else { InvalidOpcodePolicy::Error },
+        autosave: args.autosave,
+        halt_addresses,
+        input_log,
+        fingerprints,
+        dump: DumpOptions {
+            format: args.dump_format,
+            range: args.dump_range,
+            every: args.dump_every,
+            template: parse_template(&args.out_template)?,
+            date: utc_timestamp(SystemTime::now()),
+            state: args.dump_state,
+        },
+        report: args.report.is_some(),
+        profile: args.profile.clone(),
+        coverage: args.coverage,
+        stats: args.stats,
+        #[cfg(feature = "scripting")]
+        script: args.script.clone(),
+        #[cfg(feature = "remote")]
+        remote: args.remote.clone(),
+        compare: args.compare.as_deref().map(parse_compare),
+        trace,
+        watch: parse_watches(args, &symbols)?,
+        symbols,
+    })
+}
+
+
+/// Lists the instructions in an image rather than running it. Without `--org` the image ends at `$FFFF`, as a
+/// ROM does.
+fn disassemble_image(args: &DisasmArgs) -> Result<(), ProgramError>{
+    let mut image = fs::read(&args.image).map_err(|_| ProgramError::CouldNotReadFile(args.image.display().to_string()))?;
+    // a .prg file says where it loads, unless --org says otherwise
+    let mut load_address = None;
+    if is_prg(&args.image){
+        if image.len() < 2{
+            return Err(ProgramError::MalformedRomFile);
         }
+        load_address = Some(u16::from_le_bytes([image[0], image[1]]));
+        image.drain(..2);
+    }
+    if image.is_empty() || image.len() > 0x10000{
+        return Err(ProgramError::MalformedRomFile);
+    }
+
+    let symbols = match &args.symbols{
+        Some(path) => SymbolTable::load(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?,
+        None => SymbolTable::new(),
+    };
+    let org = match &args.org{
+        Some(org) => parse_location(org, &symbols).ok_or(ProgramError::InvalidAddress(org.clone()))?,
+        None => load_address.unwrap_or((0x10000 - image.len()) as u16),
+    };
+    let range = args.range.unwrap_or((org, u16::MAX));
 
-        let output_file = output_dir.join(format!("{}_ram.bin", file_name));
-        fs::write(
-            &output_file,
-            machine_bus.ram_contents()
-        ).map_err(|_| ProgramError::CouldNotWriteFile(output_file.to_str().unwrap().to_owned()))?;
+    let listing = match (args.source, &args.trace){
+        (true, Some(trace)) => {
+            let trace = fs::read_to_string(trace).map_err(|_| ProgramError::CouldNotReadFile(trace.display().to_string()))?;
+            disassembler::source(&image, org, Some(&disassembler::traced_addresses(&trace)), &symbols)
+        },
+        (true, None) => disassembler::source(&image, org, None, &symbols),
+        (false, _) => disassembler::listing(&image, org, range, &symbols),
+    };
+    match &args.output{
+        Some(output) => fs::write(output, listing).map_err(|_| ProgramError::CouldNotWriteFile(output.display().to_string())),
+        None => {
+            print!("{}", listing);
+            Ok(())
+        },
     }
+}
+
+/// Assembles a source file into an image, `<source stem>.bin` beside the source unless `--output` names another.
+/// The image spans `--origin` and `--size`, by default the ROM window, so that `run` loads it as it is.
+fn assemble_source(args: &AsmArgs) -> Result<(), ProgramError>{
+    let source = fs::read_to_string(&args.source).map_err(|_| ProgramError::CouldNotReadFile(args.source.display().to_string()))?;
 
-    //fs::write("./data/ram.bin", bus.ram_contents()).map_err(|e| Error::IO(e))?;
+    let assembly = assembler::assemble(&source).map_err(ProgramError::Assembly)?;
+    let image = assembly.windowed(args.origin, args.size as usize).ok_or(ProgramError::AssemblyOutsideImage(args.origin, args.size))?;
+    let output = args.output.clone().unwrap_or(args.source.with_extension("bin"));
+    fs::write(&output, &image).map_err(|_| ProgramError::CouldNotWriteFile(output.display().to_string()))?;
+    if let Some(listing) = &args.listing{
+        fs::write(listing, &assembly.listing).map_err(|_| ProgramError::CouldNotWriteFile(listing.display().to_string()))?;
+    }
+    if let Some(labels) = &args.labels{
+        fs::write(labels, assembly.labels()).map_err(|_| ProgramError::CouldNotWriteFile(labels.display().to_string()))?;
+    }
 
+    println!("{}: {} bytes at ${:04X}", output.display(), image.len(), args.origin);
     Ok(())
 }
+
+fn benchmark(args: &BenchArgs) -> Result<(), ProgramError>{
+    #[cfg(not(feature = "jit"))]
+    if args.jit{
+        return Err(ProgramError::FeatureNotEnabled("jit"));
+    }
+
+    let (name, mut machine) = match &args.image{
+        Some(path) => {
+            let placement = args.place_top.then_some(RomPlacement::AlignToVectors);
+            let (machine, _) = load_image(path, placement, None, None)?;
+            (path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()), machine)
+        },
+        None => {
+            let workload = assembler::assemble(bench::WORKLOAD).map_err(ProgramError::Assembly)?;
+            ("workload".to_string(), Machine::new_32k_ram_32k_rom(&workload.image).map_err(ProgramError::BusError)?)
+        },
+    };
+
+    let mut cpu = W65C02S::default();
+    cpu.reset(&mut machine).map_err(ProgramError::CpuError)?;
+    let entry = cpu.register(Register::PC);
+    #[cfg(feature = "jit")]
+    let speed = if args.jit{
+        let mut jit = Jit::new().map_err(ProgramError::CouldNotStartJit)?;
+        bench::run_jit(&mut jit, &mut cpu, &mut machine, entry, args.time)
+    }
+    else{
+        bench::run(&mut cpu, &mut machine, entry, args.time)
+    };
+    #[cfg(not(feature = "jit"))]
+    let speed = bench::run(&mut cpu, &mut machine, entry, args.time);
+    let speed = speed.map_err(ProgramError::CpuError)?;
+    println!("{}: {}", name, speed);
+    Ok(())
+}
+
+/// Runs the images as the flags say, ending the process with the status the run asks for.
+fn run(mode: Mode, args: &RunArgs, golden: Option<Golden>) -> Result<(), ProgramError>{
+    let options = parse_flags(args)?;
+    let code = session::run(mode, options, args.images.clone(), golden)?;
+    if code != 0{
+        std::process::exit(code as i32);
+    }
+    Ok(())
+}
+
+fn main(){
+    let cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet);
+    let result = match cli.command{
+        None => run(Mode::Run, &cli.run, None),
+        Some(Command::Run(args)) => run(Mode::Run, &args, None),
+        Some(Command::Debug(args)) => run(Mode::Debug, &args, None),
+        Some(Command::Test(args)) => run(Mode::Test, &args, None),
+        Some(Command::Verify(args)) => verify(&args),
+        Some(Command::Disasm(args)) => disassemble_image(&args),
+        Some(Command::Asm(args)) => assemble_source(&args),
+        Some(Command::Bench(args)) => benchmark(&args),
+    };
+
+    if let Err(e) = result{
+        match cli.errors{
+            ErrorFormat::Text => eprintln!("Error: {}", e),
+            ErrorFormat::Json => eprintln!("{}", serde_json::to_string(&ErrorReport::new(&e)).unwrap_or_default()),
+        }
+        std::process::exit(1);
+    }
+}
+
+fn verify(args: &VerifyArgs) -> Result<(), ProgramError>{
+    // rather than find out after the run
+    if !args.update && !args.against.exists(){
+        return Err(ProgramError::CouldNotReadFile(args.against.display().to_string()));
+    }
+    run(Mode::Verify, &args.run, Some(Golden { path: args.against.clone(), update: args.update }))
+}
+
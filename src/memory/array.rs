@@ -0,0 +1,14 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes fixed-size arrays, which serde only does for up to 32 elements, as sequences: use with
+/// `#[serde(with = "crate::memory::array")]` on pages, register files and the machine's tables of 256 pages.
+pub fn serialize<T: Serialize, S: Serializer, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>{
+    array.as_slice().serialize(serializer)
+}
+
+pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>{
+    let elements = Vec::<T>::deserialize(deserializer)?;
+    let length = elements.len();
+    elements.try_into().map_err(|_| D::Error::invalid_length(length, &format!("{} elements", N).as_str()))
+}
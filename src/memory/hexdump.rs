@@ -0,0 +1,40 @@
+const BYTES_PER_LINE: usize = 16;
+
+/// Formats memory as a classic listing: the address, sixteen bytes in hex split into two groups of eight, and the
+/// same bytes as ASCII with anything unprintable shown as `.`. Runs of lines identical to the one above collapse
+/// into a single `*`, and the last line holds the address just past the end.
+pub fn hexdump(bytes: &[u8], first_address: u16) -> String{
+    let mut listing = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut collapsed = false;
+
+    for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate(){
+        if previous == Some(line) && line.len() == BYTES_PER_LINE{
+            if !collapsed{
+                listing.push_str("*\n");
+                collapsed = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        collapsed = false;
+
+        let address = first_address as usize + i * BYTES_PER_LINE;
+        listing.push_str(&format!("{:04X} ", address));
+        for column in 0..BYTES_PER_LINE{
+            if column % 8 == 0{
+                listing.push(' ');
+            }
+            match line.get(column){
+                Some(byte) => listing.push_str(&format!("{:02X} ", byte)),
+                None => listing.push_str("   "),
+            }
+        }
+        listing.push_str(" |");
+        listing.extend(line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+        listing.push_str("|\n");
+    }
+
+    listing.push_str(&format!("{:04X}\n", first_address as usize + bytes.len()));
+    listing
+}
@@ -1,9 +1,29 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AccessError{
     OutOfRange(usize),
 }
+impl fmt::Display for AccessError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            AccessError::OutOfRange(idx) => write!(f, "index {:#X} is out of range", idx),
+        }
+    }
+}
+impl Error for AccessError{ }
 
 pub trait Indexed{
     fn len(&self) -> usize;
+    fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
 }
 pub trait ReadableBuffer: Indexed{
     fn peek(&self, idx: usize) -> Result<u8, AccessError>;
@@ -15,7 +35,10 @@ pub trait WritableBuffer: Indexed{
 
 
 /// A chunk of memory of a fixed size, 256 bytes.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MemoryPage{
+    #[cfg_attr(feature = "serde", serde(with = "crate::memory::array"))]
     buffer: [u8; 256]
 }
 impl MemoryPage{
@@ -51,6 +74,11 @@ impl MemoryPage{
         &self.buffer
     }
 }
+impl Default for MemoryPage{
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Indexed for MemoryPage{
     fn len(&self) -> usize {
         Self::SIZE
@@ -82,18 +110,38 @@ impl WritableBuffer for MemoryPage{
     }
 }
 
+/// Pages are shared with any snapshots taken of the segment and only copied when first written afterwards.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RAMSegment{
-    pages: Vec<MemoryPage>,
+    pages: Vec<Arc<MemoryPage>>,
     size_bytes: usize
 }
 impl RAMSegment{
     pub fn new(num_pages: usize) -> Self{
         Self { 
-            pages: (0..num_pages).map(|_| MemoryPage::new()).collect(), 
+            pages: (0..num_pages).map(|_| Arc::new(MemoryPage::new())).collect(), 
             size_bytes: MemoryPage::SIZE * num_pages 
         }
     }
 
+    /// Captures the current contents without copying any page data.
+    pub fn snapshot(&self) -> RAMSnapshot{
+        RAMSnapshot { pages: self.pages.clone() }
+    }
+    /// Rolls back to a snapshot of this segment, only touching pages written since it was taken.
+    pub fn restore(&mut self, snapshot: &RAMSnapshot){
+        for (page, saved) in self.pages.iter_mut().zip(snapshot.pages.iter()){
+            if !Arc::ptr_eq(page, saved){
+                *page = Arc::clone(saved);
+            }
+        }
+    }
+    /// Number of pages that differ from the snapshot.
+    pub fn dirty_pages(&self, snapshot: &RAMSnapshot) -> usize{
+        self.pages.iter().zip(snapshot.pages.iter()).filter(|(page, saved)| !Arc::ptr_eq(page, saved)).count()
+    }
+
     fn idx_split(global_idx: usize) -> (usize, u8){
         let page_index: usize = global_idx >> 8;
         let offset: u8 = (global_idx & 0xff) as u8;
@@ -110,26 +158,30 @@ impl RAMSegment{
     }
 
     pub fn read_page_offset(&mut self, page: usize, offset: u8) -> u8{
-        self.pages[page].read_unchecked(offset)
+        self.pages[page].peek_unchecked(offset)
     }
     pub fn peek_page_offset(&self, page: usize, offset: u8) -> u8{
         self.pages[page].peek_unchecked(offset)
     }
 
     pub fn write_page_offset(&mut self, page: usize, offset: u8, val: u8) {
-        self.pages[page].write_unchecked(offset, val);
+        Arc::make_mut(&mut self.pages[page]).write_unchecked(offset, val);
     }
 
-    pub fn load(&mut self, bytes: &[u8]) {
+    pub fn load(&mut self, bytes: &[u8]) -> Result<(), AccessError>{
+        if bytes.len() > self.size_bytes{
+            return Err(AccessError::OutOfRange(self.size_bytes));
+        }
+
         let mut i = 0usize;
         for byte in bytes{
-            if i > self.size_bytes {break;}
-
             let page = i >> 8;
             let offset = (i & 0xff) as u8;
-            self.pages[page].write_unchecked(offset, *byte);
+            Arc::make_mut(&mut self.pages[page]).write_unchecked(offset, *byte);
             i += 1;
         }
+
+        Ok(())
     }
     pub fn contents(&self) -> Box<[u8]>{
         let mut contents: Vec<u8> = Vec::new();
@@ -155,18 +207,27 @@ impl ReadableBuffer for RAMSegment{
     fn read(&mut self, idx: usize) -> Result<u8, AccessError> {
         let (page, offset) = self.check_idx(idx)?;
 
-        Ok(self.pages[page].read_unchecked(offset))
+        Ok(self.pages[page].peek_unchecked(offset))
     }
 }
 impl WritableBuffer for RAMSegment{
     fn write(&mut self, idx: usize, val: u8) -> Result<(), AccessError> {
         let (page, offset) = self.check_idx(idx)?;
 
-        self.pages[page].write_unchecked(offset, val);
+        Arc::make_mut(&mut self.pages[page]).write_unchecked(offset, val);
         Ok(())
     }
 }
 
+/// Saved state of a `RAMSegment`, sharing its pages with the segment until either side is written.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RAMSnapshot{
+    pages: Vec<Arc<MemoryPage>>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ROMSegment{
     pages: Vec<MemoryPage>,
     size_bytes: usize
@@ -216,6 +277,11 @@ impl ROMSegment{
     pub fn peek_page_offset(&self, page: usize, offset: u8) -> u8{
         self.pages[page].peek_unchecked(offset)
     }
+
+    /// Rewrites a byte of the image, as an external programmer would. Not reachable from the bus.
+    pub fn program_page_offset(&mut self, page: usize, offset: u8, val: u8){
+        self.pages[page].write_unchecked(offset, val);
+    }
 }
 impl Indexed for ROMSegment{
     fn len(&self) -> usize {
@@ -1 +1,5 @@
-pub mod memory;
\ No newline at end of file
+pub mod memory;
+pub mod hexdump;
+pub mod records;
+#[cfg(feature = "serde")]
+pub mod array;
\ No newline at end of file
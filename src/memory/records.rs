@@ -0,0 +1,52 @@
+use std::fmt::Write as _;
+
+const BYTES_PER_RECORD: usize = 16;
+
+/// Formats memory as Intel HEX, as EPROM programmers read it: data records of sixteen bytes from
+/// `first_address` on, then the end-of-file record. Each record ends with the two's complement of the sum of
+/// its bytes.
+pub fn intel_hex(bytes: &[u8], first_address: u16) -> String{
+    let mut records = String::new();
+    for (i, data) in bytes.chunks(BYTES_PER_RECORD).enumerate(){
+        let address = first_address.wrapping_add((i * BYTES_PER_RECORD) as u16);
+        push_record(&mut records, address, 0x00, data);
+    }
+    push_record(&mut records, 0, 0x01, &[]);
+    records
+}
+
+/// Formats memory as Motorola S-records: an S0 header, S1 records of sixteen bytes from `first_address` on,
+/// and an S9 record that ends the file and gives `first_address` as the start. Each record ends with the ones'
+/// complement of the sum of its count, address and data.
+pub fn s_records(bytes: &[u8], first_address: u16) -> String{
+    let mut records = String::new();
+    push_s_record(&mut records, "S0", 0, b"Steel6502");
+    for (i, data) in bytes.chunks(BYTES_PER_RECORD).enumerate(){
+        push_s_record(&mut records, "S1", first_address.wrapping_add((i * BYTES_PER_RECORD) as u16), data);
+    }
+    push_s_record(&mut records, "S9", first_address, &[]);
+    records
+}
+
+/// An S-record counts the address and checksum along with the data, and has no type byte.
+fn push_s_record(records: &mut String, kind: &str, address: u16, data: &[u8]){
+    let count = (data.len() + 3) as u8;
+    let [high, low] = address.to_be_bytes();
+    let sum = [count, high, low].iter().chain(data).fold(0u8, |sum, &b| sum.wrapping_add(b));
+    let _ = write!(records, "{}{:02X}{:04X}", kind, count, address);
+    for byte in data{
+        let _ = write!(records, "{:02X}", byte);
+    }
+    let _ = writeln!(records, "{:02X}", !sum);
+}
+
+/// An Intel HEX record: the byte count, the address, the type, the data and the checksum.
+fn push_record(records: &mut String, address: u16, kind: u8, data: &[u8]){
+    let [high, low] = address.to_be_bytes();
+    let sum = [data.len() as u8, high, low, kind].iter().chain(data).fold(0u8, |sum, &b| sum.wrapping_add(b));
+    let _ = write!(records, ":{:02X}{:04X}{:02X}", data.len(), address, kind);
+    for byte in data{
+        let _ = write!(records, "{:02X}", byte);
+    }
+    let _ = writeln!(records, "{:02X}", sum.wrapping_neg());
+}
@@ -0,0 +1,19 @@
+//! What building and running a machine takes, in one `use`:
+//!
+//! ```no_run
+//! use steel6502::prelude::*;
+//!
+//! let image = std::fs::read("rom.bin").unwrap();
+//! let mut machine = Machine::new_32k_ram_32k_rom(&image).unwrap();
+//! let mut cpu = W65C02S::default();
+//! cpu.reset(&mut machine).unwrap();
+//! while !matches!(cpu.step(&mut machine), Ok(Mnemonic::BRK) | Err(_)){ }
+//! ```
+
+pub use crate::bus::bus::{Bus, BusError, GuestMemory, Machine, RomPlacement};
+pub use crate::bus::static_machine::StaticMachine;
+pub use crate::cpu::emulator::{EmulatorHandle, Event};
+pub use crate::cpu::report::StopReason;
+pub use crate::cpu::save_state::SaveState;
+pub use crate::cpu::w65c02s::{CpuError, Mnemonic, Register, W65C02S};
+pub use crate::devices::device::{Device, DeviceId, Port, PortPeripheral};
@@ -0,0 +1,185 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::bus::board::BoardError;
+use crate::bus::bus::{BusError, RomImageError};
+use crate::bus::machine_config::MachineConfigError;
+use crate::cpu::assembler::AssemblyError;
+use crate::cpu::save_state::RestoreError;
+use crate::cpu::w65c02s::CpuError;
+
+/// Why a run of the command line, or any of its subcommands, stopped short.
+#[derive(Debug)]
+pub enum ProgramError{
+    OutputPathIsNotDirectory(String),
+    CouldNotLocateFile(String),
+    CouldNotReadFile(String),
+    CouldNotWriteFile(String),
+    CpuError(CpuError),
+    CpuErrorAt(u16, CpuError),             // stopped the run, in the instruction at the address
+    RomImageError(RomImageError),
+    BusError(BusError),
+    InvalidAddress(String),
+    CouldNotOpenTerminal(String),
+    CouldNotListen(String),
+    InvalidWatch(String),
+    InvalidConsole(String),
+    #[cfg(feature = "video")]
+    CouldNotOpenWindow(String),
+    #[cfg(feature = "jit")]
+    CouldNotStartJit(String),
+    #[cfg(feature = "video")]
+    InvalidVideoMode(String),
+    InvalidClock(String),
+    InvalidTemplate(String),
+    MachineConfig(MachineConfigError),
+    Restore(RestoreError),                 // the save state a run resumes from
+    Assembly(AssemblyError),
+    AssemblyOutsideImage(u16, u32),        // the source writes outside the image of this origin and size
+    #[cfg(feature = "scripting")]
+    Script(String),
+    #[cfg(not(all(feature = "video", feature = "scripting", feature = "remote", feature = "jit")))]
+    FeatureNotEnabled(&'static str),
+    #[cfg(not(unix))]
+    UnsupportedOnPlatform(&'static str),
+    ConflictingFlags(&'static str, &'static str),
+    MalformedRomFile,
+}
+impl fmt::Display for ProgramError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            ProgramError::OutputPathIsNotDirectory(path) => write!(f, "{} is not a directory", path),
+            ProgramError::CouldNotLocateFile(path) => write!(f, "could not find {}", path),
+            ProgramError::CouldNotReadFile(path) => write!(f, "could not read {}", path),
+            ProgramError::CouldNotWriteFile(path) => write!(f, "could not write {}", path),
+            ProgramError::CpuError(e) => write!(f, "{}", e),
+            ProgramError::CpuErrorAt(pc, e) => write!(f, "{} in the instruction at {:04X}", e, pc),
+            ProgramError::RomImageError(e) => write!(f, "{}", e),
+            ProgramError::BusError(e) => write!(f, "{}", e),
+            ProgramError::InvalidAddress(text) => write!(f, "invalid address {}", text),
+            ProgramError::CouldNotOpenTerminal(e) => write!(f, "could not open a terminal: {}", e),
+            ProgramError::CouldNotListen(e) => write!(f, "could not listen on {}", e),
+            ProgramError::InvalidWatch(text) => write!(f, "invalid watch {}", text),
+            ProgramError::InvalidConsole(text) => write!(f, "invalid console {}", text),
+            #[cfg(feature = "video")]
+            ProgramError::CouldNotOpenWindow(e) => write!(f, "could not open a window: {}", e),
+            #[cfg(feature = "jit")]
+            ProgramError::CouldNotStartJit(e) => write!(f, "could not start the JIT: {}", e),
+            #[cfg(feature = "video")]
+            ProgramError::InvalidVideoMode(text) => write!(f, "invalid video mode {}", text),
+            ProgramError::InvalidClock(text) => write!(f, "invalid clock {}", text),
+            ProgramError::InvalidTemplate(text) => write!(f, "invalid output name template {}", text),
+            ProgramError::MachineConfig(e) => write!(f, "{}", e),
+            ProgramError::Restore(e) => write!(f, "could not resume: {}", e),
+            ProgramError::Assembly(e) => write!(f, "{}", e),
+            ProgramError::AssemblyOutsideImage(origin, size) => write!(f, "the source writes outside the {} bytes from ${:04X}", size, origin),
+            #[cfg(feature = "scripting")]
+            ProgramError::Script(e) => write!(f, "{}", e),
+            #[cfg(not(all(feature = "video", feature = "scripting", feature = "remote", feature = "jit")))]
+            ProgramError::FeatureNotEnabled(feature) => write!(f, "built without the {} feature", feature),
+            #[cfg(not(unix))]
+            ProgramError::UnsupportedOnPlatform(flag) => write!(f, "{} is not supported on this platform", flag),
+            ProgramError::ConflictingFlags(a, b) => write!(f, "{} cannot be used with {}", a, b),
+            ProgramError::MalformedRomFile => write!(f, "malformed image"),
+        }
+    }
+}
+
+impl Error for ProgramError{}
+
+impl ProgramError{
+    /// The variant in kebab case, as `ErrorReport` names it.
+    pub fn kind(&self) -> &'static str{
+        match self{
+            ProgramError::OutputPathIsNotDirectory(_) => "output-path-is-not-directory",
+            ProgramError::CouldNotLocateFile(_) => "could-not-locate-file",
+            ProgramError::CouldNotReadFile(_) => "could-not-read-file",
+            ProgramError::CouldNotWriteFile(_) => "could-not-write-file",
+            ProgramError::CpuError(_) | ProgramError::CpuErrorAt(..) => "cpu-error",
+            ProgramError::RomImageError(_) => "rom-image-error",
+            ProgramError::BusError(_) => "bus-error",
+            ProgramError::InvalidAddress(_) => "invalid-address",
+            ProgramError::CouldNotOpenTerminal(_) => "could-not-open-terminal",
+            ProgramError::CouldNotListen(_) => "could-not-listen",
+            ProgramError::InvalidWatch(_) => "invalid-watch",
+            ProgramError::InvalidConsole(_) => "invalid-console",
+            #[cfg(feature = "video")]
+            ProgramError::CouldNotOpenWindow(_) => "could-not-open-window",
+            #[cfg(feature = "jit")]
+            ProgramError::CouldNotStartJit(_) => "could-not-start-jit",
+            #[cfg(feature = "video")]
+            ProgramError::InvalidVideoMode(_) => "invalid-video-mode",
+            ProgramError::InvalidClock(_) => "invalid-clock",
+            ProgramError::InvalidTemplate(_) => "invalid-template",
+            ProgramError::MachineConfig(_) => "machine-config",
+            ProgramError::Restore(_) => "restore",
+            ProgramError::Assembly(_) => "assembly",
+            ProgramError::AssemblyOutsideImage(..) => "assembly-outside-image",
+            #[cfg(feature = "scripting")]
+            ProgramError::Script(_) => "script",
+            #[cfg(not(all(feature = "video", feature = "scripting", feature = "remote", feature = "jit")))]
+            ProgramError::FeatureNotEnabled(_) => "feature-not-enabled",
+            #[cfg(not(unix))]
+            ProgramError::UnsupportedOnPlatform(_) => "unsupported-on-platform",
+            ProgramError::ConflictingFlags(..) => "conflicting-flags",
+            ProgramError::MalformedRomFile => "malformed-rom-file",
+        }
+    }
+}
+
+impl From<BoardError> for ProgramError{
+    fn from(e: BoardError) -> Self {
+        match e{
+            BoardError::MissingFile(path) => ProgramError::CouldNotLocateFile(path.display().to_string()),
+            BoardError::CouldNotRead(path) => ProgramError::CouldNotReadFile(path.display().to_string()),
+            BoardError::CouldNotWrite(path) => ProgramError::CouldNotWriteFile(path.display().to_string()),
+            BoardError::MalformedImage => ProgramError::MalformedRomFile,
+            BoardError::RomImage(e) => ProgramError::RomImageError(e),
+            BoardError::Bus(e) => ProgramError::BusError(e),
+            BoardError::CouldNotListen(e) => ProgramError::CouldNotListen(e),
+            BoardError::CouldNotOpenTerminal(e) => ProgramError::CouldNotOpenTerminal(e),
+            #[cfg(feature = "video")]
+            BoardError::CouldNotOpenWindow(e) => ProgramError::CouldNotOpenWindow(e),
+        }
+    }
+}
+
+/// An error as `--errors json` writes it, for tools wrapping the emulator: the variant in kebab case, the
+/// message and whatever of a file, PC, address or source line it concerns.
+#[derive(Serialize)]
+pub struct ErrorReport{
+    error: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pc: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+}
+impl ErrorReport{
+    pub fn new(e: &ProgramError) -> Self{
+        let bus = |e: &CpuError| match e{
+            CpuError::Bus(e) => e.address(),
+            CpuError::ExecuteFromNoExecute(address) => Some(*address),
+            _ => None,
+        };
+        let (path, pc, address, line) = match e{
+            ProgramError::OutputPathIsNotDirectory(path) | ProgramError::CouldNotLocateFile(path)
+                | ProgramError::CouldNotReadFile(path) | ProgramError::CouldNotWriteFile(path) => (Some(path.clone()), None, None, None),
+            ProgramError::CpuError(e) => (None, None, bus(e), None),
+            ProgramError::CpuErrorAt(pc, e) => (None, Some(*pc), bus(e), None),
+            ProgramError::BusError(e) | ProgramError::RomImageError(RomImageError::Bus(e))
+                | ProgramError::MachineConfig(MachineConfigError::Bus(e))
+                | ProgramError::Restore(RestoreError::Bus(e)) => (None, None, e.address(), None),
+            ProgramError::MachineConfig(MachineConfigError::Io(path, _) | MachineConfigError::RomTooLarge(path)) => (Some(path.display().to_string()), None, None, None),
+            ProgramError::Assembly(e) => (None, None, None, Some(e.line)),
+            _ => (None, None, None, None),
+        };
+        Self { error: e.kind(), message: e.to_string(), path, pc, address, line }
+    }
+}
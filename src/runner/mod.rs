@@ -0,0 +1,4 @@
+pub mod error;
+pub mod options;
+mod output;
+pub mod session;
@@ -0,0 +1,342 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bus::board::{DeviceOptions, DiskOptions, HostServicesOptions};
+use crate::bus::bus::RomPlacement;
+use crate::bus::machine_config::MachineConfig;
+use crate::cpu::limits::RunLimits;
+use crate::cpu::save_state::AutosaveInterval;
+use crate::cpu::symbols::{parse_address, parse_location, SymbolTable};
+use crate::cpu::w65c02s::{InvalidOpcodePolicy, Register, W65C02S};
+use crate::cpu::watch::WatchTarget;
+use crate::devices::disk_controller::DiskGeometry;
+#[cfg(feature = "video")]
+use crate::devices::framebuffer::FramebufferMode;
+use super::error::ProgramError;
+
+/// How the images are run, after the subcommand.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Mode{
+    Run,
+    Debug,      // under the debugger
+    Test,       // to a pass or a failure each
+    Verify,     // to a pass or a failure each, by the final state
+}
+
+/// The golden state `verify` checks against, or writes with `--update`.
+pub struct Golden{
+    pub path: PathBuf,
+    pub update: bool,
+}
+
+/// Everything the command line sets for a run, parsed and checked.
+pub struct Options{
+    pub output_dir: PathBuf,
+    pub machine: Option<PathBuf>,          // configuration file describing the board, in place of image files
+    pub placement: Option<RomPlacement>,   // Some when the input is a bare ROM image rather than a 64KB memory image
+    pub entry: Option<u16>,                // where execution begins instead of the reset vector
+    pub devices: DeviceOptions,
+    pub link: Option<PathBuf>,             // image of a second machine whose ACIA is cabled to this one's
+    pub result: Option<ResultOptions>,
+    pub limits: RunLimits,
+    pub clock: Option<u64>,                // frequency in Hz execution is paced to, None to run flat out
+    pub unlimited: bool,                   // run flat out even with a clock given
+    pub resume: Option<PathBuf>,           // save state the run picks up from
+    pub seed: Option<u64>,                 // for everything random in the run
+    pub random_ram: bool,                  // RAM powers up filled from the seed
+    pub ram_image: Option<(PathBuf, u16)>,  // loaded into RAM from the offset before the reset
+    pub invalid_opcodes: InvalidOpcodePolicy,
+    pub autosave: Option<AutosaveInterval>,
+    pub halt_addresses: Vec<u16>,          // reaching any of these ends the run
+    pub input_log: Option<InputLogFile>,
+    pub fingerprints: Option<FingerprintFile>,
+    pub dump: DumpOptions,
+    pub report: bool,                      // write the final state as JSON
+    pub profile: Option<PathBuf>,          // where the report of the hot spots goes
+    pub coverage: bool,                    // write which ROM addresses ran
+    pub stats: Option<StatsMode>,          // how the speed of the run is shown
+    #[cfg(feature = "scripting")]
+    pub script: Option<PathBuf>,           // Rhai script with callbacks into the run
+    #[cfg(feature = "remote")]
+    pub remote: Option<String>,            // host address the remote control listens on
+    pub compare: Option<(PathBuf, Option<(u16, u16)>)>,    // golden file final RAM must match, and the range it covers
+    pub symbols: SymbolTable,              // names for addresses, shown in the trace
+    pub watch: Option<WatchOptions>,
+    pub trace: Option<TraceOptions>,
+}
+
+pub struct ResultOptions{
+    pub address: u16,
+    pub pass: u8,
+    pub fail: u8,
+    pub error_address: Option<u16>,
+}
+
+pub struct WatchOptions{
+    pub watches: Vec<(String, WatchTarget)>,   // as written on the command line, and what it names
+    pub every: Option<u64>,                    // instructions between printouts, rather than on change
+}
+
+pub struct TraceOptions{
+    pub file: PathBuf,
+    pub start: Option<u16>,                // address tracing begins at
+    pub stop: Option<u16>,                 // and ends at
+    pub format: TraceFormat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TraceFormat{
+    Steel,
+    Vice,       // as VICE's monitor lays it out
+}
+
+/// How the speed of the run is shown.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum StatsMode{
+    Summary,    // at the end of each image
+    Live,       // on a status line every second as well
+}
+
+/// Where the bytes the host sends the machine's inputs are recorded to, or replayed from in their place.
+pub enum InputLogFile{
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// Where the fingerprints of the instructions are recorded to, or checked against.
+pub enum FingerprintFile{
+    Record(PathBuf),
+    Check(PathBuf),
+}
+
+#[derive(Copy, Clone)]
+pub enum DumpFormat{
+    Raw,        // the bytes as they are, in `_ram.bin`
+    Hexdump,    // an address, hex and ASCII listing, in `_ram.txt`
+    Ihex,       // Intel HEX records, in `_ram.hex`
+    Srec,       // Motorola S-records, in `_ram.srec`
+}
+
+/// A range of RAM dumped again and again while the image runs, to numbered files.
+#[derive(Copy, Clone)]
+pub struct DumpEvery{
+    pub interval: DumpInterval,
+    pub range: (u16, u16),                 // first and last RAM address
+}
+
+#[derive(Copy, Clone)]
+pub enum DumpInterval{
+    Cycles(u64),
+    Instructions(u64),
+}
+impl DumpInterval{
+    /// How far the CPU has run, in the interval's unit.
+    pub fn count(&self, cpu: &W65C02S) -> u64{
+        match self{
+            DumpInterval::Cycles(_) => cpu.cycles(),
+            DumpInterval::Instructions(_) => cpu.instructions(),
+        }
+    }
+    pub fn length(&self) -> u64{
+        match self{
+            DumpInterval::Cycles(every) | DumpInterval::Instructions(every) => *every,
+        }
+    }
+}
+
+pub struct DumpOptions{
+    pub format: DumpFormat,
+    pub range: Option<(u16, u16)>,         // first and last RAM address to dump, rather than all of it
+    pub every: Option<DumpEvery>,          // also dumped during the run
+    pub template: String,                  // the path of a dump in the output directory, with its placeholders
+    pub date: String,                      // for `{date}`, when the run started
+    pub state: bool,                       // a save state as well
+}
+
+/// Accepts `<start>-<end>`, both ends included and given as `parse_address` accepts them.
+pub fn parse_range(text: &str) -> Option<(u16, u16)>{
+    let (first, last) = text.split_once('-')?;
+    Some((parse_address(first)?, parse_address(last)?)).filter(|(first, last)| first <= last)
+}
+/// Accepts `$ff`, `0xff` and plain `ff`.
+pub fn parse_byte(text: &str) -> Option<u8>{
+    let digits = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+    u8::from_str_radix(digits, 16).ok()
+}
+/// Accepts `1MHz`, `1.8432mhz`, `500kHz`, `2M` and plain `1000000`, in Hz.
+pub fn parse_frequency(text: &str) -> Option<u64>{
+    let lower = text.to_ascii_lowercase();
+    let number = lower.strip_suffix("hz").unwrap_or(&lower);
+    let (number, scale) = match number.strip_suffix('m'){
+        Some(number) => (number, 1_000_000.0),
+        None => match number.strip_suffix('k'){
+            Some(number) => (number, 1_000.0),
+            None => (number, 1.0),
+        },
+    };
+    let hz = (number.parse::<f64>().ok()? * scale).round();
+    (hz >= 1.0 && hz <= u64::MAX as f64).then_some(hz as u64)
+}
+/// A length of time such as `30s`, `2.5s` or `500ms`.
+pub fn parse_duration(text: &str) -> Option<Duration>{
+    if let Some(ms) = text.strip_suffix("ms"){
+        Some(Duration::from_millis(ms.parse().ok()?))
+    } else {
+        let seconds = text.strip_suffix('s')?.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0)?;
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+}
+/// Accepts wall time as `5s` or `500ms`, and cycles as `20M` for millions or a plain `100000`.
+pub fn parse_autosave_interval(text: &str) -> Option<AutosaveInterval>{
+    let interval = if text.ends_with('s'){
+        AutosaveInterval::Wall(parse_duration(text)?)
+    } else if let Some(millions) = text.strip_suffix('M'){
+        AutosaveInterval::Cycles((millions.parse::<f64>().ok().filter(|m| m.is_finite() && *m >= 0.0)? * 1_000_000.0) as u64)
+    } else {
+        AutosaveInterval::Cycles(text.parse().ok()?)
+    };
+    let nonzero = match interval{
+        AutosaveInterval::Wall(every) => !every.is_zero(),
+        AutosaveInterval::Cycles(every) => every > 0,
+    };
+    nonzero.then_some(interval)
+}
+
+/// A register (`A`, `X`, `Y`, `SP`, `P`, `PC`), a byte of memory, or a word of memory written `word:<addr>`.
+/// Register names win over addresses that look the same.
+pub fn parse_watch_target(text: &str, symbols: &SymbolTable) -> Option<WatchTarget>{
+    let register = match text.to_ascii_uppercase().as_str(){
+        "A" => Some(Register::A),
+        "X" => Some(Register::X),
+        "Y" => Some(Register::Y),
+        "SP" => Some(Register::SP),
+        "P" => Some(Register::P),
+        "PC" => Some(Register::PC),
+        _ => None,
+    };
+    if let Some(register) = register{
+        return Some(WatchTarget::Register(register));
+    }
+
+    match text.strip_prefix("word:"){
+        Some(address) => parse_location(address, symbols).map(WatchTarget::Word),
+        None => parse_location(text.strip_prefix("byte:").unwrap_or(text), symbols).map(WatchTarget::Byte),
+    }
+}
+
+/// `<tracks>x<sectors>x<sector size>`.
+pub fn parse_disk_geometry(text: &str) -> Option<DiskGeometry>{
+    let (tracks, rest) = text.split_once('x')?;
+    let (sectors, sector_size) = rest.split_once('x')?;
+    Some(DiskGeometry {
+        tracks: tracks.parse().ok().filter(|t| (1..=256).contains(t))?,
+        sectors: sectors.parse().ok().filter(|s| (1..=256).contains(s))?,
+        sector_size: sector_size.parse().ok().filter(|s| *s > 0)?,
+    })
+}
+
+/// `<expected file>[:<start>-<end>]`
+pub fn parse_compare(text: &str) -> (PathBuf, Option<(u16, u16)>){
+    // only a suffix that reads as a range is one, so paths with colons in them still work
+    match text.rsplit_once(':').and_then(|(path, range)| Some((path, parse_range(range)?))){
+        Some((path, range)) => (PathBuf::from(path), Some(range)),
+        None => (PathBuf::from(text), None),
+    }
+}
+
+/// `<interval>:<start>-<end>`, where the interval is cycles such as `1000000` or `20M`, or instructions such
+/// as `50000i`.
+pub fn parse_dump_every(text: &str) -> Option<DumpEvery>{
+    let (interval, range) = text.split_once(':')?;
+    let interval = if let Some(instructions) = interval.strip_suffix('i'){
+        DumpInterval::Instructions(instructions.parse().ok()?)
+    } else if let Some(millions) = interval.strip_suffix('M'){
+        DumpInterval::Cycles((millions.parse::<f64>().ok().filter(|m| m.is_finite() && *m >= 0.0)? * 1_000_000.0) as u64)
+    } else {
+        DumpInterval::Cycles(interval.parse().ok()?)
+    };
+    (interval.length() > 0).then_some(DumpEvery { interval, range: parse_range(range)? })
+}
+
+/// An output name template, checked for placeholders that mean nothing.
+pub fn parse_template(template: &str) -> Result<String, ProgramError>{
+    let rest = TEMPLATE_PLACEHOLDERS.iter().fold(template.to_string(), |rest, placeholder| rest.replace(placeholder, ""));
+    match rest.contains(['{', '}']) || rest.is_empty(){
+        true => Err(ProgramError::InvalidTemplate(template.to_string())),
+        false => Ok(template.to_string()),
+    }
+}
+const TEMPLATE_PLACEHOLDERS: [&str; 4] = ["{name}", "{kind}", "{ext}", "{date}"];
+
+/// The time as `YYYYMMDD-HHMMSS` in UTC, which sorts as it reads.
+pub fn utc_timestamp(time: SystemTime) -> String{
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (days, second_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // the proleptic Gregorian date of a day count, by eras of 400 years
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;         // counted from March
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, second_of_day / 3600, second_of_day / 60 % 60, second_of_day % 60)
+}
+
+/// `<file>[:<offset>]`
+pub fn parse_ram_image(text: &str) -> (PathBuf, u16){
+    // only a suffix that reads as an address is one, as with --compare
+    match text.rsplit_once(':').and_then(|(path, offset)| Some((path, parse_address(offset)?))){
+        Some((path, offset)) => (PathBuf::from(path), offset),
+        None => (PathBuf::from(text), 0),
+    }
+}
+
+/// `<x>x<y>`, both nonzero.
+pub fn parse_size(text: &str) -> Option<(usize, usize)>{
+    let (x, y) = text.split_once('x')?;
+    Some((x.parse().ok().filter(|x| *x > 0)?, y.parse().ok().filter(|y| *y > 0)?))
+}
+
+/// `text:<columns>x<rows>` or `bitmap:<width>x<height>`.
+#[cfg(feature = "video")]
+pub fn parse_video_mode(text: &str) -> Option<FramebufferMode>{
+    let (kind, size) = text.split_once(':')?;
+    let (x, y) = parse_size(size)?;
+
+    match kind{
+        "text" => Some(FramebufferMode::Text { columns: x, rows: y }),
+        "bitmap" => Some(FramebufferMode::Bitmap { width: x, height: y }),
+        _ => None,
+    }
+}
+
+/// Fills in what the configuration file sets and the command line does not.
+pub fn apply_machine_config(options: &mut Options, config: &MachineConfig) -> Result<(), ProgramError>{
+    if options.clock.is_none() && let Some(frequency) = &config.clock{
+        options.clock = Some(parse_frequency(frequency).ok_or(ProgramError::InvalidClock(frequency.clone()))?);
+    }
+    options.entry = options.entry.or(config.entry);
+    options.halt_addresses.extend(&config.halt);
+
+    let (devices, configured) = (&mut options.devices, &config.devices);
+    devices.char_out = devices.char_out.or(configured.char_out);
+    devices.char_in = devices.char_in.or(configured.char_in);
+    devices.keyboard = devices.keyboard.or(configured.keyboard);
+    devices.ps2_keyboard = devices.ps2_keyboard.or(configured.ps2_keyboard);
+    devices.irq_controller = devices.irq_controller.or(configured.irq_controller);
+    devices.cycle_counter = devices.cycle_counter.or(configured.cycle_counter);
+    devices.rng = devices.rng.or(configured.rng);
+    if devices.host_services.is_none() && let Some(address) = configured.host_services{
+        devices.host_services = Some(HostServicesOptions { address, block_file: None, file_directory: None });
+    }
+    if devices.disks.is_none() && let Some(address) = configured.disk_controller{
+        devices.disks = Some(DiskOptions { images: [None, None], geometry: DiskGeometry::DEFAULT, address });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::bus::bus::{Machine, MemoryDifference};
+use crate::bus::result_watch::{ResultWatch, Verdict};
+use crate::cpu::report::StopReason;
+use crate::cpu::save_state::StateDifference;
+use crate::memory::hexdump::hexdump;
+use crate::memory::records::{intel_hex, s_records};
+use super::error::ProgramError;
+use super::options::{DumpFormat, Options};
+
+/// The part of RAM a range covers, all of it without one, and the address it starts at. A range running past the
+/// end of RAM is cut short.
+pub(crate) fn ram_range(ram: &[u8], range: Option<(u16, u16)>) -> (u16, &[u8]){
+    let (first, last) = range.unwrap_or((0, u16::MAX));
+    let end = (last as usize + 1).min(ram.len());
+    (first, ram.get(first as usize..end).unwrap_or(&[]))
+}
+
+/// Where the dump of `kind`, such as `ram`, of the image `name` goes: the output directory and the template.
+/// Directories the template names are made.
+pub(crate) fn dump_path(options: &Options, name: &str, kind: &str, ext: &str) -> Result<PathBuf, ProgramError>{
+    let file = options.dump.template.replace("{name}", name).replace("{kind}", kind).replace("{ext}", ext).replace("{date}", &options.dump.date);
+    let path = options.output_dir.join(file);
+    if let Some(parent) = path.parent(){
+        fs::create_dir_all(parent).map_err(|_| ProgramError::CouldNotWriteFile(path.display().to_string()))?;
+    }
+    Ok(path)
+}
+
+/// Writes the RAM dump of `kind` of the image `name` to the output directory, in the chosen format, of the range
+/// or else all of RAM.
+pub(crate) fn write_dump(options: &Options, name: &str, kind: &str, ram: &[u8], range: Option<(u16, u16)>) -> Result<(), ProgramError>{
+    let (first, bytes) = ram_range(ram, range);
+
+    let (ext, contents) = match options.dump.format{
+        DumpFormat::Raw => ("bin", bytes.to_vec()),
+        DumpFormat::Hexdump => ("txt", hexdump(bytes, first).into_bytes()),
+        DumpFormat::Ihex => ("hex", intel_hex(bytes, first).into_bytes()),
+        DumpFormat::Srec => ("srec", s_records(bytes, first).into_bytes()),
+    };
+    let output_file = dump_path(options, name, kind, ext)?;
+    fs::write(&output_file, contents).map_err(|_| ProgramError::CouldNotWriteFile(output_file.display().to_string()))
+}
+
+/// How one image of a batch went, for the table at the end.
+pub(crate) struct ImageSummary{
+    pub(crate) name: String,
+    pub(crate) stop: StopReason,
+    pub(crate) instructions: u64,
+    pub(crate) cycles: u64,
+    pub(crate) time: Duration,         // wall-clock, from the first instruction to the stop
+    pub(crate) pass: Option<bool>,     // by the test or `--compare`, when there is one to go by
+}
+
+/// Prints a line for each image of a batch, under a header.
+pub(crate) fn print_summary(summary: &[ImageSummary]){
+    let stops = summary.iter().map(|image| image.stop.to_string()).collect::<Vec<String>>();
+    let name_width = summary.iter().map(|image| image.name.len()).chain([5]).max().unwrap_or_default();
+    let stop_width = stops.iter().map(String::len).chain([4]).max().unwrap_or_default();
+
+    println!();
+    println!("{:<name_width$}  {:<stop_width$}  {:>13}  {:>13}  {:>9}  RESULT", "IMAGE", "STOP", "INSTRUCTIONS", "CYCLES", "TIME");
+    for (image, stop) in summary.iter().zip(&stops){
+        let pass = match image.pass{
+            Some(true) => "PASS",
+            Some(false) => "FAIL",
+            None => "-",
+        };
+        println!("{:<name_width$}  {:<stop_width$}  {:>13}  {:>13}  {:>8.3}s  {}",
+            image.name, stop, image.instructions, image.cycles, image.time.as_secs_f64(), pass);
+    }
+}
+
+/// Compares RAM, or the range of it, with the expected bytes and prints the result. True when they match.
+pub(crate) fn compare_ram(file_name: &str, ram: &[u8], range: Option<(u16, u16)>, expected: &[u8]) -> bool{
+    const MAX_LISTED: usize = 32;
+
+    let (first, actual) = ram_range(ram, range);
+    if actual.len() != expected.len(){
+        println!("{}: RAM compared is {} bytes, expected {}", file_name, actual.len(), expected.len());
+        return false;
+    }
+
+    let differences = actual.iter().zip(expected).enumerate()
+        .filter(|(_, (actual, expected))| actual != expected)
+        .map(|(i, (&actual, &expected))| MemoryDifference { address: first.wrapping_add(i as u16), expected: Some(expected), actual: Some(actual) })
+        .collect::<Vec<MemoryDifference>>();
+    if differences.is_empty(){
+        println!("{}: RAM matches", file_name);
+        return true;
+    }
+
+    let plural = if differences.len() == 1 { "" } else { "es" };
+    println!("{}: RAM differs at {} address{}", file_name, differences.len(), plural);
+    for difference in differences.iter().take(MAX_LISTED){
+        println!("  {}", difference);
+    }
+    if differences.len() > MAX_LISTED{
+        println!("  and {} more", differences.len() - MAX_LISTED);
+    }
+    false
+}
+
+/// Lists what differs from the golden state, the registers and counts first and then RAM, as `compare_ram` does.
+pub(crate) fn print_state_differences(file_name: &str, differences: &[StateDifference]){
+    const MAX_LISTED: usize = 32;
+
+    println!("{}: final state differs from the golden state", file_name);
+    for difference in differences.iter().take(MAX_LISTED){
+        println!("  {}", difference);
+    }
+    if differences.len() > MAX_LISTED{
+        println!("  and {} more", differences.len() - MAX_LISTED);
+    }
+}
+
+/// Why a test run did not pass, None when it did.
+pub(crate) fn test_failure(stop: StopReason, watch: Option<&ResultWatch>, machine: &Machine) -> Option<String>{
+    match stop{
+        StopReason::Result { pass: true } | StopReason::Exit { code: 0 } | StopReason::HaltAddress { .. } => None,
+        StopReason::Result { pass: false } => match watch.and_then(|w| w.verdict(machine)){
+            Some(Verdict::Fail { error_code: Some(error_code) }) => Some(format!("error code ${:02X}", error_code)),
+            _ => Some("failed".to_string()),
+        },
+        StopReason::Exit { code } => Some(format!("exit status {}", code)),
+        StopReason::Brk => Some("BRK without a verdict".to_string()),
+        StopReason::InstructionLimit { limit } => Some(format!("no verdict within {} instructions", limit)),
+        StopReason::CycleLimit { limit } => Some(format!("no verdict within {} cycles", limit)),
+        StopReason::TimeLimit { milliseconds } => Some(format!("no verdict within {:?}", Duration::from_millis(milliseconds))),
+        StopReason::WindowClosed => Some("window closed".to_string()),
+        StopReason::Quit => Some("quit".to_string()),
+        StopReason::Script => Some("stopped by the script without a verdict".to_string()),
+        StopReason::Diverged => Some("diverged from the recorded run".to_string()),
+    }
+}
+
@@ -0,0 +1,505 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::bus::board::{attach_devices, load_image, preload_ram, randomize_ram, AttachedDevices, ConsoleEnd};
+use crate::bus::linked::LinkedMachine;
+use crate::bus::machine_config::MachineConfig;
+use crate::bus::result_watch::{ResultWatch, Verdict};
+use crate::cpu::coverage::Coverage;
+use crate::cpu::debugger::{DebugAction, Debugger};
+use crate::cpu::determinism::Fingerprints;
+use crate::cpu::limits::RunLimits;
+use crate::cpu::profile::Profiler;
+#[cfg(feature = "remote")]
+use crate::cpu::remote::Remote;
+use crate::cpu::report::{InterruptCounts, Report, StopReason};
+use crate::cpu::save_state::{Autosave, SaveState};
+#[cfg(feature = "scripting")]
+use crate::cpu::script::{Script, ScriptStop};
+use crate::cpu::speed::SpeedMeter;
+use crate::cpu::test_suite::TestSuite;
+use crate::cpu::throttle::Throttle;
+use crate::cpu::trace::Tracer;
+use crate::cpu::w65c02s::{BlockEnd, Mnemonic, Register, W65C02S};
+use crate::cpu::watch::Watcher;
+#[cfg(feature = "video")]
+use crate::devices::framebuffer::Framebuffer;
+use crate::devices::host_services::HostServices;
+use crate::devices::input_log::InputLog;
+use crate::devices::random::Random;
+use super::error::ProgramError;
+use super::options::{apply_machine_config, FingerprintFile, Golden, InputLogFile, Mode, Options, StatsMode, TraceFormat, TraceOptions};
+use super::output::{compare_ram, dump_path, print_state_differences, print_summary, ram_range, test_failure, write_dump, ImageSummary};
+
+// exit status of a run stopped by --max-instructions or --max-cycles, as `timeout` uses
+const LIMIT_EXCEEDED_STATUS: u8 = 124;
+
+// exit status of a run whose RAM does not match --compare
+const COMPARE_FAILED_STATUS: u8 = 1;
+
+// exit status of a run that does not match --check-fingerprints
+const DIVERGED_STATUS: u8 = 1;
+
+#[cfg(feature = "scripting")]
+fn script_stop(stop: ScriptStop) -> StopReason{
+    match stop{
+        ScriptStop::Stop => StopReason::Script,
+        ScriptStop::Pass => StopReason::Result { pass: true },
+        ScriptStop::Fail => StopReason::Result { pass: false },
+    }
+}
+
+/// Runs each image, or the machine of the configuration file, as the mode and options say, and returns the exit
+/// status the process should end with.
+pub fn run(mode: Mode, mut options: Options, mut images: Vec<PathBuf>, golden: Option<Golden>) -> Result<u8, ProgramError>{
+
+    // the debugger reads its commands from the terminal, and so cannot share it
+    if mode == Mode::Debug{
+        let devices = &options.devices;
+        let stdin_console = devices.char_in.is_some() && matches!(devices.console.input, None | Some(ConsoleEnd::Stdio));
+        let readers = [("--char-in", stdin_console), ("--keyboard", devices.keyboard.is_some()), ("--ps2-keyboard", devices.ps2_keyboard.is_some()), ("--joystick", devices.joystick.is_some())];
+        if let Some((flag, _)) = readers.iter().find(|(_, reads)| *reads){
+            return Err(ProgramError::ConflictingFlags("debug", flag));
+        }
+    }
+
+    // a configuration file stands in for the image, and names the run
+    let machine_config = match options.machine.clone(){
+        Some(path) => {
+            let config = MachineConfig::load(&path).map_err(ProgramError::MachineConfig)?;
+            apply_machine_config(&mut options, &config)?;
+            images.push(path);
+            Some(config)
+        },
+        None => None,
+    };
+    // without a window to take its keys from, a joystick reads the terminal, which has one reader of its keys
+    let windowed = false;
+    #[cfg(feature = "video")]
+    let windowed = windowed || options.devices.video.is_some();
+    if options.devices.joystick.is_some() && !windowed && (options.devices.keyboard.is_some() || options.devices.ps2_keyboard.is_some()){
+        let keyboard = if options.devices.keyboard.is_some() { "--keyboard" } else { "--ps2-keyboard" };
+        return Err(ProgramError::ConflictingFlags(keyboard, "--joystick"));
+    }
+
+    let mut exit_code = None;
+    let (mut passed, mut failed) = (0, 0);
+    let batch = images.len() > 1;
+    let mut summary = Vec::new();
+    if mode == Mode::Test{
+        // directories hold the standard test suites rather than images
+        let (suites, files): (Vec<PathBuf>, Vec<PathBuf>) = images.into_iter().partition(|path| path.is_dir());
+        images = files;
+        let mut suite = TestSuite::new()
+            .with_entry(options.entry.unwrap_or(TestSuite::DEFAULT_ENTRY))
+            .with_success(&options.halt_addresses);
+        if options.limits != RunLimits::NONE{
+            suite = suite.with_limits(options.limits);
+        }
+        for root in suites{
+            let report = suite.run(&root).map_err(|_| ProgramError::CouldNotReadFile(root.display().to_string()))?;
+            print!("{}", report);
+            passed += report.passed();
+            failed += report.failed();
+        }
+    }
+    #[cfg(feature = "remote")]
+    let mut remote = match &options.remote{
+        Some(listen) => Some(Remote::serve(listen).map_err(|_| ProgramError::CouldNotListen(listen.clone()))?.with_symbols(options.symbols.clone())),
+        None => None,
+    };
+    // a run with anything random in it can only be repeated knowing its seed, so one picked here is shown
+    let seed = match options.seed{
+        Some(seed) => Some(seed),
+        None if options.random_ram || options.devices.rng.is_some() => {
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+            log::info!("Seed {}, repeat the run with --seed {}", seed, seed);
+            Some(seed)
+        },
+        None => None,
+    };
+    for rom_path in images{
+        if !rom_path.exists(){
+            return Err(ProgramError::CouldNotLocateFile(rom_path.display().to_string()));
+        }
+        let file_name = rom_path.file_stem().expect("Could not extract file name").to_str().expect("Failed to convert").to_owned();
+
+        // every image starts from the same seed, so that each can be repeated on its own
+        let mut random = Random::new(seed.unwrap_or(0));
+        let mut cpu = W65C02S::default();
+        cpu.set_invalid_opcode_policy(options.invalid_opcodes);
+        let (mut machine_bus, entry) = match &machine_config{
+            Some(config) => {
+                let mut machine = config.build().map_err(ProgramError::MachineConfig)?;
+                if options.random_ram{
+                    randomize_ram(&mut machine, &mut random)?;
+                }
+                (machine, options.entry)
+            },
+            None => load_image(&rom_path, options.placement, options.entry, options.random_ram.then_some(&mut random))?,
+        };
+        if let Some((path, offset)) = &options.ram_image{
+            let bytes = fs::read(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?;
+            preload_ram(&mut machine_bus, *offset, &bytes)?;
+        }
+
+        let mut input_log = match &options.input_log{
+            Some(InputLogFile::Record(path)) => Some(InputLog::record(path).map_err(|_| ProgramError::CouldNotWriteFile(path.display().to_string()))?),
+            Some(InputLogFile::Replay(path)) => Some(InputLog::replay(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?),
+            None => None,
+        };
+
+        let mut other = match &options.link{
+            Some(path) => Some(load_image(path, options.placement, None, options.random_ram.then_some(&mut random))?.0),
+            None => None,
+        };
+        let AttachedDevices { host_services, framebuffer } = attach_devices(&mut machine_bus, &options.devices, other.as_mut(), &mut input_log, &mut random)?;
+        let mut linked = match other{
+            Some(other) => Some(LinkedMachine::new(other).map_err(ProgramError::CpuError)?),
+            None => None,
+        };
+
+        let result_watch = options.result.as_ref().map(|result| {
+            let watch = ResultWatch::attach(&mut machine_bus, result.address, result.pass, result.fail);
+            match result.error_address{
+                Some(address) => watch.with_error_address(address),
+                None => watch,
+            }
+        });
+
+        let mut tracer = match &options.trace{
+            Some(TraceOptions { file, start, stop, format }) => {
+                let mut tracer = Tracer::create(file).map_err(|_| ProgramError::CouldNotWriteFile(file.display().to_string()))?;
+                if let Some(address) = start{
+                    tracer = tracer.with_start(*address);
+                }
+                if let Some(address) = stop{
+                    tracer = tracer.with_stop(*address);
+                }
+                if !options.symbols.is_empty(){
+                    tracer = tracer.with_symbols(options.symbols.clone());
+                }
+                if *format == TraceFormat::Vice{
+                    tracer = tracer.vice();
+                }
+                Some(tracer)
+            },
+            None => None,
+        };
+
+        log::info!("Emulating {}", file_name);
+        match entry{
+            Some(entry) => cpu.reset_to(entry),
+            None => cpu.reset(&mut machine_bus).map_err(ProgramError::CpuError)?,
+        }
+        if let Some(path) = &options.resume{
+            let state = SaveState::read(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?;
+            state.restore(&mut cpu, &mut machine_bus).map_err(ProgramError::Restore)?;
+        }
+        let mut autosave = options.autosave.map(|interval| Autosave::new(interval, &options.output_dir, &file_name, cpu.cycles()));
+        let mut fingerprints = match &options.fingerprints{
+            Some(FingerprintFile::Record(path)) => Some(Fingerprints::record(path).map_err(|_| ProgramError::CouldNotWriteFile(path.display().to_string()))?),
+            Some(FingerprintFile::Check(path)) => Some(Fingerprints::check(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?),
+            None => None,
+        };
+        if let Some(fingerprints) = fingerprints.as_mut(){
+            fingerprints.attach(&cpu, &mut machine_bus);
+        }
+        // --unlimited wins, so that it can undo a clock baked into a script or configuration
+        let mut throttle = options.clock.filter(|_| !options.unlimited).map(Throttle::new);
+
+        let mut profiler = options.profile.as_ref().map(|_| Profiler::new().with_symbols(options.symbols.clone()));
+        let mut coverage = options.coverage.then(Coverage::new);
+        let mut watcher = options.watch.as_ref().map(|watch| {
+            let mut watcher = Watcher::stderr();
+            for (name, target) in &watch.watches{
+                watcher = watcher.with_watch(name, *target);
+            }
+            match watch.every{
+                Some(every) => watcher.with_interval(every),
+                None => watcher,
+            }
+        });
+        if let Some(watcher) = watcher.as_mut(){
+            watcher.watch(&cpu, &machine_bus);
+        }
+        let mut speed = options.stats.map(|stats| match stats{
+            StatsMode::Summary => SpeedMeter::new(&cpu),
+            StatsMode::Live => SpeedMeter::new(&cpu).live(),
+        });
+        #[cfg(feature = "scripting")]
+        let mut script = match &options.script{
+            Some(path) => {
+                let script = Script::load(path).map_err(|e| ProgramError::Script(format!("{}: {}", path.display(), e)))?;
+                script.attach(&mut machine_bus);
+                Some(script)
+            },
+            None => None,
+        };
+        let mut debugger = (mode == Mode::Debug).then(|| Debugger::stdio().with_symbols(options.symbols.clone()));
+
+        // numbered from 1, the first once an interval has gone by
+        let mut snapshots = 0;
+        let mut next_snapshot = options.dump.every.map_or(0, |every| every.interval.count(&cpu) + every.interval.length());
+
+        // with nothing to do between instructions, they run in blocks as long as the limits allow
+        let stepwise = !options.halt_addresses.is_empty() || debugger.is_some() || tracer.is_some() || profiler.is_some()
+            || coverage.is_some() || input_log.is_some() || fingerprints.is_some() || linked.is_some() || throttle.is_some()
+            || speed.is_some() || autosave.is_some() || watcher.is_some() || options.dump.every.is_some()
+            || host_services.is_some() || result_watch.is_some() || framebuffer.is_some();
+        #[cfg(feature = "remote")]
+        let stepwise = stepwise || remote.is_some();
+        #[cfg(feature = "scripting")]
+        let stepwise = stepwise || script.is_some();
+
+        let mut interrupts = InterruptCounts::default();
+        let started = Instant::now();
+        let stop = loop{
+            if let Some(exceeded) = options.limits.check(&cpu, started){
+                eprintln!("{}: {}", file_name, exceeded);
+                exit_code = Some(exit_code.filter(|c| *c != 0).unwrap_or(LIMIT_EXCEEDED_STATUS));
+                break StopReason::from(exceeded);
+            }
+            if !stepwise{
+                let (count, stop_cycles) = options.limits.batch(&cpu);
+                match cpu.run_block(&mut machine_bus, count, stop_cycles){
+                    BlockEnd::Ran => continue,
+                    BlockEnd::Interrupted(arbitration) => {
+                        interrupts.record(&arbitration);
+                        continue;
+                    },
+                    BlockEnd::Brk { .. } => break StopReason::Brk,
+                    BlockEnd::Failed { pc, error } => return Err(ProgramError::CpuErrorAt(pc, error)),
+                }
+            }
+            let pc = cpu.register(Register::PC);
+            if options.halt_addresses.contains(&pc){
+                println!("{}: halted at ${:04X}", file_name, pc);
+                break StopReason::HaltAddress { address: pc };
+            }
+            if let Some(debugger) = debugger.as_mut() && debugger.check(&cpu, &machine_bus) == DebugAction::Quit{
+                break StopReason::Quit;
+            }
+            #[cfg(feature = "remote")]
+            if let Some(remote) = remote.as_mut() && remote.check(&cpu, &machine_bus) == DebugAction::Quit{
+                break StopReason::Quit;
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = script.as_mut() && let Some(stop) = script.before(&mut cpu, &mut machine_bus).map_err(|e| ProgramError::Script(e.to_string()))?{
+                break script_stop(stop);
+            }
+
+            if let Some(tracer) = tracer.as_mut(){
+                tracer.trace(&cpu, &machine_bus);
+            }
+            if let Some(profiler) = profiler.as_mut(){
+                profiler.before(&cpu, &machine_bus);
+            }
+            if let Some(coverage) = coverage.as_mut(){
+                coverage.before(&cpu);
+            }
+            if let Some(log) = input_log.as_mut(){
+                log.deliver(cpu.cycles());
+            }
+            let pc = cpu.register(Register::PC);
+            let op = cpu.step(&mut machine_bus).map_err(|e| ProgramError::CpuErrorAt(pc, e))?;
+            if let Some(arbitration) = cpu.last_arbitration(){
+                interrupts.record(&arbitration);
+            }
+            if let Some(fingerprints) = fingerprints.as_mut() && let Err(divergence) = fingerprints.after(&cpu){
+                eprintln!("{}: {}", file_name, divergence);
+                break StopReason::Diverged;
+            }
+            if let Some(profiler) = profiler.as_mut(){
+                profiler.after(&cpu);
+            }
+            if let Some(linked) = linked.as_mut(){
+                linked.catch_up(cpu.cycles()).map_err(ProgramError::CpuError)?;
+            }
+            if let Some(throttle) = throttle.as_mut(){
+                throttle.pace(cpu.cycles());
+            }
+            if let Some(speed) = speed.as_mut(){
+                speed.tick(&cpu);
+            }
+            if let Some(autosave) = autosave.as_mut() && let Err(e) = autosave.tick(&cpu, &machine_bus){
+                // the run goes on, with the saves before this one still in place
+                log::warn!("{}: could not autosave: {}", file_name, e);
+            }
+            if let Some(watcher) = watcher.as_mut(){
+                watcher.watch(&cpu, &machine_bus);
+            }
+            if let Some(every) = &options.dump.every && every.interval.count(&cpu) >= next_snapshot{
+                snapshots += 1;
+                next_snapshot = every.interval.count(&cpu) + every.interval.length();
+                write_dump(&options, &file_name, &format!("ram_{:05}", snapshots), &machine_bus.ram_contents(), Some(every.range))?;
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = script.as_mut() && let Some(stop) = script.after(&mut cpu, &mut machine_bus).map_err(|e| ProgramError::Script(e.to_string()))?{
+                break script_stop(stop);
+            }
+            if matches!(op, Mnemonic::BRK){
+                break StopReason::Brk;
+            }
+
+            if let Some(code) = host_services.and_then(|id| machine_bus.device::<HostServices>(id)?.exit_code()){
+                exit_code = exit_code.or(Some(code));
+                break StopReason::Exit { code };
+            }
+            if let Some(verdict) = result_watch.as_ref().and_then(|w| w.verdict(&machine_bus)){
+                break StopReason::Result { pass: verdict == Verdict::Pass };
+            }
+
+            #[cfg(feature = "video")]
+            if framebuffer.is_some_and(|id| machine_bus.device::<Framebuffer>(id).is_some_and(|f| !f.is_open())){
+                break StopReason::WindowClosed;
+            }
+        };
+        #[cfg(feature = "remote")]
+        if let Some(remote) = remote.as_mut(){
+            remote.finish(&cpu, stop);
+        }
+        let mut diverged = stop == StopReason::Diverged;
+        if !diverged && let Some(fingerprints) = fingerprints.as_mut() && let Err(divergence) = fingerprints.finish(){
+            eprintln!("{}: {}", file_name, divergence);
+            diverged = true;
+        }
+        if diverged{
+            // a failure outranks any pass
+            exit_code = Some(exit_code.filter(|c| *c != 0).unwrap_or(DIVERGED_STATUS));
+        }
+
+        if let Some(watch) = &result_watch && mode != Mode::Test{
+            let code = match watch.verdict(&machine_bus){
+                Some(Verdict::Pass) => {
+                    println!("{}: PASS", file_name);
+                    0
+                },
+                Some(Verdict::Fail { error_code: Some(error_code) }) => {
+                    println!("{}: FAIL, error code ${:02X}", file_name, error_code);
+                    1
+                },
+                Some(Verdict::Fail { error_code: None }) => {
+                    println!("{}: FAIL", file_name);
+                    1
+                },
+                None => {
+                    println!("{}: no result", file_name);
+                    2
+                },
+            };
+            // a failure outranks any pass
+            exit_code = Some(exit_code.filter(|c| *c != 0).unwrap_or(code));
+        }
+
+        if let Some(speed) = &speed{
+            eprintln!("{}: {}", file_name, speed.finish(&cpu));
+        }
+
+        // verify judges a run by its final state alone, however it stopped
+        let mut failure = match mode{
+            Mode::Verify => None,
+            _ => test_failure(stop, result_watch.as_ref(), &machine_bus),
+        };
+        if diverged{
+            failure = failure.or(Some("diverged from the recorded run".to_string()));
+        }
+        let mut compared = None;
+        if let Some((path, range)) = &options.compare{
+            let expected = fs::read(path).map_err(|_| ProgramError::CouldNotReadFile(path.display().to_string()))?;
+            let matched = compare_ram(&file_name, &machine_bus.ram_contents(), *range, &expected);
+            compared = Some(matched);
+            if !matched{
+                // a failure outranks any pass
+                exit_code = Some(exit_code.filter(|c| *c != 0).unwrap_or(COMPARE_FAILED_STATUS));
+                failure = failure.or(Some(format!("RAM differs from {}", path.display())));
+            }
+        }
+
+        if let Some(golden) = &golden{
+            let state = SaveState::capture(&cpu, &machine_bus);
+            if golden.update{
+                state.write(&golden.path).map_err(|_| ProgramError::CouldNotWriteFile(golden.path.display().to_string()))?;
+                println!("{}: golden state written to {}", file_name, golden.path.display());
+            }
+            else{
+                let expected = SaveState::read(&golden.path).map_err(|_| ProgramError::CouldNotReadFile(golden.path.display().to_string()))?;
+                let differences = state.differences(&expected);
+                if !differences.is_empty(){
+                    print_state_differences(&file_name, &differences);
+                    failure = failure.or(Some(format!("{} differences from {}", differences.len(), golden.path.display())));
+                }
+            }
+        }
+
+        if batch{
+            let pass = match mode{
+                Mode::Test | Mode::Verify => Some(failure.is_none()),
+                _ => compared,
+            };
+            summary.push(ImageSummary { name: file_name.clone(), stop, instructions: cpu.instructions(), cycles: cpu.cycles(), time: started.elapsed(), pass });
+        }
+
+        if matches!(mode, Mode::Test | Mode::Verify){
+            match &failure{
+                None => println!("{}: PASS", file_name),
+                Some(reason) => println!("{}: FAIL, {}", file_name, reason),
+            }
+            if failure.is_none() { passed += 1 } else { failed += 1 }
+        }
+        else{
+            write_dump(&options, &file_name, "ram", &machine_bus.ram_contents(), options.dump.range)?;
+            if let Some(linked) = &linked{
+                write_dump(&options, &file_name, "link_ram", &linked.machine().ram_contents(), options.dump.range)?;
+            }
+            if options.dump.state{
+                let output_file = dump_path(&options, &file_name, "state", "state")?;
+                SaveState::capture(&cpu, &machine_bus).write(&output_file)
+                    .map_err(|_| ProgramError::CouldNotWriteFile(output_file.display().to_string()))?;
+            }
+        }
+
+        if options.report{
+            let ram = machine_bus.ram_contents();
+            let mut report = Report::new(&file_name, stop, &cpu).with_memory("ram", 0, &ram).with_interrupts(interrupts);
+            if let Some(seed) = seed{
+                report = report.with_seed(seed);
+            }
+            if options.dump.range.is_some(){
+                let (first, bytes) = ram_range(&ram, options.dump.range);
+                report = report.with_memory("dump-range", first, bytes);
+            }
+            if let Some(linked) = &linked{
+                report = report.with_memory("link-ram", 0, &linked.machine().ram_contents());
+            }
+            let output_file = options.output_dir.join(format!("{}_report.json", file_name));
+            report.write(&output_file).map_err(|_| ProgramError::CouldNotWriteFile(output_file.display().to_string()))?;
+        }
+        if let (Some(profiler), Some(path)) = (&profiler, &options.profile){
+            fs::write(path, profiler.report(&cpu, &machine_bus)).map_err(|_| ProgramError::CouldNotWriteFile(path.display().to_string()))?;
+        }
+        if let Some(coverage) = &coverage{
+            let listing_file = options.output_dir.join(format!("{}_coverage.txt", file_name));
+            fs::write(&listing_file, coverage.listing(&machine_bus, &options.symbols))
+                .map_err(|_| ProgramError::CouldNotWriteFile(listing_file.display().to_string()))?;
+            let summary_file = options.output_dir.join(format!("{}_coverage.json", file_name));
+            coverage.summary(&file_name, &machine_bus, &options.symbols).write(&summary_file)
+                .map_err(|_| ProgramError::CouldNotWriteFile(summary_file.display().to_string()))?;
+        }
+    }
+
+    //fs::write("./data/ram.bin", bus.ram_contents()).map_err(|e| Error::IO(e))?;
+
+    if !summary.is_empty(){
+        print_summary(&summary);
+    }
+    if matches!(mode, Mode::Test | Mode::Verify){
+        println!("{} passed, {} failed", passed, failed);
+        return Ok(if failed > 0 { 1 } else { 0 });
+    }
+
+    // the first image to ask to exit decides the process's exit status, unless a later one fails its tests
+    Ok(exit_code.unwrap_or(0))
+}
+